@@ -0,0 +1,4 @@
+@vertex
+fn vs_main() -> @builtin(position) vec4<f32> {
+    return vec4(0.0, 0.0, 0.0, 1.0)
+}
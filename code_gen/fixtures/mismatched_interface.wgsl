@@ -0,0 +1,15 @@
+struct VSOut {
+    @location(0) uv: vec2<f32>,
+    @builtin(position) clip_pos: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>) -> VSOut {
+    return VSOut(position * 0.5 + 0.5, vec4(position, 0.0, 1.0));
+}
+
+// Reads the `uv` the vertex stage writes as a `vec3`.
+@fragment
+fn fs_main(@location(0) uv: vec3<f32>) -> @location(0) vec4<f32> {
+    return vec4(uv, 1.0);
+}
@@ -0,0 +1,342 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use crate::{
+    format_code, generate_many_with_manifests, write_generated, CodegenError, CodegenOptions,
+    DependencyTracker, ProgressObserver, ProjectConfig, WriteOutcome, PROJECT_FILE,
+};
+
+/// Generates the code for every config in a set of directories from a build
+/// script:
+///
+/// ```no_run
+/// // build.rs
+/// fn main() -> anyhow::Result<()> {
+///     code_gen::Builder::new().add_dir("shaders").generate()?;
+///     Ok(())
+/// }
+/// ```
+///
+/// Each `.pmd` file is written to the output directory at the same relative
+/// path with an `.rs` extension, so `shaders/sprites.pmd` is pulled in with
+/// `include!(concat!(env!("OUT_DIR"), "/sprites.rs"))`.
+#[derive(Debug, Clone, Default)]
+pub struct Builder {
+    dirs: Vec<PathBuf>,
+    out_dir: Option<PathBuf>,
+    options: Option<CodegenOptions>,
+    quiet: bool,
+    manifests: bool,
+    depfile: Option<PathBuf>,
+    force: bool,
+}
+
+/// A config [Builder::generate] wrote the code for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedFile {
+    pub config: PathBuf,
+    pub output: PathBuf,
+    pub outcome: WriteOutcome,
+    /// Where its [Manifest](crate::Manifest) was written, if
+    /// [Builder::manifests] is set.
+    pub manifest: Option<PathBuf>,
+}
+
+/// What [Builder::generate] did, in the order the configs were found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildReport {
+    pub files: Vec<GeneratedFile>,
+    /// Every file that was read, eg. configs, the files they include and
+    /// their shaders, sorted and without duplicates.
+    pub dependencies: Vec<PathBuf>,
+}
+
+impl BuildReport {
+    /// The files that were created or updated, ie. not left as they were.
+    pub fn changed(&self) -> impl Iterator<Item = &GeneratedFile> {
+        self.files
+            .iter()
+            .filter(|file| file.outcome != WriteOutcome::Unchanged)
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Generates every `.pmd` file in `dir` and its subdirectories.
+    pub fn add_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dirs.push(dir.into());
+        self
+    }
+
+    /// Where the generated files are written. Defaults to cargo's `OUT_DIR`.
+    pub fn out_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.out_dir = Some(dir.into());
+        self
+    }
+
+    /// The options to generate with. By default they're read from the
+    /// current profile of the `pipemd.toml` in the current directory, which
+    /// is the crate's root in build scripts, if there is one.
+    pub fn options(mut self, options: CodegenOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// Don't print the `cargo:rerun-if-changed` lines that have cargo rerun
+    /// the build script when a config, a file it reads or the project file
+    /// changes.
+    pub fn quiet(mut self) -> Self {
+        self.quiet = true;
+        self
+    }
+
+    /// Also writes a [Manifest](crate::Manifest) of each config next to its
+    /// code, eg. `sprites.json` beside `sprites.rs`, for tools that need to
+    /// know what the pipelines expect without reading Rust.
+    pub fn manifests(mut self) -> Self {
+        self.manifests = true;
+        self
+    }
+
+    /// Also writes a Makefile style depfile to `path` once everything has
+    /// been generated, saying the outputs depend on every config, shader
+    /// and project file that was read, for build systems like Ninja.
+    pub fn depfile(mut self, path: impl Into<PathBuf>) -> Self {
+        self.depfile = Some(path.into());
+        self
+    }
+
+    /// Overwrite outputs that were edited by hand, or weren't generated by
+    /// pipemd, instead of failing. See [write_generated](crate::write_generated).
+    pub fn force(mut self) -> Self {
+        self.force = true;
+        self
+    }
+
+    pub(crate) fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Finds the configs, generates their code and writes it out. Every
+    /// config is generated before failing, so the error lists all of the
+    /// broken ones.
+    pub fn generate(&self) -> Result<BuildReport> {
+        let out_dir = match &self.out_dir {
+            Some(dir) => dir.clone(),
+            None => std::env::var_os("OUT_DIR")
+                .context(
+                    "OUT_DIR isn't set, call `Builder::out_dir` when not running in a build script",
+                )?
+                .into(),
+        };
+        let mut project_file = false;
+        let mut options = match &self.options {
+            Some(options) => options.clone(),
+            None if Path::new(PROJECT_FILE).exists() => {
+                project_file = true;
+                self.rerun_if_changed(Path::new(PROJECT_FILE));
+                ProjectConfig::from_path(PROJECT_FILE)?.options_from_env()
+            }
+            None => CodegenOptions::default(),
+        };
+        // Observers are only `Arc`s so they can be shared, they don't have
+        // to be sent between threads.
+        #[allow(clippy::arc_with_non_send_sync)]
+        let dependencies = Arc::new(Dependencies {
+            options: options.clone(),
+            tracker: DependencyTracker::default(),
+            print: !self.quiet,
+        });
+        options.observer = Some(dependencies.clone());
+
+        // Configs keyed by their output path, so two directories can't
+        // write to the same file.
+        let mut outputs = BTreeMap::new();
+        let mut configs = Vec::new();
+        for dir in &self.dirs {
+            // Directories are rerun on any change inside them, which covers
+            // new configs as well as edited ones.
+            self.rerun_if_changed(dir);
+            for config in find_configs(dir)? {
+                let relative = config.strip_prefix(dir).unwrap_or(&config);
+                let output = out_dir.join(relative).with_extension("rs");
+                if let Some(other) = outputs.insert(output.clone(), config.clone()) {
+                    anyhow::bail!(
+                        "{} and {} would both be written to {}",
+                        other.display(),
+                        config.display(),
+                        output.display()
+                    );
+                }
+                configs.push((config, output));
+            }
+        }
+
+        let inputs = configs
+            .iter()
+            .map(|(config, _)| config.clone())
+            .collect::<Vec<_>>();
+        let mut report = BuildReport::default();
+        let mut errors = Vec::new();
+        let results = generate_many_with_manifests(&inputs, &options);
+        for ((config, result), (_, output)) in results.into_iter().zip(configs) {
+            let (code, manifest) = match result {
+                Ok((code, manifest)) => (format_code(&code), manifest),
+                // Parse errors already say which file they're in.
+                Err(CodegenError::Parse(e)) => {
+                    errors.push(format!("{:#}", e));
+                    continue;
+                }
+                Err(CodegenError::Generate(e)) => {
+                    errors.push(format!("{}: {:#}", config.display(), e));
+                    continue;
+                }
+            };
+            if let Some(parent) = output.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("failed to create {}", parent.display()))?;
+            }
+            let outcome = write_generated(&output, &code, self.force)?;
+            if let Some(observer) = &options.observer {
+                observer.bytes_written(&output, code.len());
+            }
+            let manifest = match self.manifests {
+                true => {
+                    let path = output.with_extension("json");
+                    let json = manifest.to_json();
+                    // Left alone when unchanged, like the code.
+                    if std::fs::read_to_string(&path).ok().as_deref() != Some(json.as_str()) {
+                        std::fs::write(&path, &json)
+                            .with_context(|| format!("failed to write {}", path.display()))?;
+                    }
+                    if let Some(observer) = &options.observer {
+                        observer.bytes_written(&path, json.len());
+                    }
+                    Some(path)
+                }
+                false => None,
+            };
+            report.files.push(GeneratedFile {
+                config,
+                output,
+                outcome,
+                manifest,
+            });
+        }
+        if !errors.is_empty() {
+            anyhow::bail!(
+                "{} of {} configs failed:\n{}",
+                errors.len(),
+                inputs.len(),
+                errors.join("\n")
+            );
+        }
+        if project_file {
+            dependencies.tracker.file_parsed(Path::new(PROJECT_FILE));
+        }
+        report.dependencies = dependencies.tracker.files();
+        if let Some(path) = &self.depfile {
+            let targets = report
+                .files
+                .iter()
+                .map(|file| file.output.as_path())
+                .collect::<Vec<_>>();
+            dependencies
+                .tracker
+                .write_depfile(path, &targets)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+        }
+        Ok(report)
+    }
+
+    fn rerun_if_changed(&self, path: &Path) {
+        if !self.quiet {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+}
+
+/// Records every file that's read, and has cargo rerun the build script
+/// when one changes if `print` is set. Events are passed on to the observer
+/// in `options`.
+#[derive(Debug)]
+struct Dependencies {
+    options: CodegenOptions,
+    tracker: DependencyTracker,
+    print: bool,
+}
+
+impl Dependencies {
+    fn record(&self, path: &Path) {
+        self.tracker.file_read(path);
+        if self.print {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+}
+
+impl ProgressObserver for Dependencies {
+    fn file_parsed(&self, path: &Path) {
+        self.record(path);
+        if let Some(observer) = &self.options.observer {
+            observer.file_parsed(path);
+        }
+    }
+
+    fn shader_reflected(&self, path: &str) {
+        if let Some(observer) = &self.options.observer {
+            observer.shader_reflected(path);
+        }
+    }
+
+    fn file_read(&self, path: &Path) {
+        self.record(path);
+        if let Some(observer) = &self.options.observer {
+            observer.file_read(path);
+        }
+    }
+
+    fn warning(&self, message: &str) {
+        if let Some(observer) = &self.options.observer {
+            observer.warning(message);
+        }
+    }
+
+    fn pipeline_generated(&self, name: &str, done: usize, total: usize) {
+        if let Some(observer) = &self.options.observer {
+            observer.pipeline_generated(name, done, total);
+        }
+    }
+
+    fn bytes_written(&self, path: &Path, len: usize) {
+        if let Some(observer) = &self.options.observer {
+            observer.bytes_written(path, len);
+        }
+    }
+}
+
+/// The `.pmd` files in `dir` and its subdirectories, sorted so output
+/// doesn't depend on the order the file system lists them in.
+pub fn find_configs(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut configs = Vec::new();
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("failed to read {}", dir.display()))?
+            .path();
+        if path.is_dir() {
+            configs.extend(find_configs(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "pmd") {
+            configs.push(path);
+        }
+    }
+    configs.sort();
+    Ok(configs)
+}
@@ -1,6 +1,78 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use crate::grammar::{self, GrammarVersion, InvalidGrammarVersion, GRAMMAR_VERSION};
 use crate::lex::{self, TokenStream};
+use crate::variants;
+
+/// Formats the `expected` list of [ParseError::InvalidVariant].
+fn one_of(variants: &[&str]) -> String {
+    variants.join(", ")
+}
+
+/// Formats where the error in [ParseError::Include] is.
+fn include_location(path: &Path, line: &Option<usize>) -> String {
+    match line {
+        Some(line) => format!("{}:{}", path.display(), line),
+        None => path.display().to_string(),
+    }
+}
+
+/// Formats the `#include` chain of [ParseError::Include], one per line like
+/// C compilers do.
+fn include_sites(sites: &[IncludeSite]) -> String {
+    sites
+        .iter()
+        .map(|site| format!("\n  included from {}", site))
+        .collect()
+}
+
+/// Where an `#include` directive or a pipeline is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludeSite {
+    /// The including file, or `None` if it was parsed from a string.
+    pub path: Option<PathBuf>,
+    pub line: Option<usize>,
+}
+
+impl std::fmt::Display for IncludeSite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.path, self.line) {
+            (Some(path), Some(line)) => write!(f, "{}:{}", path.display(), line),
+            (Some(path), None) => write!(f, "{}", path.display()),
+            (None, Some(line)) => write!(f, "line {}", line),
+            (None, None) => write!(f, "input"),
+        }
+    }
+}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+/// Formats [ParseError::UnsupportedGrammar].
+fn unsupported_grammar(required: &GrammarVersion) -> String {
+    if *required > GRAMMAR_VERSION {
+        format!(
+            "This config needs version {} of the pipemd grammar, but this pipemd only supports up to {}. \
+            Upgrade pipemd to use it",
+            required, GRAMMAR_VERSION
+        )
+    } else {
+        format!(
+            "This config was written for version {} of the pipemd grammar, which isn't supported by this \
+            pipemd (grammar {})",
+            required, GRAMMAR_VERSION
+        )
+    }
+}
+
+/// Formats the chain of files in [ParseError::IncludeCycle].
+fn include_chain(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
 pub enum ParseError<'a> {
     #[error("Unable to process input")]
     Lex(#[from] lex::LexError),
@@ -10,16 +82,446 @@ pub enum ParseError<'a> {
         expected: lex::Token<'a>,
     },
     #[error("Unexpected field: {0:?}")]
-    UnexpectedField(&'a str),
+    UnexpectedField(Cow<'a, str>),
     #[error("Unexpected end of input")]
     EndOfInput,
     #[error("Missing field: {0:?}")]
     MissingField(&'a str),
     #[error("Expected end of input, but found {0:?}")]
     ExpectedEndOfInput(lex::Token<'a>),
+    #[error("Invalid number: {0:?}")]
+    InvalidNumber(Cow<'a, str>),
+    #[error("Invalid value for {field:?}: {reason}")]
+    InvalidValue {
+        field: Cow<'a, str>,
+        reason: &'static str,
+    },
+    #[error(
+        "Invalid value {found:?} for {field:?}, expected one of: {}",
+        one_of(expected)
+    )]
+    InvalidVariant {
+        field: Cow<'a, str>,
+        found: Cow<'a, str>,
+        expected: &'static [&'static str],
+    },
+    /// The directive may be from a newer grammar than this build's.
+    #[error(
+        "Unknown directive: #{0}. It may need a newer pipemd, this one supports grammar {}",
+        GRAMMAR_VERSION
+    )]
+    UnknownDirective(Cow<'a, str>),
+    /// An included file couldn't be read or failed to parse. Errors from the
+    /// included file are owned by it, so only their message is kept, along
+    /// with the line it was found on. `included_from` is the chain of
+    /// `#include` directives that led to `path`, innermost first.
+    #[error("{}: {message}{}", include_location(path, line), include_sites(.included_from))]
+    Include {
+        path: PathBuf,
+        line: Option<usize>,
+        /// The [ParseError::code] of the error in the included file.
+        code: &'static str,
+        message: String,
+        included_from: Vec<IncludeSite>,
+    },
+    #[error("Include cycle: {}", include_chain(.0))]
+    IncludeCycle(Vec<PathBuf>),
+    /// An `#include` leaves the root of the loader it's read with, like
+    /// [crate::LoadError::PathOutsideRoot].
+    #[error("{path:?} resolves to a location outside of {root:?}")]
+    PathOutsideRoot { path: PathBuf, root: PathBuf },
+    #[error("Undefined variable: ${0}")]
+    UndefinedVariable(Cow<'a, str>),
+    #[error("Variable ${0} is already defined")]
+    VariableRedefined(Cow<'a, str>),
+    #[error("Unknown template: {0}")]
+    UnknownTemplate(Cow<'a, str>),
+    #[error("Template {0} is already defined")]
+    TemplateRedefined(Cow<'a, str>),
+    #[error("Template {template} needs a value for {param:?}")]
+    MissingTemplateArgument {
+        template: Cow<'a, str>,
+        param: String,
+    },
+    /// A template instantiates itself, directly or through other templates.
+    /// Holds the templates being instantiated, outermost first, ending with
+    /// the one that was instantiated again.
+    #[error("Template cycle: {}", .0.join(" -> "))]
+    TemplateCycle(Vec<String>),
+    /// Instantiating a template failed. The template's tokens are owned by the
+    /// parser, so only the message is kept.
+    #[error("In template {template}: {message}")]
+    Template { template: String, message: String },
+    #[error(transparent)]
+    InvalidGrammarVersion(#[from] InvalidGrammarVersion),
+    /// The config asked for a grammar version with `#grammar` that this
+    /// build can't parse.
+    #[error("{}", unsupported_grammar(.0))]
+    UnsupportedGrammar(GrammarVersion),
+    /// Something added to the grammar after the version the config declares
+    /// with `#grammar` was used, eg. `#compute_pipeline` in a 1.8 config.
+    #[error("{what} was added in version {added} of the pipemd grammar, but this config declares {declared}")]
+    NewerThanGrammar {
+        what: String,
+        added: GrammarVersion,
+        declared: GrammarVersion,
+    },
+    #[error("Unknown binding preset: {0:?}")]
+    UnknownBindingPreset(Cow<'a, str>),
+    #[error("Binding preset {0:?} is already defined")]
+    BindingPresetRedefined(Cow<'a, str>),
+    #[error("Unknown bind group: {0:?}")]
+    UnknownBindGroup(Cow<'a, str>),
+    #[error("Bind group {0:?} is already defined")]
+    BindGroupRedefined(Cow<'a, str>),
+    #[error("Unknown vertex layout: {0:?}")]
+    UnknownVertexLayout(Cow<'a, str>),
+    #[error("Vertex layout {0:?} is already defined")]
+    VertexLayoutRedefined(Cow<'a, str>),
+    #[error("Unknown shader: {0:?}")]
+    UnknownShader(Cow<'a, str>),
+    #[error("Shader {0:?} is already defined")]
+    ShaderRedefined(Cow<'a, str>),
+    #[error("Unknown pipeline layout: {0:?}")]
+    UnknownPipelineLayout(Cow<'a, str>),
+    #[error("Pipeline layout {0:?} is already defined")]
+    PipelineLayoutRedefined(Cow<'a, str>),
+    #[error("Unknown color target: {0:?}")]
+    UnknownColorTarget(Cow<'a, str>),
+    #[error("Color target {0:?} is already defined")]
+    ColorTargetRedefined(Cow<'a, str>),
+    #[error("Alias {0:?} is already defined")]
+    AliasRedefined(Cow<'a, str>),
+}
+
+impl ParseError<'_> {
+    /// A stable name for the kind of error, for [crate::Diagnostic::code].
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Lex(_) => "lex",
+            Self::UnexpectedToken { .. } => "unexpected_token",
+            Self::UnexpectedField(_) => "unexpected_field",
+            Self::EndOfInput => "end_of_input",
+            Self::MissingField(_) => "missing_field",
+            Self::ExpectedEndOfInput(_) => "expected_end_of_input",
+            Self::InvalidNumber(_) => "invalid_number",
+            Self::InvalidValue { .. } => "invalid_value",
+            Self::InvalidVariant { .. } => "invalid_variant",
+            Self::UnknownDirective(_) => "unknown_directive",
+            Self::Include { code, .. } => code,
+            Self::IncludeCycle(_) => "include_cycle",
+            Self::PathOutsideRoot { .. } => "path_outside_root",
+            Self::UndefinedVariable(_) => "undefined_variable",
+            Self::VariableRedefined(_) => "variable_redefined",
+            Self::UnknownTemplate(_) => "unknown_template",
+            Self::TemplateRedefined(_) => "template_redefined",
+            Self::MissingTemplateArgument { .. } => "missing_template_argument",
+            Self::TemplateCycle(_) => "template_cycle",
+            Self::Template { .. } => "template",
+            Self::InvalidGrammarVersion(_) => "invalid_grammar_version",
+            Self::UnsupportedGrammar(_) => "unsupported_grammar",
+            Self::NewerThanGrammar { .. } => "newer_than_grammar",
+            Self::UnknownBindingPreset(_) => "unknown_binding_preset",
+            Self::BindingPresetRedefined(_) => "binding_preset_redefined",
+            Self::UnknownBindGroup(_) => "unknown_bind_group",
+            Self::BindGroupRedefined(_) => "bind_group_redefined",
+            Self::UnknownVertexLayout(_) => "unknown_vertex_layout",
+            Self::VertexLayoutRedefined(_) => "vertex_layout_redefined",
+            Self::UnknownShader(_) => "unknown_shader",
+            Self::ShaderRedefined(_) => "shader_redefined",
+            Self::UnknownPipelineLayout(_) => "unknown_pipeline_layout",
+            Self::PipelineLayoutRedefined(_) => "pipeline_layout_redefined",
+            Self::UnknownColorTarget(_) => "unknown_color_target",
+            Self::ColorTargetRedefined(_) => "color_target_redefined",
+            Self::AliasRedefined(_) => "alias_redefined",
+        }
+    }
+}
+
+/// Builds the error for finding `found` where `expected` should be. Running
+/// into a `$name` that wasn't substituted means it was never defined.
+fn unexpected<'a>(found: lex::Token<'a>, expected: lex::Token<'a>) -> ParseError<'a> {
+    match found {
+        lex::Token::Variable(name) => ParseError::UndefinedVariable(name),
+        found => ParseError::UnexpectedToken { found, expected },
+    }
+}
+
+/// A `#name(...)` line between pipeline declarations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Directive<'a> {
+    /// `#include("path")` where `path` is relative to the including file.
+    /// Shader paths in the included file aren't, they're taken from the
+    /// current directory like any other.
+    Include(Cow<'a, str>),
+    /// `#let(name: value, ...)` defines values that can be referenced as
+    /// `$name` by everything after it, including files that are included
+    /// later and the files that include this one. Each value is kept as the
+    /// tokens it was written with.
+    Let(RawFields<'a>),
+    /// `#cfg("predicate") [...]` gates every pipeline in the brackets,
+    /// including those from included files, as if they had
+    /// `cfg: "predicate"`. Only the predicate is parsed here; the block is
+    /// parsed like the rest of the file. Variables defined in the block are
+    /// still defined after it.
+    Cfg(Cow<'a, str>),
+    /// `#defaults(field: value, ...)` gives `render_pipeline` fields that
+    /// every later pipeline in the same file starts out with. Included files
+    /// don't see them.
+    Defaults(RawFields<'a>),
+    /// `#template(name, param, ...) [...]` defines pipelines and directives
+    /// that are stamped out by `#instantiate`. The body refers to parameters
+    /// as `$param` and is kept as tokens until then.
+    Template {
+        name: Cow<'a, str>,
+        params: Vec<Cow<'a, str>>,
+        body: Vec<lex::Token<'a>>,
+    },
+    /// `#instantiate(name, param: value, ...)` expands a template with every
+    /// parameter replaced by its value.
+    Instantiate {
+        name: Cow<'a, str>,
+        args: RawFields<'a>,
+    },
+    /// `#grammar("1.5")` states which version of the grammar a config is
+    /// written for, so older builds of pipemd fail with a clear message
+    /// instead of tripping over syntax they don't know. The rest of the file
+    /// can't use directives or fields added after that version.
+    Grammar(GrammarVersion),
+    /// `#binding_preset(name: "...", ty: ..., visibility: ...)` declares a
+    /// binding that pipelines can use in their `bind_groups`.
+    BindingPreset(BindingPreset),
+    /// `#compute_pipeline("name", "entry", "shader")` declares a compute
+    /// pipeline running `entry` from `shader`.
+    ComputePipeline(ComputePipelineConfig),
+    /// `#bind_group(name: "Camera", entries: [...])` declares a bind group
+    /// layout that pipelines can use by name in their `bind_groups`. Each
+    /// entry is the name of a `#binding_preset` or a preset written inline.
+    BindGroup {
+        name: Cow<'a, str>,
+        entries: Vec<BindGroupEntry>,
+    },
+    /// `#vertex_layout(name: "StaticMesh", attributes: [...])` declares a
+    /// vertex buffer layout that pipelines can use by name in their
+    /// `vertex_buffers`.
+    VertexLayout(VertexLayoutConfig),
+    /// `#shader(name: "pbr", path: "shaders/pbr.wgsl")` declares a shader
+    /// module that pipelines can use by name with `shader: "pbr"`.
+    /// GLSL shaders, eg. `lang: Glsl` or a `.comp` path, have a single stage,
+    /// so render pipelines, which need both of theirs in one module, can't
+    /// use them.
+    Shader(ShaderModuleConfig),
+    /// `#pipeline_layout(name: "Scene", bind_groups: [...], push_constants:
+    /// [...])` declares a pipeline layout that pipelines can share with
+    /// `layout: "Scene"`.
+    PipelineLayout(PipelineLayoutConfig),
+    /// `#color_target(name: "Hdr", format: Rgba16Float, blend: Additive)`
+    /// declares a color target that pipelines can use by name in their
+    /// `targets`.
+    ColorTarget(ColorTargetConfig),
+    /// `#alias(ShadowFormat: Depth32Float, ...)` names values, such as
+    /// formats, so that everything after it can write `ShadowFormat` where
+    /// `Depth32Float` is expected. Like `#let`, aliases are seen by included
+    /// files and the files that include them. Names must start with an
+    /// uppercase letter so they can't be mistaken for fields.
+    Alias(Vec<(Cow<'a, str>, lex::Token<'a>)>),
+    /// `#import_rust(name: "Mesh", path: "crate::mesh::Vertex")` declares an
+    /// existing Rust vertex type that pipelines can use by name in their
+    /// `vertex_buffers` instead of a `#vertex_layout`.
+    ImportRust(RustImportConfig),
+}
+
+impl<'a> Directive<'a> {
+    pub(crate) fn parse(tokens: &mut TokenStream<'a>) -> Result<Self, ParseError<'a>> {
+        expect_token(tokens, lex::Token::Hash)?;
+        let name = parse_ident(tokens)?;
+        match name.as_ref() {
+            "include" => {
+                expect_token(tokens, lex::Token::LeftParen)?;
+                let path = parse_string(tokens)?;
+                expect_token(tokens, lex::Token::RightParen)?;
+                Ok(Directive::Include(path))
+            }
+            "let" => Ok(Directive::Let(parse_raw_struct(tokens)?)),
+            "alias" => {
+                let mut aliases = Vec::new();
+                for (name, value) in parse_raw_struct(tokens)? {
+                    if !name.starts_with(|c: char| c.is_ascii_uppercase()) {
+                        return Err(ParseError::InvalidValue {
+                            field: name,
+                            reason: "alias names must start with an uppercase letter",
+                        });
+                    }
+                    match <[_; 1]>::try_from(value) {
+                        Ok(
+                            [value @ (lex::Token::Ident(_)
+                            | lex::Token::Number(_)
+                            | lex::Token::String(_))],
+                        ) => {
+                            aliases.push((name, value));
+                        }
+                        _ => {
+                            return Err(ParseError::InvalidValue {
+                                field: name,
+                                reason: "an alias must be a single value, eg. `Depth32Float`",
+                            })
+                        }
+                    }
+                }
+                Ok(Directive::Alias(aliases))
+            }
+            "defaults" => Ok(Directive::Defaults(parse_raw_struct(tokens)?)),
+            "template" => {
+                expect_token(tokens, lex::Token::LeftParen)?;
+                let name = parse_ident(tokens)?;
+                let mut params = Vec::new();
+                while let Some(lex::Token::Comma) = tokens.peek() {
+                    tokens.next();
+                    if let Some(lex::Token::RightParen) = tokens.peek() {
+                        break;
+                    }
+                    params.push(parse_ident(tokens)?);
+                }
+                expect_token(tokens, lex::Token::RightParen)?;
+                if tokens.peek() != Some(&lex::Token::LeftBracket) {
+                    return Err(match tokens.next() {
+                        Some(t) => unexpected(t, lex::Token::LeftBracket),
+                        None => ParseError::EndOfInput,
+                    });
+                }
+                // Everything inside the brackets, without them.
+                let mut body = take_value(tokens)?;
+                body.pop();
+                body.remove(0);
+                Ok(Directive::Template { name, params, body })
+            }
+            "instantiate" => {
+                expect_token(tokens, lex::Token::LeftParen)?;
+                let name = parse_ident(tokens)?;
+                let mut args = Vec::new();
+                while let Some(lex::Token::Comma) = tokens.peek() {
+                    tokens.next();
+                    if let Some(lex::Token::RightParen) = tokens.peek() {
+                        break;
+                    }
+                    let param = parse_ident(tokens)?;
+                    expect_token(tokens, lex::Token::Colon)?;
+                    args.push((param, take_value(tokens)?));
+                }
+                expect_token(tokens, lex::Token::RightParen)?;
+                Ok(Directive::Instantiate { name, args })
+            }
+            "cfg" => {
+                expect_token(tokens, lex::Token::LeftParen)?;
+                let predicate = parse_string(tokens)?;
+                expect_token(tokens, lex::Token::RightParen)?;
+                Ok(Directive::Cfg(predicate))
+            }
+            "binding_preset" => Ok(Directive::BindingPreset(BindingPreset::parse(tokens)?)),
+            "vertex_layout" => Ok(Directive::VertexLayout(VertexLayoutConfig::parse(tokens)?)),
+            "shader" => Ok(Directive::Shader(ShaderModuleConfig::parse(tokens)?)),
+            "pipeline_layout" => Ok(Directive::PipelineLayout(PipelineLayoutConfig::parse(
+                tokens,
+            )?)),
+            "color_target" => Ok(Directive::ColorTarget(ColorTargetConfig::parse(tokens)?)),
+            "import_rust" => Ok(Directive::ImportRust(RustImportConfig::parse(tokens)?)),
+            "bind_group" => {
+                let mut name = None;
+                let mut entries = None;
+                parse_struct(tokens, |field, tokens| {
+                    match field.as_ref() {
+                        "name" => name = Some(parse_string(tokens)?),
+                        "entries" => {
+                            entries = Some(parse_list(tokens, |tokens| match tokens.peek() {
+                                Some(lex::Token::LeftParen) => {
+                                    Ok(BindGroupEntry::Inline(BindingPreset::parse(tokens)?))
+                                }
+                                _ => Ok(BindGroupEntry::Preset(parse_string(tokens)?.into_owned())),
+                            })?)
+                        }
+                        _ => return Err(ParseError::UnexpectedField(field)),
+                    }
+                    Ok(())
+                })?;
+                Ok(Directive::BindGroup {
+                    name: name.ok_or(ParseError::MissingField("name"))?,
+                    entries: entries.ok_or(ParseError::MissingField("entries"))?,
+                })
+            }
+            "compute_pipeline" => {
+                expect_token(tokens, lex::Token::LeftParen)?;
+                let name = parse_string(tokens)?;
+                expect_token(tokens, lex::Token::Comma)?;
+                let entry = parse_string(tokens)?;
+                expect_token(tokens, lex::Token::Comma)?;
+                let path = parse_string(tokens)?;
+                expect_token(tokens, lex::Token::RightParen)?;
+                Ok(Directive::ComputePipeline(ComputePipelineConfig {
+                    name: name.into_owned(),
+                    entry: entry.into_owned(),
+                    path: path.into_owned(),
+                    shader: None,
+                    cfg: None,
+                    declared_at: None,
+                }))
+            }
+            "grammar" => {
+                expect_token(tokens, lex::Token::LeftParen)?;
+                let version = parse_string(tokens)?.parse::<GrammarVersion>()?;
+                expect_token(tokens, lex::Token::RightParen)?;
+                if !grammar::supports(version) {
+                    return Err(ParseError::UnsupportedGrammar(version));
+                }
+                Ok(Directive::Grammar(version))
+            }
+            _ => Err(ParseError::UnknownDirective(name)),
+        }
+    }
+}
+
+/// `name: value` pairs whose values are kept as the tokens they were written
+/// with.
+pub(crate) type RawFields<'a> = Vec<(Cow<'a, str>, Vec<lex::Token<'a>>)>;
+
+/// Parses `(name: value, ...)` without interpreting the values.
+fn parse_raw_struct<'a>(tokens: &mut TokenStream<'a>) -> Result<RawFields<'a>, ParseError<'a>> {
+    let mut fields = Vec::new();
+    parse_struct(tokens, |name, tokens| {
+        fields.push((name, take_value(tokens)?));
+        Ok(())
+    })?;
+    Ok(fields)
+}
+
+/// Takes the tokens making up a single value, ie. one token or everything up
+/// to and including the bracket matching the opening one.
+fn take_value<'a>(tokens: &mut TokenStream<'a>) -> Result<Vec<lex::Token<'a>>, ParseError<'a>> {
+    let mut value = Vec::new();
+    let mut depth = 0usize;
+    loop {
+        let token = tokens.next().ok_or(ParseError::EndOfInput)?;
+        match token {
+            lex::Token::LeftParen | lex::Token::LeftBracket => depth += 1,
+            lex::Token::RightParen | lex::Token::RightBracket if depth > 0 => depth -= 1,
+            lex::Token::RightParen
+            | lex::Token::RightBracket
+            | lex::Token::Comma
+            | lex::Token::Colon
+            | lex::Token::Hash
+                if depth == 0 =>
+            {
+                return Err(unexpected(token, lex::Token::ident("value")));
+            }
+            _ => (),
+        }
+        value.push(token);
+        if depth == 0 {
+            return Ok(value);
+        }
+    }
 }
 
-fn expect_token<'a>(
+pub(crate) fn expect_token<'a>(
     tokens: &mut lex::TokenStream<'a>,
     expected: lex::Token<'a>,
 ) -> Result<(), ParseError<'a>> {
@@ -28,19 +530,304 @@ fn expect_token<'a>(
             if t == expected {
                 Ok(())
             } else {
-                Err(ParseError::UnexpectedToken { found: t, expected })
+                Err(unexpected(t, expected))
             }
         }
         None => Err(ParseError::EndOfInput),
     }
 }
 
+fn parse_ident<'a>(tokens: &mut TokenStream<'a>) -> Result<Cow<'a, str>, ParseError<'a>> {
+    match tokens.next() {
+        Some(lex::Token::Ident(id)) => Ok(id),
+        Some(t) => Err(unexpected(t, lex::Token::ident("ident_name"))),
+        None => Err(ParseError::EndOfInput),
+    }
+}
+
+fn parse_string<'a>(tokens: &mut TokenStream<'a>) -> Result<Cow<'a, str>, ParseError<'a>> {
+    match tokens.next() {
+        Some(lex::Token::String(s)) => Ok(s),
+        Some(t) => Err(unexpected(t, lex::Token::string("Some String"))),
+        None => Err(ParseError::EndOfInput),
+    }
+}
+
+/// Parses the name of a wgpu enum variant, eg. `Depth32Float`, checking it
+/// against `variants`. The name can be given either as a bare identifier or
+/// as a string.
+fn parse_variant<'a>(
+    tokens: &mut TokenStream<'a>,
+    field: &Cow<'a, str>,
+    variants: &'static [&'static str],
+) -> Result<&'static str, ParseError<'a>> {
+    let found = match tokens.next() {
+        Some(lex::Token::Ident(id)) => id,
+        Some(lex::Token::String(s)) => s,
+        Some(t) => return Err(unexpected(t, lex::Token::ident("VariantName"))),
+        None => return Err(ParseError::EndOfInput),
+    };
+    match variants.iter().find(|v| **v == found) {
+        Some(variant) => Ok(variant),
+        None => Err(ParseError::InvalidVariant {
+            field: field.clone(),
+            found,
+            expected: variants,
+        }),
+    }
+}
+
+fn parse_bool<'a>(tokens: &mut TokenStream<'a>) -> Result<bool, ParseError<'a>> {
+    match tokens.next() {
+        Some(lex::Token::Ident(id)) if id == "true" => Ok(true),
+        Some(lex::Token::Ident(id)) if id == "false" => Ok(false),
+        Some(t) => Err(unexpected(t, lex::Token::ident("true or false"))),
+        None => Err(ParseError::EndOfInput),
+    }
+}
+
+fn parse_number<'a, T: std::str::FromStr>(
+    tokens: &mut TokenStream<'a>,
+) -> Result<T, ParseError<'a>> {
+    match tokens.next() {
+        Some(lex::Token::Number(n)) => n
+            .replace('_', "")
+            .parse()
+            .map_err(|_| ParseError::InvalidNumber(n)),
+        Some(t) => Err(unexpected(t, lex::Token::number("0"))),
+        None => Err(ParseError::EndOfInput),
+    }
+}
+
+/// Parses `[item, ...]`, calling `parse_item` for each item. A trailing comma
+/// is allowed.
+fn parse_list<'a, T>(
+    tokens: &mut TokenStream<'a>,
+    mut parse_item: impl FnMut(&mut TokenStream<'a>) -> Result<T, ParseError<'a>>,
+) -> Result<Vec<T>, ParseError<'a>> {
+    let mut items = Vec::new();
+    expect_token(tokens, lex::Token::LeftBracket)?;
+    while !matches!(tokens.peek(), Some(lex::Token::RightBracket) | None) {
+        items.push(parse_item(tokens)?);
+        match tokens.peek() {
+            Some(lex::Token::Comma) => {
+                let _ = tokens.next();
+            }
+            _ => break,
+        }
+    }
+    expect_token(tokens, lex::Token::RightBracket)?;
+    Ok(items)
+}
+
+/// Parses `(field: value, ...)`. `parse_field` is called with the name of each
+/// field after its `:` has been consumed and should parse the value. A
+/// trailing comma is allowed.
+fn parse_struct<'a>(
+    tokens: &mut TokenStream<'a>,
+    mut parse_field: impl FnMut(Cow<'a, str>, &mut TokenStream<'a>) -> Result<(), ParseError<'a>>,
+) -> Result<(), ParseError<'a>> {
+    let mut parse_named_field = |tokens: &mut TokenStream<'a>| -> Result<(), ParseError<'a>> {
+        let ident = parse_ident(tokens)?;
+        expect_token(tokens, lex::Token::Colon)?;
+        parse_field(ident, tokens)
+    };
+
+    expect_token(tokens, lex::Token::LeftParen)?;
+
+    if let Some(lex::Token::Ident(_)) = tokens.peek() {
+        parse_named_field(tokens)?;
+
+        while let Some(lex::Token::Comma) = tokens.peek() {
+            let _ = tokens.next();
+            if let Some(lex::Token::RightParen) = tokens.peek() {
+                break;
+            }
+            parse_named_field(tokens)?;
+        }
+    }
+
+    expect_token(tokens, lex::Token::RightParen)?;
+
+    Ok(())
+}
+
+/// A config value that hasn't been given a meaning. Strings are unescaped and
+/// numbers are kept as written, eg. `Number("2.0")`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Ident(String),
+    Number(String),
+    /// `[value, ...]`
+    List(Vec<Value>),
+    /// `(key: value, ...)`, in the order the keys were written.
+    Map(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Parses a single value, eg. `(vendor: "adreno", versions: [1, 2])`.
+    pub fn from_src(src: &str) -> Result<Self, ParseError<'_>> {
+        let mut tokens = lex::TokenStream::new(src)?;
+        let value = Self::parse(&mut tokens)?;
+        if let Some(t) = tokens.next() {
+            return Err(ParseError::ExpectedEndOfInput(t));
+        }
+        Ok(value)
+    }
+
+    pub(crate) fn parse<'a>(tokens: &mut TokenStream<'a>) -> Result<Self, ParseError<'a>> {
+        match tokens.peek() {
+            Some(lex::Token::LeftBracket) => Ok(Value::List(parse_list(tokens, Self::parse)?)),
+            Some(lex::Token::LeftParen) => {
+                let mut entries = Vec::new();
+                parse_struct(tokens, |key, tokens| {
+                    entries.push((key.into_owned(), Self::parse(tokens)?));
+                    Ok(())
+                })?;
+                Ok(Value::Map(entries))
+            }
+            _ => match tokens.next() {
+                Some(lex::Token::String(s)) => Ok(Value::String(s.into_owned())),
+                Some(lex::Token::Ident(id)) => Ok(Value::Ident(id.into_owned())),
+                Some(lex::Token::Number(n)) => Ok(Value::Number(n.into_owned())),
+                Some(t) => Err(unexpected(t, lex::Token::ident("value"))),
+                None => Err(ParseError::EndOfInput),
+            },
+        }
+    }
+
+    /// Returns the text of a string or identifier.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) | Value::Ident(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Ident(id) if id == "true" => Some(true),
+            Value::Ident(id) if id == "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Parses a number, ignoring any `_` separators.
+    pub fn as_number<T: std::str::FromStr>(&self) -> Option<T> {
+        match self {
+            Value::Number(n) => n.replace('_', "").parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Looks up `key` in a map. If a key was written more than once the last
+    /// value wins.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries.iter().rev().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComputePipelineConfig {
+    pub name: String,
+    /// The compute entry point in the shader.
+    pub entry: String,
+    /// The third argument can name a `#shader` instead of giving a path, in
+    /// which case this is the shader's path.
+    pub path: String,
+    /// The `#shader` the pipeline uses, if it names one.
+    pub shader: Option<String>,
+    /// Set when the pipeline is declared inside a `#cfg` block.
+    pub cfg: Option<String>,
+    /// Where the `#compute_pipeline` is, or the `#instantiate` that made
+    /// it. Only known when it's parsed as part of a [crate::PipelineConfig].
+    pub declared_at: Option<IncludeSite>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct RenderPipelineConfig {
     pub name: String,
+    /// Overrides the debug label given to the shader module, pipeline layout
+    /// and pipeline, which otherwise default to the shader and pipeline names.
+    pub label: Option<String>,
+    /// When the pipeline uses a `#shader` this is filled in from it. It can
+    /// use `${NAME}`s from `CodegenOptions::path_variables` or the
+    /// environment, eg. `${CARGO_MANIFEST_DIR}/shaders/lit.wgsl`.
     pub path: String,
-    pub vs_entry: String,
-    pub fs_entry: String,
+    /// `shader: "pbr"` uses a `#shader` in place of `path`.
+    pub shader: Option<String>,
+    /// When `None` the entry point is inferred from the shader during codegen.
+    pub vs_entry: Option<String>,
+    /// When `None` the entry point is inferred from the shader during codegen.
+    pub fs_entry: Option<String>,
+    pub primitive: PrimitiveConfig,
+    pub depth_stencil: Option<DepthStencilConfig>,
+    /// Lists the MSAA sample counts this pipeline is used with. When set, the
+    /// generated struct takes the sample count as a const generic parameter
+    /// and only accepts the listed counts.
+    pub sample_counts: Option<Vec<u32>>,
+    /// Names the bind group layouts the pipeline layout is made of, in group
+    /// order. Each one becomes a `&wgpu::BindGroupLayout` parameter of the
+    /// generated constructor.
+    pub bind_group_layouts: Vec<String>,
+    /// `bind_groups: ["Camera", ["albedo", "linear_sampler"]]` gives each
+    /// bind group as the name of a `#bind_group` or a list of the
+    /// `#binding_preset`s in it, in binding order. The generated pipeline
+    /// creates these layouts itself, so this can't be used together with
+    /// `bind_group_layouts`.
+    pub bind_groups: Vec<BindGroupRef>,
+    /// `layout: "Scene"` uses a `#pipeline_layout`, which the generated
+    /// constructor takes instead of creating its own layout. It can't be
+    /// used together with `bind_groups` or `bind_group_layouts`.
+    pub layout: Option<String>,
+    /// Names the `#vertex_layout`s of the pipeline's vertex buffers, in slot
+    /// order.
+    pub vertex_buffers: Vec<String>,
+    /// Names the `#color_target`s the fragment stage writes to, in order.
+    pub targets: Vec<String>,
+    /// `target_formats: [Bgra8UnormSrgb]` gives the formats of unblended
+    /// color targets instead of naming `targets`. Each is one of
+    /// [variants::TEXTURE_FORMATS].
+    pub target_formats: Vec<String>,
+    /// `bindings: (t_hdr: (format: Rgba32Float), ...)` overrides what's
+    /// reflected for the named texture, sampler and uniform buffer bindings.
+    pub bindings: Vec<(String, BindingConfig)>,
+    /// `variants: [SKINNED, QUALITY: [LOW, HIGH]]` lists the shader defines
+    /// the pipeline is generated for. Every combination gets its own
+    /// pipeline, along with a key type to pick between them.
+    pub variants: Vec<ShaderDefine>,
+    /// `defines: [SHADOWS]` lists names defined for the shader's `#ifdef`
+    /// blocks in every variant of the pipeline.
+    pub defines: Vec<String>,
+    /// `constants: (MAX_LIGHTS: 64, USE_FOG: 1.0)` gives values to the
+    /// shader's `override` constants, which are baked into its source.
+    pub constants: Vec<(String, f64)>,
+    /// A `cfg` predicate, eg. `feature = "editor"`, that gates everything
+    /// generated for this pipeline. `feature: "editor"` is a shorthand for
+    /// that, and both must hold when both are given.
+    pub cfg: Option<String>,
+    /// Who owns the pipeline. Carried into the generated code and the
+    /// descriptor, but otherwise unused.
+    pub author: Option<String>,
+    /// What the pipeline is for. Also becomes the generated item's docs.
+    pub description: Option<String>,
+    /// Free-form labels for grouping pipelines, eg. `["post", "hdr"]`.
+    pub tags: Vec<String>,
+    /// Where the `render_pipeline` is, or the `#instantiate` that made it.
+    /// Only known when it's parsed as part of a [crate::PipelineConfig].
+    pub declared_at: Option<IncludeSite>,
+    extras: Vec<(String, Value)>,
 }
 
 impl RenderPipelineConfig {
@@ -51,95 +838,984 @@ impl RenderPipelineConfig {
     /// # Errors
     /// - Lex: occurs when failing to convert `src` to a [crate::lex::TokenStream]
     ///
-    pub fn from_src<'a>(src: &'a str) -> Result<Self, ParseError<'_>> {
+    pub fn from_src(src: &str) -> Result<Self, ParseError<'_>> {
         let mut tokens = lex::TokenStream::new(src)?;
-        Self::parse(&mut tokens)
+        let config = Self::parse(&mut tokens)?;
+        if let Some(t) = tokens.next() {
+            return Err(ParseError::ExpectedEndOfInput(t));
+        }
+        Ok(config)
+    }
+
+    /// The free-form `extras: (key: value, ...)` section, in the order it was
+    /// written. pipemd doesn't look at these itself; they're for tools built
+    /// on top of it, such as engine-side generators or driver workarounds.
+    pub fn extras(&self) -> &[(String, Value)] {
+        &self.extras
+    }
+
+    /// Looks up a single entry of [RenderPipelineConfig::extras]. If a key was
+    /// written more than once the last value wins.
+    pub fn extra(&self, key: &str) -> Option<&Value> {
+        self.extras
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
     }
-    
+
+    /// The label to use for the pipeline and its pipeline layout.
+    pub fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.name)
+    }
+
     pub fn parse<'a>(tokens: &mut TokenStream<'a>) -> Result<RenderPipelineConfig, ParseError<'a>> {
-        expect_token(tokens, lex::Token::Ident("render_pipeline"))?;
-        let mut name = None;
-        let mut path = None;
-        let mut vs_entry = None;
-        let mut fs_entry = None;
-        let parse_ident = |tokens: &mut TokenStream<'a>| -> Result<&'a str, ParseError<'a>> {
-            match tokens.next() {
-                Some(lex::Token::Ident(id)) => Ok(id),
-                Some(t) => Err(ParseError::UnexpectedToken {
-                    found: t,
-                    expected: lex::Token::Ident("ident_name"),
-                }),
-                None => Err(ParseError::EndOfInput),
+        Self::parse_with_defaults(tokens, &PipelineDefaults::default())
+    }
+
+    /// Parses a pipeline whose fields start out as `defaults`.
+    pub(crate) fn parse_with_defaults<'a>(
+        tokens: &mut TokenStream<'a>,
+        defaults: &PipelineDefaults<'a>,
+    ) -> Result<RenderPipelineConfig, ParseError<'a>> {
+        expect_token(tokens, lex::Token::ident("render_pipeline"))?;
+        let mut fields = PipelineFields::default();
+        for (field, value) in &defaults.fields {
+            fields.parse_field(field.clone(), &mut TokenStream::from_tokens(value.clone()))?;
+        }
+        parse_struct(tokens, |field, tokens| fields.parse_field(field, tokens))?;
+        fields.finish()
+    }
+}
+
+/// The fields of a `render_pipeline` as they're parsed. If a field is given
+/// more than once the last value wins.
+#[derive(Default)]
+struct PipelineFields<'a> {
+    name: Option<Cow<'a, str>>,
+    label: Option<String>,
+    path: Option<Cow<'a, str>>,
+    shader: Option<String>,
+    vs_entry: Option<Cow<'a, str>>,
+    fs_entry: Option<Cow<'a, str>>,
+    primitive: PrimitiveConfig,
+    depth_stencil: Option<DepthStencilConfig>,
+    sample_counts: Option<Vec<u32>>,
+    bind_group_layouts: Vec<String>,
+    bind_groups: Vec<BindGroupRef>,
+    layout: Option<String>,
+    vertex_buffers: Vec<String>,
+    targets: Vec<String>,
+    target_formats: Vec<String>,
+    bindings: Vec<(String, BindingConfig)>,
+    variants: Vec<ShaderDefine>,
+    defines: Vec<String>,
+    constants: Vec<(String, f64)>,
+    cfg: Option<String>,
+    feature: Option<String>,
+    author: Option<String>,
+    description: Option<String>,
+    tags: Vec<String>,
+    extras: Vec<(String, Value)>,
+}
+
+impl<'a> PipelineFields<'a> {
+    fn parse_field(
+        &mut self,
+        field: Cow<'a, str>,
+        tokens: &mut TokenStream<'a>,
+    ) -> Result<(), ParseError<'a>> {
+        match field.as_ref() {
+            "name" => self.name = Some(parse_string(tokens)?),
+            "label" => self.label = Some(parse_string(tokens)?.into_owned()),
+            "path" => self.path = Some(parse_string(tokens)?),
+            "shader" => self.shader = Some(parse_string(tokens)?.into_owned()),
+            "vs_entry" => self.vs_entry = Some(parse_string(tokens)?),
+            "fs_entry" => self.fs_entry = Some(parse_string(tokens)?),
+            "primitive" => self.primitive.parse(tokens)?,
+            "depth_stencil" => self.depth_stencil = Some(DepthStencilConfig::parse(tokens)?),
+            "sample_counts" => self.sample_counts = Some(parse_sample_counts(tokens, &field)?),
+            "bind_group_layouts" => self.bind_group_layouts = parse_names(tokens, &field)?,
+            "bind_groups" => self.bind_groups = parse_bind_groups(tokens)?,
+            "layout" => self.layout = Some(parse_string(tokens)?.into_owned()),
+            "vertex_buffers" => self.vertex_buffers = parse_names(tokens, &field)?,
+            "targets" => {
+                self.targets = parse_list(tokens, |tokens| Ok(parse_string(tokens)?.into_owned()))?
             }
-        };
-        let mut parse_field = |tokens: &mut TokenStream<'a>| -> Result<(), ParseError<'a>> {
-            let ident = parse_ident(tokens)?;
-            // These fields are simple so we can just use an &mut. If
-            // the fields get more complicated (which is likely) then:
-            // TODO: make this handle nested structures/arrays
-            let field = match ident {
-                "name" => &mut name,
-                "path" => &mut path,
-                "vs_entry" => &mut vs_entry,
-                "fs_entry" => &mut fs_entry,
-                f => return Err(ParseError::UnexpectedField(f)),
-            };
-    
-            expect_token(tokens, lex::Token::Colon)?;
-    
-            *field = match tokens.next() {
-                Some(lex::Token::String(s)) => Some(s),
-                Some(t) => {
-                    return Err(ParseError::UnexpectedToken {
-                        found: t,
-                        expected: lex::Token::String("Some String"),
+            "target_formats" => {
+                self.target_formats = parse_list(tokens, |tokens| {
+                    Ok(parse_variant(tokens, &field, variants::TEXTURE_FORMATS)?.to_owned())
+                })?
+            }
+            "bindings" => parse_struct(tokens, |name, tokens| {
+                self.bindings
+                    .push((name.into_owned(), BindingConfig::parse(tokens)?));
+                Ok(())
+            })?,
+            "variants" => self.variants = parse_shader_defines(tokens, &field)?,
+            "defines" => {
+                self.defines = parse_list(tokens, |tokens| Ok(parse_ident(tokens)?.into_owned()))?
+            }
+            "constants" => parse_struct(tokens, |name, tokens| {
+                if self.constants.iter().any(|(constant, _)| *constant == name) {
+                    return Err(ParseError::InvalidValue {
+                        field: name,
+                        reason: "is given more than one value",
+                    });
+                }
+                self.constants
+                    .push((name.into_owned(), parse_number(tokens)?));
+                Ok(())
+            })?,
+            "author" => self.author = Some(parse_string(tokens)?.into_owned()),
+            "description" => self.description = Some(parse_string(tokens)?.into_owned()),
+            "tags" => {
+                self.tags = parse_list(tokens, |tokens| Ok(parse_string(tokens)?.into_owned()))?
+            }
+            "cfg" => self.cfg = Some(parse_string(tokens)?.into_owned()),
+            "feature" => self.feature = Some(parse_string(tokens)?.into_owned()),
+            "extras" => parse_struct(tokens, |key, tokens| {
+                self.extras.push((key.into_owned(), Value::parse(tokens)?));
+                Ok(())
+            })?,
+            // The primitive state can also be given as top level fields.
+            _ => {
+                if !self.primitive.parse_field(&field, tokens)? {
+                    return Err(ParseError::UnexpectedField(field));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<RenderPipelineConfig, ParseError<'a>> {
+        if !self.bind_groups.is_empty() && !self.bind_group_layouts.is_empty() {
+            return Err(ParseError::InvalidValue {
+                field: "bind_groups".into(),
+                reason: "can't be used together with bind_group_layouts",
+            });
+        }
+        if !self.targets.is_empty() && !self.target_formats.is_empty() {
+            return Err(ParseError::InvalidValue {
+                field: "target_formats".into(),
+                reason: "can't be used together with targets",
+            });
+        }
+        if self
+            .defines
+            .iter()
+            .any(|name| self.variants.iter().any(|define| define.name == *name))
+        {
+            return Err(ParseError::InvalidValue {
+                field: "defines".into(),
+                reason: "can't name a define that's also in variants",
+            });
+        }
+        if self.layout.is_some()
+            && !(self.bind_groups.is_empty() && self.bind_group_layouts.is_empty())
+        {
+            return Err(ParseError::InvalidValue {
+                field: "layout".into(),
+                reason: "can't be used together with bind_groups or bind_group_layouts",
+            });
+        }
+        Ok(RenderPipelineConfig {
+            name: self
+                .name
+                .ok_or(ParseError::MissingField("name"))?
+                .into_owned(),
+            label: self.label,
+            // Filled in once the shader is looked up.
+            path: match (self.path, &self.shader) {
+                (Some(_), Some(_)) => {
+                    return Err(ParseError::InvalidValue {
+                        field: "shader".into(),
+                        reason: "can't be used together with path",
                     })
                 }
-                None => return Err(ParseError::EndOfInput),
-            };
-    
+                (Some(path), None) => path.into_owned(),
+                (None, Some(_)) => String::new(),
+                (None, None) => return Err(ParseError::MissingField("path")),
+            },
+            shader: self.shader,
+            vs_entry: self.vs_entry.map(Cow::into_owned),
+            fs_entry: self.fs_entry.map(Cow::into_owned),
+            primitive: self.primitive,
+            depth_stencil: self.depth_stencil,
+            sample_counts: self.sample_counts,
+            bind_group_layouts: self.bind_group_layouts,
+            bind_groups: self.bind_groups,
+            layout: self.layout,
+            vertex_buffers: self.vertex_buffers,
+            targets: self.targets,
+            target_formats: self.target_formats,
+            bindings: self.bindings,
+            variants: self.variants,
+            defines: self.defines,
+            constants: self.constants,
+            cfg: match (self.cfg, self.feature) {
+                (cfg, None) => cfg,
+                (None, Some(feature)) => Some(format!("feature = {:?}", feature)),
+                (Some(cfg), Some(feature)) => {
+                    Some(format!("all({}, feature = {:?})", cfg, feature))
+                }
+            },
+            author: self.author,
+            description: self.description,
+            tags: self.tags,
+            declared_at: None,
+            extras: self.extras,
+        })
+    }
+}
+
+/// The fields set with `#defaults(...)`, kept as the tokens they were written
+/// with and parsed again for every pipeline they apply to.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PipelineDefaults<'a> {
+    fields: RawFields<'a>,
+}
+
+impl<'a> PipelineDefaults<'a> {
+    /// Adds `fields` after the current defaults so they win over them. Each
+    /// value is checked now so mistakes are reported where they're made.
+    pub(crate) fn extend(&mut self, fields: RawFields<'a>) -> Result<(), ParseError<'a>> {
+        for (field, value) in fields {
+            if field == "name" || field == "label" {
+                return Err(ParseError::InvalidValue {
+                    field,
+                    reason: "every pipeline needs its own, so it can't have a default",
+                });
+            }
+            PipelineFields::default()
+                .parse_field(field.clone(), &mut TokenStream::from_tokens(value.clone()))?;
+            self.fields.push((field, value));
+        }
+        Ok(())
+    }
+}
+
+fn parse_sample_counts<'a>(
+    tokens: &mut TokenStream<'a>,
+    field: &Cow<'a, str>,
+) -> Result<Vec<u32>, ParseError<'a>> {
+    let counts = parse_list(tokens, parse_number::<u32>)?;
+    let invalid = |reason| ParseError::InvalidValue {
+        field: field.clone(),
+        reason,
+    };
+    if counts.is_empty() {
+        return Err(invalid("expected at least one sample count"));
+    }
+    if counts.iter().any(|c| !c.is_power_of_two()) {
+        return Err(invalid("sample counts must be powers of two"));
+    }
+    if counts
+        .iter()
+        .enumerate()
+        .any(|(i, c)| counts[..i].contains(c))
+    {
+        return Err(invalid("sample counts must be unique"));
+    }
+    Ok(counts)
+}
+
+/// A shader define that a pipeline's variants are generated for. Boolean
+/// defines are either set or not; enum defines have one of `values` set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderDefine {
+    pub name: String,
+    /// Empty for boolean defines.
+    pub values: Vec<String>,
+}
+
+impl ShaderDefine {
+    /// How many variants this define splits a pipeline into.
+    pub fn value_count(&self) -> usize {
+        if self.values.is_empty() {
+            2
+        } else {
+            self.values.len()
+        }
+    }
+}
+
+/// Pipelines with more variants than this are rejected, since each one is
+/// compiled separately at runtime.
+const MAX_VARIANTS: usize = 64;
+
+/// Parses `[NAME, NAME: [VALUE, ...], ...]`.
+fn parse_shader_defines<'a>(
+    tokens: &mut TokenStream<'a>,
+    field: &Cow<'a, str>,
+) -> Result<Vec<ShaderDefine>, ParseError<'a>> {
+    let defines = parse_list(tokens, |tokens| {
+        let name = parse_ident(tokens)?.into_owned();
+        let mut values = Vec::new();
+        if let Some(lex::Token::Colon) = tokens.peek() {
+            tokens.next();
+            values = parse_list(tokens, |tokens| Ok(parse_ident(tokens)?.into_owned()))?;
+            if values.is_empty() {
+                return Err(ParseError::InvalidValue {
+                    field: field.clone(),
+                    reason: "enum defines need at least one value",
+                });
+            }
+        }
+        Ok(ShaderDefine { name, values })
+    })?;
+    let invalid = |reason| ParseError::InvalidValue {
+        field: field.clone(),
+        reason,
+    };
+    if defines
+        .iter()
+        .enumerate()
+        .any(|(i, d)| defines[..i].iter().any(|o| o.name == d.name))
+    {
+        return Err(invalid("defines must be unique"));
+    }
+    for define in &defines {
+        if define
+            .values
+            .iter()
+            .enumerate()
+            .any(|(i, v)| define.values[..i].contains(v))
+        {
+            return Err(invalid("the values of a define must be unique"));
+        }
+    }
+    let count = defines
+        .iter()
+        .try_fold(1usize, |count, d| count.checked_mul(d.value_count()));
+    if count.is_none_or(|count| count > MAX_VARIANTS) {
+        return Err(invalid("more than 64 combinations of defines"));
+    }
+    Ok(defines)
+}
+
+/// Parses a list of unique strings that are used as Rust identifiers in the
+/// generated code.
+fn parse_names<'a>(
+    tokens: &mut TokenStream<'a>,
+    field: &Cow<'a, str>,
+) -> Result<Vec<String>, ParseError<'a>> {
+    let names = parse_list(tokens, parse_string)?;
+    let invalid = |reason| ParseError::InvalidValue {
+        field: field.clone(),
+        reason,
+    };
+    let is_ident = |name: &str| {
+        let mut chars = name.chars();
+        chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || c == '_')
+    };
+    if !names.iter().all(|name| is_ident(name)) {
+        return Err(invalid("names must be valid identifiers"));
+    }
+    if names
+        .iter()
+        .enumerate()
+        .any(|(i, n)| names[..i].contains(n))
+    {
+        return Err(invalid("names must be unique"));
+    }
+    Ok(names.into_iter().map(Cow::into_owned).collect())
+}
+
+/// Mirrors `wgpu::PrimitiveState`. Enum values are stored as the name of the
+/// wgpu variant, eg. `"TriangleList"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrimitiveConfig {
+    pub topology: String,
+    pub strip_index_format: Option<String>,
+    pub front_face: String,
+    /// `None` disables culling.
+    pub cull_mode: Option<String>,
+    /// `Line` and `Point` require `wgpu::Features::POLYGON_MODE_LINE` and
+    /// `wgpu::Features::POLYGON_MODE_POINT` respectively.
+    pub polygon_mode: String,
+    /// Requires `wgpu::Features::DEPTH_CLIP_CONTROL`.
+    pub unclipped_depth: bool,
+    /// Requires `wgpu::Features::CONSERVATIVE_RASTERIZATION`.
+    pub conservative: bool,
+}
+
+impl PrimitiveConfig {
+    /// Parses `(topology: TriangleList, cull_mode: Back, ...)` on top of the
+    /// current values.
+    fn parse<'a>(&mut self, tokens: &mut TokenStream<'a>) -> Result<(), ParseError<'a>> {
+        parse_struct(tokens, |field, tokens| {
+            if self.parse_field(&field, tokens)? {
+                Ok(())
+            } else {
+                Err(ParseError::UnexpectedField(field))
+            }
+        })
+    }
+
+    /// Parses the value of `field` if it's one of the primitive state fields.
+    /// Returns `false`, without consuming anything, if it isn't.
+    fn parse_field<'a>(
+        &mut self,
+        field: &Cow<'a, str>,
+        tokens: &mut TokenStream<'a>,
+    ) -> Result<bool, ParseError<'a>> {
+        match field.as_ref() {
+            "topology" => {
+                self.topology =
+                    parse_variant(tokens, field, variants::PRIMITIVE_TOPOLOGIES)?.to_owned()
+            }
+            "strip_index_format" => {
+                self.strip_index_format =
+                    Some(parse_variant(tokens, field, variants::INDEX_FORMATS)?.to_owned())
+            }
+            "front_face" => {
+                self.front_face = parse_variant(tokens, field, variants::FRONT_FACES)?.to_owned()
+            }
+            // `cull` is accepted as a shorthand.
+            "cull_mode" | "cull" => {
+                self.cull_mode = match parse_variant(tokens, field, variants::CULL_MODES)? {
+                    "None" => None,
+                    face => Some(face.to_owned()),
+                }
+            }
+            "polygon_mode" => {
+                self.polygon_mode =
+                    parse_variant(tokens, field, variants::POLYGON_MODES)?.to_owned()
+            }
+            "conservative" => self.conservative = parse_bool(tokens)?,
+            "unclipped_depth" => self.unclipped_depth = parse_bool(tokens)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+impl Default for PrimitiveConfig {
+    fn default() -> Self {
+        Self {
+            topology: "TriangleList".to_owned(),
+            strip_index_format: None,
+            front_face: "Ccw".to_owned(),
+            cull_mode: Some("Back".to_owned()),
+            polygon_mode: "Fill".to_owned(),
+            unclipped_depth: false,
+            conservative: false,
+        }
+    }
+}
+
+/// Mirrors `wgpu::DepthStencilState`. Enum values are stored as the name of
+/// the wgpu variant, eg. `"Depth32Float"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthStencilConfig {
+    pub format: String,
+    pub depth_write_enabled: bool,
+    pub depth_compare: String,
+    pub depth_bias: DepthBiasConfig,
+}
+
+impl DepthStencilConfig {
+    fn parse<'a>(tokens: &mut TokenStream<'a>) -> Result<Self, ParseError<'a>> {
+        let mut format = None;
+        let mut depth_write_enabled = None;
+        let mut depth_compare = None;
+        let mut depth_bias = None;
+        parse_struct(tokens, |field, tokens| {
+            match field.as_ref() {
+                "format" => format = Some(parse_variant(tokens, &field, variants::DEPTH_FORMATS)?),
+                "depth_write_enabled" => depth_write_enabled = Some(parse_bool(tokens)?),
+                "depth_compare" => {
+                    depth_compare =
+                        Some(parse_variant(tokens, &field, variants::COMPARE_FUNCTIONS)?)
+                }
+                "depth_bias" => depth_bias = Some(DepthBiasConfig::parse(tokens)?),
+                _ => return Err(ParseError::UnexpectedField(field)),
+            }
             Ok(())
-        };
-        let mut parse_struct = || -> Result<(), ParseError<'_>> {
-            expect_token(tokens, lex::Token::LeftParen)?;
-    
-            if let Some(lex::Token::Ident(_)) = tokens.peek() {
-                parse_field(tokens)?;
-        
-                while let Some(lex::Token::Comma) = tokens.peek() {
-                    let _ = tokens.next();
-                    if let Some(lex::Token::RightParen) = tokens.peek() {
-                        break;
+        })?;
+        Ok(Self {
+            format: format.ok_or(ParseError::MissingField("format"))?.to_owned(),
+            depth_write_enabled: depth_write_enabled.unwrap_or(true),
+            depth_compare: depth_compare.unwrap_or("Less").to_owned(),
+            depth_bias: depth_bias.unwrap_or_default(),
+        })
+    }
+}
+
+/// A bind group layout entry declared once with `#binding_preset` and used
+/// by name in any number of pipelines' `bind_groups`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindingPreset {
+    pub name: String,
+    pub ty: BindingPresetType,
+    /// Each one of [variants::SHADER_STAGES].
+    pub visibility: Vec<String>,
+}
+
+/// One of a pipeline's `bind_groups`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindGroupRef {
+    /// A group declared with `#bind_group`.
+    Named(String),
+    /// The names of the `#binding_preset`s in the group, in binding order.
+    Presets(Vec<String>),
+}
+
+/// An entry of a `#bind_group` as it's written, before presets are looked
+/// up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum BindGroupEntry {
+    Preset(String),
+    Inline(BindingPreset),
+}
+
+/// A bind group layout declared with `#bind_group`. Each entry's binding is
+/// its index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindGroupConfig {
+    pub name: String,
+    pub entries: Vec<BindingPreset>,
+}
+
+/// A vertex buffer layout declared with `#vertex_layout`. Attributes are
+/// tightly packed in the order they're listed and use consecutive shader
+/// locations starting at `first_location`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexLayoutConfig {
+    pub name: String,
+    /// One of [variants::VERTEX_STEP_MODES]. Defaults to `Vertex`.
+    pub step_mode: String,
+    pub first_location: u32,
+    /// Each one of [variants::VERTEX_FORMATS].
+    pub attributes: Vec<String>,
+}
+
+impl VertexLayoutConfig {
+    /// The shader locations the attributes use.
+    pub fn locations(&self) -> std::ops::Range<u32> {
+        self.first_location..self.first_location + self.attributes.len() as u32
+    }
+
+    fn parse<'a>(tokens: &mut TokenStream<'a>) -> Result<Self, ParseError<'a>> {
+        let mut name = None;
+        let mut step_mode = "Vertex";
+        let mut first_location = 0;
+        let mut attributes = None;
+        parse_struct(tokens, |field, tokens| {
+            match field.as_ref() {
+                "name" => name = Some(parse_string(tokens)?.into_owned()),
+                "step_mode" => {
+                    step_mode = parse_variant(tokens, &field, variants::VERTEX_STEP_MODES)?
+                }
+                "first_location" => first_location = parse_number(tokens)?,
+                "attributes" => {
+                    let formats = parse_list(tokens, |tokens| {
+                        parse_variant(tokens, &field, variants::VERTEX_FORMATS)
+                    })?;
+                    if formats.is_empty() {
+                        return Err(ParseError::InvalidValue {
+                            field,
+                            reason: "expected at least one attribute",
+                        });
                     }
-                    parse_field(tokens)?;
+                    attributes = Some(formats.into_iter().map(str::to_owned).collect());
                 }
+                _ => return Err(ParseError::UnexpectedField(field)),
             }
-    
-            expect_token(tokens, lex::Token::RightParen)?;
-    
             Ok(())
-        };
-        parse_struct()?;
-        if let Some(t) = tokens.next() {
-            return Err(ParseError::ExpectedEndOfInput(t));
-        }
+        })?;
+        Ok(Self {
+            name: name.ok_or(ParseError::MissingField("name"))?,
+            step_mode: step_mode.to_owned(),
+            first_location,
+            attributes: attributes.ok_or(ParseError::MissingField("attributes"))?,
+        })
+    }
+}
+
+/// Parses one of [variants::SHADER_STAGES] or a non-empty list of them.
+fn parse_stages<'a>(
+    tokens: &mut TokenStream<'a>,
+    field: Cow<'a, str>,
+) -> Result<Vec<String>, ParseError<'a>> {
+    let stages = match tokens.peek() {
+        Some(lex::Token::LeftBracket) => parse_list(tokens, |tokens| {
+            parse_variant(tokens, &field, variants::SHADER_STAGES)
+        })?,
+        _ => vec![parse_variant(tokens, &field, variants::SHADER_STAGES)?],
+    };
+    if stages.is_empty() {
+        return Err(ParseError::InvalidValue {
+            field,
+            reason: "expected at least one stage",
+        });
+    }
+    Ok(stages.into_iter().map(str::to_owned).collect())
+}
+
+/// Parses `["Camera", ["albedo", "linear_sampler"]]`, where each group is the
+/// name of a `#bind_group` or a list of `#binding_preset`s.
+fn parse_bind_groups<'a>(
+    tokens: &mut TokenStream<'a>,
+) -> Result<Vec<BindGroupRef>, ParseError<'a>> {
+    parse_list(tokens, |tokens| match tokens.peek() {
+        Some(lex::Token::LeftBracket) => Ok(BindGroupRef::Presets(parse_list(tokens, |tokens| {
+            Ok(parse_string(tokens)?.into_owned())
+        })?)),
+        _ => Ok(BindGroupRef::Named(parse_string(tokens)?.into_owned())),
+    })
+}
+
+/// A pipeline layout declared with `#pipeline_layout`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipelineLayoutConfig {
+    pub name: String,
+    /// The layout's bind groups, given like a pipeline's `bind_groups`.
+    pub bind_groups: Vec<BindGroupRef>,
+    /// `push_constants: [(stages: Vertex, range: [0, 64])]`. Using any
+    /// requires `wgpu::Features::PUSH_CONSTANTS`.
+    pub push_constants: Vec<PushConstantRangeConfig>,
+}
+
+/// Mirrors `wgpu::PushConstantRange`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushConstantRangeConfig {
+    /// Each one of [variants::SHADER_STAGES].
+    pub stages: Vec<String>,
+    pub range: std::ops::Range<u32>,
+}
+
+impl PipelineLayoutConfig {
+    fn parse<'a>(tokens: &mut TokenStream<'a>) -> Result<Self, ParseError<'a>> {
+        let mut name = None;
+        let mut bind_groups = Vec::new();
+        let mut push_constants = Vec::new();
+        parse_struct(tokens, |field, tokens| {
+            match field.as_ref() {
+                "name" => name = Some(parse_string(tokens)?.into_owned()),
+                "bind_groups" => bind_groups = parse_bind_groups(tokens)?,
+                "push_constants" => {
+                    push_constants = parse_list(tokens, PushConstantRangeConfig::parse)?
+                }
+                _ => return Err(ParseError::UnexpectedField(field)),
+            }
+            Ok(())
+        })?;
+        Ok(Self {
+            name: name.ok_or(ParseError::MissingField("name"))?,
+            bind_groups,
+            push_constants,
+        })
+    }
+}
+
+impl PushConstantRangeConfig {
+    fn parse<'a>(tokens: &mut TokenStream<'a>) -> Result<Self, ParseError<'a>> {
+        let mut stages = None;
+        let mut range = None;
+        parse_struct(tokens, |field, tokens| {
+            match field.as_ref() {
+                "stages" => stages = Some(parse_stages(tokens, field)?),
+                "range" => match parse_list(tokens, parse_number::<u32>)?[..] {
+                    [start, end] if start < end => range = Some(start..end),
+                    _ => {
+                        return Err(ParseError::InvalidValue {
+                            field,
+                            reason: "expected `[start, end]` with start before end",
+                        })
+                    }
+                },
+                _ => return Err(ParseError::UnexpectedField(field)),
+            }
+            Ok(())
+        })?;
+        Ok(Self {
+            stages: stages.ok_or(ParseError::MissingField("stages"))?,
+            range: range.ok_or(ParseError::MissingField("range"))?,
+        })
+    }
+}
+
+/// A color target declared with `#color_target`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorTargetConfig {
+    pub name: String,
+    /// One of [variants::TEXTURE_FORMATS].
+    pub format: String,
+    /// One of [variants::BLEND_MODES]. `None` writes without blending.
+    pub blend: Option<String>,
+}
+
+impl ColorTargetConfig {
+    fn parse<'a>(tokens: &mut TokenStream<'a>) -> Result<Self, ParseError<'a>> {
+        let mut name = None;
+        let mut format = None;
+        let mut blend = None;
+        parse_struct(tokens, |field, tokens| {
+            match field.as_ref() {
+                "name" => name = Some(parse_string(tokens)?.into_owned()),
+                "format" => {
+                    format =
+                        Some(parse_variant(tokens, &field, variants::TEXTURE_FORMATS)?.to_owned())
+                }
+                "blend" => {
+                    blend = Some(parse_variant(tokens, &field, variants::BLEND_MODES)?.to_owned())
+                }
+                _ => return Err(ParseError::UnexpectedField(field)),
+            }
+            Ok(())
+        })?;
+        Ok(Self {
+            name: name.ok_or(ParseError::MissingField("name"))?,
+            format: format.ok_or(ParseError::MissingField("format"))?,
+            blend,
+        })
+    }
+}
+
+/// A Rust vertex type declared with `#import_rust`, used in place of a
+/// generated vertex layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RustImportConfig {
+    pub name: String,
+    /// The path of the type, eg. `crate::mesh::Vertex`.
+    pub path: String,
+    /// The associated `wgpu::VertexBufferLayout<'static>` const of the type.
+    /// Defaults to `LAYOUT`.
+    pub layout: String,
+}
+
+impl RustImportConfig {
+    fn parse<'a>(tokens: &mut TokenStream<'a>) -> Result<Self, ParseError<'a>> {
+        let mut name = None;
+        let mut path = None;
+        let mut layout = None;
+        parse_struct(tokens, |field, tokens| {
+            match field.as_ref() {
+                "name" => name = Some(parse_string(tokens)?.into_owned()),
+                "path" => path = Some(parse_string(tokens)?.into_owned()),
+                "layout" => layout = Some(parse_string(tokens)?.into_owned()),
+                _ => return Err(ParseError::UnexpectedField(field)),
+            }
+            Ok(())
+        })?;
+        Ok(Self {
+            name: name.ok_or(ParseError::MissingField("name"))?,
+            path: path.ok_or(ParseError::MissingField("path"))?,
+            layout: layout.unwrap_or_else(|| "LAYOUT".to_owned()),
+        })
+    }
+}
+
+/// A shader module declared with `#shader`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderModuleConfig {
+    pub name: String,
+    /// Like every shader path, it can use `${NAME}`s from
+    /// `CodegenOptions::path_variables` or the environment.
+    pub path: String,
+    /// `lang: Glsl` is one of [variants::SHADER_LANGUAGES]. Defaults to
+    /// `Glsl` for `.vert`, `.frag`, `.comp` and `.glsl` files and `Wgsl`
+    /// otherwise.
+    pub language: String,
+    /// `defines: [SKINNED]` are defined for the shader's `#ifdef`s in every
+    /// pipeline that uses it, along with the defines of each pipeline's
+    /// `variants`.
+    pub defines: Vec<String>,
+}
+
+impl ShaderModuleConfig {
+    fn parse<'a>(tokens: &mut TokenStream<'a>) -> Result<Self, ParseError<'a>> {
+        let mut name = None;
+        let mut path = None;
+        let mut language = None;
+        let mut defines = Vec::new();
+        parse_struct(tokens, |field, tokens| {
+            match field.as_ref() {
+                "name" => name = Some(parse_string(tokens)?.into_owned()),
+                "path" => path = Some(parse_string(tokens)?.into_owned()),
+                "lang" | "language" => {
+                    language = Some(parse_variant(tokens, &field, variants::SHADER_LANGUAGES)?)
+                }
+                "defines" => {
+                    defines = parse_list(tokens, |tokens| Ok(parse_ident(tokens)?.into_owned()))?
+                }
+                _ => return Err(ParseError::UnexpectedField(field)),
+            }
+            Ok(())
+        })?;
+        let path = path.ok_or(ParseError::MissingField("path"))?;
+        Ok(Self {
+            name: name.ok_or(ParseError::MissingField("name"))?,
+            language: language.unwrap_or_else(|| path_language(&path)).to_owned(),
+            path,
+            defines,
+        })
+    }
+}
+
+/// The language of a shader without a `lang`, from its extension, eg.
+/// `Glsl` for `blur.frag`, `blur.frag.glsl` or `blur.glsl`.
+pub(crate) fn path_language(path: &str) -> &'static str {
+    let name = Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    match name
+        .split('.')
+        .skip(1)
+        .any(|extension| matches!(extension, "vert" | "frag" | "comp" | "glsl"))
+    {
+        true => "Glsl",
+        false => "Wgsl",
+    }
+}
+
+/// Mirrors the parts of `wgpu::BindingType` that presets can describe.
+/// Textures are always 2D and not multisampled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindingPresetType {
+    Uniform,
+    Storage {
+        read_only: bool,
+    },
+    /// One of [variants::SAMPLER_BINDING_TYPES].
+    Sampler(String),
+    /// One of [variants::TEXTURE_SAMPLE_TYPES].
+    Texture(String),
+}
+
+impl BindingPreset {
+    fn parse<'a>(tokens: &mut TokenStream<'a>) -> Result<Self, ParseError<'a>> {
+        let mut name = None;
+        let mut ty = None;
+        let mut visibility = None;
+        parse_struct(tokens, |field, tokens| {
+            match field.as_ref() {
+                "name" => name = Some(parse_string(tokens)?.into_owned()),
+                "ty" => ty = Some(BindingPresetType::parse(tokens, &field)?),
+                "visibility" => visibility = Some(parse_stages(tokens, field)?),
+                _ => return Err(ParseError::UnexpectedField(field)),
+            }
+            Ok(())
+        })?;
         Ok(Self {
-            name: name
-                .ok_or_else(|| ParseError::MissingField("name"))?
-                .to_owned(),
-            path: path
-                .ok_or_else(|| ParseError::MissingField("path"))?
-                .to_owned(),
-            vs_entry: vs_entry
-                .ok_or_else(|| ParseError::MissingField("vs_entry"))?
-                .to_owned(),
-            fs_entry: fs_entry
-                .ok_or_else(|| ParseError::MissingField("fs_entry"))?
-                .to_owned(),
+            name: name.ok_or(ParseError::MissingField("name"))?,
+            ty: ty.ok_or(ParseError::MissingField("ty"))?,
+            visibility: visibility.ok_or(ParseError::MissingField("visibility"))?,
         })
     }
 }
 
+impl BindingPresetType {
+    /// Parses `Uniform`, `Storage`, `ReadOnlyStorage`, `Sampler(kind)` or
+    /// `Texture(sample_type)`.
+    fn parse<'a>(
+        tokens: &mut TokenStream<'a>,
+        field: &Cow<'a, str>,
+    ) -> Result<Self, ParseError<'a>> {
+        let parse_argument =
+            |tokens: &mut TokenStream<'a>, variants| -> Result<String, ParseError<'a>> {
+                expect_token(tokens, lex::Token::LeftParen)?;
+                let argument = parse_variant(tokens, field, variants)?;
+                expect_token(tokens, lex::Token::RightParen)?;
+                Ok(argument.to_owned())
+            };
+        let ty = parse_ident(tokens)?;
+        match ty.as_ref() {
+            "Uniform" => Ok(Self::Uniform),
+            "Storage" => Ok(Self::Storage { read_only: false }),
+            "ReadOnlyStorage" => Ok(Self::Storage { read_only: true }),
+            "Sampler" => Ok(Self::Sampler(parse_argument(
+                tokens,
+                variants::SAMPLER_BINDING_TYPES,
+            )?)),
+            "Texture" => Ok(Self::Texture(parse_argument(
+                tokens,
+                variants::TEXTURE_SAMPLE_TYPES,
+            )?)),
+            _ => Err(ParseError::InvalidVariant {
+                field: field.clone(),
+                found: ty,
+                expected: &[
+                    "Uniform",
+                    "Storage",
+                    "ReadOnlyStorage",
+                    "Sampler",
+                    "Texture",
+                ],
+            }),
+        }
+    }
+}
+
+/// Overrides what's reflected for a texture, sampler or uniform buffer
+/// binding, which is named after its global variable in the shader.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BindingConfig {
+    /// Textures only. One of [variants::TEXTURE_SAMPLE_TYPES].
+    pub sample_type: Option<String>,
+    /// Textures only. The format of the textures that will be bound, which
+    /// decides the sample type unless `sample_type` is given.
+    pub format: Option<String>,
+    /// Samplers only. Whether the sampler filters.
+    pub filtering: Option<bool>,
+    /// Uniform buffers only. Whether the buffer is bound with an offset
+    /// given when its bind group is set, eg. to pick each object's data out
+    /// of one buffer.
+    pub dynamic: Option<bool>,
+}
+
+impl BindingConfig {
+    fn parse<'a>(tokens: &mut TokenStream<'a>) -> Result<Self, ParseError<'a>> {
+        let mut binding = Self::default();
+        parse_struct(tokens, |field, tokens| {
+            match field.as_ref() {
+                "sample_type" => {
+                    binding.sample_type = Some(
+                        parse_variant(tokens, &field, variants::TEXTURE_SAMPLE_TYPES)?.to_owned(),
+                    )
+                }
+                "format" => {
+                    binding.format =
+                        Some(parse_variant(tokens, &field, variants::TEXTURE_FORMATS)?.to_owned())
+                }
+                "filtering" => binding.filtering = Some(parse_bool(tokens)?),
+                "dynamic" => binding.dynamic = Some(parse_bool(tokens)?),
+                _ => return Err(ParseError::UnexpectedField(field)),
+            }
+            Ok(())
+        })?;
+        Ok(binding)
+    }
+}
+
+/// Mirrors `wgpu::DepthBiasState`. Commonly used by shadow map pipelines to
+/// avoid shadow acne.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DepthBiasConfig {
+    pub constant: i32,
+    pub slope_scale: f32,
+    pub clamp: f32,
+}
+
+impl DepthBiasConfig {
+    fn parse<'a>(tokens: &mut TokenStream<'a>) -> Result<Self, ParseError<'a>> {
+        let mut bias = Self::default();
+        parse_struct(tokens, |field, tokens| {
+            match field.as_ref() {
+                "constant" => bias.constant = parse_number(tokens)?,
+                "slope_scale" => bias.slope_scale = parse_number(tokens)?,
+                "clamp" => bias.clamp = parse_number(tokens)?,
+                _ => return Err(ParseError::UnexpectedField(field)),
+            }
+            Ok(())
+        })?;
+        Ok(bias)
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -169,15 +1845,86 @@ mod tests {
             assert_eq!(
                 Ok(RenderPipelineConfig {
                     name: "TexturedPipeline".to_owned(),
+                    label: None,
                     path: "pipeline.pmd".to_owned(),
-                    vs_entry: "vs_textured".to_owned(),
-                    fs_entry: "fs_textured".to_owned()
+                    shader: None,
+                    vs_entry: Some("vs_textured".to_owned()),
+                    fs_entry: Some("fs_textured".to_owned()),
+                    primitive: PrimitiveConfig::default(),
+                    depth_stencil: None,
+                    sample_counts: None,
+                    bind_group_layouts: Vec::new(),
+                    bind_groups: Vec::new(),
+                    layout: None,
+                    vertex_buffers: Vec::new(),
+                    targets: Vec::new(),
+                    target_formats: Vec::new(),
+                    bindings: Vec::new(),
+                    variants: Vec::new(),
+                    defines: Vec::new(),
+                    constants: Vec::new(),
+                    cfg: None,
+                    author: None,
+                    description: None,
+                    tags: Vec::new(),
+                    declared_at: None,
+                    extras: Vec::new(),
                 }),
                 RenderPipelineConfig::from_src(src),
             )
         }
     }
 
+    #[test]
+    fn render_pipeline_config_parse_bindings() {
+        let config = RenderPipelineConfig::from_src(
+            r#"render_pipeline(
+                name: "HdrPipeline",
+                path: "hdr.wgsl",
+                bindings: (
+                    t_hdr: (format: Rgba32Float, sample_type: UnfilterableFloat),
+                    s_nearest: (filtering: false),
+                    camera: (dynamic: true),
+                ),
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                (
+                    "t_hdr".to_owned(),
+                    BindingConfig {
+                        sample_type: Some("UnfilterableFloat".to_owned()),
+                        format: Some("Rgba32Float".to_owned()),
+                        filtering: None,
+                        dynamic: None,
+                    },
+                ),
+                (
+                    "s_nearest".to_owned(),
+                    BindingConfig {
+                        filtering: Some(false),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "camera".to_owned(),
+                    BindingConfig {
+                        dynamic: Some(true),
+                        ..Default::default()
+                    },
+                ),
+            ],
+            config.bindings,
+        );
+        assert!(matches!(
+            RenderPipelineConfig::from_src(
+                r#"render_pipeline(name: "A", path: "a.wgsl", bindings: (t: (format: Rgb32)))"#
+            ),
+            Err(ParseError::InvalidVariant { .. }),
+        ));
+    }
+
     #[test]
     fn render_pipeline_config_parse_missing_fields() {
         let configs = [
@@ -194,4 +1941,601 @@ mod tests {
         }
     }
 
+    #[test]
+    fn render_pipeline_config_parse_bools() {
+        let src = r#"
+            render_pipeline(
+                name: "Voxelize",
+                path: "voxelize.wgsl",
+                vs_entry: "vs_main",
+                fs_entry: "fs_main",
+                conservative: true,
+                unclipped_depth: false,
+            )
+        "#;
+        let config = RenderPipelineConfig::from_src(src).unwrap();
+        assert!(config.primitive.conservative);
+        assert!(!config.primitive.unclipped_depth);
+
+        let src = r#"render_pipeline(conservative: "yes")"#;
+        assert_eq!(
+            Err(ParseError::UnexpectedToken {
+                found: lex::Token::string("yes"),
+                expected: lex::Token::ident("true or false"),
+            }),
+            RenderPipelineConfig::from_src(src),
+        );
+    }
+
+    #[test]
+    fn render_pipeline_config_parse_depth_stencil() {
+        let src = r#"
+            render_pipeline(
+                name: "ShadowPipeline",
+                path: "shadow.wgsl",
+                vs_entry: "vs_main",
+                fs_entry: "fs_main",
+                depth_stencil: (
+                    format: "Depth32Float",
+                    depth_compare: "LessEqual",
+                    depth_bias: (constant: 2, slope_scale: 2.0, clamp: 0.0),
+                ),
+            )
+        "#;
+        let config = RenderPipelineConfig::from_src(src).unwrap();
+        assert_eq!(
+            Some(DepthStencilConfig {
+                format: "Depth32Float".to_owned(),
+                depth_write_enabled: true,
+                depth_compare: "LessEqual".to_owned(),
+                depth_bias: DepthBiasConfig {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            config.depth_stencil,
+        );
+
+        let src = r#"render_pipeline(depth_stencil: (depth_bias: (constant: 0.5)))"#;
+        assert_eq!(
+            Err(ParseError::InvalidNumber("0.5".into())),
+            RenderPipelineConfig::from_src(src),
+        );
+        let src = r#"render_pipeline(depth_stencil: (depth_compare: "Less"))"#;
+        assert_eq!(
+            Err(ParseError::MissingField("format")),
+            RenderPipelineConfig::from_src(src),
+        );
+    }
+
+    #[test]
+    fn render_pipeline_config_parse_cfg() {
+        let src = r#"
+            render_pipeline(
+                name: "GizmoPipeline",
+                path: "gizmo.wgsl",
+                vs_entry: "vs_main",
+                fs_entry: "fs_main",
+                cfg: "feature = \"editor\"",
+            )
+        "#;
+        let config = RenderPipelineConfig::from_src(src).unwrap();
+        assert_eq!(Some(r#"feature = "editor""#), config.cfg.as_deref());
+
+        let src =
+            r#"render_pipeline(name: "GizmoPipeline", path: "gizmo.wgsl", feature: "editor")"#;
+        let config = RenderPipelineConfig::from_src(src).unwrap();
+        assert_eq!(Some(r#"feature = "editor""#), config.cfg.as_deref());
+        let src = r#"render_pipeline(name: "GizmoPipeline", path: "gizmo.wgsl", feature: "editor", cfg: "debug_assertions")"#;
+        let config = RenderPipelineConfig::from_src(src).unwrap();
+        assert_eq!(
+            Some(r#"all(debug_assertions, feature = "editor")"#),
+            config.cfg.as_deref()
+        );
+    }
+
+    #[test]
+    fn render_pipeline_config_parse_label() {
+        let src = r#"
+            render_pipeline(
+                name: "TextPipeline",
+                label: "ui/text (alpha blended)",
+                path: "text.wgsl",
+                vs_entry: "vs_main",
+                fs_entry: "fs_main",
+            )
+        "#;
+        let config = RenderPipelineConfig::from_src(src).unwrap();
+        assert_eq!("ui/text (alpha blended)", config.label());
+
+        let src = src.replace(r#"label: "ui/text (alpha blended)","#, "");
+        let config = RenderPipelineConfig::from_src(&src).unwrap();
+        assert_eq!("TextPipeline", config.label());
+    }
+
+    #[test]
+    fn render_pipeline_config_parse_variants() {
+        let src = r#"
+            render_pipeline(
+                name: "LinePipeline",
+                path: "lines.wgsl",
+                vs_entry: "vs_main",
+                fs_entry: "fs_main",
+                topology: LineStrip,
+                strip_index_format: "Uint32",
+                cull_mode: None,
+                depth_stencil: (format: Depth32Float, depth_compare: Always),
+            )
+        "#;
+        let config = RenderPipelineConfig::from_src(src).unwrap();
+        assert_eq!("LineStrip", config.primitive.topology);
+        assert_eq!(
+            Some("Uint32"),
+            config.primitive.strip_index_format.as_deref()
+        );
+        assert_eq!(None, config.primitive.cull_mode);
+        assert_eq!("Ccw", config.primitive.front_face);
+        let depth_stencil = config.depth_stencil.unwrap();
+        assert_eq!("Depth32Float", depth_stencil.format);
+        assert_eq!("Always", depth_stencil.depth_compare);
+
+        let err =
+            RenderPipelineConfig::from_src("render_pipeline(cull_mode: Backwards)").unwrap_err();
+        assert_eq!(
+            ParseError::InvalidVariant {
+                field: "cull_mode".into(),
+                found: "Backwards".into(),
+                expected: variants::CULL_MODES,
+            },
+            err,
+        );
+        assert_eq!(
+            r#"Invalid value "Backwards" for "cull_mode", expected one of: None, Front, Back"#,
+            err.to_string(),
+        );
+        assert!(matches!(
+            RenderPipelineConfig::from_src("render_pipeline(depth_stencil: (format: Rgba8Unorm))"),
+            Err(ParseError::InvalidVariant { field, .. }) if field == "format",
+        ));
+    }
+
+    #[test]
+    fn render_pipeline_config_parse_sample_counts() {
+        let src = r#"
+            render_pipeline(
+                name: "TexturedPipeline",
+                path: "texture.wgsl",
+                vs_entry: "vs_main",
+                fs_entry: "fs_main",
+                sample_counts: [1, 4,],
+            )
+        "#;
+        let config = RenderPipelineConfig::from_src(src).unwrap();
+        assert_eq!(Some(vec![1, 4]), config.sample_counts);
+
+        for (counts, reason) in [
+            ("[]", "expected at least one sample count"),
+            ("[1, 3]", "sample counts must be powers of two"),
+            ("[4, 4]", "sample counts must be unique"),
+        ] {
+            let src = format!("render_pipeline(sample_counts: {})", counts);
+            assert_eq!(
+                Err(ParseError::InvalidValue {
+                    field: "sample_counts".into(),
+                    reason,
+                }),
+                RenderPipelineConfig::from_src(&src),
+            );
+        }
+        assert!(matches!(
+            RenderPipelineConfig::from_src("render_pipeline(sample_counts: [1 4])"),
+            Err(ParseError::UnexpectedToken { .. }),
+        ));
+    }
+
+    #[test]
+    fn render_pipeline_config_parse_lists() {
+        let src = r#"
+            render_pipeline(
+                name: "TexturedPipeline",
+                path: "texture.wgsl",
+                vs_entry: "vs_main",
+                fs_entry: "fs_main",
+                bind_group_layouts: ["camera", "material"],
+            )
+        "#;
+        let config = RenderPipelineConfig::from_src(src).unwrap();
+        assert_eq!(vec!["camera", "material"], config.bind_group_layouts);
+
+        for (names, reason) in [
+            (r#"["camera", "camera"]"#, "names must be unique"),
+            (r#"["2d"]"#, "names must be valid identifiers"),
+            (r#"["camera", ""]"#, "names must be valid identifiers"),
+        ] {
+            let src = format!("render_pipeline(bind_group_layouts: {})", names);
+            assert_eq!(
+                Err(ParseError::InvalidValue {
+                    field: "bind_group_layouts".into(),
+                    reason,
+                }),
+                RenderPipelineConfig::from_src(&src),
+            );
+        }
+        assert_eq!(
+            Err(ParseError::UnexpectedToken {
+                found: lex::Token::ident("camera"),
+                expected: lex::Token::string("Some String"),
+            }),
+            RenderPipelineConfig::from_src("render_pipeline(bind_group_layouts: [camera])"),
+        );
+    }
+
+    #[test]
+    fn render_pipeline_config_parse_nested_primitive() {
+        let src = r#"
+            render_pipeline(
+                name: "TexturedPipeline",
+                path: "texture.wgsl",
+                vs_entry: "vs_main",
+                fs_entry: "fs_main",
+                primitive: (
+                    topology: TriangleStrip,
+                    strip_index_format: Uint16,
+                    cull: None,
+                ),
+            )
+        "#;
+        let config = RenderPipelineConfig::from_src(src).unwrap();
+        assert_eq!(
+            PrimitiveConfig {
+                topology: "TriangleStrip".to_owned(),
+                strip_index_format: Some("Uint16".to_owned()),
+                cull_mode: None,
+                ..Default::default()
+            },
+            config.primitive,
+        );
+        assert_eq!(
+            Err(ParseError::UnexpectedField("name".into())),
+            RenderPipelineConfig::from_src(r#"render_pipeline(primitive: (name: "Nested"))"#),
+        );
+    }
+
+    #[test]
+    fn value_parse() {
+        let value = Value::from_src(
+            r#"(vendor: "adreno", versions: [1, 2.5,], enabled: true, nested: (a: b))"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Value::Map(vec![
+                ("vendor".to_owned(), Value::String("adreno".to_owned())),
+                (
+                    "versions".to_owned(),
+                    Value::List(vec![
+                        Value::Number("1".to_owned()),
+                        Value::Number("2.5".to_owned()),
+                    ]),
+                ),
+                ("enabled".to_owned(), Value::Ident("true".to_owned())),
+                (
+                    "nested".to_owned(),
+                    Value::Map(vec![("a".to_owned(), Value::Ident("b".to_owned()))]),
+                ),
+            ]),
+            value,
+        );
+        assert_eq!(Some("adreno"), value.get("vendor").and_then(Value::as_str));
+        assert_eq!(Some(true), value.get("enabled").and_then(Value::as_bool));
+        let versions = value.get("versions").and_then(Value::as_list).unwrap();
+        assert_eq!(Some(2.5), versions[1].as_number::<f32>());
+        assert_eq!(None, versions[1].as_number::<u32>());
+        assert_eq!(
+            Some("b"),
+            value
+                .get("nested")
+                .and_then(|n| n.get("a"))
+                .and_then(Value::as_str)
+        );
+
+        assert_eq!(Ok(Value::List(Vec::new())), Value::from_src("[]"));
+        assert_eq!(
+            Ok(Value::String("say \"hi\"".to_owned())),
+            Value::from_src(r#""say \"hi\"""#)
+        );
+        assert!(matches!(Value::from_src(""), Err(ParseError::Lex(_))));
+        assert_eq!(Err(ParseError::EndOfInput), Value::from_src("[1, 2"));
+        assert!(matches!(
+            Value::from_src("[1 2]"),
+            Err(ParseError::UnexpectedToken { .. })
+        ));
+    }
+
+    #[test]
+    fn render_pipeline_config_parse_extras() {
+        let src = r#"
+            render_pipeline(
+                name: "TexturedPipeline",
+                path: "texture.wgsl",
+                vs_entry: "vs_main",
+                fs_entry: "fs_main",
+                extras: (
+                    workaround: "disable on Adreno",
+                    max_batch: 64,
+                    workaround: "disable on Mali",
+                ),
+            )
+        "#;
+        let config = RenderPipelineConfig::from_src(src).unwrap();
+        let keys = config
+            .extras()
+            .iter()
+            .map(|(k, _)| k.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["workaround", "max_batch", "workaround"], keys);
+        assert_eq!(
+            Some("disable on Mali"),
+            config.extra("workaround").and_then(Value::as_str)
+        );
+        assert_eq!(
+            Some(64),
+            config.extra("max_batch").and_then(Value::as_number::<u32>)
+        );
+        assert_eq!(None, config.extra("missing"));
+
+        assert!(matches!(
+            RenderPipelineConfig::from_src(r#"render_pipeline(extras: ["not", "a", "map"])"#),
+            Err(ParseError::UnexpectedToken { .. }),
+        ));
+    }
+
+    #[test]
+    fn directive_parse() {
+        let mut tokens = TokenStream::new(r#"#include("common.pmd") #inclde("x")"#).unwrap();
+        assert_eq!(
+            Ok(Directive::Include("common.pmd".into())),
+            Directive::parse(&mut tokens)
+        );
+        assert_eq!(
+            Err(ParseError::UnknownDirective("inclde".into())),
+            Directive::parse(&mut tokens)
+        );
+        assert_eq!(
+            format!("Unknown directive: #inclde. It may need a newer pipemd, this one supports grammar {}", GRAMMAR_VERSION),
+            ParseError::UnknownDirective("inclde".into()).to_string()
+        );
+
+        let mut tokens = TokenStream::new(r#"#cfg("feature = \"bloom\"") ["#).unwrap();
+        assert_eq!(
+            Ok(Directive::Cfg(r#"feature = "bloom""#.into())),
+            Directive::parse(&mut tokens)
+        );
+        assert_eq!(Some(&lex::Token::LeftBracket), tokens.peek());
+
+        let mut tokens = TokenStream::new("#defaults(sample_counts: [4])").unwrap();
+        assert_eq!(
+            Ok(Directive::Defaults(vec![(
+                "sample_counts".into(),
+                vec![
+                    lex::Token::LeftBracket,
+                    lex::Token::number("4"),
+                    lex::Token::RightBracket
+                ],
+            )])),
+            Directive::parse(&mut tokens),
+        );
+
+        let mut tokens = TokenStream::new("#template(blit, fs_entry) [ render_pipeline() ] #instantiate(blit, fs_entry: \"fs_blit\")").unwrap();
+        assert_eq!(
+            Ok(Directive::Template {
+                name: "blit".into(),
+                params: vec!["fs_entry".into()],
+                body: vec![
+                    lex::Token::ident("render_pipeline"),
+                    lex::Token::LeftParen,
+                    lex::Token::RightParen
+                ],
+            }),
+            Directive::parse(&mut tokens),
+        );
+        assert_eq!(
+            Ok(Directive::Instantiate {
+                name: "blit".into(),
+                args: vec![(
+                    "fs_entry".into(),
+                    vec![lex::Token::String("fs_blit".into())]
+                )],
+            }),
+            Directive::parse(&mut tokens),
+        );
+
+        let mut tokens =
+            TokenStream::new(r#"#grammar("1.2") #grammar("99.0") #grammar("one")"#).unwrap();
+        assert_eq!(
+            Ok(Directive::Grammar(GrammarVersion::new(1, 2, 0))),
+            Directive::parse(&mut tokens)
+        );
+        let e = Directive::parse(&mut tokens).unwrap_err();
+        assert_eq!(
+            ParseError::UnsupportedGrammar(GrammarVersion::new(99, 0, 0)),
+            e
+        );
+        assert!(e.to_string().ends_with("Upgrade pipemd to use it"), "{}", e);
+        assert!(matches!(
+            Directive::parse(&mut tokens),
+            Err(ParseError::InvalidGrammarVersion(_))
+        ));
+
+        let mut tokens = TokenStream::new(
+            r#"#compute_pipeline("Cull", "cs_cull", "cull.wgsl") #compute_pipeline("Cull")"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Ok(Directive::ComputePipeline(ComputePipelineConfig {
+                name: "Cull".to_owned(),
+                entry: "cs_cull".to_owned(),
+                path: "cull.wgsl".to_owned(),
+                shader: None,
+                cfg: None,
+                declared_at: None,
+            })),
+            Directive::parse(&mut tokens),
+        );
+        assert!(matches!(
+            Directive::parse(&mut tokens),
+            Err(ParseError::UnexpectedToken { .. })
+        ));
+
+        let src = r#"#bind_group(name: "Camera", entries: ["view", (name: "lights", ty: ReadOnlyStorage, visibility: Fragment)])"#;
+        let mut tokens = TokenStream::new(src).unwrap();
+        assert_eq!(
+            Ok(Directive::BindGroup {
+                name: "Camera".into(),
+                entries: vec![
+                    BindGroupEntry::Preset("view".to_owned()),
+                    BindGroupEntry::Inline(BindingPreset {
+                        name: "lights".to_owned(),
+                        ty: BindingPresetType::Storage { read_only: true },
+                        visibility: vec!["Fragment".to_owned()],
+                    }),
+                ],
+            }),
+            Directive::parse(&mut tokens),
+        );
+        let mut tokens = TokenStream::new(r#"#bind_group(name: "Camera")"#).unwrap();
+        assert_eq!(
+            Err(ParseError::MissingField("entries")),
+            Directive::parse(&mut tokens)
+        );
+
+        let src = r#"#vertex_layout(name: "Instance", step_mode: Instance, first_location: 5, attributes: [Float32x4, Uint32])"#;
+        let mut tokens = TokenStream::new(src).unwrap();
+        let layout = VertexLayoutConfig {
+            name: "Instance".to_owned(),
+            step_mode: "Instance".to_owned(),
+            first_location: 5,
+            attributes: vec!["Float32x4".to_owned(), "Uint32".to_owned()],
+        };
+        assert_eq!(5..7, layout.locations());
+        assert_eq!(
+            Ok(Directive::VertexLayout(layout)),
+            Directive::parse(&mut tokens)
+        );
+        let src = r#"#pipeline_layout(name: "Scene", bind_groups: ["Camera"], push_constants: [(stages: [Vertex, Fragment], range: [0, 64])])"#;
+        let mut tokens = TokenStream::new(src).unwrap();
+        assert_eq!(
+            Ok(Directive::PipelineLayout(PipelineLayoutConfig {
+                name: "Scene".to_owned(),
+                bind_groups: vec![BindGroupRef::Named("Camera".to_owned())],
+                push_constants: vec![PushConstantRangeConfig {
+                    stages: vec!["Vertex".to_owned(), "Fragment".to_owned()],
+                    range: 0..64,
+                }],
+            })),
+            Directive::parse(&mut tokens),
+        );
+        let src = r#"#pipeline_layout(name: "Scene", push_constants: [(stages: Vertex, range: [64, 0])])"#;
+        let mut tokens = TokenStream::new(src).unwrap();
+        assert!(matches!(
+            Directive::parse(&mut tokens),
+            Err(ParseError::InvalidValue { .. })
+        ));
+
+        let mut tokens =
+            TokenStream::new(r#"#color_target(name: "Hdr", format: Rgba16Float, blend: Additive)"#)
+                .unwrap();
+        assert_eq!(
+            Ok(Directive::ColorTarget(ColorTargetConfig {
+                name: "Hdr".to_owned(),
+                format: "Rgba16Float".to_owned(),
+                blend: Some("Additive".to_owned()),
+            })),
+            Directive::parse(&mut tokens),
+        );
+        let mut tokens =
+            TokenStream::new(r#"#color_target(name: "Hdr", blend: Additive)"#).unwrap();
+        assert_eq!(
+            Err(ParseError::MissingField("format")),
+            Directive::parse(&mut tokens)
+        );
+
+        let mut tokens =
+            TokenStream::new(r#"#vertex_layout(name: "Empty", attributes: [])"#).unwrap();
+        assert!(matches!(
+            Directive::parse(&mut tokens),
+            Err(ParseError::InvalidValue { .. })
+        ));
+        let mut tokens =
+            TokenStream::new(r#"#vertex_layout(name: "Bad", attributes: [Float32x5])"#).unwrap();
+        assert!(matches!(
+            Directive::parse(&mut tokens),
+            Err(ParseError::InvalidVariant { .. })
+        ));
+    }
+
+    #[test]
+    fn directive_parse_let() {
+        let mut tokens = TokenStream::new(
+            r#"#let(hdr_format: Rgba16Float, bias: (constant: 2), counts: [1, 4],)"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Ok(Directive::Let(vec![
+                ("hdr_format".into(), vec![lex::Token::ident("Rgba16Float")]),
+                (
+                    "bias".into(),
+                    vec![
+                        lex::Token::LeftParen,
+                        lex::Token::ident("constant"),
+                        lex::Token::Colon,
+                        lex::Token::number("2"),
+                        lex::Token::RightParen,
+                    ],
+                ),
+                (
+                    "counts".into(),
+                    vec![
+                        lex::Token::LeftBracket,
+                        lex::Token::number("1"),
+                        lex::Token::Comma,
+                        lex::Token::number("4"),
+                        lex::Token::RightBracket,
+                    ],
+                ),
+            ])),
+            Directive::parse(&mut tokens),
+        );
+
+        let mut tokens = TokenStream::new("#let(empty: )").unwrap();
+        assert!(matches!(
+            Directive::parse(&mut tokens),
+            Err(ParseError::UnexpectedToken { .. })
+        ));
+
+        assert_eq!(
+            Err(ParseError::UndefinedVariable("missing".into())),
+            RenderPipelineConfig::from_src("render_pipeline(name: $missing)"),
+        );
+
+        let mut tokens =
+            TokenStream::new("#alias(ShadowFormat: Depth32Float, Cascades: 4)").unwrap();
+        assert_eq!(
+            Ok(Directive::Alias(vec![
+                ("ShadowFormat".into(), lex::Token::ident("Depth32Float")),
+                ("Cascades".into(), lex::Token::number("4")),
+            ])),
+            Directive::parse(&mut tokens),
+        );
+        let mut tokens = TokenStream::new("#alias(shadow_format: Depth32Float)").unwrap();
+        assert!(matches!(
+            Directive::parse(&mut tokens),
+            Err(ParseError::InvalidValue { .. })
+        ));
+        let mut tokens = TokenStream::new("#alias(Bias: (constant: 2))").unwrap();
+        assert!(matches!(
+            Directive::parse(&mut tokens),
+            Err(ParseError::InvalidValue { .. })
+        ));
+    }
 }
@@ -0,0 +1,172 @@
+//! Shader variants selected with `#ifdef` blocks in WGSL.
+
+use crate::config::ShaderDefine;
+
+/// One combination of a pipeline's `variants`, holding the index of the
+/// chosen value of every define. Boolean defines are `0` when unset and `1`
+/// when set.
+pub(crate) type Combination = Vec<usize>;
+
+/// Every combination of `defines`, with the last define changing fastest.
+pub(crate) fn combinations(defines: &[ShaderDefine]) -> Vec<Combination> {
+    let mut combinations = vec![Vec::new()];
+    for define in defines {
+        combinations = combinations
+            .into_iter()
+            .flat_map(|combination| {
+                (0..define.value_count()).map(move |value| {
+                    let mut combination = combination.clone();
+                    combination.push(value);
+                    combination
+                })
+            })
+            .collect();
+    }
+    combinations
+}
+
+/// The names that are defined for the shader in `combination`. A boolean
+/// define is named as-is and an enum define is named after the define and
+/// its value, eg. `QUALITY_HIGH`.
+pub(crate) fn defined_names(defines: &[ShaderDefine], combination: &[usize]) -> Vec<String> {
+    defines
+        .iter()
+        .zip(combination)
+        .filter_map(|(define, &value)| match define.values.get(value) {
+            Some(value) => Some(format!("{}_{}", define.name, value)),
+            None if define.values.is_empty() && value == 1 => Some(define.name.clone()),
+            None => None,
+        })
+        .collect()
+}
+
+/// The suffix added to the pipeline name for `combination`, eg.
+/// `SkinnedHigh`. It's `Base` if nothing is defined.
+pub(crate) fn type_suffix(defines: &[ShaderDefine], combination: &[usize]) -> String {
+    let suffix = defines
+        .iter()
+        .zip(combination)
+        .filter_map(|(define, &value)| match define.values.get(value) {
+            Some(value) => Some(pascal_case(value)),
+            None if value == 1 => Some(pascal_case(&define.name)),
+            None => None,
+        })
+        .collect::<String>();
+    if suffix.is_empty() {
+        "Base".to_owned()
+    } else {
+        suffix
+    }
+}
+
+/// Converts a `SCREAMING_SNAKE_CASE` define to `PascalCase`.
+pub(crate) fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first)
+                .chain(chars.map(|c| c.to_ascii_lowercase()))
+                .collect::<String>()
+        })
+        .collect()
+}
+
+/// Whether `src` has any `#ifdef` or `#ifndef` blocks to preprocess.
+pub(crate) fn has_blocks(src: &str) -> bool {
+    src.lines().any(|line| {
+        let line = line.trim_start();
+        line.starts_with("#ifdef") || line.starts_with("#ifndef")
+    })
+}
+
+/// Keeps or removes the lines between `#ifdef NAME`/`#ifndef NAME`, `#else`
+/// and `#endif` depending on whether `NAME` is in `defined`. Blocks can be
+/// nested. Removed lines are left empty so naga's errors still point at the
+/// right line of the original file.
+pub(crate) fn preprocess(src: &str, defined: &[String]) -> Result<String, String> {
+    // Whether each open block is currently keeping its lines.
+    let mut stack: Vec<bool> = Vec::new();
+    let mut out = String::with_capacity(src.len());
+    for (i, line) in src.lines().enumerate() {
+        let directive = line.trim_start();
+        let keeping = stack.iter().all(|&keep| keep);
+        let mut words = directive.split_whitespace();
+        match words.next() {
+            Some(kind @ ("#ifdef" | "#ifndef")) => {
+                let name = words.next().ok_or_else(|| {
+                    format!("line {}: {} needs the name of a define", i + 1, kind)
+                })?;
+                let is_defined = defined.iter().any(|d| d == name);
+                stack.push(is_defined == (kind == "#ifdef"));
+            }
+            Some("#else") => {
+                let keep = stack
+                    .last_mut()
+                    .ok_or_else(|| format!("line {}: #else without #ifdef", i + 1))?;
+                *keep = !*keep;
+            }
+            Some("#endif") => {
+                stack
+                    .pop()
+                    .ok_or_else(|| format!("line {}: #endif without #ifdef", i + 1))?;
+            }
+            _ if keeping => out.push_str(line),
+            _ => (),
+        }
+        out.push('\n');
+    }
+    if !stack.is_empty() {
+        return Err("#ifdef without #endif".to_owned());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn define(name: &str, values: &[&str]) -> ShaderDefine {
+        ShaderDefine {
+            name: name.to_owned(),
+            values: values.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn combinations_and_names() {
+        let defines = [define("SKINNED", &[]), define("QUALITY", &["LOW", "HIGH"])];
+        let combinations = combinations(&defines);
+        assert_eq!(
+            vec![vec![0, 0], vec![0, 1], vec![1, 0], vec![1, 1]],
+            combinations
+        );
+        let suffixes = combinations
+            .iter()
+            .map(|c| type_suffix(&defines, c))
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["Low", "High", "SkinnedLow", "SkinnedHigh"], suffixes);
+        assert_eq!(
+            vec!["SKINNED", "QUALITY_HIGH"],
+            defined_names(&defines, &[1, 1])
+        );
+        assert_eq!(vec!["Base"], vec![type_suffix(&defines[..1], &[0])]);
+        assert_eq!("AlphaTest", pascal_case("ALPHA_TEST"));
+    }
+
+    #[test]
+    fn preprocess_blocks() {
+        let src = "a\n#ifdef SKINNED\nb\n  #ifndef ALPHA_TEST\nc\n  #else\nd\n  #endif\n#endif\ne";
+        let defined = vec!["SKINNED".to_owned()];
+        assert_eq!(
+            "a\n\nb\n\nc\n\n\n\n\ne\n",
+            preprocess(src, &defined).unwrap()
+        );
+        assert_eq!("a\n\n\n\n\n\n\n\n\ne\n", preprocess(src, &[]).unwrap());
+        assert!(preprocess("#ifdef A\n", &[]).is_err());
+        assert!(preprocess("#endif\n", &[]).is_err());
+        assert!(has_blocks(src));
+        assert!(!has_blocks("a\n// #ifdef A\n"));
+    }
+}
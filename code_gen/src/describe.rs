@@ -0,0 +1,288 @@
+//! Human-readable dumps of the state a pipeline is created with, for
+//! debugging configs without reading the generated code.
+
+use std::fmt::Write;
+
+use anyhow::Result;
+
+use crate::{
+    expand_variants, gen_pipeline_code_with_manifest, BindGroupRef, CodegenOptions, PipelineConfig,
+    PipelineKind, PipelineManifest, RenderPipelineConfig,
+};
+
+/// Describes the pipeline called `name` in `config` as it will be created:
+/// after `#defaults`, templates and variants have been applied, and with
+/// the entry points, vertex buffers and bind groups reflected from its
+/// shader. It's laid out like the `wgpu::RenderPipelineDescriptor` the
+/// generated code builds, or the `wgpu::ComputePipelineDescriptor`.
+///
+/// Variants are described one at a time by their generated names, eg.
+/// `LitPipelineSkinned`. Code is generated for the whole config, so every
+/// shader in it has to be valid.
+pub fn describe_pipeline(
+    config: &PipelineConfig,
+    name: &str,
+    options: &CodegenOptions,
+) -> Result<String> {
+    let (_, manifest) = gen_pipeline_code_with_manifest(config, options)?;
+    let Some(pipeline) = manifest
+        .pipelines
+        .iter()
+        .find(|pipeline| pipeline.name == name)
+    else {
+        let names = manifest
+            .pipelines
+            .iter()
+            .map(|pipeline| pipeline.name.as_str())
+            .collect::<Vec<_>>();
+        anyhow::bail!(
+            "there's no pipeline called {}, expected one of: {}",
+            name,
+            names.join(", ")
+        );
+    };
+
+    let mut out = String::new();
+    match pipeline.kind {
+        PipelineKind::Render => {
+            let (rp, defines) = config
+                .render_pipelines()
+                .iter()
+                .flat_map(expand_variants)
+                .find(|(rp, _)| rp.name == name)
+                .expect("every render pipeline in the manifest should be in the config");
+            describe_render(
+                &mut out,
+                config,
+                &rp,
+                defines.as_deref().unwrap_or_default(),
+                pipeline,
+            )
+        }
+        PipelineKind::Compute => describe_compute(&mut out, pipeline),
+    }
+    .expect("writing to a String can't fail");
+    Ok(out)
+}
+
+fn describe_render(
+    out: &mut String,
+    config: &PipelineConfig,
+    rp: &RenderPipelineConfig,
+    variant_defines: &[String],
+    pipeline: &PipelineManifest,
+) -> std::fmt::Result {
+    writeln!(out, "{} (render pipeline)", pipeline.name)?;
+    describe_common(out, pipeline)?;
+    describe_layout(out, config, rp, pipeline)?;
+
+    writeln!(out, "vertex:")?;
+    writeln!(out, "    entry_point: {}", pipeline.entry_points[0])?;
+    writeln!(out, "    buffers:")?;
+    if pipeline.vertex_buffers.is_empty() {
+        writeln!(out, "        none")?;
+    }
+    for (slot, buffer) in pipeline.vertex_buffers.iter().enumerate() {
+        writeln!(
+            out,
+            "        slot {}: {} (step_mode: {})",
+            slot, buffer.name, buffer.step_mode
+        )?;
+        if buffer.attributes.is_empty() {
+            writeln!(out, "            attributes from the Rust type")?;
+        }
+        for attribute in &buffer.attributes {
+            match &attribute.name {
+                Some(name) => writeln!(
+                    out,
+                    "            location {}: {} {}",
+                    attribute.location, attribute.format, name
+                )?,
+                None => writeln!(
+                    out,
+                    "            location {}: {}",
+                    attribute.location, attribute.format
+                )?,
+            }
+        }
+    }
+
+    let primitive = &rp.primitive;
+    writeln!(out, "primitive:")?;
+    writeln!(out, "    topology: {}", primitive.topology)?;
+    writeln!(
+        out,
+        "    strip_index_format: {}",
+        primitive.strip_index_format.as_deref().unwrap_or("None")
+    )?;
+    writeln!(out, "    front_face: {}", primitive.front_face)?;
+    writeln!(
+        out,
+        "    cull_mode: {}",
+        primitive.cull_mode.as_deref().unwrap_or("None")
+    )?;
+    writeln!(out, "    polygon_mode: {}", primitive.polygon_mode)?;
+    writeln!(out, "    unclipped_depth: {}", primitive.unclipped_depth)?;
+    writeln!(out, "    conservative: {}", primitive.conservative)?;
+
+    match &rp.depth_stencil {
+        Some(ds) => {
+            writeln!(out, "depth_stencil:")?;
+            writeln!(out, "    format: {}", ds.format)?;
+            writeln!(out, "    depth_write_enabled: {}", ds.depth_write_enabled)?;
+            writeln!(out, "    depth_compare: {}", ds.depth_compare)?;
+            let bias = &ds.depth_bias;
+            writeln!(
+                out,
+                "    bias: (constant: {}, slope_scale: {:?}, clamp: {:?})",
+                bias.constant, bias.slope_scale, bias.clamp
+            )?;
+        }
+        None => writeln!(out, "depth_stencil: None")?,
+    }
+
+    match &rp.sample_counts {
+        Some(counts) => {
+            let counts = counts.iter().map(u32::to_string).collect::<Vec<_>>();
+            writeln!(
+                out,
+                "multisample: count is the SAMPLES parameter, one of {}",
+                counts.join(", ")
+            )?;
+        }
+        None => writeln!(out, "multisample: count 1")?,
+    }
+
+    writeln!(out, "fragment:")?;
+    writeln!(out, "    entry_point: {}", pipeline.entry_points[1])?;
+    writeln!(out, "    targets:")?;
+    for name in &rp.targets {
+        match config
+            .color_targets()
+            .iter()
+            .find(|target| &target.name == name)
+        {
+            Some(target) => writeln!(
+                out,
+                "        {}: {}, blend: {}",
+                name,
+                target.format,
+                target.blend.as_deref().unwrap_or("None")
+            )?,
+            None => writeln!(out, "        {}", name)?,
+        }
+    }
+    for format in &rp.target_formats {
+        writeln!(out, "        {}, blend: None", format)?;
+    }
+    if rp.targets.is_empty() && rp.target_formats.is_empty() {
+        writeln!(out, "        the formats passed to `new`, blend: None")?;
+    }
+
+    let defines = rp
+        .defines
+        .iter()
+        .chain(variant_defines)
+        .cloned()
+        .collect::<Vec<_>>();
+    if !defines.is_empty() {
+        writeln!(out, "defines: {}", defines.join(", "))?;
+    }
+    if !rp.constants.is_empty() {
+        let constants = rp
+            .constants
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect::<Vec<_>>();
+        writeln!(out, "constants: {}", constants.join(", "))?;
+    }
+    Ok(())
+}
+
+fn describe_compute(out: &mut String, pipeline: &PipelineManifest) -> std::fmt::Result {
+    writeln!(out, "{} (compute pipeline)", pipeline.name)?;
+    describe_common(out, pipeline)?;
+    writeln!(out, "layout:")?;
+    describe_bind_groups(out, pipeline)?;
+    writeln!(out, "entry_point: {}", pipeline.entry_points[0])?;
+    if let Some([x, y, z]) = pipeline.workgroup_size {
+        writeln!(out, "workgroup_size: ({}, {}, {})", x, y, z)?;
+    }
+    Ok(())
+}
+
+fn describe_common(out: &mut String, pipeline: &PipelineManifest) -> std::fmt::Result {
+    writeln!(out, "label: {:?}", pipeline.label)?;
+    writeln!(out, "shader: {}", pipeline.shader)?;
+    if let Some(cfg) = &pipeline.cfg {
+        writeln!(out, "cfg: {}", cfg)?;
+    }
+    Ok(())
+}
+
+/// The pipeline layout, which is reflected unless the config gives it.
+fn describe_layout(
+    out: &mut String,
+    config: &PipelineConfig,
+    rp: &RenderPipelineConfig,
+    pipeline: &PipelineManifest,
+) -> std::fmt::Result {
+    if let Some(layout) = &rp.layout {
+        return writeln!(out, "layout: the `#pipeline_layout` {}", layout);
+    }
+    if !rp.bind_group_layouts.is_empty() {
+        return writeln!(
+            out,
+            "layout: the bind group layouts passed to `new`: {}",
+            rp.bind_group_layouts.join(", ")
+        );
+    }
+    writeln!(out, "layout:")?;
+    if rp.bind_groups.is_empty() {
+        return describe_bind_groups(out, pipeline);
+    }
+    for (group, bind_group) in rp.bind_groups.iter().enumerate() {
+        match bind_group {
+            BindGroupRef::Named(name) => {
+                let entries = config
+                    .bind_group(name)
+                    .map(|group| group.entries.len())
+                    .unwrap_or_default();
+                writeln!(
+                    out,
+                    "    group {}: the `#bind_group` {} ({} entries)",
+                    group, name, entries
+                )?
+            }
+            BindGroupRef::Presets(presets) => {
+                writeln!(out, "    group {}: {}", group, presets.join(", "))?
+            }
+        }
+    }
+    Ok(())
+}
+
+fn describe_bind_groups(out: &mut String, pipeline: &PipelineManifest) -> std::fmt::Result {
+    if pipeline.bind_groups.is_empty() {
+        writeln!(out, "    no bind groups")?;
+    }
+    for group in &pipeline.bind_groups {
+        writeln!(out, "    group {}:", group.group)?;
+        for entry in &group.entries {
+            let count = entry
+                .count
+                .map(|count| format!(", count: {}", count))
+                .unwrap_or_default();
+            writeln!(
+                out,
+                "        binding {}: {} ({}, visible to {}{})",
+                entry.binding,
+                entry.name,
+                entry.kind,
+                entry.visibility.join(" | "),
+                count
+            )?;
+        }
+    }
+    Ok(())
+}
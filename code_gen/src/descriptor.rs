@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{PipelineConfig, RenderPipelineConfig};
+
+/// Bumped whenever the layout of [Descriptor] changes in a way older tooling
+/// can't read.
+pub const DESCRIPTOR_VERSION: u32 = 1;
+
+/// A machine-readable summary of the pipelines in a config, for tools that
+/// need to attribute or categorize them without parsing the config
+/// themselves.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Descriptor {
+    pub version: u32,
+    pub pipelines: Vec<PipelineDescriptor>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineDescriptor {
+    pub name: String,
+    pub label: String,
+    pub path: String,
+    pub cfg: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl From<&RenderPipelineConfig> for PipelineDescriptor {
+    fn from(rp: &RenderPipelineConfig) -> Self {
+        Self {
+            name: rp.name.clone(),
+            label: rp.label().to_owned(),
+            path: rp.path.clone(),
+            cfg: rp.cfg.clone(),
+            author: rp.author.clone(),
+            description: rp.description.clone(),
+            tags: rp.tags.clone(),
+        }
+    }
+}
+
+impl Descriptor {
+    pub fn from_config(config: &PipelineConfig) -> Self {
+        Self {
+            version: DESCRIPTOR_VERSION,
+            pipelines: config
+                .render_pipelines()
+                .iter()
+                .map(PipelineDescriptor::from)
+                .collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Descriptor should always serialize")
+    }
+
+    pub fn from_json(src: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(src)
+    }
+}
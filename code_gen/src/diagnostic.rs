@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+
+/// An error or warning with where it was found, for tools like editors and
+/// CI that want to point at the problem rather than show a message.
+///
+/// Errors from parsing configs and shaders are returned as `Diagnostic`s
+/// wrapped in an [anyhow::Error], and display the same way they always
+/// have. [Diagnostic::from_error] gets them back out, and turns anything
+/// else into one without a location.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub level: Level,
+    /// The config or shader it's in, as it was written or passed in.
+    pub file: Option<String>,
+    pub span: Option<Span>,
+    /// A stable, `snake_case` name for the kind of problem, eg.
+    /// `missing_field` or `shader_validation`.
+    pub code: String,
+    pub message: String,
+    /// Extra context, eg. the `#include`s that led to the file.
+    pub notes: Vec<String>,
+    /// Other places in the file involved, eg. each expression naga points
+    /// at for a shader error.
+    pub labels: Vec<Label>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+/// A position in a file. Both are 1 based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub line: usize,
+    /// Only known for shaders.
+    pub column: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Label {
+    pub span: Span,
+    /// The file the span is in, if it's not the diagnostic's, eg. a shader
+    /// imported into it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// What's at the span. Often empty.
+    pub text: String,
+    /// The line of source the span is on.
+    pub source_line: String,
+}
+
+impl Diagnostic {
+    pub fn error(code: &str, message: impl Into<String>) -> Self {
+        Self {
+            level: Level::Error,
+            file: None,
+            span: None,
+            code: code.to_owned(),
+            message: message.into(),
+            notes: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self {
+            level: Level::Warning,
+            code: "warning".to_owned(),
+            ..Self::error("", message)
+        }
+    }
+
+    pub fn in_file(mut self, file: impl Into<String>, line: Option<usize>) -> Self {
+        self.file = Some(file.into());
+        self.span = line.map(|line| Span { line, column: None });
+        self
+    }
+
+    /// The diagnostic in `e`'s chain, or one with `e`'s message and the
+    /// `error` code if there isn't one. Context added around it is kept as
+    /// notes.
+    pub fn from_error(e: &anyhow::Error) -> Self {
+        let mut context = Vec::new();
+        for cause in e.chain() {
+            if let Some(diagnostic) = cause.downcast_ref::<Diagnostic>() {
+                let mut diagnostic = diagnostic.clone();
+                diagnostic.notes.extend(context);
+                return diagnostic;
+            }
+            context.push(cause.to_string());
+        }
+        // Diagnostics given as context, eg. around a [crate::LoadError], aren't
+        // in the chain as themselves.
+        if let Some(diagnostic) = e.downcast_ref::<Diagnostic>() {
+            return diagnostic.clone();
+        }
+        Self::error("error", format!("{:#}", e))
+    }
+
+    /// The diagnostic as a single line of JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Diagnostic should always serialize")
+    }
+
+    pub fn from_json(src: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(src)
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    /// The way the errors have always read, eg. `a.pmd:3: Missing field`.
+    /// Diagnostics with labels put each one on its own lines instead.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.file, self.span) {
+            (Some(file), Some(span)) if self.labels.is_empty() => {
+                write!(f, "{}:{}: ", file, span.line)?
+            }
+            (Some(file), _) => write!(f, "{}: ", file)?,
+            (None, _) => (),
+        }
+        f.write_str(&self.message)?;
+        let file = self.file.as_deref().unwrap_or("input");
+        for label in &self.labels {
+            let file = label.file.as_deref().unwrap_or(file);
+            write!(
+                f,
+                "\n  --> {}:{}:{}",
+                file,
+                label.span.line,
+                label.span.column.unwrap_or(1)
+            )?;
+            if !label.text.is_empty() {
+                write!(f, " ({})", label.text)?;
+            }
+            write!(f, "\n   | {}", label.source_line)?;
+        }
+        for note in &self.notes {
+            write!(f, "\n  {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {}
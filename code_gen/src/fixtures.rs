@@ -0,0 +1,229 @@
+//! A corpus of configs and shaders with the results pipemd is expected to
+//! produce for them. Tools building on pipemd's parser can run these through
+//! their own code to check they agree with it.
+//!
+//! Configs reference their shaders by file name and are read with a
+//! [FixtureLoader], so nothing needs to be on disk.
+
+use std::path::{Path, PathBuf};
+
+use crate::{LoadError, ShaderLoader};
+
+/// A config along with every shader it references.
+#[derive(Debug, Clone, Copy)]
+pub struct Fixture {
+    pub name: &'static str,
+    pub config: &'static str,
+    /// `(path, source)` pairs, where `path` is what the config uses.
+    pub shaders: &'static [(&'static str, &'static str)],
+    pub expected: Expected,
+}
+
+impl Fixture {
+    /// A loader that serves [Fixture::shaders].
+    pub fn loader(&self) -> FixtureLoader {
+        FixtureLoader {
+            shaders: self.shaders,
+        }
+    }
+}
+
+/// What processing a [Fixture] should result in.
+#[derive(Debug, Clone, Copy)]
+pub enum Expected {
+    /// Parsing and codegen succeed, producing these pipelines in order.
+    Pipelines(&'static [ExpectedPipeline]),
+    /// Parsing the config fails with an error whose message contains this.
+    ParseError(&'static str),
+    /// The config parses, but codegen fails with an error whose message
+    /// contains this.
+    CodegenError(&'static str),
+}
+
+/// The information pipemd reflects for a pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpectedPipeline {
+    pub name: &'static str,
+    pub vs_entry: &'static str,
+    pub fs_entry: &'static str,
+}
+
+/// Serves the shaders of a [Fixture] from memory.
+#[derive(Debug, Clone, Copy)]
+pub struct FixtureLoader {
+    shaders: &'static [(&'static str, &'static str)],
+}
+
+impl ShaderLoader for FixtureLoader {
+    fn load(&self, path: &str) -> Result<String, LoadError> {
+        self.shaders
+            .iter()
+            .find(|(p, _)| *p == path)
+            .map(|(_, src)| src.to_string())
+            .ok_or_else(|| LoadError::Io {
+                path: path.into(),
+                source: std::io::ErrorKind::NotFound.into(),
+            })
+    }
+
+    /// Fixtures are shipped in the `fixtures` directory of this crate.
+    fn resolve(&self, path: &str) -> Result<PathBuf, LoadError> {
+        self.load(path)?;
+        Ok(Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(path))
+    }
+}
+
+const TEXTURED_WGSL: (&str, &str) = ("textured.wgsl", include_str!("../fixtures/textured.wgsl"));
+const FULLSCREEN_WGSL: (&str, &str) = (
+    "fullscreen.wgsl",
+    include_str!("../fixtures/fullscreen.wgsl"),
+);
+const INVALID_WGSL: (&str, &str) = ("invalid.wgsl", include_str!("../fixtures/invalid.wgsl"));
+const MISMATCHED_INTERFACE_WGSL: (&str, &str) = (
+    "mismatched_interface.wgsl",
+    include_str!("../fixtures/mismatched_interface.wgsl"),
+);
+
+/// Fixtures that generate code successfully.
+pub const VALID: &[Fixture] = &[
+    Fixture {
+        name: "textured",
+        config: include_str!("../fixtures/textured.pmd"),
+        shaders: &[TEXTURED_WGSL],
+        expected: Expected::Pipelines(&[ExpectedPipeline {
+            name: "TexturedPipeline",
+            vs_entry: "vs_textured",
+            fs_entry: "fs_textured",
+        }]),
+    },
+    // The only vertex stage isn't the conventional `vs_main` but there's just
+    // one, so it's used.
+    Fixture {
+        name: "fullscreen",
+        config: include_str!("../fixtures/fullscreen.pmd"),
+        shaders: &[FULLSCREEN_WGSL],
+        expected: Expected::Pipelines(&[ExpectedPipeline {
+            name: "FullscreenPipeline",
+            vs_entry: "vs_main",
+            fs_entry: "fs_checker",
+        }]),
+    },
+    Fixture {
+        name: "shadow",
+        config: include_str!("../fixtures/shadow.pmd"),
+        shaders: &[TEXTURED_WGSL],
+        expected: Expected::Pipelines(&[
+            ExpectedPipeline {
+                name: "ShadowPipeline",
+                vs_entry: "vs_textured",
+                fs_entry: "fs_textured",
+            },
+            ExpectedPipeline {
+                name: "SkyPipeline",
+                vs_entry: "vs_textured",
+                fs_entry: "fs_textured",
+            },
+        ]),
+    },
+];
+
+/// Fixtures that fail to parse or generate.
+pub const INVALID: &[Fixture] = &[
+    Fixture {
+        name: "unknown_field",
+        config: include_str!("../fixtures/unknown_field.pmd"),
+        shaders: &[TEXTURED_WGSL],
+        expected: Expected::ParseError("Unexpected field: \"blend\""),
+    },
+    Fixture {
+        name: "invalid_variant",
+        config: include_str!("../fixtures/invalid_variant.pmd"),
+        shaders: &[TEXTURED_WGSL],
+        expected: Expected::ParseError("Invalid value \"Triangles\" for \"topology\""),
+    },
+    // Two fragment entry points and neither is called `fs_main`.
+    Fixture {
+        name: "ambiguous_entry",
+        config: include_str!("../fixtures/ambiguous_entry.pmd"),
+        shaders: &[FULLSCREEN_WGSL],
+        expected: Expected::CodegenError("`fs_entry` must be given"),
+    },
+    Fixture {
+        name: "invalid_wgsl",
+        config: include_str!("../fixtures/invalid_wgsl.pmd"),
+        shaders: &[INVALID_WGSL],
+        expected: Expected::CodegenError("expected ';'"),
+    },
+    Fixture {
+        name: "mismatched_interface",
+        config: include_str!("../fixtures/mismatched_interface.pmd"),
+        shaders: &[MISMATCHED_INTERFACE_WGSL],
+        expected: Expected::CodegenError(
+            "vs_main writes location 0 as vec2<f32>, but fs_main reads it as vec3<f32>",
+        ),
+    },
+];
+
+/// Every fixture, valid ones first.
+pub fn all() -> impl Iterator<Item = &'static Fixture> {
+    VALID.iter().chain(INVALID)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{CodegenMode, CodegenOptions, PipelineConfig};
+
+    #[test]
+    fn fixtures_match_expectations() {
+        for fixture in all() {
+            let parsed = PipelineConfig::from_src(fixture.config);
+            let options = CodegenOptions {
+                mode: CodegenMode::ConstantsOnly,
+                loader: Arc::new(fixture.loader()),
+                ..Default::default()
+            };
+            match (fixture.expected, parsed) {
+                (Expected::Pipelines(pipelines), Ok(config)) => {
+                    crate::gen_pipeline_code_with_options(&config, &options).unwrap();
+                    assert_eq!(
+                        pipelines.len(),
+                        config.render_configs.len(),
+                        "{}",
+                        fixture.name
+                    );
+                    for (expected, rp) in pipelines.iter().zip(&config.render_configs) {
+                        let src = fixture.loader().load(&rp.path).unwrap();
+                        let module = naga::front::wgsl::parse_str(&src).unwrap();
+                        let entry = |stage| crate::resolve_entry_point(rp, &module, stage).unwrap();
+                        assert_eq!(expected.name, rp.name, "{}", fixture.name);
+                        assert_eq!(expected.vs_entry, entry(naga::ShaderStage::Vertex));
+                        assert_eq!(expected.fs_entry, entry(naga::ShaderStage::Fragment));
+                    }
+                }
+                (Expected::ParseError(message), Err(e)) => {
+                    assert!(e.to_string().contains(message), "{}: {}", fixture.name, e);
+                }
+                (Expected::CodegenError(message), Ok(config)) => {
+                    let e = crate::gen_pipeline_code_with_options(&config, &options).unwrap_err();
+                    assert!(
+                        format!("{:#}", e).contains(message),
+                        "{}: {:#}",
+                        fixture.name,
+                        e
+                    );
+                }
+                (expected, parsed) => panic!(
+                    "{}: expected {:?} but parsing gave {:?}",
+                    fixture.name,
+                    expected,
+                    parsed.map(|_| ())
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! Laying out configs in one canonical style, so they can be checked in CI
+//! and diffs only show real changes.
+
+use crate::config::ParseError;
+use crate::lex::{Token, TokenStream};
+
+/// Lists and argument lists longer than this are split over several lines.
+const MAX_INLINE_LEN: usize = 80;
+
+/// The fields of a pipeline that come first, in this order, so the most
+/// important ones are always at the top. The rest keep their order.
+const LEADING_FIELDS: &[&str] = &["name", "label", "path", "shader", "vs_entry", "fs_entry"];
+
+/// Formats a config. Items are put on their own lines, and the fields of
+/// top level items and long lists one per line with trailing commas. Only
+/// the order of a pipeline's fields is changed, with `name`, `label`,
+/// `path`, `shader`, `vs_entry` and `fs_entry` moved to the front, since it
+/// matters everywhere else, eg. for `#let`.
+///
+/// The config is only lexed, not parsed, so anything with balanced brackets
+/// is formatted even if it isn't valid.
+pub fn format_pmd(src: &str) -> Result<String, ParseError<'_>> {
+    let mut tokens = TokenStream::new(src)?;
+    let trees = parse_trees(&mut tokens, None)?;
+    let mut out = String::new();
+    write_items(&trees, 0, &mut out);
+    Ok(out)
+}
+
+#[derive(Debug)]
+enum Tree<'a> {
+    Token(Token<'a>),
+    /// The trees between a `(` and `)`, or `[` and `]` if `bracket`.
+    Group {
+        bracket: bool,
+        trees: Vec<Tree<'a>>,
+    },
+}
+
+fn parse_trees<'a>(
+    tokens: &mut TokenStream<'a>,
+    close: Option<Token<'a>>,
+) -> Result<Vec<Tree<'a>>, ParseError<'a>> {
+    let mut trees = Vec::new();
+    loop {
+        let token = match tokens.next() {
+            Some(token) => token,
+            None if close.is_none() => return Ok(trees),
+            None => return Err(ParseError::EndOfInput),
+        };
+        match token {
+            Token::LeftParen => trees.push(Tree::Group {
+                bracket: false,
+                trees: parse_trees(tokens, Some(Token::RightParen))?,
+            }),
+            Token::LeftBracket => trees.push(Tree::Group {
+                bracket: true,
+                trees: parse_trees(tokens, Some(Token::RightBracket))?,
+            }),
+            token if Some(&token) == close.as_ref() => return Ok(trees),
+            token @ (Token::RightParen | Token::RightBracket) => {
+                return Err(ParseError::ExpectedEndOfInput(token))
+            }
+            token => trees.push(Tree::Token(token)),
+        }
+    }
+}
+
+/// Writes a sequence of items, eg. the whole file or the body of a
+/// `#cfg` block, one per line.
+fn write_items(trees: &[Tree], indent: usize, out: &mut String) {
+    let mut start_of_line = true;
+    let mut item = None;
+    for (i, tree) in trees.iter().enumerate() {
+        if start_of_line {
+            out.push_str(&"    ".repeat(indent));
+            start_of_line = false;
+        }
+        match tree {
+            Tree::Token(token) => {
+                if let Token::Ident(ident) = token {
+                    item = Some(ident.as_ref());
+                }
+                write_token(token, out);
+                if !matches!(token, Token::Hash)
+                    && !matches!(trees.get(i + 1), Some(Tree::Group { .. }))
+                {
+                    out.push('\n');
+                    start_of_line = true;
+                }
+            }
+            Tree::Group {
+                bracket: true,
+                trees,
+            } if is_block(trees) => {
+                out.push_str("[\n");
+                write_items(trees, indent + 1, out);
+                out.push_str(&"    ".repeat(indent));
+                out.push(']');
+            }
+            Tree::Group { bracket, trees } => {
+                let pipeline = item == Some("render_pipeline");
+                write_group(*bracket, trees, indent, Layout::Item { pipeline }, out);
+            }
+        }
+        if let Tree::Group { .. } = tree {
+            match trees.get(i + 1) {
+                Some(Tree::Group {
+                    bracket: true,
+                    trees,
+                }) if is_block(trees) => out.push(' '),
+                _ => {
+                    out.push('\n');
+                    start_of_line = true;
+                }
+            }
+        }
+    }
+}
+
+/// Whether the contents of brackets are items rather than a list of values.
+/// Items aren't separated by commas.
+fn is_block(trees: &[Tree]) -> bool {
+    trees.len() > 1
+        && !trees
+            .iter()
+            .any(|tree| matches!(tree, Tree::Token(Token::Comma)))
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Layout {
+    /// The arguments of an item in a sequence of items.
+    Item { pipeline: bool },
+    /// A value nested in another.
+    Value,
+}
+
+fn write_group(bracket: bool, trees: &[Tree], indent: usize, layout: Layout, out: &mut String) {
+    let (open, close) = if bracket { ('[', ']') } else { ('(', ')') };
+    let mut elements = trees
+        .split(|tree| matches!(tree, Tree::Token(Token::Comma)))
+        .collect::<Vec<_>>();
+    if elements.last().is_some_and(|element| element.is_empty()) {
+        elements.pop();
+    }
+    let named = |element: &[Tree]| {
+        matches!(
+            element,
+            [Tree::Token(Token::Ident(_)), Tree::Token(Token::Colon), ..]
+        )
+    };
+    if let Layout::Item { pipeline: true } = layout {
+        let rank = |element: &&[Tree]| match element {
+            [Tree::Token(Token::Ident(field)), Tree::Token(Token::Colon), ..] => LEADING_FIELDS
+                .iter()
+                .position(|leading| leading == field)
+                .unwrap_or(LEADING_FIELDS.len()),
+            _ => LEADING_FIELDS.len(),
+        };
+        elements.sort_by_key(rank);
+    }
+
+    let mut inline = String::new();
+    inline.push(open);
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            inline.push_str(", ");
+        }
+        write_element(element, indent, &mut inline);
+    }
+    inline.push(close);
+    let all_named = !elements.is_empty() && elements.iter().all(|element| named(element));
+    let item = matches!(layout, Layout::Item { .. });
+    let multiline =
+        (all_named && item) || inline.len() + indent * 4 > MAX_INLINE_LEN || inline.contains('\n');
+    if !multiline {
+        out.push_str(&inline);
+        return;
+    }
+    out.push(open);
+    out.push('\n');
+    for element in elements {
+        out.push_str(&"    ".repeat(indent + 1));
+        write_element(element, indent + 1, out);
+        out.push_str(",\n");
+    }
+    out.push_str(&"    ".repeat(indent));
+    out.push(close);
+}
+
+/// Writes a single argument or list element, eg. `name: "Sprite"`.
+fn write_element(trees: &[Tree], indent: usize, out: &mut String) {
+    for (i, tree) in trees.iter().enumerate() {
+        match tree {
+            Tree::Token(Token::Colon) => out.push_str(": "),
+            Tree::Token(token) => {
+                write_token(token, out);
+                let spaced = matches!(trees.get(i + 1), Some(Tree::Token(token)) if !matches!(token, Token::Colon));
+                if spaced && !matches!(token, Token::Hash) {
+                    out.push(' ');
+                }
+            }
+            Tree::Group {
+                bracket: true,
+                trees,
+            } if is_block(trees) => {
+                out.push_str("[\n");
+                write_items(trees, indent + 1, out);
+                out.push_str(&"    ".repeat(indent));
+                out.push(']');
+            }
+            Tree::Group { bracket, trees } => {
+                write_group(*bracket, trees, indent, Layout::Value, out)
+            }
+        }
+    }
+}
+
+fn write_token(token: &Token, out: &mut String) {
+    match token {
+        Token::Ident(ident) => out.push_str(ident),
+        Token::Number(number) => out.push_str(number),
+        Token::String(s) => {
+            out.push('"');
+            out.push_str(&s.replace('\\', "\\\\").replace('"', "\\\""));
+            out.push('"');
+        }
+        Token::Variable(name) => {
+            out.push('$');
+            out.push_str(name);
+        }
+        Token::Hash => out.push('#'),
+        Token::Comma => out.push(','),
+        Token::LeftParen => out.push('('),
+        Token::RightParen => out.push(')'),
+        Token::LeftBracket => out.push('['),
+        Token::RightBracket => out.push(']'),
+        Token::Colon => out.push(':'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_config() {
+        let src = r#"#let(shader:"./a.wgsl",bias:(constant:2,slope_scale:2.0,clamp:0.0))
+            #compute_pipeline( "Integrate" , "cs_main", $shader )
+            #cfg("feature = \"editor\"") [ render_pipeline(path: $shader, cull_mode: Front, name: "Editor") ]
+            render_pipeline(cull_mode: None, targets: [Bgra8Unorm, Rgba16Float,], name: "Sprite",)"#;
+        let expected = r#"#let(
+    shader: "./a.wgsl",
+    bias: (constant: 2, slope_scale: 2.0, clamp: 0.0),
+)
+#compute_pipeline("Integrate", "cs_main", $shader)
+#cfg("feature = \"editor\"") [
+    render_pipeline(
+        name: "Editor",
+        path: $shader,
+        cull_mode: Front,
+    )
+]
+render_pipeline(
+    name: "Sprite",
+    cull_mode: None,
+    targets: [Bgra8Unorm, Rgba16Float],
+)
+"#;
+        let formatted = format_pmd(src).unwrap();
+        assert_eq!(expected, formatted);
+        assert_eq!(formatted, format_pmd(&formatted).unwrap());
+
+        assert!(format_pmd("render_pipeline(name: \"A\"").is_err());
+        assert!(format_pmd("render_pipeline(name: \"A\"))").is_err());
+    }
+}
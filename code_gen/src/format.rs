@@ -0,0 +1,16 @@
+//! Laying out generated code so it can be read, eg. when reviewing it or
+//! debugging a build script's output.
+
+use proc_macro2::TokenStream;
+
+/// Formats `code` with prettyplease. Unlike running rustfmt, this doesn't
+/// depend on what's installed or configured, so the same code is always
+/// laid out the same way.
+pub fn format_code(code: &TokenStream) -> String {
+    match syn::parse2::<syn::File>(code.clone()) {
+        Ok(file) => prettyplease::unparse(&file),
+        // Generated code is always a file's worth of items, but anything
+        // else is still written out as-is.
+        Err(_) => code.to_string(),
+    }
+}
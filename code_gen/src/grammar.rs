@@ -0,0 +1,282 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::lex::Token;
+
+/// The version of the config language this build of pipemd understands.
+///
+/// It follows semantic versioning: the minor version is bumped whenever
+/// something is added, such as a directive or field, and the major version
+/// when existing configs could stop parsing or change meaning.
+///
+/// | Version | Added |
+/// |---------|-------|
+/// | 1.0 | `render_pipeline` |
+/// | 1.1 | `#include` |
+/// | 1.2 | `#let` |
+/// | 1.3 | `#cfg` |
+/// | 1.4 | `#defaults` |
+/// | 1.5 | `#template`, `#instantiate` and `#grammar` |
+/// | 1.6 | `variants` |
+/// | 1.7 | `author`, `description` and `tags` |
+/// | 1.8 | `#binding_preset` and `bind_groups` |
+/// | 1.9 | `#compute_pipeline` |
+/// | 1.10 | `#bind_group` |
+/// | 1.11 | `#vertex_layout` and `vertex_buffers` |
+/// | 1.12 | `#shader` and `shader` |
+/// | 1.13 | `#pipeline_layout` and `layout` |
+/// | 1.14 | `#color_target` and `targets` |
+/// | 1.15 | `constants` |
+/// | 1.16 | `defines` for render pipelines |
+/// | 1.17 | `#alias` |
+/// | 1.18 | `#import_rust` |
+/// | 1.19 | `target_formats` |
+/// | 1.20 | `feature` |
+/// | 1.21 | `dynamic` for uniform buffer `bindings` |
+/// | 1.22 | `lang` for `#shader` |
+pub const GRAMMAR_VERSION: GrammarVersion = GrammarVersion::new(1, 22, 0);
+
+/// When each directive was added, from the table on [GRAMMAR_VERSION].
+const DIRECTIVES: &[(&str, GrammarVersion)] = &[
+    ("include", GrammarVersion::new(1, 1, 0)),
+    ("let", GrammarVersion::new(1, 2, 0)),
+    ("cfg", GrammarVersion::new(1, 3, 0)),
+    ("defaults", GrammarVersion::new(1, 4, 0)),
+    ("template", GrammarVersion::new(1, 5, 0)),
+    ("instantiate", GrammarVersion::new(1, 5, 0)),
+    ("grammar", GrammarVersion::new(1, 5, 0)),
+    ("binding_preset", GrammarVersion::new(1, 8, 0)),
+    ("compute_pipeline", GrammarVersion::new(1, 9, 0)),
+    ("bind_group", GrammarVersion::new(1, 10, 0)),
+    ("vertex_layout", GrammarVersion::new(1, 11, 0)),
+    ("shader", GrammarVersion::new(1, 12, 0)),
+    ("pipeline_layout", GrammarVersion::new(1, 13, 0)),
+    ("color_target", GrammarVersion::new(1, 14, 0)),
+    ("alias", GrammarVersion::new(1, 17, 0)),
+    ("import_rust", GrammarVersion::new(1, 18, 0)),
+];
+
+/// When each field added after 1.0 was, along with the directive or
+/// `render_pipeline` it's a field of.
+const FIELDS: &[(&str, &str, GrammarVersion)] = &[
+    ("render_pipeline", "variants", GrammarVersion::new(1, 6, 0)),
+    ("render_pipeline", "author", GrammarVersion::new(1, 7, 0)),
+    (
+        "render_pipeline",
+        "description",
+        GrammarVersion::new(1, 7, 0),
+    ),
+    ("render_pipeline", "tags", GrammarVersion::new(1, 7, 0)),
+    (
+        "render_pipeline",
+        "bind_groups",
+        GrammarVersion::new(1, 8, 0),
+    ),
+    (
+        "render_pipeline",
+        "vertex_buffers",
+        GrammarVersion::new(1, 11, 0),
+    ),
+    ("render_pipeline", "shader", GrammarVersion::new(1, 12, 0)),
+    ("render_pipeline", "layout", GrammarVersion::new(1, 13, 0)),
+    ("render_pipeline", "targets", GrammarVersion::new(1, 14, 0)),
+    (
+        "render_pipeline",
+        "constants",
+        GrammarVersion::new(1, 15, 0),
+    ),
+    ("render_pipeline", "defines", GrammarVersion::new(1, 16, 0)),
+    (
+        "render_pipeline",
+        "target_formats",
+        GrammarVersion::new(1, 19, 0),
+    ),
+    ("render_pipeline", "feature", GrammarVersion::new(1, 20, 0)),
+    ("render_pipeline", "dynamic", GrammarVersion::new(1, 21, 0)),
+    ("shader", "lang", GrammarVersion::new(1, 22, 0)),
+];
+
+/// Returns whether configs written for `version` of the grammar can be
+/// parsed by this build of pipemd.
+pub fn supports(version: GrammarVersion) -> bool {
+    version.major == GRAMMAR_VERSION.major && version <= GRAMMAR_VERSION
+}
+
+/// Finds the first directive or field in `tokens` that was added after
+/// `version`. Returns where it is, what it is, eg. `#compute_pipeline` or
+/// `targets` in `render_pipeline`, and the version it was added in.
+pub(crate) fn newer_than(
+    tokens: &[Token],
+    version: GrammarVersion,
+) -> Option<(usize, String, GrammarVersion)> {
+    // What each open paren is the fields of: a directive, a pipeline, or
+    // `None` for values nested inside them.
+    let mut parens: Vec<Option<&str>> = Vec::new();
+    // The directive or pipeline the next paren opens, if it's right after
+    // its name.
+    let mut item = None;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::LeftParen => {
+                parens.push(item.take());
+                continue;
+            }
+            Token::RightParen => {
+                parens.pop();
+            }
+            Token::Ident(name) if i > 0 && tokens[i - 1] == Token::Hash => {
+                let added = DIRECTIVES
+                    .iter()
+                    .find(|(directive, _)| directive == name)
+                    .map(|(_, added)| *added);
+                if let Some(added) = added.filter(|added| *added > version) {
+                    return Some((i, format!("`#{}`", name), added));
+                }
+                // `#defaults` are given as pipeline fields.
+                item = Some(match name.as_ref() {
+                    "defaults" => "render_pipeline",
+                    name => name,
+                });
+                continue;
+            }
+            Token::Ident(name) if name == "render_pipeline" => {
+                item = Some("render_pipeline");
+                continue;
+            }
+            Token::Ident(field) if tokens.get(i + 1) == Some(&Token::Colon) => {
+                let Some(&of) = parens.iter().rev().flatten().next() else {
+                    continue;
+                };
+                let added = FIELDS
+                    .iter()
+                    .find(|(owner, name, _)| *owner == of && name == field);
+                let added = added.map(|(.., added)| *added);
+                if let Some(added) = added.filter(|added| *added > version) {
+                    let of = match of {
+                        "render_pipeline" => of.to_owned(),
+                        directive => format!("#{}", directive),
+                    };
+                    return Some((i, format!("`{}` in `{}`", field, of), added));
+                }
+            }
+            _ => (),
+        }
+        item = None;
+    }
+    None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GrammarVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl GrammarVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl fmt::Display for GrammarVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid grammar version {0:?}, expected `major.minor` or `major.minor.patch`")]
+pub struct InvalidGrammarVersion(pub String);
+
+impl FromStr for GrammarVersion {
+    type Err = InvalidGrammarVersion;
+
+    /// Parses `major.minor` or `major.minor.patch`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidGrammarVersion(s.to_owned());
+        let parts = s
+            .split('.')
+            .map(|part| part.parse::<u32>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?;
+        match parts[..] {
+            [major, minor] => Ok(Self::new(major, minor, 0)),
+            [major, minor, patch] => Ok(Self::new(major, minor, patch)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_versions() {
+        assert_eq!(Ok(GrammarVersion::new(1, 2, 0)), "1.2".parse());
+        assert_eq!(Ok(GrammarVersion::new(1, 2, 3)), "1.2.3".parse());
+        for invalid in ["1", "1.2.3.4", "1.x", "", "-1.0"] {
+            assert!(invalid.parse::<GrammarVersion>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn supported_versions() {
+        assert!(supports(GrammarVersion::new(1, 0, 0)));
+        assert!(supports(GRAMMAR_VERSION));
+        assert!(!supports(GrammarVersion::new(
+            GRAMMAR_VERSION.major,
+            GRAMMAR_VERSION.minor + 1,
+            0
+        )));
+        assert!(!supports(GrammarVersion::new(
+            GRAMMAR_VERSION.major + 1,
+            0,
+            0
+        )));
+        assert!(!supports(GrammarVersion::new(0, 9, 0)));
+    }
+
+    #[test]
+    fn newer_items() {
+        let newer = |src: &str, version: &str| {
+            let tokens = crate::lex::TokenStream::new(src).unwrap();
+            let newer = newer_than(tokens.remaining(), version.parse().unwrap());
+            newer.map(|(i, what, added)| (i, what, added.to_string()))
+        };
+        let src = r#"#include("a.pmd") #compute_pipeline("Cull", "cs_cull", "cull.wgsl")"#;
+        assert_eq!(None, newer(src, "1.9"));
+        assert_eq!(
+            Some((6, "`#compute_pipeline`".to_owned(), "1.9.0".to_owned())),
+            newer(src, "1.8")
+        );
+
+        let src = r#"render_pipeline(name: "A", primitive: (cull_mode: Back), bindings: (t: (dynamic: true)))"#;
+        assert_eq!(None, newer(src, "1.21"));
+        let (_, what, _) = newer(src, "1.20").unwrap();
+        assert_eq!("`dynamic` in `render_pipeline`", what);
+        // Pipeline fields given to `#defaults` count too, but not the same
+        // name in another directive.
+        assert_eq!(
+            "`tags` in `render_pipeline`",
+            newer("#defaults(tags: [])", "1.6").unwrap().1
+        );
+        assert_eq!(
+            None,
+            newer(
+                r#"#shader(name: "a", path: "a.wgsl", defines: [A])"#,
+                "1.12"
+            )
+        );
+        assert_eq!(
+            "`lang` in `#shader`",
+            newer(r#"#shader(name: "a", path: "a.wgsl", lang: Wgsl)"#, "1.21")
+                .unwrap()
+                .1
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Sharing code between shaders with `#import "path"`, which WGSL has no
+//! way of doing itself.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::loader::{self, ShaderLoader};
+
+/// Whether `src` has any `#import` lines to compose.
+pub(crate) fn has_imports(src: &str) -> bool {
+    src.lines()
+        .any(|line| line.trim_start().starts_with("#import"))
+}
+
+/// Replaces each `#import "path"` line of the shader at `path` with the
+/// file it names, relative to the importing file and composed the same way.
+/// A file is only pulled in the first time it's imported, so shared modules
+/// can be imported from several places, and import cycles are an error.
+/// Imports are resolved before `#ifdef` blocks, so they can't be made
+/// conditional. `read` is called with the path of each imported file.
+///
+/// Returns the composed source along with where each of its lines came
+/// from, so errors in it can point at the file they're really in.
+pub(crate) fn compose(
+    path: &str,
+    src: &str,
+    loader: &dyn ShaderLoader,
+    read: &mut dyn FnMut(&str),
+) -> Result<(String, LineMap)> {
+    let mut composer = Composer {
+        loader,
+        read,
+        stack: vec![path.to_owned()],
+        seen: HashSet::from([path.to_owned()]),
+        lines: LineMap::default(),
+    };
+    let mut out = String::with_capacity(src.len());
+    composer.compose(path, src, &mut out)?;
+    Ok((out, composer.lines))
+}
+
+/// Where each line of a composed shader came from.
+#[derive(Debug, Default)]
+pub(crate) struct LineMap {
+    /// The imported files, after the one they were composed into.
+    files: Vec<String>,
+    /// The index in `files` and 1 based line of each composed line.
+    lines: Vec<(usize, usize)>,
+}
+
+impl LineMap {
+    /// The file and 1 based line that the composed source's `line` is from.
+    /// The file is `None` for the shader the others were imported into.
+    pub(crate) fn get(&self, line: usize) -> Option<(Option<&str>, usize)> {
+        let &(file, line) = self.lines.get(line.checked_sub(1)?)?;
+        let file = file.checked_sub(1).map(|file| self.files[file].as_str());
+        Some((file, line))
+    }
+}
+
+struct Composer<'l> {
+    loader: &'l dyn ShaderLoader,
+    read: &'l mut dyn FnMut(&str),
+    /// The files currently being composed, outermost first.
+    stack: Vec<String>,
+    seen: HashSet<String>,
+    lines: LineMap,
+}
+
+impl Composer<'_> {
+    fn compose(&mut self, path: &str, src: &str, out: &mut String) -> Result<()> {
+        let dir = Path::new(path).parent();
+        let file = match self.stack.len() {
+            1 => 0,
+            _ => {
+                self.lines.files.push(path.to_owned());
+                self.lines.files.len()
+            }
+        };
+        for (i, line) in src.lines().enumerate() {
+            let Some(import) = line.trim_start().strip_prefix("#import") else {
+                out.push_str(line);
+                out.push('\n');
+                self.lines.lines.push((file, i + 1));
+                continue;
+            };
+            let import = import
+                .trim()
+                .strip_prefix('"')
+                .and_then(|import| import.strip_suffix('"'))
+                .with_context(|| {
+                    format!(
+                        "{}:{}: #import needs a quoted path, eg. `#import \"lighting.wgsl\"`",
+                        path,
+                        i + 1
+                    )
+                })?;
+            let import = loader::normalize(dir, import);
+            if self.stack.contains(&import) {
+                let chain = self
+                    .stack
+                    .iter()
+                    .chain([&import])
+                    .cloned()
+                    .collect::<Vec<_>>();
+                anyhow::bail!("{}:{}: Import cycle {}", path, i + 1, chain.join(" -> "));
+            }
+            if !self.seen.insert(import.clone()) {
+                continue;
+            }
+            let src = self
+                .loader
+                .load(&import)
+                .with_context(|| format!("{}:{}: Unable to import {:?}", path, i + 1, import))?;
+            (self.read)(&import);
+            self.stack.push(import.clone());
+            self.compose(&import, &src, out)?;
+            self.stack.pop();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::LoadError;
+
+    #[derive(Debug)]
+    struct Files(HashMap<&'static str, &'static str>);
+
+    impl ShaderLoader for Files {
+        fn load(&self, path: &str) -> Result<String, LoadError> {
+            self.0
+                .get(path)
+                .map(|src| src.to_string())
+                .ok_or_else(|| LoadError::Io {
+                    path: path.into(),
+                    source: std::io::ErrorKind::NotFound.into(),
+                })
+        }
+
+        fn resolve(&self, path: &str) -> Result<PathBuf, LoadError> {
+            Ok(path.into())
+        }
+    }
+
+    #[test]
+    fn compose_imports() {
+        let files = Files(HashMap::from([
+            (
+                "shaders/common/lighting.wgsl",
+                "#import \"math.wgsl\"\nfn light() {}",
+            ),
+            ("shaders/common/math.wgsl", "fn pi() {}"),
+            ("shaders/cycle.wgsl", "#import \"./lit.wgsl\""),
+        ]));
+        let src = "#import \"common/lighting.wgsl\"\n  #import \"common/math.wgsl\"\nfn main() {}";
+        let mut read = Vec::new();
+        let (composed, lines) = compose("shaders/lit.wgsl", src, &files, &mut |path| {
+            read.push(path.to_owned())
+        })
+        .unwrap();
+        assert_eq!("fn pi() {}\nfn light() {}\nfn main() {}\n", composed);
+        assert_eq!(Some((Some("shaders/common/math.wgsl"), 1)), lines.get(1));
+        assert_eq!(
+            Some((Some("shaders/common/lighting.wgsl"), 2)),
+            lines.get(2)
+        );
+        assert_eq!(Some((None, 3)), lines.get(3));
+        assert_eq!(None, lines.get(4));
+        assert_eq!(
+            vec!["shaders/common/lighting.wgsl", "shaders/common/math.wgsl"],
+            read
+        );
+        assert!(has_imports(src));
+        assert!(!has_imports("// none"));
+
+        let e = compose(
+            "shaders/lit.wgsl",
+            "#import \"cycle.wgsl\"",
+            &files,
+            &mut |_| (),
+        )
+        .unwrap_err();
+        assert_eq!(
+            "shaders/cycle.wgsl:1: Import cycle shaders/lit.wgsl -> shaders/cycle.wgsl -> shaders/lit.wgsl",
+            e.to_string()
+        );
+        let e = compose("lit.wgsl", "\n#import lighting", &files, &mut |_| ()).unwrap_err();
+        assert!(
+            e.to_string()
+                .starts_with("lit.wgsl:2: #import needs a quoted path"),
+            "{}",
+            e
+        );
+        let e = compose("lit.wgsl", "#import \"missing.wgsl\"", &files, &mut |_| ()).unwrap_err();
+        assert_eq!(
+            "lit.wgsl:1: Unable to import \"missing.wgsl\"",
+            e.to_string()
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Writing out what naga lowers each pipeline's shader to on the other
+//! backends, for debugging pipelines that only misbehave on one of them.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    expand_variants, gen_pipeline_code_with_manifest, pipeline_defines, prune, shader_defines,
+    shader_language, validator, CodegenOptions, PipelineConfig, PipelineManifest, ShaderCache,
+};
+
+/// Writes the source naga generates from each pipeline's shader for Metal,
+/// DirectX and OpenGL to a directory per pipeline in `dir`, eg.
+/// `dir/SkyPipeline/shader.metal`, along with the WGSL it's generated from
+/// after preprocessing. Shaders are pruned to the pipeline's entry points
+/// first, so each file only has what the pipeline uses.
+///
+/// | file                      | backend                 |
+/// |---------------------------|-------------------------|
+/// | `shader.wgsl`             | the preprocessed input  |
+/// | `shader.metal`            | MSL 2.0                 |
+/// | `shader.hlsl`             | HLSL shader model 5.1   |
+/// | `<entry>.<stage>.glsl`    | GLSL ES 3.10, per stage |
+///
+/// A backend failing to translate a shader is reported as a warning to the
+/// observer rather than an error, since the others are still worth seeing.
+/// Returns the files that were written.
+///
+/// Only built with the `inspect` feature, which the `pipemd` binary enables.
+pub fn write_inspection(
+    config: &PipelineConfig,
+    options: &CodegenOptions,
+    dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    // Generating first checks the config and resolves the entry points.
+    let (_, manifest) = gen_pipeline_code_with_manifest(config, options)?;
+    let mut cache = ShaderCache::default();
+    let render = config
+        .render_pipelines()
+        .iter()
+        .flat_map(expand_variants)
+        .map(|(rp, variant)| {
+            let module = rp.shader.as_ref().and_then(|name| config.shader(name));
+            let defines = shader_defines(module, &pipeline_defines(&rp, &variant));
+            let language = shader_language(module, &rp.path).to_owned();
+            (rp.path.clone(), language, defines, rp.constants.clone())
+        });
+    let compute = config.compute_pipelines().iter().map(|cp| {
+        let module = cp.shader.as_ref().and_then(|name| config.shader(name));
+        let language = shader_language(module, &cp.path).to_owned();
+        (
+            cp.path.clone(),
+            language,
+            shader_defines(module, &None),
+            Vec::new(),
+        )
+    });
+
+    let mut written = Vec::new();
+    for ((path, language, defines, constants), pipeline) in
+        render.chain(compute).zip(&manifest.pipelines)
+    {
+        let shader = cache.get(&path, &language, defines.as_deref(), &constants, options)?;
+        let used = pipeline.entry_points.iter().cloned().collect();
+        let pruned = naga::front::wgsl::parse_str(&shader.src)
+            .ok()
+            .and_then(|module| prune::prune(module, &used))
+            .and_then(|module| Some((validator().validate(&module).ok()?, module)));
+        let (info, module) = match &pruned {
+            Some((info, module)) => (info, module),
+            None => (&shader.info, &shader.module),
+        };
+
+        let pipeline_dir = dir.join(&pipeline.name);
+        std::fs::create_dir_all(&pipeline_dir)
+            .with_context(|| format!("failed to create {}", pipeline_dir.display()))?;
+        let mut write = |name: &str, result: Result<String, String>| -> Result<()> {
+            match result {
+                Ok(src) => {
+                    let path = pipeline_dir.join(name);
+                    std::fs::write(&path, &src)
+                        .with_context(|| format!("failed to write {}", path.display()))?;
+                    if let Some(observer) = &options.observer {
+                        observer.bytes_written(&path, src.len());
+                    }
+                    written.push(path);
+                }
+                Err(e) => {
+                    if let Some(observer) = &options.observer {
+                        observer.warning(&format!(
+                            "{}: Unable to write {}: {}",
+                            pipeline.name, name, e
+                        ));
+                    }
+                }
+            }
+            Ok(())
+        };
+        write("shader.wgsl", Ok(shader.src.clone()))?;
+        write("shader.metal", msl(module, info))?;
+        write("shader.hlsl", hlsl(module, info))?;
+        for (entry, stage) in stages(module, pipeline) {
+            let extension = match stage {
+                naga::ShaderStage::Vertex => "vert",
+                naga::ShaderStage::Fragment => "frag",
+                naga::ShaderStage::Compute => "comp",
+            };
+            write(
+                &format!("{}.{}.glsl", entry, extension),
+                glsl(module, info, entry, stage),
+            )?;
+        }
+    }
+    Ok(written)
+}
+
+/// The pipeline's entry points with their stages.
+fn stages<'a>(
+    module: &naga::Module,
+    pipeline: &'a PipelineManifest,
+) -> Vec<(&'a str, naga::ShaderStage)> {
+    pipeline
+        .entry_points
+        .iter()
+        .filter_map(|name| {
+            Some((
+                name.as_str(),
+                module
+                    .entry_points
+                    .iter()
+                    .find(|ep| &ep.name == name)?
+                    .stage,
+            ))
+        })
+        .collect()
+}
+
+fn msl(module: &naga::Module, info: &naga::valid::ModuleInfo) -> Result<String, String> {
+    let options = naga::back::msl::Options::default();
+    naga::back::msl::write_string(
+        module,
+        info,
+        &options,
+        &naga::back::msl::PipelineOptions::default(),
+    )
+    .map(|(src, _)| src)
+    .map_err(|e| e.to_string())
+}
+
+fn hlsl(module: &naga::Module, info: &naga::valid::ModuleInfo) -> Result<String, String> {
+    let options = naga::back::hlsl::Options::default();
+    let mut src = String::new();
+    naga::back::hlsl::Writer::new(&mut src, &options)
+        .write(module, info)
+        .map_err(|e| e.to_string())?;
+    Ok(src)
+}
+
+fn glsl(
+    module: &naga::Module,
+    info: &naga::valid::ModuleInfo,
+    entry: &str,
+    stage: naga::ShaderStage,
+) -> Result<String, String> {
+    let options = naga::back::glsl::Options::default();
+    let pipeline_options = naga::back::glsl::PipelineOptions {
+        shader_stage: stage,
+        entry_point: entry.to_owned(),
+        multiview: None,
+    };
+    let mut src = String::new();
+    naga::back::glsl::Writer::new(
+        &mut src,
+        module,
+        info,
+        &options,
+        &pipeline_options,
+        Default::default(),
+    )
+    .and_then(|mut writer| writer.write())
+    .map_err(|e| e.to_string())?;
+    Ok(src)
+}
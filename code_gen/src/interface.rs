@@ -0,0 +1,412 @@
+use std::collections::BTreeMap;
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever the layout of [Interface] changes in a way older tooling
+/// can't read.
+pub const INTERFACE_VERSION: u32 = 1;
+
+/// A stable, machine-readable description of the public API generated for a
+/// config. Two interfaces can be compared with [Interface::diff] to detect
+/// unintended breakage of the generated code.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interface {
+    pub version: u32,
+    pub items: Vec<InterfaceItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterfaceItem {
+    /// Path of the item relative to the generated code, eg.
+    /// `TexturedPipeline::new`.
+    pub path: String,
+    pub kind: ItemKind,
+    /// The item's signature with bodies and private details stripped.
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    Struct,
+    Enum,
+    Trait,
+    Fn,
+    Const,
+    Static,
+    Type,
+    Mod,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterfaceChange {
+    Added(InterfaceItem),
+    Removed(InterfaceItem),
+    Changed {
+        old: InterfaceItem,
+        new: InterfaceItem,
+    },
+}
+
+impl InterfaceChange {
+    /// Added items can't break downstream code, everything else can.
+    pub fn is_breaking(&self) -> bool {
+        !matches!(self, Self::Added(_))
+    }
+}
+
+impl std::fmt::Display for InterfaceChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Added(item) => write!(f, "+ {}: {}", item.path, item.signature),
+            Self::Removed(item) => write!(f, "- {}: {}", item.path, item.signature),
+            Self::Changed { old, new } => {
+                write!(f, "~ {}: {} => {}", new.path, old.signature, new.signature)
+            }
+        }
+    }
+}
+
+impl Interface {
+    /// Collects every public item in `tokens`, which should be the output of
+    /// [crate::gen_pipeline_code].
+    pub fn from_tokens(tokens: &TokenStream) -> syn::Result<Self> {
+        let file: syn::File = syn::parse2(tokens.clone())?;
+        let mut items = Vec::new();
+        collect_items("", &file.items, &mut items);
+        items.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Self {
+            version: INTERFACE_VERSION,
+            items,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Interface should always serialize")
+    }
+
+    pub fn from_json(src: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(src)
+    }
+
+    /// Lists the changes needed to turn `old` into `self`, ordered by path.
+    pub fn diff(&self, old: &Interface) -> Vec<InterfaceChange> {
+        let old_items = old
+            .items
+            .iter()
+            .map(|i| (i.path.as_str(), i))
+            .collect::<BTreeMap<_, _>>();
+        let new_items = self
+            .items
+            .iter()
+            .map(|i| (i.path.as_str(), i))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut changes = Vec::new();
+        for (path, old_item) in &old_items {
+            match new_items.get(path) {
+                None => changes.push(InterfaceChange::Removed((*old_item).clone())),
+                Some(new_item) if new_item != old_item => changes.push(InterfaceChange::Changed {
+                    old: (*old_item).clone(),
+                    new: (*new_item).clone(),
+                }),
+                Some(_) => (),
+            }
+        }
+        for (path, new_item) in &new_items {
+            if !old_items.contains_key(path) {
+                changes.push(InterfaceChange::Added((*new_item).clone()));
+            }
+        }
+        changes.sort_by(|a, b| change_path(a).cmp(change_path(b)));
+        changes
+    }
+}
+
+fn change_path(change: &InterfaceChange) -> &str {
+    match change {
+        InterfaceChange::Added(item) | InterfaceChange::Removed(item) => &item.path,
+        InterfaceChange::Changed { new, .. } => &new.path,
+    }
+}
+
+fn is_public(vis: &syn::Visibility) -> bool {
+    matches!(vis, syn::Visibility::Public(_))
+}
+
+fn join(prefix: &str, name: &impl std::fmt::Display) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}::{}", prefix, name)
+    }
+}
+
+/// Only `#[repr]` and `#[derive]` change how a generated type can be used, so
+/// every other attribute (docs especially) is left out of signatures.
+fn layout_attrs(attrs: &[syn::Attribute]) -> TokenStream {
+    let attrs = attrs
+        .iter()
+        .filter(|a| a.path().is_ident("repr") || a.path().is_ident("derive"));
+    quote! { #(#attrs)* }
+}
+
+fn collect_items(prefix: &str, items: &[syn::Item], out: &mut Vec<InterfaceItem>) {
+    let mut push = |path: String, kind: ItemKind, signature: TokenStream| {
+        out.push(InterfaceItem {
+            path,
+            kind,
+            signature: signature.to_string(),
+        })
+    };
+
+    let mut nested = Vec::new();
+    for item in items {
+        match item {
+            syn::Item::Struct(s) if is_public(&s.vis) => {
+                let attrs = layout_attrs(&s.attrs);
+                let ident = &s.ident;
+                let generics = &s.generics;
+                let public_fields = s.fields.iter().filter(|f| is_public(&f.vis)).map(|f| {
+                    let ident = &f.ident;
+                    let ty = &f.ty;
+                    match ident {
+                        Some(ident) => quote! { pub #ident: #ty },
+                        None => quote! { pub #ty },
+                    }
+                });
+                let has_private = s.fields.iter().any(|f| !is_public(&f.vis));
+                let rest = has_private.then(|| quote! { .. });
+                let fields = match &s.fields {
+                    syn::Fields::Named(_) => quote! { { #(#public_fields,)* #rest } },
+                    syn::Fields::Unnamed(_) => quote! { ( #(#public_fields,)* #rest ) },
+                    syn::Fields::Unit => quote! {},
+                };
+                push(
+                    join(prefix, ident),
+                    ItemKind::Struct,
+                    quote! { #attrs pub struct #ident #generics #fields },
+                );
+            }
+            syn::Item::Enum(e) if is_public(&e.vis) => {
+                let attrs = layout_attrs(&e.attrs);
+                let ident = &e.ident;
+                let generics = &e.generics;
+                let variants = e.variants.iter().map(|v| {
+                    let ident = &v.ident;
+                    let fields = &v.fields;
+                    quote! { #ident #fields }
+                });
+                push(
+                    join(prefix, ident),
+                    ItemKind::Enum,
+                    quote! { #attrs pub enum #ident #generics { #(#variants,)* } },
+                );
+            }
+            syn::Item::Trait(t) if is_public(&t.vis) => {
+                let ident = &t.ident;
+                let generics = &t.generics;
+                let supertraits = &t.supertraits;
+                let colon = t.colon_token;
+                push(
+                    join(prefix, ident),
+                    ItemKind::Trait,
+                    quote! { pub trait #ident #generics #colon #supertraits },
+                );
+                for trait_item in &t.items {
+                    match trait_item {
+                        syn::TraitItem::Fn(f) => {
+                            let sig = &f.sig;
+                            push(
+                                join(&join(prefix, ident), &sig.ident),
+                                ItemKind::Fn,
+                                quote! { #sig },
+                            );
+                        }
+                        syn::TraitItem::Const(c) => {
+                            let (name, ty) = (&c.ident, &c.ty);
+                            push(
+                                join(&join(prefix, ident), name),
+                                ItemKind::Const,
+                                quote! { const #name: #ty },
+                            );
+                        }
+                        syn::TraitItem::Type(ty) => {
+                            let name = &ty.ident;
+                            push(
+                                join(&join(prefix, ident), name),
+                                ItemKind::Type,
+                                quote! { type #name },
+                            );
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            syn::Item::Fn(f) if is_public(&f.vis) => {
+                let sig = &f.sig;
+                push(join(prefix, &sig.ident), ItemKind::Fn, quote! { pub #sig });
+            }
+            syn::Item::Const(c) if is_public(&c.vis) => {
+                let (ident, ty) = (&c.ident, &c.ty);
+                push(
+                    join(prefix, ident),
+                    ItemKind::Const,
+                    quote! { pub const #ident: #ty },
+                );
+            }
+            syn::Item::Static(s) if is_public(&s.vis) => {
+                let (ident, ty) = (&s.ident, &s.ty);
+                push(
+                    join(prefix, ident),
+                    ItemKind::Static,
+                    quote! { pub static #ident: #ty },
+                );
+            }
+            syn::Item::Type(t) if is_public(&t.vis) => {
+                let (ident, generics, ty) = (&t.ident, &t.generics, &t.ty);
+                push(
+                    join(prefix, ident),
+                    ItemKind::Type,
+                    quote! { pub type #ident #generics = #ty },
+                );
+            }
+            syn::Item::Mod(m) if is_public(&m.vis) => {
+                let ident = &m.ident;
+                push(
+                    join(prefix, ident),
+                    ItemKind::Mod,
+                    quote! { pub mod #ident },
+                );
+                if let Some((_, items)) = &m.content {
+                    nested.push((join(prefix, ident), items));
+                }
+            }
+            syn::Item::Impl(imp) => {
+                let self_ty = imp.self_ty.to_token_stream().to_string().replace(' ', "");
+                let self_path = join(prefix, &self_ty);
+                match &imp.trait_ {
+                    // Trait impls are part of the API even though their items
+                    // never have a visibility.
+                    Some((_, trait_path, _)) => {
+                        let generics = &imp.generics;
+                        let self_ty = &imp.self_ty;
+                        let trait_name = trait_path.to_token_stream().to_string().replace(' ', "");
+                        push(
+                            format!("{} as {}", self_path, trait_name),
+                            ItemKind::Trait,
+                            quote! { impl #generics #trait_path for #self_ty },
+                        );
+                    }
+                    None => {
+                        for impl_item in &imp.items {
+                            match impl_item {
+                                syn::ImplItem::Fn(f) if is_public(&f.vis) => {
+                                    let sig = &f.sig;
+                                    push(
+                                        join(&self_path, &sig.ident),
+                                        ItemKind::Fn,
+                                        quote! { pub #sig },
+                                    );
+                                }
+                                syn::ImplItem::Const(c) if is_public(&c.vis) => {
+                                    let (ident, ty) = (&c.ident, &c.ty);
+                                    push(
+                                        join(&self_path, ident),
+                                        ItemKind::Const,
+                                        quote! { pub const #ident: #ty },
+                                    );
+                                }
+                                _ => (),
+                            }
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    for (prefix, items) in nested {
+        collect_items(&prefix, items, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interface_collects_public_items() {
+        let tokens = quote! {
+            const SHADER0: &'static str = "";
+            pub const LABEL: &str = "Textured";
+            #[doc = " Ignored"]
+            #[repr(C)]
+            pub struct Vertex {
+                pub position: [f32; 2],
+                padding: u32,
+            }
+            pub struct TexturedPipeline {
+                render_pipeline: ::wgpu::RenderPipeline,
+            }
+            impl TexturedPipeline {
+                pub const REQUIRED_FEATURES: ::wgpu::Features = ::wgpu::Features::empty();
+                pub fn new(device: &::wgpu::Device) -> Self { todo!() }
+                fn private(&self) {}
+            }
+        };
+        let interface = Interface::from_tokens(&tokens).unwrap();
+        let paths = interface
+            .items
+            .iter()
+            .map(|i| i.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "LABEL",
+                "TexturedPipeline",
+                "TexturedPipeline::REQUIRED_FEATURES",
+                "TexturedPipeline::new",
+                "Vertex",
+            ],
+            paths
+        );
+        assert_eq!(
+            quote! { #[repr(C)] pub struct Vertex { pub position: [f32; 2], .. } }.to_string(),
+            interface.items[4].signature,
+        );
+        assert_eq!(
+            interface,
+            Interface::from_json(&interface.to_json()).unwrap()
+        );
+    }
+
+    #[test]
+    fn interface_diff() {
+        let old = Interface::from_tokens(&quote! {
+            pub struct A;
+            pub fn changed(device: ::wgpu::Device) {}
+            pub fn removed() {}
+        })
+        .unwrap();
+        let new = Interface::from_tokens(&quote! {
+            pub struct A;
+            pub fn changed(device: &::wgpu::Device) {}
+            pub fn added() {}
+        })
+        .unwrap();
+        let changes = new.diff(&old);
+        assert_eq!(3, changes.len());
+        assert!(matches!(&changes[0], InterfaceChange::Added(i) if i.path == "added"));
+        assert!(
+            matches!(&changes[1], InterfaceChange::Changed { new, .. } if new.path == "changed")
+        );
+        assert!(matches!(&changes[2], InterfaceChange::Removed(i) if i.path == "removed"));
+        assert!(!changes[0].is_breaking());
+        assert!(changes[1].is_breaking());
+        assert!(new.diff(&new).is_empty());
+    }
+}
@@ -0,0 +1,397 @@
+//! Rust structs with the same memory layout as the WGSL structs shaders
+//! read from buffers.
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote};
+
+use crate::reflect;
+
+/// The structs generated so far, shared by every pipeline so a struct used
+/// by several is only generated once.
+#[derive(Default)]
+pub(crate) struct HostStructs {
+    /// Each struct's name, the type it was generated from and its tokens.
+    structs: Vec<(String, String, TokenStream)>,
+}
+
+impl HostStructs {
+    /// Generates the WGSL struct `ty` and the structs it's built from,
+    /// returning its name. Structs written by different shaders can share a
+    /// name as long as their layouts are the same.
+    pub fn add(
+        &mut self,
+        module: &naga::Module,
+        ty: naga::Handle<naga::Type>,
+        bytemuck: bool,
+    ) -> Result<Ident, String> {
+        let naga::TypeInner::Struct { members, span } = &module.types[ty].inner else {
+            return Err("only structs can be generated".to_owned());
+        };
+        let name = module.types[ty]
+            .name
+            .as_deref()
+            .ok_or_else(|| "an unnamed struct can't be generated".to_owned())?;
+        let ident = format_ident!("{}", name);
+
+        let mut fields = Vec::new();
+        let mut cursor = 0;
+        // Storage buffers can end with a runtime-sized array, which is left
+        // out of the struct and written after it.
+        let (members, runtime_array) = match members.split_last() {
+            Some((last, head)) => match module.types[last.ty].inner {
+                naga::TypeInner::Array {
+                    base,
+                    size: naga::ArraySize::Dynamic,
+                    stride,
+                } => (head, Some((last, base, stride))),
+                _ => (&members[..], None),
+            },
+            None => (&members[..], None),
+        };
+        let pad = |fields: &mut Vec<TokenStream>, len: u32| {
+            let pad = format_ident!("_pad{}", fields.len());
+            let len = len as usize;
+            fields.push(quote! {
+                #[doc(hidden)]
+                pub #pad: [u8; #len]
+            });
+        };
+        for member in members {
+            if member.offset > cursor {
+                pad(&mut fields, member.offset - cursor);
+            }
+            let member_name = member.name.as_deref().unwrap_or_default();
+            let (field_ty, size) = self
+                .rust_type(module, member.ty, bytemuck)
+                .map_err(|e| format!("`{}.{}` {}", name, member_name, e))?;
+            let field = field_ident(member_name);
+            fields.push(quote! { pub #field: #field_ty });
+            cursor = member.offset + size;
+        }
+        let size = match runtime_array {
+            Some((array, _, _)) => array.offset,
+            None => *span,
+        };
+        if size > cursor {
+            pad(&mut fields, size - cursor);
+        }
+        let runtime_array = match runtime_array {
+            Some((array, base, stride)) => {
+                let array_name = array.name.as_deref().unwrap_or_default();
+                self.add_element(module, base, stride, bytemuck)
+                    .map_err(|e| format!("`{}.{}` {}", name, array_name, e))?;
+                let doc = format!(
+                    " The distance between the elements of `{}`, which follow the struct.",
+                    array_name
+                );
+                let stride = stride as u64;
+                quote! {
+                    impl #ident {
+                        #[doc = #doc]
+                        pub const ELEMENT_STRIDE: u64 = #stride;
+
+                        /// The size of a buffer holding this struct followed by `len` elements.
+                        pub const fn buffer_size(len: u64) -> u64 {
+                            ::std::mem::size_of::<Self>() as u64 + len * Self::ELEMENT_STRIDE
+                        }
+                    }
+                }
+            }
+            None => quote! {},
+        };
+
+        let derives = match bytemuck {
+            true => {
+                quote! { #[derive(Debug, Clone, Copy, PartialEq, ::bytemuck::Pod, ::bytemuck::Zeroable)] }
+            }
+            false => quote! { #[derive(Debug, Clone, Copy, PartialEq)] },
+        };
+        let doc = format!(
+            " The layout of the WGSL struct `{}`, padded to match it byte for byte.",
+            name
+        );
+        let size = size as usize;
+        let tokens = quote! {
+            #[doc = #doc]
+            #[repr(C)]
+            #derives
+            pub struct #ident {
+                #(#fields,)*
+            }
+
+            const _: () = assert!(::std::mem::size_of::<#ident>() == #size);
+
+            impl Default for #ident {
+                fn default() -> Self {
+                    // SAFETY: every field is a number or an array of them, so
+                    // all zeroes is a valid value.
+                    unsafe { ::std::mem::zeroed() }
+                }
+            }
+
+            impl #ident {
+                /// The bytes to write to a buffer holding this struct.
+                pub fn as_bytes(&self) -> &[u8] {
+                    // SAFETY: the struct is `repr(C)` and its padding is
+                    // explicit, so every byte is initialized.
+                    unsafe {
+                        ::std::slice::from_raw_parts((self as *const Self).cast::<u8>(), ::std::mem::size_of::<Self>())
+                    }
+                }
+            }
+
+            #runtime_array
+        };
+        let generated = tokens.to_string();
+        match self.structs.iter().find(|(n, _, _)| n == name) {
+            Some((_, g, _)) if *g == generated => (),
+            Some(_) => {
+                return Err(format!(
+                    "struct `{}` has a different layout in another shader",
+                    name
+                ))
+            }
+            None => self.structs.push((name.to_owned(), generated, tokens)),
+        }
+        Ok(ident)
+    }
+
+    /// Generates the structs held by the uniform buffers, storage buffers and
+    /// push constants in `bindings`.
+    pub fn add_buffers(
+        &mut self,
+        module: &naga::Module,
+        bindings: &reflect::Bindings,
+        bytemuck: bool,
+    ) -> Result<(), String> {
+        let push_constants = bindings.push_constants.iter().map(|pc| pc.ty);
+        for ty in bindings
+            .uniforms
+            .iter()
+            .map(|uniform| uniform.ty)
+            .chain(push_constants)
+        {
+            if let naga::TypeInner::Struct { .. } = module.types[ty].inner {
+                self.add(module, ty, bytemuck)?;
+            }
+        }
+        for storage in &bindings.storage {
+            self.add_storage(module, storage.ty, bytemuck)?;
+        }
+        Ok(())
+    }
+
+    /// Generates what a storage buffer holding `ty` is written from: a
+    /// struct, or the elements of a runtime-sized array.
+    fn add_storage(
+        &mut self,
+        module: &naga::Module,
+        ty: naga::Handle<naga::Type>,
+        bytemuck: bool,
+    ) -> Result<(), String> {
+        match module.types[ty].inner {
+            naga::TypeInner::Struct { .. } => self.add(module, ty, bytemuck).map(|_| ()),
+            naga::TypeInner::Array {
+                base,
+                size: naga::ArraySize::Dynamic,
+                stride,
+            } => self.add_element(module, base, stride, bytemuck),
+            _ => Ok(()),
+        }
+    }
+
+    /// Generates the elements of a runtime-sized array if they're structs,
+    /// along with their `STRIDE`.
+    fn add_element(
+        &mut self,
+        module: &naga::Module,
+        base: naga::Handle<naga::Type>,
+        stride: u32,
+        bytemuck: bool,
+    ) -> Result<(), String> {
+        let naga::TypeInner::Struct { span, .. } = module.types[base].inner else {
+            return Ok(());
+        };
+        let ident = self.add(module, base, bytemuck)?;
+        if span != stride {
+            return Err(format!(
+                "has elements {} bytes apart, which can't be matched",
+                stride
+            ));
+        }
+        let name = format!("{} stride", ident);
+        if !self.structs.iter().any(|(n, _, _)| *n == name) {
+            let stride = stride as u64;
+            let tokens = quote! {
+                impl #ident {
+                    /// The distance between the elements of a runtime-sized array of this struct.
+                    pub const STRIDE: u64 = #stride;
+                }
+            };
+            self.structs.push((name, String::new(), tokens));
+        }
+        Ok(())
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = &TokenStream> {
+        self.structs.iter().map(|(_, _, tokens)| tokens)
+    }
+
+    /// The Rust type laid out like `ty` and its size in bytes.
+    fn rust_type(
+        &mut self,
+        module: &naga::Module,
+        ty: naga::Handle<naga::Type>,
+        bytemuck: bool,
+    ) -> Result<(TokenStream, u32), String> {
+        Ok(match module.types[ty].inner {
+            naga::TypeInner::Scalar { kind, width } | naga::TypeInner::Atomic { kind, width } => {
+                (scalar(kind, width)?, width as u32)
+            }
+            naga::TypeInner::Vector { size, kind, width } => {
+                let scalar = scalar(kind, width)?;
+                let len = size as usize;
+                (quote! { [#scalar; #len] }, width as u32 * len as u32)
+            }
+            naga::TypeInner::Matrix {
+                columns,
+                rows,
+                width,
+            } => {
+                // Columns are aligned like vectors, so a vec3 column takes
+                // up as much room as a vec4.
+                let scalar = scalar(naga::ScalarKind::Float, width)?;
+                let (columns, rows) = (columns as usize, rows as usize);
+                let padded = if rows == 3 { 4 } else { rows };
+                (
+                    quote! { [[#scalar; #padded]; #columns] },
+                    (width as usize * padded * columns) as u32,
+                )
+            }
+            naga::TypeInner::Array { base, size, stride } => {
+                let len = match size {
+                    naga::ArraySize::Constant(constant) => match module.constants[constant].inner {
+                        naga::ConstantInner::Scalar {
+                            value: naga::ScalarValue::Uint(n),
+                            ..
+                        } => n as usize,
+                        naga::ConstantInner::Scalar {
+                            value: naga::ScalarValue::Sint(n),
+                            ..
+                        } => n as usize,
+                        _ => return Err("is an array without a constant length".to_owned()),
+                    },
+                    naga::ArraySize::Dynamic => return Err("is a runtime-sized array".to_owned()),
+                };
+                let (element, size) = self.rust_type(module, base, bytemuck)?;
+                let element = match module.types[base].inner {
+                    _ if size == stride => element,
+                    // Scalars and vectors are padded out to the stride, eg.
+                    // an `array<vec3<f32>, 4>` becomes a `[[f32; 4]; 4]`.
+                    naga::TypeInner::Scalar { kind, width }
+                    | naga::TypeInner::Vector { kind, width, .. }
+                        if stride % width as u32 == 0 =>
+                    {
+                        let scalar = scalar(kind, width)?;
+                        let padded = (stride / width as u32) as usize;
+                        quote! { [#scalar; #padded] }
+                    }
+                    _ => {
+                        return Err(format!(
+                            "has elements {} bytes apart, which can't be matched",
+                            stride
+                        ))
+                    }
+                };
+                (quote! { [#element; #len] }, stride * len as u32)
+            }
+            naga::TypeInner::Struct { span, .. } => {
+                let ident = self.add(module, ty, bytemuck)?;
+                (quote! { #ident }, span)
+            }
+            ref inner => {
+                return Err(format!(
+                    "is a {:?}, which can't be written from Rust",
+                    inner
+                ))
+            }
+        })
+    }
+}
+
+fn scalar(kind: naga::ScalarKind, width: naga::Bytes) -> Result<TokenStream, String> {
+    Ok(match (kind, width) {
+        (naga::ScalarKind::Float, 4) => quote! { f32 },
+        (naga::ScalarKind::Float, 8) => quote! { f64 },
+        (naga::ScalarKind::Sint, 4) => quote! { i32 },
+        (naga::ScalarKind::Uint, 4) => quote! { u32 },
+        _ => {
+            return Err(format!(
+                "is a {:?} of {} bytes, which can't be written from Rust",
+                kind, width
+            ))
+        }
+    })
+}
+
+/// Member names that are Rust keywords become raw identifiers.
+pub(crate) fn field_ident(name: &str) -> Ident {
+    match syn::parse_str::<Ident>(name) {
+        Ok(ident) => ident,
+        Err(_) => Ident::new_raw(name, Span::call_site()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padded_structs() {
+        let module = naga::front::wgsl::parse_str(
+            "struct Light { position: vec3<f32>, intensity: f32, color: vec3<f32>, }
+            struct Globals { normal: mat3x3<f32>, time: f32, offset: vec2<f32>, light: Light, }",
+        )
+        .unwrap();
+        let (globals, _) = module
+            .types
+            .iter()
+            .find(|(_, ty)| ty.name.as_deref() == Some("Globals"))
+            .unwrap();
+        let mut structs = HostStructs::default();
+        assert_eq!(
+            "Globals",
+            structs.add(&module, globals, false).unwrap().to_string()
+        );
+        let generated = structs
+            .tokens()
+            .map(|tokens| tokens.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(2, generated.len());
+        assert!(
+            generated[0].contains("pub struct Light"),
+            "{}",
+            generated[0]
+        );
+        assert!(
+            generated[0].contains("size_of :: < Light > () == 32usize"),
+            "{}",
+            generated[0]
+        );
+        assert!(
+            generated[1].contains("pub normal : [[f32 ; 4usize] ; 3usize]"),
+            "{}",
+            generated[1]
+        );
+        assert!(
+            generated[1].contains("pub _pad2 : [u8 ; 4usize]"),
+            "{}",
+            generated[1]
+        );
+        assert!(
+            generated[1].contains("size_of :: < Globals > () == 96usize"),
+            "{}",
+            generated[1]
+        );
+    }
+}
@@ -1,47 +1,187 @@
-use std::str::CharIndices;
+use std::borrow::Cow;
+use std::collections::HashMap;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// Tokens borrow their text from the source when they can. Strings containing
+/// escapes, and tokens synthesized by directives, own their text instead.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Token<'a> {
-    Ident(&'a str),
-    String(&'a str),
+    Ident(Cow<'a, str>),
+    /// The contents of a string literal with its escapes processed.
+    String(Cow<'a, str>),
+    /// The source text of a number literal, eg. `-2` or `0.5`.
+    Number(Cow<'a, str>),
+    /// A `$name` reference to a value defined with `#let`.
+    Variable(Cow<'a, str>),
     Hash,
     Comma,
     LeftParen,
     RightParen,
+    LeftBracket,
+    RightBracket,
     Colon,
 }
 
+impl<'a> Token<'a> {
+    /// Shorthand for an identifier that borrows `ident`.
+    pub fn ident(ident: &'a str) -> Self {
+        Token::Ident(Cow::Borrowed(ident))
+    }
+
+    /// Shorthand for a string that borrows `s`.
+    pub fn string(s: &'a str) -> Self {
+        Token::String(Cow::Borrowed(s))
+    }
+
+    /// Shorthand for a number literal that borrows `n`.
+    pub fn number(n: &'a str) -> Self {
+        Token::Number(Cow::Borrowed(n))
+    }
+
+    /// Copies any borrowed text so the token no longer depends on its source.
+    pub fn into_owned(self) -> Token<'static> {
+        match self {
+            Token::Ident(id) => Token::Ident(Cow::Owned(id.into_owned())),
+            Token::String(s) => Token::String(Cow::Owned(s.into_owned())),
+            Token::Number(n) => Token::Number(Cow::Owned(n.into_owned())),
+            Token::Variable(name) => Token::Variable(Cow::Owned(name.into_owned())),
+            Token::Hash => Token::Hash,
+            Token::Comma => Token::Comma,
+            Token::LeftParen => Token::LeftParen,
+            Token::RightParen => Token::RightParen,
+            Token::LeftBracket => Token::LeftBracket,
+            Token::RightBracket => Token::RightBracket,
+            Token::Colon => Token::Colon,
+        }
+    }
+}
+
 pub struct TokenStream<'a> {
     index: usize,
     tokens: Vec<Token<'a>>,
+    /// The 1 based line each token starts on. Empty if the tokens didn't come
+    /// from a source.
+    lines: Vec<usize>,
 }
 
 impl<'a> TokenStream<'a> {
     pub fn new(src: &'a str) -> Result<Self, LexError> {
         let mut tokens = Vec::new();
+        let mut lines = Vec::new();
+        let mut line = Lines::new(src);
         let (token, mut remaining) = lex_token(src)?;
         tokens.push(token);
+        lines.push(line.at(src));
         while let Some(span) = remaining {
-            let (token, new_remaining) = match lex_token(span.substring()) {
+            let rest = span.substring();
+            let (token, new_remaining) = match lex_token(rest) {
                 Err(LexError::EndOfInput) => break,
                 e => e?,
             };
             tokens.push(token);
+            lines.push(line.at(rest));
             remaining = new_remaining;
         }
-        Ok(Self { tokens, index: 0 })
+        Ok(Self {
+            tokens,
+            index: 0,
+            lines,
+        })
     }
 
-    pub fn peek(&self) -> Option<Token<'a>> {
-        if self.index < self.tokens.len() {
-            Some(self.tokens[self.index])
-        } else {
-            None
+    /// Reads all of `reader` and lexes it. The tokens own their text so the
+    /// stream doesn't borrow from the buffer. Lexing errors are returned as
+    /// [std::io::ErrorKind::InvalidData].
+    pub fn from_reader(mut reader: impl std::io::Read) -> std::io::Result<TokenStream<'static>> {
+        let mut src = String::new();
+        reader.read_to_string(&mut src)?;
+        let stream = TokenStream::new(&src)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok(TokenStream {
+            tokens: stream.tokens.into_iter().map(Token::into_owned).collect(),
+            index: 0,
+            lines: stream.lines,
+        })
+    }
+
+    /// Creates a stream from tokens that didn't come from lexing a source,
+    /// eg. ones produced by expanding a directive.
+    pub fn from_tokens(tokens: Vec<Token<'a>>) -> Self {
+        Self {
+            tokens,
+            index: 0,
+            lines: Vec::new(),
+        }
+    }
+
+    /// Replaces every remaining `$name` with the tokens of its value, leaving
+    /// references to undefined variables in place. The value's tokens are
+    /// given the line of the reference.
+    pub fn substitute(&mut self, variables: &HashMap<String, Vec<Token<'static>>>) {
+        let has_variables = self.tokens[self.index..]
+            .iter()
+            .any(|t| matches!(t, Token::Variable(name) if variables.contains_key(name.as_ref())));
+        if !has_variables {
+            return;
+        }
+        let remaining = self.tokens.split_off(self.index);
+        let remaining_lines = self.lines.split_off(self.index.min(self.lines.len()));
+        for (i, token) in remaining.into_iter().enumerate() {
+            let start = self.tokens.len();
+            match token {
+                Token::Variable(name) if variables.contains_key(name.as_ref()) => {
+                    self.tokens.extend(variables[name.as_ref()].iter().cloned());
+                }
+                token => self.tokens.push(token),
+            }
+            if let Some(&line) = remaining_lines.get(i) {
+                self.lines
+                    .resize(self.lines.len() + self.tokens.len() - start, line);
+            }
+        }
+    }
+
+    /// Replaces every remaining identifier named in `aliases` with its value,
+    /// except where it's followed by `:` and so names a field.
+    pub fn resolve_aliases(&mut self, aliases: &HashMap<String, Token<'static>>) {
+        if aliases.is_empty() {
+            return;
+        }
+        for i in self.index..self.tokens.len() {
+            let value = match &self.tokens[i] {
+                Token::Ident(name) if self.tokens.get(i + 1) != Some(&Token::Colon) => {
+                    aliases.get(name.as_ref())
+                }
+                _ => None,
+            };
+            if let Some(value) = value {
+                self.tokens[i] = value.clone();
+            }
         }
     }
 
+    /// The line of the last token taken from the stream, or of the first one
+    /// if none have been. This is where a parse error was found.
+    pub fn line(&self) -> Option<usize> {
+        self.lines.get(self.index.saturating_sub(1)).copied()
+    }
+
+    /// The line of the next token in the stream, where whatever it starts
+    /// was declared.
+    pub fn peek_line(&self) -> Option<usize> {
+        self.lines.get(self.index).copied()
+    }
+
+    /// The tokens that haven't been taken from the stream yet.
+    pub fn remaining(&self) -> &[Token<'a>] {
+        &self.tokens[self.index..]
+    }
+
+    pub fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.index)
+    }
+
     pub fn next(&mut self) -> Option<Token<'a>> {
-        let token = self.peek();
+        let token = self.tokens.get(self.index).cloned();
         if token.is_some() {
             self.index += 1;
         }
@@ -49,6 +189,33 @@ impl<'a> TokenStream<'a> {
     }
 }
 
+/// Counts the lines leading up to each token. Tokens are lexed in order, so
+/// only the text since the previous one needs to be counted.
+struct Lines<'a> {
+    src: &'a str,
+    offset: usize,
+    line: usize,
+}
+
+impl<'a> Lines<'a> {
+    fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            offset: 0,
+            line: 1,
+        }
+    }
+
+    /// The line of the token at the start of `rest`, which is a suffix of the
+    /// source.
+    fn at(&mut self, rest: &str) -> usize {
+        let start = self.src.len() - rest.trim_start().len();
+        self.line += self.src[self.offset..start].matches('\n').count();
+        self.offset = start;
+        self.line
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Span {
     start_byte: usize,
@@ -100,9 +267,8 @@ impl<'a> SpannedStr<'a> {
         let src = self.substring();
         let start_byte = self.span.start_byte;
         let mut new_start = start_byte;
-        let mut iter = src.char_indices().take(n + 1);
         let mut num = 0;
-        while let Some((i, _)) = iter.next() {
+        for (i, _) in src.char_indices().take(n + 1) {
             new_start = start_byte + i;
             num += 1;
         }
@@ -131,7 +297,7 @@ pub enum LexError {
     NonterminatedString,
 }
 
-pub fn lex<'a>(src: &'a str, matcher: impl Fn(char, usize) -> bool) -> SpannedStr<'a> {
+pub fn lex<'a>(src: &'a str, mut matcher: impl FnMut(char, usize) -> bool) -> SpannedStr<'a> {
     let mut chars = src.char_indices();
     let mut span = Span {
         start_byte: 0,
@@ -154,6 +320,26 @@ pub fn lex<'a>(src: &'a str, matcher: impl Fn(char, usize) -> bool) -> SpannedSt
     SpannedStr { src, span }
 }
 
+/// Processes the `\"` and `\\` escapes in the contents of a string literal.
+/// Any other backslash is left in place. Only allocates if there are escapes.
+pub fn unescape(src: &str) -> Cow<'_, str> {
+    if !src.contains('\\') {
+        return Cow::Borrowed(src);
+    }
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(&next)) if next == '"' || next == '\\' => {
+                out.push(next);
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
 pub fn lex_token<'a>(src: &'a str) -> Result<(Token<'a>, Option<SpannedStr<'a>>), LexError> {
     let span = lex(src, |c, _| c.is_whitespace());
     let span = span.remaining().ok_or(LexError::EndOfInput)?;
@@ -161,24 +347,56 @@ pub fn lex_token<'a>(src: &'a str) -> Result<(Token<'a>, Option<SpannedStr<'a>>)
     match span.first_char().ok_or(LexError::EndOfInput)? {
         c if c.is_alphabetic() || c == '_' => {
             let data = lex(span.substring(), |c, _| c.is_alphanumeric() || c == '_');
-            Ok((Token::Ident(data.substring()), data.remaining()))
+            Ok((Token::ident(data.substring()), data.remaining()))
         }
-        c if c == '#' => Ok((Token::Hash, span.skip(1))),
-        c if c == '(' => Ok((Token::LeftParen, span.skip(1))),
-        c if c == ')' => Ok((Token::RightParen, span.skip(1))),
-        c if c == ',' => Ok((Token::Comma, span.skip(1))),
-        c if c == ':' => Ok((Token::Colon, span.skip(1))),
-        c if c == '"' => {
+        c if c.is_ascii_digit() || c == '-' => {
+            let data = lex(span.substring(), |c, i| {
+                c.is_ascii_digit() || c == '.' || c == '_' || (i == 0 && c == '-')
+            });
+            Ok((Token::number(data.substring()), data.remaining()))
+        }
+        '$' => match span.skip(1) {
+            Some(rest)
+                if rest
+                    .first_char()
+                    .is_some_and(|c| c.is_alphabetic() || c == '_') =>
+            {
+                let name = lex(rest.substring(), |c, _| c.is_alphanumeric() || c == '_');
+                Ok((
+                    Token::Variable(Cow::Borrowed(name.substring())),
+                    name.remaining(),
+                ))
+            }
+            _ => Err(LexError::InvalidChar('$')),
+        },
+        '#' => Ok((Token::Hash, span.skip(1))),
+        '(' => Ok((Token::LeftParen, span.skip(1))),
+        ')' => Ok((Token::RightParen, span.skip(1))),
+        '[' => Ok((Token::LeftBracket, span.skip(1))),
+        ']' => Ok((Token::RightBracket, span.skip(1))),
+        ',' => Ok((Token::Comma, span.skip(1))),
+        ':' => Ok((Token::Colon, span.skip(1))),
+        '"' => {
             let data = span.skip(1).ok_or(LexError::NonterminatedString)?;
+            // Escaped characters are skipped here and processed by [unescape].
+            let mut escaped = false;
             let data = lex(data.substring(), |c, _| {
-                c != '"' && c != '\n'
+                if escaped {
+                    escaped = false;
+                    c != '\n'
+                } else if c == '\\' {
+                    escaped = true;
+                    true
+                } else {
+                    c != '"' && c != '\n'
+                }
             });
             let remaining = data.remaining().ok_or(LexError::NonterminatedString)?;
             if remaining.first_char() != Some('"') {
                 return Err(LexError::NonterminatedString);
             }
 
-            Ok((Token::String(data.substring()), remaining.skip(1)))
+            Ok((Token::String(unescape(data.substring())), remaining.skip(1)))
         }
         c => Err(LexError::InvalidChar(c)),
     }
@@ -300,23 +518,49 @@ mod tests {
 
     #[test]
     fn test_parse_token() {
-        assert_eq!(Token::Ident("test"), just_token(lex_token("  test   ")).unwrap());
+        assert_eq!(
+            Token::ident("test"),
+            just_token(lex_token("  test   ")).unwrap()
+        );
         assert_eq!(Token::Hash, just_token(lex_token("  #   ")).unwrap());
         assert_eq!(Token::LeftParen, just_token(lex_token("  (   ")).unwrap());
         assert_eq!(Token::RightParen, just_token(lex_token("  )   ")).unwrap());
         assert_eq!(Token::Comma, just_token(lex_token("  ,   ")).unwrap());
+        assert_eq!(Token::LeftBracket, just_token(lex_token("  [   ")).unwrap());
         assert_eq!(
-            Token::String("test()a;sldkfj"),
-            lex_token("  \"test()a;sldkfj\"   ").unwrap().0
+            Token::RightBracket,
+            just_token(lex_token("  ]   ")).unwrap()
         );
         assert_eq!(
-            Ok(Token::Colon),
-            just_token(lex_token("  :   ")),
+            Token::string("test()a;sldkfj"),
+            lex_token("  \"test()a;sldkfj\"   ").unwrap().0
         );
+        assert_eq!(Ok(Token::Colon), just_token(lex_token("  :   ")),);
+        assert_eq!(Ok(Token::number("42")), just_token(lex_token("  42  ")));
+        assert_eq!(Ok(Token::number("-0.5")), just_token(lex_token(" -0.5,")));
+        assert_eq!(Ok(Token::number("1_000")), just_token(lex_token("1_000)")));
         assert_eq!(Err(LexError::EndOfInput), lex_token("     "));
         assert_eq!(Err(LexError::InvalidChar('$')), lex_token("   $  "));
         assert_eq!(Err(LexError::NonterminatedString), lex_token("  \""));
         assert_eq!(Err(LexError::NonterminatedString), lex_token("  \"\n\""));
+        assert_eq!(
+            Ok(Token::String(Cow::Owned(
+                r#"feature = "editor""#.to_owned()
+            ))),
+            just_token(lex_token(r#" "feature = \"editor\"" "#)),
+        );
+        assert_eq!(Err(LexError::NonterminatedString), lex_token(r#"  "\""#));
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(r#"feature = "editor""#, unescape(r#"feature = \"editor\""#));
+        assert_eq!(r#"a\b"#, unescape(r#"a\\b"#));
+        assert_eq!(r#"C:\shaders"#, unescape(r#"C:\shaders"#));
+        assert!(matches!(
+            unescape("no escapes"),
+            Cow::Borrowed("no escapes")
+        ));
     }
 
     #[test]
@@ -324,12 +568,12 @@ mod tests {
         let mut tokens = TokenStream::new("#render_pipeline()").unwrap();
         let expected = [
             Token::Hash,
-            Token::Ident("render_pipeline"),
+            Token::ident("render_pipeline"),
             Token::LeftParen,
             Token::RightParen,
         ];
         for t in expected {
-            assert_eq!(Some(t), tokens.peek());
+            assert_eq!(Some(&t), tokens.peek());
             assert_eq!(tokens.peek(), tokens.peek());
             assert_eq!(Some(t), tokens.next());
         }
@@ -342,7 +586,7 @@ mod tests {
         let mut tokens = TokenStream::new("#render_pipeline()").unwrap();
         let expected = [
             Token::Hash,
-            Token::Ident("render_pipeline"),
+            Token::ident("render_pipeline"),
             Token::LeftParen,
             Token::RightParen,
         ];
@@ -365,19 +609,19 @@ mod tests {
         let mut tokens = TokenStream::new(config).unwrap();
         let expected = [
             Token::Hash,
-            Token::Ident("render_pipeline"),
+            Token::ident("render_pipeline"),
             Token::LeftParen,
-            Token::Ident("name"),
+            Token::ident("name"),
             Token::Colon,
-            Token::String("TexturedPipeline"),
+            Token::string("TexturedPipeline"),
             Token::Comma,
-            Token::Ident("vs_entry"),
+            Token::ident("vs_entry"),
             Token::Colon,
-            Token::String("vs_textured"),
+            Token::string("vs_textured"),
             Token::Comma,
-            Token::Ident("fs_entry"),
+            Token::ident("fs_entry"),
             Token::Colon,
-            Token::String("fs_textured"),
+            Token::string("fs_textured"),
             Token::Comma,
             Token::RightParen,
         ];
@@ -386,4 +630,115 @@ mod tests {
         }
         assert_eq!(None, tokens.next());
     }
+
+    #[test]
+    fn token_stream_from_reader() {
+        let mut tokens = TokenStream::from_reader(&b"#include(\"a.pmd\")"[..]).unwrap();
+        assert_eq!(Some(Token::Hash), tokens.next());
+        assert_eq!(Some(Token::ident("include")), tokens.next());
+        assert_eq!(Some(Token::LeftParen), tokens.next());
+        assert_eq!(Some(Token::string("a.pmd")), tokens.next());
+        assert_eq!(Some(Token::RightParen), tokens.next());
+        assert_eq!(None, tokens.next());
+
+        let e = TokenStream::from_reader(&b"  $  "[..]).err().unwrap();
+        assert_eq!(std::io::ErrorKind::InvalidData, e.kind());
+        let e = TokenStream::from_reader(&[0xff, 0xfe][..]).err().unwrap();
+        assert_eq!(std::io::ErrorKind::InvalidData, e.kind());
+    }
+
+    #[test]
+    fn token_stream_from_owned_tokens() {
+        let tokens = {
+            let src = String::from(r#"name: "Synth\"esized""#);
+            TokenStream::new(&src)
+                .unwrap()
+                .tokens
+                .into_iter()
+                .map(Token::into_owned)
+                .collect::<Vec<_>>()
+        };
+        let mut tokens = TokenStream::from_tokens(tokens);
+        assert_eq!(Some(Token::ident("name")), tokens.next());
+        assert_eq!(Some(Token::Colon), tokens.next());
+        assert_eq!(Some(Token::string(r#"Synth"esized"#)), tokens.next());
+        assert_eq!(None, tokens.next());
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(
+            Ok(Token::Variable(Cow::Borrowed("hdr_format"))),
+            just_token(lex_token("  $hdr_format,")),
+        );
+        assert_eq!(Err(LexError::InvalidChar('$')), lex_token("$1"));
+        assert_eq!(Err(LexError::InvalidChar('$')), lex_token("$ x"));
+
+        let mut tokens = TokenStream::new("a: $x, b: $y, c: $x").unwrap();
+        let _ = tokens.next();
+        let variables = HashMap::from([(
+            "x".to_owned(),
+            vec![Token::LeftBracket, Token::number("1"), Token::RightBracket],
+        )]);
+        tokens.substitute(&variables);
+        let expected = [
+            Token::Colon,
+            Token::LeftBracket,
+            Token::number("1"),
+            Token::RightBracket,
+            Token::Comma,
+            Token::ident("b"),
+            Token::Colon,
+            Token::Variable(Cow::Borrowed("y")),
+            Token::Comma,
+            Token::ident("c"),
+            Token::Colon,
+            Token::LeftBracket,
+            Token::number("1"),
+            Token::RightBracket,
+        ];
+        for t in expected {
+            assert_eq!(Some(t), tokens.next());
+        }
+        assert_eq!(None, tokens.next());
+    }
+
+    #[test]
+    fn aliases() {
+        let mut tokens = TokenStream::new("format: ShadowFormat, ShadowFormat: 1").unwrap();
+        tokens.resolve_aliases(&HashMap::from([(
+            "ShadowFormat".to_owned(),
+            Token::ident("Depth32Float"),
+        )]));
+        let expected = [
+            Token::ident("format"),
+            Token::Colon,
+            Token::ident("Depth32Float"),
+            Token::Comma,
+            Token::ident("ShadowFormat"),
+            Token::Colon,
+            Token::number("1"),
+        ];
+        for t in expected {
+            assert_eq!(Some(t), tokens.next());
+        }
+        assert_eq!(None, tokens.next());
+    }
+
+    #[test]
+    fn token_lines() {
+        let mut tokens = TokenStream::new("\n  a(\n$x,\n\n  \"b\")").unwrap();
+        assert_eq!(Some(2), tokens.line());
+        tokens.next();
+        tokens.next();
+        tokens.substitute(&HashMap::from([(
+            "x".to_owned(),
+            vec![Token::number("1"), Token::number("2")],
+        )]));
+        let lines = std::iter::from_fn(|| tokens.next().map(|_| tokens.line().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![3, 3, 3, 5, 5], lines);
+
+        assert_eq!(None, TokenStream::from_tokens(vec![Token::Comma]).line());
+    }
 }
@@ -1,143 +1,5056 @@
+mod builder;
 mod config;
+mod defines;
+mod describe;
+mod descriptor;
+mod diagnostic;
+#[cfg(any(test, feature = "test-fixtures"))]
+pub mod fixtures;
+mod fmt;
+mod format;
+mod grammar;
+mod imports;
+#[cfg(feature = "inspect")]
+mod inspect;
+mod interface;
+mod layout;
 mod lex;
+mod loader;
+mod manifest;
+mod minify;
+mod output;
+mod overrides;
+mod progress;
+mod project;
+mod prune;
+mod reflect;
+mod split;
+pub mod variants;
+mod watch;
 
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
 
-use anyhow::Result;
-use config::{ParseError, RenderPipelineConfig};
+use anyhow::{Context, Result};
+pub use builder::{find_configs, BuildReport, Builder, GeneratedFile};
+pub use config::{
+    BindGroupConfig, BindGroupRef, BindingConfig, BindingPreset, BindingPresetType,
+    ColorTargetConfig, ComputePipelineConfig, DepthBiasConfig, DepthStencilConfig, IncludeSite,
+    ParseError, PipelineLayoutConfig, PrimitiveConfig, PushConstantRangeConfig,
+    RenderPipelineConfig, RustImportConfig, ShaderDefine, ShaderModuleConfig, Value,
+    VertexLayoutConfig,
+};
+pub use describe::describe_pipeline;
+pub use descriptor::{Descriptor, PipelineDescriptor, DESCRIPTOR_VERSION};
+pub use diagnostic::{Diagnostic, Label, Level, Span};
+pub use fmt::format_pmd;
+pub use format::format_code;
+pub use grammar::{supports, GrammarVersion, InvalidGrammarVersion, GRAMMAR_VERSION};
+#[cfg(feature = "inspect")]
+pub use inspect::write_inspection;
+pub use interface::{Interface, InterfaceChange, InterfaceItem, ItemKind, INTERFACE_VERSION};
+pub use loader::{FsLoader, JailedLoader, LoadError, ShaderLoader};
+pub use manifest::{
+    BindGroupManifest, BindingManifest, Manifest, PipelineKind, PipelineManifest,
+    VertexAttributeManifest, VertexBufferManifest, MANIFEST_VERSION,
+};
+pub use output::{write_generated, WriteError, WriteOutcome, GENERATED_HEADER};
 use proc_macro2::TokenStream;
+pub use progress::{DependencyTracker, ProgressObserver};
+pub use project::{ProfileOptions, ProjectConfig, PROJECT_FILE};
 use quote::{format_ident, quote};
+pub use split::{gen_pipeline_code_split, write_split, SplitCode};
+pub use watch::{watch, Watcher};
 
+#[derive(Debug, Clone)]
 pub struct PipelineConfig {
     render_configs: Vec<RenderPipelineConfig>,
+    compute_configs: Vec<ComputePipelineConfig>,
+    binding_presets: Vec<BindingPreset>,
+    bind_groups: Vec<BindGroupConfig>,
+    vertex_layouts: Vec<VertexLayoutConfig>,
+    shaders: Vec<ShaderModuleConfig>,
+    pipeline_layouts: Vec<PipelineLayoutConfig>,
+    color_targets: Vec<ColorTargetConfig>,
+    rust_imports: Vec<RustImportConfig>,
+    files: Vec<PathBuf>,
+}
+
+/// What `#include`s are read with, [FsLoader] unless one is given.
+#[derive(Clone, Copy)]
+struct IncludeLoader<'l>(&'l dyn ShaderLoader);
+
+impl Default for IncludeLoader<'_> {
+    fn default() -> Self {
+        Self(&FsLoader)
+    }
+}
+
+/// State shared by a config and every file it includes while it's parsed.
+#[derive(Default)]
+struct ParseState<'l> {
+    loader: IncludeLoader<'l>,
+    /// The canonical paths of the files currently being parsed, outermost
+    /// first.
+    stack: Vec<PathBuf>,
+    /// Every file included so far. Each file is only included once so that
+    /// shared declarations can be included from several places.
+    seen: HashSet<PathBuf>,
+    /// The values defined with `#let` so far.
+    variables: HashMap<String, Vec<lex::Token<'static>>>,
+    /// The values named with `#alias` so far.
+    aliases: HashMap<String, lex::Token<'static>>,
+    /// The templates defined with `#template` so far.
+    templates: HashMap<String, Template>,
+    /// The templates currently being instantiated, outermost first.
+    instantiating: Vec<String>,
+    /// The presets declared with `#binding_preset` so far, in order.
+    binding_presets: Vec<BindingPreset>,
+    /// The pipelines declared with `#compute_pipeline` so far, in order.
+    compute_pipelines: Vec<ComputePipelineConfig>,
+    /// The groups declared with `#bind_group` so far, in order.
+    bind_groups: Vec<BindGroupConfig>,
+    /// The layouts declared with `#vertex_layout` so far, in order.
+    vertex_layouts: Vec<VertexLayoutConfig>,
+    /// The modules declared with `#shader` so far, in order.
+    shaders: Vec<ShaderModuleConfig>,
+    /// The layouts declared with `#pipeline_layout` so far, in order.
+    pipeline_layouts: Vec<PipelineLayoutConfig>,
+    /// The targets declared with `#color_target` so far, in order.
+    color_targets: Vec<ColorTargetConfig>,
+    /// The types declared with `#import_rust` so far, in order.
+    rust_imports: Vec<RustImportConfig>,
+}
+
+struct Template {
+    params: Vec<String>,
+    body: Vec<lex::Token<'static>>,
+}
+
+impl ParseState<'_> {
+    fn define<'a>(
+        &mut self,
+        name: Cow<'a, str>,
+        value: Vec<lex::Token<'a>>,
+    ) -> Result<(), ParseError<'a>> {
+        if self.variables.contains_key(name.as_ref()) {
+            return Err(ParseError::VariableRedefined(name));
+        }
+        // Resolve references to earlier variables, including ones defined by
+        // the same `#let`.
+        let mut tokens = lex::TokenStream::from_tokens(value);
+        tokens.substitute(&self.variables);
+        let mut value = Vec::new();
+        while let Some(token) = tokens.next() {
+            value.push(token.into_owned());
+        }
+        self.variables.insert(name.into_owned(), value);
+        Ok(())
+    }
+
+    /// Whether `name` is a `#vertex_layout` or `#import_rust`.
+    fn has_vertex_layout(&self, name: &str) -> bool {
+        self.vertex_layouts.iter().any(|layout| layout.name == name)
+            || self.rust_imports.iter().any(|import| import.name == name)
+    }
+
+    fn alias<'a>(
+        &mut self,
+        name: Cow<'a, str>,
+        value: lex::Token<'a>,
+    ) -> Result<(), ParseError<'a>> {
+        if self.aliases.contains_key(name.as_ref()) {
+            return Err(ParseError::AliasRedefined(name));
+        }
+        // Aliases of earlier aliases name the same value.
+        let value = match &value {
+            lex::Token::Ident(id) => self
+                .aliases
+                .get(id.as_ref())
+                .cloned()
+                .unwrap_or_else(|| value.into_owned()),
+            _ => value.into_owned(),
+        };
+        self.aliases.insert(name.into_owned(), value);
+        Ok(())
+    }
+
+    /// Presets and groups have to be declared before they're used.
+    fn check_bind_groups<'a>(&self, groups: &[BindGroupRef]) -> Result<(), ParseError<'a>> {
+        for group in groups {
+            match group {
+                BindGroupRef::Named(name) => {
+                    if !self.bind_groups.iter().any(|group| group.name == *name) {
+                        return Err(ParseError::UnknownBindGroup(name.clone().into()));
+                    }
+                }
+                BindGroupRef::Presets(names) => {
+                    let unknown = names.iter().find(|name| {
+                        !self
+                            .binding_presets
+                            .iter()
+                            .any(|preset| preset.name == **name)
+                    });
+                    if let Some(name) = unknown {
+                        return Err(ParseError::UnknownBindingPreset(name.clone().into()));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl PipelineConfig {
+    fn from_state(render_configs: Vec<RenderPipelineConfig>, state: ParseState) -> Self {
+        Self {
+            render_configs,
+            compute_configs: state.compute_pipelines,
+            binding_presets: state.binding_presets,
+            bind_groups: state.bind_groups,
+            vertex_layouts: state.vertex_layouts,
+            shaders: state.shaders,
+            pipeline_layouts: state.pipeline_layouts,
+            color_targets: state.color_targets,
+            rust_imports: state.rust_imports,
+            files: {
+                let mut files = state.seen.into_iter().collect::<Vec<_>>();
+                files.sort();
+                files
+            },
+        }
+    }
+
+    /// Parses a config. `#include` paths are resolved relative to the current
+    /// directory; use [PipelineConfig::from_path] to resolve them relative to
+    /// the config file instead.
     pub fn from_src<'a>(src: &'a str) -> Result<Self, ParseError<'a>> {
+        let mut state = ParseState::default();
+        let mut render_configs = Vec::new();
+        Self::parse(src, Path::new(""), &mut state, &mut render_configs).map_err(|(e, _)| e)?;
+        Ok(Self::from_state(render_configs, state))
+    }
+
+    /// Like [PipelineConfig::from_src], but reads `#include`s with `loader`,
+    /// eg. a [JailedLoader] for configs from untrusted sources. Paths are
+    /// relative to the loader's root. Includes outside of it fail with
+    /// [LoadError::PathOutsideRoot].
+    pub fn from_src_with_loader(src: &str, loader: &dyn ShaderLoader) -> Result<Self> {
+        let mut state = ParseState {
+            loader: IncludeLoader(loader),
+            ..Default::default()
+        };
+        let mut render_configs = Vec::new();
+        Self::parse(src, Path::new(""), &mut state, &mut render_configs)
+            .map_err(|(e, line)| parse_error(e, None, line))?;
+        Ok(Self::from_state(render_configs, state))
+    }
+
+    /// Reads and parses the config at `path`.
+    ///
+    /// Paths in an included file are relative to it for its own
+    /// `#include`s, but shader `path`s are taken from the current directory,
+    /// or [CodegenOptions::shader_base], wherever they're written.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_path_with_loader(path, &FsLoader)
+    }
+
+    /// Like [PipelineConfig::from_path], but reads the config and its
+    /// `#include`s with `loader`. With a [JailedLoader], `path` is relative
+    /// to its root and includes outside of it fail with
+    /// [LoadError::PathOutsideRoot].
+    pub fn from_path_with_loader(
+        path: impl AsRef<Path>,
+        loader: &dyn ShaderLoader,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let read = |e: LoadError| {
+            anyhow::Error::new(e).context(format!("failed to read {}", path.display()))
+        };
+        let src = loader.load(&path.to_string_lossy()).map_err(read)?;
+        let canonical = loader.resolve(&path.to_string_lossy()).map_err(read)?;
+        let mut state = ParseState {
+            loader: IncludeLoader(loader),
+            stack: vec![canonical.clone()],
+            seen: HashSet::from([canonical]),
+            ..Default::default()
+        };
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut render_configs = Vec::new();
+        Self::parse(&src, dir, &mut state, &mut render_configs)
+            .map_err(|(e, line)| parse_error(e, Some(path), line))?;
+        Ok(Self::from_state(render_configs, state))
+    }
+
+    /// Reads and parses a config from `reader`, eg. a pipe or an entry in an
+    /// archive. `#include` paths are resolved relative to the current
+    /// directory.
+    pub fn from_reader(reader: impl std::io::Read) -> Result<Self> {
+        let mut tokens = lex::TokenStream::from_reader(reader).context("failed to read config")?;
+        let mut state = ParseState::default();
         let mut render_configs = Vec::new();
-        let mut tokens = lex::TokenStream::new(src)?;
+        Self::parse_tokens(&mut tokens, Path::new(""), &mut state, &mut render_configs)?;
+        Ok(Self::from_state(render_configs, state))
+    }
+
+    /// Every pipeline in the config, including those from included files, in
+    /// the order they were declared.
+    pub fn render_pipelines(&self) -> &[RenderPipelineConfig] {
+        &self.render_configs
+    }
+
+    /// Every `#compute_pipeline` in the config, including those from included
+    /// files, in the order they were declared.
+    pub fn compute_pipelines(&self) -> &[ComputePipelineConfig] {
+        &self.compute_configs
+    }
+
+    /// Every `#binding_preset` in the config, including those from included
+    /// files, in the order they were declared.
+    pub fn binding_presets(&self) -> &[BindingPreset] {
+        &self.binding_presets
+    }
+
+    fn binding_preset(&self, name: &str) -> Option<&BindingPreset> {
+        self.binding_presets
+            .iter()
+            .find(|preset| preset.name == name)
+    }
+
+    /// Every `#bind_group` in the config, including those from included
+    /// files, in the order they were declared.
+    pub fn bind_groups(&self) -> &[BindGroupConfig] {
+        &self.bind_groups
+    }
+
+    fn bind_group(&self, name: &str) -> Option<&BindGroupConfig> {
+        self.bind_groups.iter().find(|group| group.name == name)
+    }
+
+    /// Every `#vertex_layout` in the config, including those from included
+    /// files, in the order they were declared.
+    pub fn vertex_layouts(&self) -> &[VertexLayoutConfig] {
+        &self.vertex_layouts
+    }
+
+    fn vertex_layout(&self, name: &str) -> Option<&VertexLayoutConfig> {
+        self.vertex_layouts
+            .iter()
+            .find(|layout| layout.name == name)
+    }
+
+    /// Every `#import_rust` in the config, including those from included
+    /// files, in the order they were declared.
+    pub fn rust_imports(&self) -> &[RustImportConfig] {
+        &self.rust_imports
+    }
+
+    fn rust_import(&self, name: &str) -> Option<&RustImportConfig> {
+        self.rust_imports.iter().find(|import| import.name == name)
+    }
+
+    /// Every `#shader` in the config, including those from included files,
+    /// in the order they were declared.
+    pub fn shaders(&self) -> &[ShaderModuleConfig] {
+        &self.shaders
+    }
 
-        while let Some(lex::Token::Ident(ident)) = tokens.peek() {
-            match ident {
-                "render_pipeline" => {
-                    render_configs.push(RenderPipelineConfig::parse(&mut tokens)?);
+    fn shader(&self, name: &str) -> Option<&ShaderModuleConfig> {
+        self.shaders.iter().find(|shader| shader.name == name)
+    }
+
+    /// Every `#pipeline_layout` in the config, including those from included
+    /// files, in the order they were declared.
+    pub fn pipeline_layouts(&self) -> &[PipelineLayoutConfig] {
+        &self.pipeline_layouts
+    }
+
+    /// Every `#color_target` in the config, including those from included
+    /// files, in the order they were declared.
+    pub fn color_targets(&self) -> &[ColorTargetConfig] {
+        &self.color_targets
+    }
+
+    /// The canonical paths of the config's file, if it was read from one,
+    /// and every file it includes, for working out when to regenerate it.
+    /// The shaders it uses are reported to [ProgressObserver::file_read]
+    /// while its code is generated.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Parses a whole file. Errors come with the line they were found on if
+    /// it's known.
+    fn parse<'a>(
+        src: &'a str,
+        dir: &Path,
+        state: &mut ParseState,
+        render_configs: &mut Vec<RenderPipelineConfig>,
+    ) -> Result<(), (ParseError<'a>, Option<usize>)> {
+        let mut tokens = lex::TokenStream::new(src).map_err(|e| (e.into(), None))?;
+        Self::parse_tokens(&mut tokens, dir, state, render_configs).map_err(|e| (e, tokens.line()))
+    }
+
+    fn parse_tokens<'a>(
+        tokens: &mut lex::TokenStream<'a>,
+        dir: &Path,
+        state: &mut ParseState,
+        render_configs: &mut Vec<RenderPipelineConfig>,
+    ) -> Result<(), ParseError<'a>> {
+        // Variables and aliases defined by the including file apply to this
+        // one too.
+        tokens.substitute(&state.variables);
+        tokens.resolve_aliases(&state.aliases);
+        Self::parse_items(
+            tokens,
+            dir,
+            state,
+            &mut config::PipelineDefaults::default(),
+            render_configs,
+        )?;
+        if let Some(t) = tokens.next() {
+            return Err(ParseError::ExpectedEndOfInput(t));
+        }
+        Ok(())
+    }
+
+    /// Parses directives and pipelines up to the end of input or a token
+    /// that can't start either, which is left for the caller.
+    fn parse_items<'a>(
+        tokens: &mut lex::TokenStream<'a>,
+        dir: &Path,
+        state: &mut ParseState,
+        defaults: &mut config::PipelineDefaults<'a>,
+        render_configs: &mut Vec<RenderPipelineConfig>,
+    ) -> Result<(), ParseError<'a>> {
+        loop {
+            // Template bodies don't have lines, so those pipelines are given
+            // the line of the `#instantiate` instead.
+            let declared_at = tokens.peek_line().map(|line| IncludeSite {
+                path: state.stack.last().cloned(),
+                line: Some(line),
+            });
+            match tokens.peek() {
+                Some(lex::Token::Hash) => match config::Directive::parse(tokens)? {
+                    config::Directive::Include(path) => {
+                        let site = IncludeSite {
+                            path: state.stack.last().cloned(),
+                            line: tokens.line(),
+                        };
+                        // Normalized without touching the file system, so a `..` that
+                        // leaves the loader's root is still there for it to reject.
+                        let path = PathBuf::from(loader::normalize(Some(dir), &path));
+                        Self::include(&path, site, state, render_configs)?;
+                        tokens.substitute(&state.variables);
+                        tokens.resolve_aliases(&state.aliases);
+                    }
+                    config::Directive::Let(variables) => {
+                        for (name, value) in variables {
+                            state.define(name, value)?;
+                        }
+                        tokens.substitute(&state.variables);
+                        tokens.resolve_aliases(&state.aliases);
+                    }
+                    config::Directive::Alias(aliases) => {
+                        for (name, value) in aliases {
+                            state.alias(name, value)?;
+                        }
+                        tokens.resolve_aliases(&state.aliases);
+                    }
+                    config::Directive::Defaults(fields) => defaults.extend(fields)?,
+                    config::Directive::Template { name, params, body } => {
+                        if state.templates.contains_key(name.as_ref()) {
+                            return Err(ParseError::TemplateRedefined(name));
+                        }
+                        // A variable would have already replaced the parameter
+                        // in the body.
+                        if let Some(param) = params
+                            .iter()
+                            .find(|p| state.variables.contains_key(p.as_ref()))
+                        {
+                            return Err(ParseError::VariableRedefined(param.clone()));
+                        }
+                        let template = Template {
+                            params: params.into_iter().map(Cow::into_owned).collect(),
+                            body: body.into_iter().map(lex::Token::into_owned).collect(),
+                        };
+                        state.templates.insert(name.into_owned(), template);
+                    }
+                    // Already checked against this build's grammar.
+                    config::Directive::Grammar(declared) => {
+                        // The rest of the file has to keep to the version it
+                        // declares, so it doesn't quietly need a newer pipemd.
+                        if let Some((i, what, added)) =
+                            grammar::newer_than(tokens.remaining(), declared)
+                        {
+                            // Taken up to it, so the error has its line.
+                            for _ in 0..=i {
+                                tokens.next();
+                            }
+                            return Err(ParseError::NewerThanGrammar {
+                                what,
+                                added,
+                                declared,
+                            });
+                        }
+                    }
+                    config::Directive::BindingPreset(preset) => {
+                        if state.binding_presets.iter().any(|p| p.name == preset.name) {
+                            return Err(ParseError::BindingPresetRedefined(preset.name.into()));
+                        }
+                        state.binding_presets.push(preset);
+                    }
+                    config::Directive::ComputePipeline(mut cp) => {
+                        if let Some(shader) =
+                            state.shaders.iter().find(|shader| shader.name == cp.path)
+                        {
+                            cp.shader = Some(shader.name.clone());
+                            cp.path = shader.path.clone();
+                        }
+                        cp.declared_at = declared_at;
+                        state.compute_pipelines.push(cp);
+                    }
+                    config::Directive::PipelineLayout(layout) => {
+                        if state.pipeline_layouts.iter().any(|l| l.name == layout.name) {
+                            return Err(ParseError::PipelineLayoutRedefined(layout.name.into()));
+                        }
+                        state.check_bind_groups(&layout.bind_groups)?;
+                        state.pipeline_layouts.push(layout);
+                    }
+                    config::Directive::ColorTarget(target) => {
+                        if state.color_targets.iter().any(|t| t.name == target.name) {
+                            return Err(ParseError::ColorTargetRedefined(target.name.into()));
+                        }
+                        state.color_targets.push(target);
+                    }
+                    config::Directive::Shader(shader) => {
+                        if state.shaders.iter().any(|s| s.name == shader.name) {
+                            return Err(ParseError::ShaderRedefined(shader.name.into()));
+                        }
+                        state.shaders.push(shader);
+                    }
+                    config::Directive::VertexLayout(layout) => {
+                        if state.has_vertex_layout(&layout.name) {
+                            return Err(ParseError::VertexLayoutRedefined(layout.name.into()));
+                        }
+                        state.vertex_layouts.push(layout);
+                    }
+                    // Imported types share their names with vertex layouts.
+                    config::Directive::ImportRust(import) => {
+                        if state.has_vertex_layout(&import.name) {
+                            return Err(ParseError::VertexLayoutRedefined(import.name.into()));
+                        }
+                        state.rust_imports.push(import);
+                    }
+                    config::Directive::BindGroup { name, entries } => {
+                        if state.bind_groups.iter().any(|group| group.name == name) {
+                            return Err(ParseError::BindGroupRedefined(name));
+                        }
+                        let entries = entries
+                            .into_iter()
+                            .map(|entry| match entry {
+                                config::BindGroupEntry::Inline(preset) => Ok(preset),
+                                config::BindGroupEntry::Preset(preset) => state
+                                    .binding_presets
+                                    .iter()
+                                    .find(|p| p.name == preset)
+                                    .cloned()
+                                    .ok_or(ParseError::UnknownBindingPreset(preset.into())),
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+                        // Entry names become parameters of `create_bind_group`.
+                        if entries
+                            .iter()
+                            .enumerate()
+                            .any(|(i, e)| entries[..i].iter().any(|p| p.name == e.name))
+                        {
+                            return Err(ParseError::InvalidValue {
+                                field: "entries".into(),
+                                reason: "entry names must be unique",
+                            });
+                        }
+                        state.bind_groups.push(BindGroupConfig {
+                            name: name.into_owned(),
+                            entries,
+                        });
+                    }
+                    config::Directive::Instantiate { name, args } => {
+                        let (render_start, compute_start) =
+                            (render_configs.len(), state.compute_pipelines.len());
+                        Self::instantiate(name, args, dir, state, defaults, render_configs)?;
+                        let render = render_configs[render_start..]
+                            .iter_mut()
+                            .map(|rp| &mut rp.declared_at);
+                        let compute = state.compute_pipelines[compute_start..]
+                            .iter_mut()
+                            .map(|cp| &mut cp.declared_at);
+                        for site in render.chain(compute).filter(|site| site.is_none()) {
+                            *site = declared_at.clone();
+                        }
+                    }
+                    config::Directive::Cfg(predicate) => {
+                        config::expect_token(tokens, lex::Token::LeftBracket)?;
+                        let mut gated = Vec::new();
+                        let compute_start = state.compute_pipelines.len();
+                        Self::parse_items(tokens, dir, state, defaults, &mut gated)?;
+                        config::expect_token(tokens, lex::Token::RightBracket)?;
+                        let gate = |cfg: Option<String>| {
+                            Some(match cfg {
+                                Some(cfg) => format!("all({}, {})", predicate, cfg),
+                                None => predicate.to_string(),
+                            })
+                        };
+                        for mut rp in gated {
+                            rp.cfg = gate(rp.cfg);
+                            render_configs.push(rp);
+                        }
+                        for cp in &mut state.compute_pipelines[compute_start..] {
+                            cp.cfg = gate(cp.cfg.take());
+                        }
+                    }
+                },
+                Some(lex::Token::Ident(ident)) if ident == "render_pipeline" => {
+                    let mut rp = RenderPipelineConfig::parse_with_defaults(tokens, defaults)?;
+                    rp.declared_at = declared_at;
+                    if let Some(name) = &rp.shader {
+                        match state.shaders.iter().find(|shader| shader.name == *name) {
+                            Some(shader) => rp.path = shader.path.clone(),
+                            None => return Err(ParseError::UnknownShader(name.clone().into())),
+                        }
+                    }
+                    state.check_bind_groups(&rp.bind_groups)?;
+                    if let Some(name) = &rp.layout {
+                        if !state
+                            .pipeline_layouts
+                            .iter()
+                            .any(|layout| layout.name == *name)
+                        {
+                            return Err(ParseError::UnknownPipelineLayout(name.clone().into()));
+                        }
+                    }
+                    let unknown = rp
+                        .vertex_buffers
+                        .iter()
+                        .find(|name| !state.has_vertex_layout(name));
+                    if let Some(name) = unknown {
+                        return Err(ParseError::UnknownVertexLayout(name.clone().into()));
+                    }
+                    let unknown = rp.targets.iter().find(|name| {
+                        !state
+                            .color_targets
+                            .iter()
+                            .any(|target| target.name == **name)
+                    });
+                    if let Some(name) = unknown {
+                        return Err(ParseError::UnknownColorTarget(name.clone().into()));
+                    }
+                    render_configs.push(rp);
                 }
-                ident => {
+                Some(lex::Token::Ident(_)) => {
+                    // Taken so the error is reported on its line.
+                    let found = tokens.next().unwrap();
                     return Err(ParseError::UnexpectedToken {
-                        found: lex::Token::Ident(ident),
-                        expected: lex::Token::Ident("render_pipeline"),
-                    })
+                        found,
+                        expected: lex::Token::ident("render_pipeline"),
+                    });
                 }
+                _ => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn instantiate<'a>(
+        name: Cow<'a, str>,
+        args: config::RawFields<'a>,
+        dir: &Path,
+        state: &mut ParseState,
+        defaults: &mut config::PipelineDefaults<'a>,
+        render_configs: &mut Vec<RenderPipelineConfig>,
+    ) -> Result<(), ParseError<'a>> {
+        let Some(template) = state.templates.get(name.as_ref()) else {
+            return Err(ParseError::UnknownTemplate(name));
+        };
+        if state.instantiating.iter().any(|t| *t == name) {
+            let mut chain = state.instantiating.clone();
+            chain.push(name.into_owned());
+            return Err(ParseError::TemplateCycle(chain));
+        }
+        let mut values = HashMap::new();
+        for (param, value) in args {
+            if !template.params.iter().any(|p| *p == param) {
+                return Err(ParseError::UnexpectedField(param));
+            }
+            // Like fields, the last value given wins.
+            values.insert(
+                param.into_owned(),
+                value.into_iter().map(lex::Token::into_owned).collect(),
+            );
+        }
+        if let Some(param) = template.params.iter().find(|p| !values.contains_key(*p)) {
+            return Err(ParseError::MissingTemplateArgument {
+                template: name,
+                param: param.clone(),
+            });
+        }
+
+        let mut tokens = lex::TokenStream::from_tokens(template.body.clone());
+        tokens.substitute(&values);
+        tokens.substitute(&state.variables);
+        tokens.resolve_aliases(&state.aliases);
+        let wrap = |e: ParseError| ParseError::Template {
+            template: name.to_string(),
+            message: e.to_string(),
+        };
+        state.instantiating.push(name.to_string());
+        let result = Self::parse_items(&mut tokens, dir, state, defaults, render_configs);
+        state.instantiating.pop();
+        match result {
+            // Keep cycles intact so the whole chain is reported once.
+            Err(ParseError::TemplateCycle(chain)) => Err(ParseError::TemplateCycle(chain)),
+            Err(e) => Err(wrap(e)),
+            Ok(()) => match tokens.next() {
+                Some(found) => Err(wrap(ParseError::ExpectedEndOfInput(found))),
+                None => Ok(()),
+            },
+        }
+    }
+
+    fn include<'a>(
+        path: &Path,
+        site: IncludeSite,
+        state: &mut ParseState,
+        render_configs: &mut Vec<RenderPipelineConfig>,
+    ) -> Result<(), ParseError<'a>> {
+        let wrap = |path: &Path, line, code, message: String| ParseError::Include {
+            path: path.to_owned(),
+            line,
+            code,
+            message,
+            included_from: vec![site.clone()],
+        };
+        let load_error = |e: LoadError| match e {
+            LoadError::Io { path, source } => wrap(&path, None, "io", source.to_string()),
+            LoadError::PathOutsideRoot { path, root } => ParseError::PathOutsideRoot { path, root },
+        };
+        let loader = state.loader.0;
+        let name = path.to_string_lossy();
+        let canonical = loader.resolve(&name).map_err(load_error)?;
+        if state.stack.contains(&canonical) {
+            let mut chain = state.stack.clone();
+            chain.push(canonical);
+            return Err(ParseError::IncludeCycle(chain));
+        }
+        if !state.seen.insert(canonical.clone()) {
+            return Ok(());
+        }
+
+        let src = loader.load(&name).map_err(load_error)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        state.stack.push(canonical.clone());
+        let result = Self::parse(&src, dir, state, render_configs);
+        state.stack.pop();
+        match result {
+            Ok(()) => Ok(()),
+            // Keep cycles and escapes intact so they're reported as they are.
+            Err((ParseError::IncludeCycle(chain), _)) => Err(ParseError::IncludeCycle(chain)),
+            Err((ParseError::PathOutsideRoot { path, root }, _)) => {
+                Err(ParseError::PathOutsideRoot { path, root })
+            }
+            // The error came from a file this one includes, so it only needs
+            // to say where this one was included.
+            Err((
+                ParseError::Include {
+                    path,
+                    line,
+                    code,
+                    message,
+                    mut included_from,
+                },
+                _,
+            )) => {
+                included_from.push(site);
+                Err(ParseError::Include {
+                    path,
+                    line,
+                    code,
+                    message,
+                    included_from,
+                })
             }
+            Err((e, line)) => Err(wrap(&canonical, line, e.code(), e.to_string())),
+        }
+    }
+}
+
+/// Turns an error from parsing the config at `path` into a [Diagnostic], or
+/// into a [LoadError] for an `#include` outside of the loader's root.
+fn parse_error(e: ParseError, path: Option<&Path>, line: Option<usize>) -> anyhow::Error {
+    let in_file = |diagnostic: Diagnostic, line| match path {
+        Some(path) => diagnostic.in_file(path.display().to_string(), line),
+        None => Diagnostic {
+            span: line.map(|line| Span { line, column: None }),
+            ..diagnostic
+        },
+    };
+    let diagnostic = match e {
+        // These say which file they're in themselves.
+        ParseError::Include {
+            path,
+            line,
+            code,
+            message,
+            included_from,
+        } => {
+            let mut diagnostic =
+                Diagnostic::error(code, message).in_file(path.display().to_string(), line);
+            diagnostic.notes = included_from
+                .iter()
+                .map(|site| format!("included from {}", site))
+                .collect();
+            diagnostic
+        }
+        ParseError::PathOutsideRoot { path, root } => {
+            let e = LoadError::PathOutsideRoot { path, root };
+            let diagnostic = in_file(Diagnostic::error("path_outside_root", e.to_string()), line);
+            return anyhow::Error::new(e).context(diagnostic);
+        }
+        e => in_file(Diagnostic::error(e.code(), e.to_string()), line),
+    };
+    anyhow::Error::new(diagnostic)
+}
+
+/// Controls what kind of items [gen_pipeline_code_with_options] emits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CodegenMode {
+    /// Generate a struct per pipeline that creates and owns its
+    /// `wgpu::RenderPipeline`.
+    #[default]
+    Pipelines,
+    /// Only emit the constants derived from the config and shaders (shader
+    /// source, entry points, primitive and multisample state) as free items.
+    /// This lets an existing renderer adopt pipemd without replacing its own
+    /// pipeline construction code.
+    ConstantsOnly,
+}
+
+/// Controls how shader source ends up in the generated code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceEmbedding {
+    /// Embed the source as a string literal so the generated code is self
+    /// contained.
+    #[default]
+    Inline,
+    /// Reference the shader file with `include_str!` using its absolute
+    /// path. This keeps very large shaders out of the generated file.
+    IncludeStr,
+    /// Compile the shader to SPIR-V with naga and embed the binary, so
+    /// there's no WGSL to compile at runtime or to read in the shipped
+    /// binary. Modules are created with `Device::create_shader_module_spirv`,
+    /// so render pipelines require `wgpu::Features::SPIRV_SHADER_PASSTHROUGH`.
+    /// Compute pipelines can't be used, since wgpu can't derive their layout
+    /// from SPIR-V.
+    #[serde(rename = "spirv")]
+    SpirV,
+}
+
+/// Who can use the items [gen_pipeline_code_with_options] emits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    /// `pub`, for code included into its own module or a crate that exports
+    /// its pipelines.
+    #[default]
+    Public,
+    /// `pub(crate)`, so a library's pipelines don't end up in its public API.
+    Crate,
+    /// Only usable from the module the code is included into.
+    Private,
+}
+
+impl Visibility {
+    fn tokens(self) -> TokenStream {
+        match self {
+            Self::Public => quote! { pub },
+            Self::Crate => quote! { pub(crate) },
+            Self::Private => quote! {},
         }
+    }
+}
+
+/// The default for [CodegenOptions::max_inline_source_len]. Generated files
+/// past a few megabytes get noticeably slow for rustc to process.
+pub const DEFAULT_MAX_INLINE_SOURCE_LEN: usize = 1 << 20;
+
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "{path:?} is {len} bytes, which exceeds the {limit} byte limit for inlined shader source. \
+    Use `SourceEmbedding::IncludeStr` to reference the file with `include_str!` instead, \
+    or raise `CodegenOptions::max_inline_source_len`"
+)]
+pub struct SourceTooLarge {
+    pub path: String,
+    pub len: usize,
+    pub limit: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct CodegenOptions {
+    pub mode: CodegenMode,
+    /// Emit a `PIPELINE_REGISTRY` listing a `PipelineFactory` for every
+    /// pipeline so they can be looked up by name at runtime. Only used with
+    /// [CodegenMode::Pipelines].
+    pub registry: bool,
+    /// Emit `Pipelines::warmup`, which creates every pipeline in the config
+    /// and reports how long each one took, for tracking shader compile
+    /// costs. Only used with [CodegenMode::Pipelines].
+    pub warmup: bool,
+    /// Give the shader modules, layouts and pipelines debug labels. Only
+    /// used with [CodegenMode::Pipelines].
+    pub labels: bool,
+    /// Used to read every shader referenced by the config. Use a
+    /// [JailedLoader] when the config comes from an untrusted source.
+    pub loader: Arc<dyn ShaderLoader>,
+    pub source_embedding: SourceEmbedding,
+    /// Shaders longer than this many bytes fail with [SourceTooLarge] when
+    /// using [SourceEmbedding::Inline].
+    pub max_inline_source_len: usize,
+    /// Notified as each shader and pipeline is processed.
+    pub observer: Option<Arc<dyn ProgressObserver>>,
+    /// Derive `bytemuck::Pod` and `bytemuck::Zeroable` for the structs
+    /// generated for buffers and push constants, for crates that depend on
+    /// bytemuck.
+    pub bytemuck: bool,
+    /// The visibility of the generated structs, constants and functions.
+    /// Their fields and methods stay `pub`, so they can be used wherever
+    /// the items themselves can.
+    pub visibility: Visibility,
+    /// Emit a `#[cfg(test)]` module with a test for each pipeline that
+    /// creates it on a headless device and fails on validation errors. The
+    /// tests pass without creating anything when there's no adapter, and
+    /// pipelines taking bind group layouts aren't tested. Only used with
+    /// [CodegenMode::Pipelines].
+    pub tests: bool,
+    /// The directory relative shader paths in the config are taken from
+    /// before they're passed to the loader. Paths are normalized either
+    /// way, so `./a.wgsl` and `a.wgsl` are only embedded once.
+    pub shader_base: Option<PathBuf>,
+    /// Remove the entry points none of the config's pipelines use from the
+    /// embedded shaders, along with the functions only they call. Pruned
+    /// shaders are written back out by naga, so they lose their comments
+    /// and formatting and can't be pulled in with `include_str!`.
+    pub prune_shaders: bool,
+    /// Strip comments and redundant whitespace from embedded WGSL. Minified
+    /// shaders can't be pulled in with `include_str!`, and aren't affected
+    /// by [SourceEmbedding::SpirV].
+    pub minify_shaders: bool,
+    /// Values for the `${NAME}`s in shader paths. Names that aren't given
+    /// are taken from the environment, eg. `${CARGO_MANIFEST_DIR}` in a
+    /// build script.
+    pub path_variables: BTreeMap<String, String>,
+    /// Have debug builds read each shader from its file when a pipeline is
+    /// created, falling back to the embedded source if it can't be read, so
+    /// edits show up without rebuilding. Preprocessed shaders, eg. ones with
+    /// defines, are always embedded. Not used with [SourceEmbedding::SpirV].
+    pub runtime_shaders: bool,
+    /// Emit a hidden `{NAME}_SOURCE_LOCATION` constant ahead of each
+    /// pipeline's items with where it's declared, eg. `"shaders/scene.pmd:42"`,
+    /// so that errors in the generated code can be traced back to the
+    /// config. Paths under the current directory are made relative to it.
+    pub source_locations: bool,
+    /// Wrap everything generated in `mod <name> { ... }`, so that configs
+    /// generated into the same module can't collide and their items read as
+    /// eg. `pipelines::scene::TexturedPipeline`. [CodegenOptions::visibility]
+    /// applies to the module and the items in it are `pub`. The module uses
+    /// everything in its parent, so relative `#import_rust` paths still work.
+    /// Not used by [gen_pipeline_code_split].
+    pub module: Option<String>,
+    /// Items in [CodegenOptions::module] to re-export alongside it, eg.
+    /// `["TexturedPipeline"]` for one that's used all over.
+    pub reexports: Vec<String>,
+}
 
-        Ok(Self { render_configs })
+impl Default for CodegenOptions {
+    fn default() -> Self {
+        Self {
+            mode: CodegenMode::default(),
+            registry: false,
+            warmup: false,
+            labels: true,
+            loader: Arc::new(FsLoader),
+            source_embedding: SourceEmbedding::default(),
+            max_inline_source_len: DEFAULT_MAX_INLINE_SOURCE_LEN,
+            observer: None,
+            bytemuck: false,
+            visibility: Visibility::default(),
+            tests: false,
+            shader_base: None,
+            prune_shaders: false,
+            minify_shaders: false,
+            path_variables: BTreeMap::new(),
+            runtime_shaders: false,
+            source_locations: false,
+            module: None,
+            reexports: Vec::new(),
+        }
     }
 }
 
 pub fn gen_pipeline_code(config: &PipelineConfig) -> Result<TokenStream> {
+    gen_pipeline_code_with_options(config, &CodegenOptions::default())
+}
+
+pub fn gen_pipeline_code_with_options(
+    config: &PipelineConfig,
+    options: &CodegenOptions,
+) -> Result<TokenStream> {
+    gen_pipeline_code_cached(
+        config,
+        options,
+        &mut ShaderCache::default(),
+        &mut Vec::new(),
+    )
+}
+
+/// Like [gen_pipeline_code_with_options], along with a [Manifest] of the
+/// pipelines for tools that can't read Rust.
+pub fn gen_pipeline_code_with_manifest(
+    config: &PipelineConfig,
+    options: &CodegenOptions,
+) -> Result<(TokenStream, Manifest)> {
+    let mut pipelines = Vec::new();
+    let code =
+        gen_pipeline_code_cached(config, options, &mut ShaderCache::default(), &mut pipelines)?;
+    Ok((
+        code,
+        Manifest {
+            version: MANIFEST_VERSION,
+            pipelines,
+        },
+    ))
+}
+
+/// Like [gen_pipeline_code_with_options], but laid out with [format_code]
+/// for writing to a file someone will read.
+pub fn gen_pipeline_code_formatted(
+    config: &PipelineConfig,
+    options: &CodegenOptions,
+) -> Result<String> {
+    gen_pipeline_code_with_options(config, options).map(|code| format_code(&code))
+}
+
+/// Why an input to [generate_many] failed.
+#[derive(Debug, thiserror::Error)]
+pub enum CodegenError {
+    /// The config couldn't be read or parsed.
+    #[error(transparent)]
+    Parse(anyhow::Error),
+    /// The config was parsed but code couldn't be generated for it, eg.
+    /// because a shader is invalid.
+    #[error(transparent)]
+    Generate(anyhow::Error),
+}
+
+/// Generates the code for several configs at once. Each shader is only
+/// loaded and parsed once, however many of the configs use it.
+///
+/// Every input gets a result, so one broken config doesn't hide problems
+/// with the others. Results are in the same order as `inputs`.
+pub fn generate_many(
+    inputs: &[PathBuf],
+    options: &CodegenOptions,
+) -> Vec<(PathBuf, Result<TokenStream, CodegenError>)> {
+    generate_many_with_manifests(inputs, options)
+        .into_iter()
+        .map(|(path, result)| (path, result.map(|(code, _)| code)))
+        .collect()
+}
+
+/// A config's code and [Manifest].
+type Generated = (TokenStream, Manifest);
+
+/// Like [generate_many], along with each config's [Manifest].
+pub fn generate_many_with_manifests(
+    inputs: &[PathBuf],
+    options: &CodegenOptions,
+) -> Vec<(PathBuf, Result<Generated, CodegenError>)> {
+    let mut cache = ShaderCache::default();
+    inputs
+        .iter()
+        .map(|path| {
+            let result = PipelineConfig::from_path_with_loader(path, &*options.loader)
+                .map_err(CodegenError::Parse)
+                .and_then(|config| {
+                    if let Some(observer) = &options.observer {
+                        config
+                            .files()
+                            .iter()
+                            .for_each(|file| observer.file_parsed(file));
+                    }
+                    let mut pipelines = Vec::new();
+                    gen_pipeline_code_cached(&config, options, &mut cache, &mut pipelines)
+                        .map(|code| {
+                            (
+                                code,
+                                Manifest {
+                                    version: MANIFEST_VERSION,
+                                    pipelines,
+                                },
+                            )
+                        })
+                        .map_err(CodegenError::Generate)
+                });
+            (path.clone(), result)
+        })
+        .collect()
+}
+
+/// Shaders that have been loaded and parsed, keyed by their path in the
+/// config.
+#[derive(Default)]
+struct ShaderCache {
+    /// Keyed by normalized path, the defines the source was preprocessed with for
+    /// pipelines with variants, and the override constants it was
+    /// specialized with.
+    shaders: HashMap<ShaderKey, Rc<CachedShader>>,
+}
+
+type ShaderKey = (String, Option<Vec<String>>, ConstantsKey);
+
+/// Override constants with their values as bits, so they can be hashed.
+type ConstantsKey = Vec<(String, u64)>;
+
+fn constants_key(constants: &[(String, f64)]) -> ConstantsKey {
+    constants
+        .iter()
+        .map(|(name, value)| (name.clone(), value.to_bits()))
+        .collect()
+}
+
+struct CachedShader {
+    src: String,
+    module: naga::Module,
+    info: naga::valid::ModuleInfo,
+    /// Whether `src` differs from the file, so it can't be pulled in with
+    /// `include_str!`.
+    preprocessed: bool,
+}
+
+impl ShaderCache {
+    /// Translates `path` from `language`, one of
+    /// [variants::SHADER_LANGUAGES], when it isn't WGSL.
+    fn get(
+        &mut self,
+        path: &str,
+        language: &str,
+        defines: Option<&[String]>,
+        constants: &[(String, f64)],
+        options: &CodegenOptions,
+    ) -> Result<Rc<CachedShader>> {
+        // Errors use the path as it's written, but it's loaded and cached
+        // normalized so each file is only read once.
+        let normalized = shader_path(options, path)?;
+        let key = (
+            normalized,
+            defines.map(<[String]>::to_vec),
+            constants_key(constants),
+        );
+        if let Some(shader) = self.shaders.get(&key) {
+            return Ok(shader.clone());
+        }
+        let stage = match language {
+            "Glsl" => Some(glsl_stage(path)?),
+            _ => None,
+        };
+        // Files that are already too long to inline are rejected before
+        // they're read. GLSL is inlined as its translation, which can be a
+        // different length.
+        if options.source_embedding == SourceEmbedding::Inline && stage.is_none() {
+            let len = options.loader.len(&key.0)?;
+            if let Some(len) = len.filter(|len| *len > options.max_inline_source_len as u64) {
+                let limit = options.max_inline_source_len;
+                return Err(SourceTooLarge {
+                    path: path.to_owned(),
+                    len: len as usize,
+                    limit,
+                }
+                .into());
+            }
+        }
+        let mut src = options.loader.load(&key.0)?;
+        file_read(options, &key.0);
+        if let Some(stage) = stage {
+            if !constants.is_empty() {
+                anyhow::bail!(
+                    "{}: override constants can only be given to WGSL shaders",
+                    path
+                );
+            }
+            let shader = Rc::new(glsl_shader(path, &src, stage, defines.unwrap_or_default())?);
+            if let Some(observer) = &options.observer {
+                observer.shader_reflected(path);
+            }
+            self.shaders.insert(key, shader.clone());
+            return Ok(shader);
+        }
+        let mut preprocessed = false;
+        // Preprocessing keeps every line where it is, so this still applies
+        // to the source naga parses.
+        let mut lines = None;
+        if imports::has_imports(&src) {
+            let (composed, map) =
+                imports::compose(&key.0, &src, &*options.loader, &mut |import| {
+                    file_read(options, import)
+                })?;
+            src = composed;
+            lines = Some(map);
+            preprocessed = true;
+        }
+        // Shaders with blocks are still preprocessed when nothing is defined.
+        let defines = defines.or_else(|| defines::has_blocks(&src).then_some(&[]));
+        if let Some(defines) = defines {
+            src = defines::preprocess(&src, defines)
+                .map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+            preprocessed = true;
+        }
+        if let Cow::Owned(specialized) = overrides::specialize(&src, constants)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path, e))?
+        {
+            src = specialized;
+            preprocessed = true;
+        }
+        let module = naga::front::wgsl::parse_str(&src).map_err(|e| {
+            let span = e.location(&src).map(|location| (location, String::new()));
+            shader_error(
+                (path, lines.as_ref()),
+                &src,
+                "shader_syntax",
+                e.to_string(),
+                span,
+            )
+        })?;
+        let info = validate((path, lines.as_ref()), &src, &module)?;
+        if let Some(observer) = &options.observer {
+            observer.shader_reflected(path);
+        }
+        let shader = Rc::new(CachedShader {
+            src,
+            module,
+            info,
+            preprocessed,
+        });
+        self.shaders.insert(key, shader.clone());
+        Ok(shader)
+    }
+}
+
+/// Validates `module`, parsed from `src`. Parsing doesn't catch everything,
+/// eg. type mismatches, which would otherwise only show up when wgpu creates
+/// the shader module.
+fn validate(
+    file: (&str, Option<&imports::LineMap>),
+    src: &str,
+    module: &naga::Module,
+) -> Result<naga::valid::ModuleInfo> {
+    validator().validate(module).map_err(|e| {
+        let mut message = e.to_string();
+        let mut source = std::error::Error::source(&e);
+        while let Some(e) = source {
+            message = format!("{}: {}", message, e);
+            source = e.source();
+        }
+        let spans = e
+            .spans()
+            .map(|(span, label)| (span.location(src), label.clone()));
+        shader_error(file, src, "shader_validation", message, spans)
+    })
+}
+
+/// Parses the GLSL shader at `path` with naga's GLSL frontend and translates
+/// it to WGSL, which the generated code creates its module from. `defines`
+/// are `#define`d for its preprocessor. A GLSL shader only has the one
+/// stage, and its entry point is `main`.
+fn glsl_shader(
+    path: &str,
+    src: &str,
+    stage: naga::ShaderStage,
+    defines: &[String],
+) -> Result<CachedShader> {
+    let options = naga::front::glsl::Options {
+        stage,
+        defines: defines
+            .iter()
+            .map(|define| (define.clone(), "1".to_owned()))
+            .collect(),
+    };
+    let module = naga::front::glsl::Parser::default()
+        .parse(&options, src)
+        .map_err(|errors| {
+            let message = errors
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            // The message already says what each error is.
+            let spans = errors
+                .iter()
+                .filter(|e| e.meta.is_defined())
+                .map(|e| (e.meta.location(src), String::new()));
+            shader_error((path, None), src, "shader_syntax", message, spans)
+        })?;
+    let info = validate((path, None), src, &module)?;
+    let wgsl =
+        naga::back::wgsl::write_string(&module, &info, naga::back::wgsl::WriterFlags::empty())
+            .map_err(|e| anyhow::anyhow!("{}: Unable to translate to WGSL: {}", path, e))?;
+    // The translation is what's embedded, since the file itself isn't WGSL.
+    Ok(CachedShader {
+        src: wgsl,
+        module,
+        info,
+        preprocessed: true,
+    })
+}
+
+/// The stage of a GLSL shader from its extension, eg. `blur.frag` or
+/// `blur.frag.glsl`.
+fn glsl_stage(path: &str) -> Result<naga::ShaderStage> {
+    let name = Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let stage = name
+        .split('.')
+        .skip(1)
+        .find_map(|extension| match extension {
+            "vert" => Some(naga::ShaderStage::Vertex),
+            "frag" => Some(naga::ShaderStage::Fragment),
+            "comp" => Some(naga::ShaderStage::Compute),
+            _ => None,
+        });
+    stage.ok_or_else(|| {
+        let message = "GLSL shaders need a `.vert`, `.frag` or `.comp` extension to say which stage they're for";
+        Diagnostic::error("glsl_stage", message).in_file(path, None).into()
+    })
+}
+
+/// Reports the file at `path`, as passed to the loader, to the observer.
+fn file_read(options: &CodegenOptions, path: &str) {
+    if let Some(observer) = &options.observer {
+        let resolved = options.loader.resolve(path).unwrap_or_else(|_| path.into());
+        observer.file_read(&resolved);
+    }
+}
+
+/// Where the shader at `path` in a config is loaded from: with its
+/// variables filled in and normalized.
+fn shader_path(options: &CodegenOptions, path: &str) -> Result<String> {
+    let path = loader::interpolate(path, &options.path_variables).map_err(anyhow::Error::msg)?;
+    Ok(loader::normalize(options.shader_base.as_deref(), &path))
+}
+
+/// An error in the shader at `path`, followed by the lines of `src` that
+/// `spans` point at. If `src` had files imported into it, `lines` maps the
+/// spans back to the file and line they were written at.
+fn shader_error(
+    (path, lines): (&str, Option<&imports::LineMap>),
+    src: &str,
+    code: &str,
+    message: String,
+    spans: impl IntoIterator<Item = (naga::SourceLocation, String)>,
+) -> anyhow::Error {
+    let mut diagnostic = Diagnostic::error(code, message).in_file(path, None);
+    for (location, label) in spans {
+        let line = location.line_number as usize;
+        let source_line = src.lines().nth(line - 1).unwrap_or_default();
+        let (file, line) = match lines.and_then(|lines| lines.get(line)) {
+            Some((file, line)) => (Some(file.unwrap_or(path).to_owned()), line),
+            None => (None, line),
+        };
+        diagnostic.labels.push(Label {
+            span: Span {
+                line,
+                column: Some(location.line_position as usize),
+            },
+            file,
+            // naga labels some spans with the handle they came from, which
+            // means nothing outside of it.
+            text: if label.starts_with("naga::") {
+                String::new()
+            } else {
+                label
+            },
+            source_line: source_line.trim_end().to_owned(),
+        });
+    }
+    // Errors in an imported file are reported in it, with labels in the
+    // shader that imported it still saying so.
+    if let Some(file) = diagnostic
+        .labels
+        .first()
+        .and_then(|label| label.file.clone())
+    {
+        if file != path {
+            diagnostic.notes.push(format!("imported by {}", path));
+        }
+        diagnostic.file = Some(file);
+    }
+    for label in &mut diagnostic.labels {
+        if label.file == diagnostic.file {
+            label.file = None;
+        }
+    }
+    diagnostic.span = diagnostic.labels.first().map(|label| label.span);
+    diagnostic.into()
+}
+
+fn gen_pipeline_code_cached(
+    config: &PipelineConfig,
+    options: &CodegenOptions,
+    cache: &mut ShaderCache,
+    manifest: &mut Vec<PipelineManifest>,
+) -> Result<TokenStream> {
+    let CodeParts {
+        head,
+        pipelines,
+        tail,
+    } = gen_code_parts(config, options, cache, manifest)?;
+    let pipelines = pipelines.into_iter().map(|(_, tokens)| tokens);
+    let code = quote! {
+        #head
+        #(#pipelines)*
+        #tail
+    };
+    match &options.module {
+        Some(module) => in_module(code, module, &options.reexports, options.visibility),
+        None => Ok(match options.visibility {
+            Visibility::Public => code,
+            visibility => with_visibility(code, visibility.tokens()),
+        }),
+    }
+}
+
+/// Wraps `code` in `mod #module`, for [CodegenOptions::module].
+fn in_module(
+    code: TokenStream,
+    module: &str,
+    reexports: &[String],
+    visibility: Visibility,
+) -> Result<TokenStream> {
+    let ident = |name: &str, what: &str| {
+        syn::parse_str::<syn::Ident>(name)
+            .map_err(|_| anyhow::anyhow!("{} {:?} isn't a valid identifier", what, name))
+    };
+    let module = ident(module, "module")?;
+    let reexports = reexports
+        .iter()
+        .map(|name| ident(name, "re-export"))
+        .collect::<Result<Vec<_>>>()?;
+    let visibility = visibility.tokens();
+    let reexports =
+        (!reexports.is_empty()).then(|| quote! { #visibility use #module::{#(#reexports),*}; });
+    Ok(quote! {
+        #visibility mod #module {
+            #[allow(unused_imports)]
+            use super::*;
+            #code
+        }
+        #reexports
+    })
+}
+
+/// The code for a config, before [gen_pipeline_code_cached] puts it back
+/// together or [gen_pipeline_code_split] splits it up.
+struct CodeParts {
+    /// What comes before the pipelines, eg. the shader sources and bind
+    /// groups.
+    head: TokenStream,
+    /// Each pipeline's items by name, in the order they're declared, with
+    /// variants as separate pipelines.
+    pipelines: Vec<(String, TokenStream)>,
+    /// What comes after, eg. variant keys, the registry and the tests.
+    tail: TokenStream,
+}
+
+fn gen_code_parts(
+    config: &PipelineConfig,
+    options: &CodegenOptions,
+    cache: &mut ShaderCache,
+    manifest: &mut Vec<PipelineManifest>,
+) -> Result<CodeParts> {
     struct ShaderData {
-        module: naga::Module,
-        src: String,
+        shader: Rc<CachedShader>,
         name: String,
+        /// Set when the source should be pulled in with `include_str!`.
+        include_path: Option<PathBuf>,
+        /// The path as the config wrote it, for errors.
+        path: String,
+        /// Set when debug builds should read the shader from this file.
+        runtime_path: Option<PathBuf>,
+        /// The entry points of the pipelines using this shader.
+        entry_points: BTreeSet<String>,
+        /// The `cfg` predicates of the pipelines using this shader. `None`
+        /// means an ungated pipeline uses it.
+        cfgs: Option<Vec<TokenStream>>,
     }
-    let mut modules = HashMap::new();
-    let mut index = 0;
-    let render_pipelines = config.render_configs.iter().map(|rp| {
-        let name = format_ident!("{}", rp.name);
-        let label = &rp.name;
-        let vs_entry = &rp.vs_entry;
-        let fs_entry = &rp.fs_entry;
+    // Ordered so the sources come out in the same order every time.
+    type Modules =
+        BTreeMap<(String, Option<Vec<String>>, ConstantsKey, Option<String>), ShaderData>;
+    /// Loads the shader at `path`, preprocessed with `defines` and those of
+    /// its `#shader` and specialized with `constants`, the first time it's
+    /// used and records that a pipeline gated by `cfg` uses it.
+    fn use_shader<'m>(
+        modules: &'m mut Modules,
+        cache: &mut ShaderCache,
+        options: &CodegenOptions,
+        path: &str,
+        module: Option<&ShaderModuleConfig>,
+        (defines, constants): (&Option<Vec<String>>, &[(String, f64)]),
+        cfg: &Option<TokenStream>,
+    ) -> Result<&'m mut ShaderData> {
+        let all_defines = shader_defines(module, defines);
+        let normalized = shader_path(options, path)?;
+        let key = (
+            normalized.clone(),
+            all_defines,
+            constants_key(constants),
+            module.map(|module| module.name.clone()),
+        );
+        if !modules.contains_key(&key) {
+            let shader = cache.get(
+                path,
+                shader_language(module, path),
+                key.1.as_deref(),
+                constants,
+                options,
+            )?;
+            let include_path = match options.source_embedding {
+                SourceEmbedding::Inline if shader.src.len() > options.max_inline_source_len => {
+                    return Err(SourceTooLarge {
+                        path: path.to_owned(),
+                        len: shader.src.len(),
+                        limit: options.max_inline_source_len,
+                    }
+                    .into());
+                }
+                SourceEmbedding::Inline => None,
+                // Preprocessed source only exists in memory.
+                SourceEmbedding::IncludeStr if shader.preprocessed => None,
+                SourceEmbedding::IncludeStr => Some(options.loader.resolve(&normalized)?),
+                SourceEmbedding::SpirV => None,
+            };
+            // Preprocessed source doesn't match the file, and SPIR-V isn't
+            // read from one.
+            let runtime_path = match options.runtime_shaders && !shader.preprocessed {
+                true if options.source_embedding != SourceEmbedding::SpirV => {
+                    Some(options.loader.resolve(&normalized)?)
+                }
+                _ => None,
+            };
+            // Declared modules are named after themselves, with variants named
+            // after the variant's defines. Other shaders are named after their
+            // file and a hash of their source, so names don't depend on the
+            // order things are generated in and outputs can be merged.
+            let name = match (module, defines) {
+                (Some(module), None) => format!("SHADER_{}", screaming_snake_case(&module.name)),
+                (Some(module), Some(defines)) if defines.is_empty() => {
+                    format!("SHADER_{}_BASE", screaming_snake_case(&module.name))
+                }
+                (Some(module), Some(defines)) => {
+                    format!(
+                        "SHADER_{}_{}",
+                        screaming_snake_case(&module.name),
+                        defines.join("_")
+                    )
+                }
+                (None, _) => format!(
+                    "SHADER_{}_{}",
+                    path_const_name(path),
+                    content_hash(&shader.src)
+                ),
+            };
+            // The same source at different paths is numbered in the order the
+            // config uses it.
+            let mut unique = name.clone();
+            for n in 1.. {
+                if !modules.values().any(|data| data.name == unique) {
+                    break;
+                }
+                unique = format!("{}_{}", name, n);
+            }
+            let name = unique;
+            modules.insert(
+                key.clone(),
+                ShaderData {
+                    shader,
+                    name,
+                    include_path,
+                    path: path.to_owned(),
+                    runtime_path,
+                    entry_points: BTreeSet::new(),
+                    cfgs: Some(Vec::new()),
+                },
+            );
+        }
 
-        if !modules.contains_key(&rp.path) {
-            let src = std::fs::read_to_string(&rp.path)?;
-            let name = format!("SHADER{}", index);
-            index += 1;
-            let module = naga::front::wgsl::parse_str(&src)?;
-            modules.insert(&rp.path, ShaderData { module, src, name });
+        let data = modules.get_mut(&key).unwrap();
+        match (cfg, &mut data.cfgs) {
+            (Some(cfg), Some(cfgs)) => cfgs.push(cfg.clone()),
+            _ => data.cfgs = None,
         }
+        Ok(data)
+    }
 
-        let data = &modules[&rp.path];
-        let shader_name = &data.name;
-        let shader_ident = format_ident!("{}", shader_name);
+    let mut modules = Modules::new();
+    for import in &config.rust_imports {
+        rust_import_layout(import)?;
+    }
+    let cfgs = config
+        .render_configs
+        .iter()
+        .map(|rp| rp.cfg.as_deref().map(parse_cfg).transpose())
+        .collect::<Result<Vec<_>>>()?;
+    let expanded = config
+        .render_configs
+        .iter()
+        .zip(&cfgs)
+        .flat_map(|(rp, cfg)| {
+            expand_variants(rp)
+                .into_iter()
+                .map(move |(rp, defines)| ExpandedPipeline { rp, defines, cfg })
+        })
+        .collect::<Vec<_>>();
+    let compute_cfgs = config
+        .compute_configs
+        .iter()
+        .map(|cp| cp.cfg.as_deref().map(parse_cfg).transpose())
+        .collect::<Result<Vec<_>>>()?;
+    check_pipeline_names(
+        expanded
+            .iter()
+            .map(|p| (p.rp.name.as_str(), &p.rp.declared_at, p.cfg))
+            .chain(
+                config
+                    .compute_configs
+                    .iter()
+                    .zip(&compute_cfgs)
+                    .map(|(cp, cfg)| (cp.name.as_str(), &cp.declared_at, cfg)),
+            ),
+    )?;
+    let total = expanded.len() + config.compute_configs.len();
+    let mut structs = layout::HostStructs::default();
+    // How many target formats each pipeline's constructor takes.
+    let mut runtime_targets = HashMap::new();
+    let mut owned_layouts = Vec::new();
+    let render_pipelines = expanded
+        .iter()
+        .enumerate()
+        .map(|(i, ExpandedPipeline { rp, defines, cfg })| {
+            let module = rp.shader.as_ref().and_then(|name| config.shader(name));
+            let defines = pipeline_defines(rp, defines);
+            let data = use_shader(
+                &mut modules,
+                cache,
+                options,
+                &rp.path,
+                module,
+                (&defines, &rp.constants),
+                cfg,
+            )?;
+            let shader_ident = format_ident!("{}", data.name);
+            let wgsl_source = wgsl_source(&shader_ident, data.runtime_path.as_deref());
+            let cfg_attr = cfg_attr(cfg);
+            // Layout names become parameters, so keywords can't be used.
+            for name in &rp.bind_group_layouts {
+                syn::parse_str::<syn::Ident>(name).map_err(|_| {
+                    anyhow::anyhow!(
+                        "{}: bind group layout {:?} is a reserved word",
+                        rp.name,
+                        name
+                    )
+                })?;
+            }
 
-        Ok(quote! {
-            pub struct #name {
-                render_pipeline: ::wgpu::RenderPipeline,
+            let vs_entry = resolve_entry_point(rp, &data.shader.module, naga::ShaderStage::Vertex)?;
+            let fs_entry =
+                resolve_entry_point(rp, &data.shader.module, naga::ShaderStage::Fragment)?;
+            let entries = (vs_entry.as_str(), fs_entry.as_str());
+            data.entry_points
+                .extend([vs_entry.clone(), fs_entry.clone()]);
+            let bindings = reflect::reflect_bindings(
+                &data.shader.module,
+                &[entries.0, entries.1],
+                &rp.bindings,
+            )
+            .map_err(|e| anyhow::anyhow!("{}: {}", rp.name, e))?;
+            if let Some(observer) = &options.observer {
+                for warning in &bindings.warnings {
+                    observer.warning(&format!("{}: {}", rp.name, warning));
+                }
             }
 
-            impl #name {
-                pub fn new(device: ::wgpu::Device) -> Self {
-                    let module = device.create_shader_module(::wgpu::ShaderModuleDescriptor {
-                        label: Some(#shader_name),
-                        source: ::wgpu::ShaderSource::Wgsl(::std::borrow::Cow::from(#shader_ident)),
-                    });
-                    let pipeline_layout = device.create_pipeline_layout(&::wgpu::PipelineLayoutDescriptor {
-                        label: Some(#label),
-                        bind_group_layouts: &[],
-                        push_constant_ranges: &[],
-                    });
-                    let render_pipeline = device.create_render_pipeline(&::wgpu::RenderPipelineDescriptor {
-                        label: Some(#label),
-                        layout: Some(&pipeline_layout),
-                        vertex: ::wgpu::VertexState {
-                            module: &module,
-                            entry_point: #vs_entry,
-                            buffers: &[
-                                // TODO: pull this data from the module
-                            ],
-                        },
-                        primitive: ::wgpu::PrimitiveState {
-                            // TODO: add this data to RenderPipelineConfig
-                            topology: ::wgpu::PrimitiveTopology::TriangleList,
-                            strip_index_format: None,
-                            front_face: ::wgpu::FrontFace::Ccw,
-                            cull_mode: Some(::wgpu::Face::Back),
-                            unclipped_depth: false,
-                            polygon_mode: ::wgpu::PolygonMode::Fill,
-                            conservative: false,
-                        },
-                        depth_stencil: None,
-                        multisample: ::wgpu::MultisampleState {
-                            count: 1,
-                            mask: !0,
-                            alpha_to_coverage_enabled: false,
-                        },
-                        fragment: Some(::wgpu::FragmentState {
-                            module: &module,
-                            entry_point: #fs_entry,
-                            targets: &[
-                                // TODO: pull this data from the module
-                            ],
-                        }),
-                        // Might want to support this 
-                        multiview: None,
-                    });
+            structs
+                .add_buffers(&data.shader.module, &bindings, options.bytemuck)
+                .map_err(|e| anyhow::anyhow!("{}: {}", rp.name, e))?;
 
-                    Self {
-                        render_pipeline,
-                    }
+            check_vertex_locations(rp, config)?;
+            let vertex_inputs = match &rp.vertex_buffers[..] {
+                [] => reflect::reflect_vertex_inputs(&data.shader.module, entries.0)
+                    .map_err(|e| anyhow::anyhow!("{}: {}", rp.name, e))?,
+                _ => Vec::new(),
+            };
+            let outputs = reflect::reflect_fragment_outputs(&data.shader.module, entries.1)
+                .map_err(|e| anyhow::anyhow!("{}: {}", rp.name, e))?;
+            check_color_targets(rp, &outputs)?;
+            reflect::check_stage_interface(&data.shader.module, entries.0, entries.1)
+                .map_err(|e| anyhow::anyhow!("{}: {}", rp.name, e))?;
+            runtime_targets.insert(rp.name.clone(), runtime_target_count(rp, &outputs));
+            if let Some(entries) = pipeline_layout_entries(rp, config, &bindings) {
+                owned_layouts.push((&**rp, *cfg, entries, push_constant_ranges(&bindings)));
+            }
+            manifest.push(manifest::render_pipeline(
+                rp,
+                config,
+                entries,
+                &bindings,
+                &vertex_inputs,
+            ));
+            let reflected = (&bindings, &vertex_inputs[..], &outputs[..]);
+            let tokens = match options.mode {
+                CodegenMode::Pipelines => {
+                    let spirv = options.source_embedding == SourceEmbedding::SpirV;
+                    let shader = (data.name.as_str(), &wgsl_source);
+                    gen_render_pipeline(
+                        rp,
+                        config,
+                        shader,
+                        entries,
+                        reflected,
+                        (options.labels, spirv),
+                        &cfg_attr,
+                    )
+                }
+                CodegenMode::ConstantsOnly => {
+                    gen_render_constants(rp, config, &shader_ident, entries, reflected, &cfg_attr)
                 }
+            };
+            let location = options
+                .source_locations
+                .then(|| gen_source_location(&rp.name, &rp.declared_at, &cfg_attr));
+            if let Some(observer) = &options.observer {
+                observer.pipeline_generated(&rp.name, i + 1, total);
             }
+            Ok((rp.name.clone(), quote! { #location #tokens }))
         })
+        .collect::<Result<Vec<_>>>()?;
+    let compute_pipelines = config.compute_configs.iter().zip(&compute_cfgs).enumerate().map(|(i, (cp, cfg))| {
+        if options.source_embedding == SourceEmbedding::SpirV && options.mode == CodegenMode::Pipelines {
+            anyhow::bail!(
+                "{}: compute pipelines can't be created from SPIR-V, since wgpu can't derive their layout from it",
+                cp.name
+            );
+        }
+        let module = cp.shader.as_ref().and_then(|name| config.shader(name));
+        let data = use_shader(&mut modules, cache, options, &cp.path, module, (&None, &[]), cfg)?;
+        let module = &data.shader.module;
+        check_entry_point(module, naga::ShaderStage::Compute, &cp.entry)
+            .map_err(|e| anyhow::anyhow!("{}: {} {}", cp.name, cp.path, e))?;
+        let entry_point = module.entry_points.iter().find(|ep| ep.name == cp.entry).unwrap();
+        data.entry_points.insert(cp.entry.clone());
+        let workgroup_size = entry_point.workgroup_size;
+        let bindings = reflect::reflect_bindings(&data.shader.module, &[cp.entry.as_str()], &[])
+            .map_err(|e| anyhow::anyhow!("{}: {}", cp.name, e))?;
+        structs
+            .add_buffers(&data.shader.module, &bindings, options.bytemuck)
+            .map_err(|e| anyhow::anyhow!("{}: {}", cp.name, e))?;
+        manifest.push(manifest::compute_pipeline(cp, workgroup_size, &bindings));
+        let cfg_attr = cfg_attr(cfg);
+        let tokens = match options.mode {
+            CodegenMode::Pipelines => {
+                let wgsl_source = wgsl_source(&format_ident!("{}", data.name), data.runtime_path.as_deref());
+                gen_compute_pipeline(cp, (&data.name, &wgsl_source), workgroup_size, options.labels, &cfg_attr)
+            }
+            CodegenMode::ConstantsOnly => {
+                gen_compute_constants(cp, &format_ident!("{}", data.name), workgroup_size, &cfg_attr)
+            }
+        };
+        let location = options.source_locations.then(|| gen_source_location(&cp.name, &cp.declared_at, &cfg_attr));
+        if let Some(observer) = &options.observer {
+            observer.pipeline_generated(&cp.name, expanded.len() + i + 1, total);
+        }
+        Ok((cp.name.clone(), quote! { #location #tokens }))
     }).collect::<Result<Vec<_>>>()?;
+    let bind_groups = config
+        .bind_groups
+        .iter()
+        .map(|group| gen_bind_group(group, options))
+        .collect::<Result<Vec<_>>>()?;
+    let vertex_layouts = config.vertex_layouts.iter().map(gen_vertex_layout);
+    let color_targets = config.color_targets.iter().map(gen_color_target);
+    let pipeline_layouts = config
+        .pipeline_layouts
+        .iter()
+        .map(|layout| gen_pipeline_layout(layout, config, options));
+    let shared_layouts = match options.mode {
+        CodegenMode::Pipelines => shared_layouts(owned_layouts),
+        CodegenMode::ConstantsOnly => Vec::new(),
+    };
+    let shared_layout_items = shared_layouts
+        .iter()
+        .map(|shared| gen_shared_layout(shared, &runtime_targets, options.labels));
+    let variant_keys = config
+        .render_configs
+        .iter()
+        .zip(&cfgs)
+        .filter(|(rp, _)| !rp.variants.is_empty())
+        .map(|(rp, cfg)| gen_variant_key(rp, options.mode, &runtime_targets, &cfg_attr(cfg)))
+        .collect::<Result<Vec<_>>>()?;
 
-    let sources = modules.values().map(|data| {
-        let ident = format_ident!("{}", data.name);
-        let src = &data.src;
-        quote! {
-            const #ident: &'static str = #src;
-        }
-    }).collect::<Vec<_>>();
+    // Gated like the shaders read at runtime, so it's never unused.
+    let read_shader_source = (options.mode == CodegenMode::Pipelines)
+        .then(|| {
+            let mut runtime = modules
+                .values()
+                .filter(|data| data.runtime_path.is_some())
+                .peekable();
+            runtime.peek()?;
+            let cfgs = runtime
+                .map(|data| data.cfgs.clone())
+                .collect::<Option<Vec<_>>>();
+            let cfg_attr = cfgs.map(|cfgs| {
+                let cfgs = cfgs.into_iter().flatten();
+                quote! { #[cfg(any(#(#cfgs),*))] }
+            });
+            let read_shader_source = gen_read_shader_source();
+            Some(quote! { #cfg_attr #read_shader_source })
+        })
+        .flatten();
+    let sources = modules
+        .values()
+        .map(|data| {
+            let ident = format_ident!("{}", data.name);
+            // A shader is only compiled in when at least one of its pipelines is.
+            let cfg_attr = match &data.cfgs {
+                Some(cfgs) => quote! { #[cfg(any(#(#cfgs),*))] },
+                None => quote! {},
+            };
+            // naga's modules can't be cloned, so the source is parsed again to
+            // get one to prune.
+            let pruned = match options.prune_shaders {
+                true => prune::prune(
+                    naga::front::wgsl::parse_str(&data.shader.src)?,
+                    &data.entry_points,
+                )
+                .map(|module| {
+                    let info = validator().validate(&module)?;
+                    anyhow::Ok((module, info))
+                })
+                .transpose()
+                .map_err(|e| {
+                    anyhow::anyhow!("{}: Unable to prune unused entry points: {}", data.path, e)
+                })?,
+                false => None,
+            };
+            let (module, info) = match &pruned {
+                Some((module, info)) => (module, info),
+                None => (&data.shader.module, &data.shader.info),
+            };
+            if options.source_embedding == SourceEmbedding::SpirV {
+                let words = spirv_words(module, info).map_err(|e| {
+                    anyhow::anyhow!("{}: Unable to compile to SPIR-V: {}", data.path, e)
+                })?;
+                return Ok(quote! {
+                    #cfg_attr
+                    const #ident: &'static [u32] = &[#(#words),*];
+                });
+            }
+            let mut written = None;
+            if pruned.is_some() {
+                let src = naga::back::wgsl::write_string(
+                    module,
+                    info,
+                    naga::back::wgsl::WriterFlags::empty(),
+                )
+                .map_err(|e| {
+                    anyhow::anyhow!("{}: Unable to write the pruned shader: {}", data.path, e)
+                })?;
+                // naga renames some identifiers, eg. ones ending in a digit, and
+                // the pipelines refer to their entry points by name.
+                match data
+                    .entry_points
+                    .iter()
+                    .all(|name| src.contains(&format!("fn {}(", name)))
+                {
+                    true => written = Some(src),
+                    false => {
+                        if let Some(observer) = &options.observer {
+                            observer.warning(&format!(
+                                "{}: Not pruned, since writing it out renames an entry point",
+                                data.path
+                            ));
+                        }
+                    }
+                }
+            }
+            let src = match (written, &data.include_path) {
+                (None, Some(path)) if !options.minify_shaders => {
+                    let path = path.to_string_lossy();
+                    return Ok(quote! {
+                        #cfg_attr
+                        const #ident: &'static str = include_str!(#path);
+                    });
+                }
+                (Some(src), _) => Cow::Owned(src),
+                (None, _) => Cow::Borrowed(&data.shader.src),
+            };
+            let src = match options.minify_shaders {
+                true => {
+                    let minified = minify::minify(&src);
+                    // Checked so a mistake in minifying shows up here rather than
+                    // when the shader is created.
+                    naga::front::wgsl::parse_str(&minified).map_err(|e| {
+                        anyhow::anyhow!("{}: Minifying broke the shader: {}", data.path, e)
+                    })?;
+                    Cow::Owned(minified)
+                }
+                false => src,
+            };
+            Ok(quote! {
+                #cfg_attr
+                const #ident: &'static str = #src;
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    Ok(quote! {
+    let registry = (options.registry && options.mode == CodegenMode::Pipelines)
+        .then(|| gen_registry(&expanded, &runtime_targets));
+    let handle = (options.mode == CodegenMode::Pipelines && !expanded.is_empty())
+        .then(gen_render_pipeline_handle);
+    let tests = (options.tests && options.mode == CodegenMode::Pipelines && total > 0).then(|| {
+        gen_pipeline_tests(
+            &expanded,
+            (&config.compute_configs, &compute_cfgs),
+            &runtime_targets,
+        )
+    });
+    let pipeline_set = (options.mode == CodegenMode::Pipelines && total > 0).then(|| {
+        gen_pipeline_set(
+            config,
+            (&cfgs, &compute_cfgs),
+            &shared_layouts,
+            &runtime_targets,
+            options.warmup,
+        )
+    });
+
+    let structs = structs.tokens();
+
+    let head = quote! {
         #(#sources)*
-        #(#render_pipelines)*
+        #read_shader_source
+        #(#structs)*
+        #(#bind_groups)*
+        #(#vertex_layouts)*
+        #(#color_targets)*
+        #(#pipeline_layouts)*
+        #(#shared_layout_items)*
+        #handle
+    };
+    let tail = quote! {
+        #(#variant_keys)*
+        #pipeline_set
+        #registry
+        #tests
+    };
+    Ok(CodeParts {
+        head,
+        pipelines: render_pipelines
+            .into_iter()
+            .chain(compute_pipelines)
+            .collect(),
+        tail,
     })
 }
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn pipeline_config_from() {}
+/// Replaces the `pub` of every item in `code` with `visibility`. Only the
+/// outermost tokens are items, so fields and methods are left alone.
+fn with_visibility(code: TokenStream, visibility: TokenStream) -> TokenStream {
+    code.into_iter()
+        .flat_map(|token| match &token {
+            proc_macro2::TokenTree::Ident(ident) if ident == "pub" => visibility.clone(),
+            _ => token.into(),
+        })
+        .collect()
+}
+
+/// A pipeline to generate, after [expand_variants].
+struct ExpandedPipeline<'c> {
+    rp: Cow<'c, RenderPipelineConfig>,
+    /// The defines the shader is preprocessed with, if it has variants.
+    defines: Option<Vec<String>>,
+    cfg: &'c Option<TokenStream>,
+}
+
+/// The defines of a render pipeline's shader: its own `defines` and those
+/// of the variant, with `None` when it has neither.
+fn pipeline_defines(
+    rp: &RenderPipelineConfig,
+    variant: &Option<Vec<String>>,
+) -> Option<Vec<String>> {
+    match &rp.defines[..] {
+        [] => variant.clone(),
+        names => Some(
+            names
+                .iter()
+                .chain(variant.iter().flatten())
+                .cloned()
+                .collect(),
+        ),
+    }
+}
+
+/// The language of the shader at `path`, from its `#shader` if it has one.
+fn shader_language<'m>(module: Option<&'m ShaderModuleConfig>, path: &str) -> &'m str {
+    module.map_or_else(|| config::path_language(path), |module| &module.language)
+}
+
+/// `defines` along with those of the `#shader` a pipeline uses, if any.
+fn shader_defines(
+    module: Option<&ShaderModuleConfig>,
+    defines: &Option<Vec<String>>,
+) -> Option<Vec<String>> {
+    match module {
+        Some(module) if !module.defines.is_empty() => Some(
+            module
+                .defines
+                .iter()
+                .chain(defines.iter().flatten())
+                .cloned()
+                .collect(),
+        ),
+        _ => defines.clone(),
+    }
+}
+
+/// Splits a pipeline with `variants` into one pipeline per combination of
+/// its defines, named after the combination, eg. `MaterialPipelineSkinned`.
+/// Each comes with the defines its shader is preprocessed with. Pipelines
+/// without variants are returned as-is with no preprocessing.
+fn expand_variants(
+    rp: &RenderPipelineConfig,
+) -> Vec<(Cow<'_, RenderPipelineConfig>, Option<Vec<String>>)> {
+    if rp.variants.is_empty() {
+        return vec![(Cow::Borrowed(rp), None)];
+    }
+    defines::combinations(&rp.variants)
+        .into_iter()
+        .map(|combination| {
+            let suffix = defines::type_suffix(&rp.variants, &combination);
+            let mut variant = rp.clone();
+            variant.name = format!("{}{}", rp.name, suffix);
+            variant.label = rp
+                .label
+                .as_ref()
+                .map(|label| format!("{} ({})", label, suffix));
+            variant.variants.clear();
+            (
+                Cow::Owned(variant),
+                Some(defines::defined_names(&rp.variants, &combination)),
+            )
+        })
+        .collect()
+}
+
+/// Emits `{Name}Key`, with a field per define, to pick between the variants
+/// of a pipeline. Enum defines get an enum named `{Name}{Define}`. With
+/// [CodegenMode::Pipelines] this also emits a `{Name}` struct that creates
+/// every variant and hands them out by key.
+fn gen_variant_key(
+    rp: &RenderPipelineConfig,
+    mode: CodegenMode,
+    runtime_targets: &HashMap<String, usize>,
+    cfg_attr: &TokenStream,
+) -> Result<TokenStream> {
+    let name = format_ident!("{}", rp.name);
+    let key = format_ident!("{}Key", rp.name);
+    let combinations = defines::combinations(&rp.variants);
+    let count = combinations.len();
+
+    let mut enums = Vec::new();
+    let mut fields = Vec::new();
+    let mut field_types = Vec::new();
+    // The value of each field for each combination.
+    let mut field_values = vec![Vec::new(); count];
+    for (i, define) in rp.variants.iter().enumerate() {
+        let field = define.name.to_lowercase();
+        let field = syn::parse_str::<syn::Ident>(&field).map_err(|_| {
+            anyhow::anyhow!(
+                "{}: define {} can't be used as a field name",
+                rp.name,
+                define.name
+            )
+        })?;
+        let values = if define.values.is_empty() {
+            field_types.push(quote! { bool });
+            vec![quote! { false }, quote! { true }]
+        } else {
+            let ty = format_ident!("{}{}", rp.name, defines::pascal_case(&define.name));
+            let variants = define
+                .values
+                .iter()
+                .map(|value| format_ident!("{}", defines::pascal_case(value)))
+                .collect::<Vec<_>>();
+            let first = &variants[0];
+            let rest = &variants[1..];
+            enums.push(quote! {
+                #cfg_attr
+                #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+                pub enum #ty {
+                    #[default]
+                    #first,
+                    #(#rest,)*
+                }
+            });
+            field_types.push(quote! { #ty });
+            variants
+                .iter()
+                .map(|variant| quote! { #ty::#variant })
+                .collect()
+        };
+        for (values_of_combination, combination) in field_values.iter_mut().zip(&combinations) {
+            values_of_combination.push(values[combination[i]].clone());
+        }
+        fields.push(field);
+    }
+
+    // The last define changes fastest, like `combinations` does.
+    let mut stride = 1usize;
+    let mut index_terms = Vec::new();
+    for (field, define) in fields.iter().zip(&rp.variants).rev() {
+        index_terms.push(quote! { self.#field as usize * #stride });
+        stride *= define.value_count();
+    }
+    index_terms.reverse();
+
+    let all = field_values
+        .iter()
+        .map(|values| quote! { Self { #(#fields: #values),* } });
+    let indices = 0..count;
+    let defines = combinations.iter().map(|combination| {
+        let names = defines::defined_names(&rp.variants, combination);
+        quote! { &[#(#names),*] }
+    });
+    let variant_names = combinations
+        .iter()
+        .map(|combination| {
+            format!(
+                "{}{}",
+                rp.name,
+                defines::type_suffix(&rp.variants, combination)
+            )
+        })
+        .collect::<Vec<_>>();
+    let key_doc = format!(" Selects one of the variants of `{}`.", rp.name);
+    // Every variant is created with the same target formats.
+    let mut target_counts = variant_names
+        .iter()
+        .map(|name| runtime_targets[name])
+        .collect::<Vec<_>>();
+    target_counts.dedup();
+    let [target_count] = target_counts[..] else {
+        anyhow::bail!(
+            "{}: the variants write different numbers of color targets, so `targets` or `target_formats` must be given",
+            rp.name
+        );
+    };
+
+    let aggregate = (mode == CodegenMode::Pipelines).then(|| {
+        let (params, args) = constructor_params(rp, target_count);
+        let (generics, type_args) = match rp.sample_counts {
+            Some(_) => (quote! { <const SAMPLES: u32> }, quote! { <SAMPLES> }),
+            None => (quote! {}, quote! {}),
+        };
+        let creates = variant_names.iter().map(|name| {
+            let ty = format_ident!("{}", name);
+            quote! { <#ty #type_args>::create_render_pipeline(device #(, #args)*) }
+        });
+        let doc = format!(" Every variant of `{}`, created up front.", rp.name);
+        quote! {
+            #cfg_attr
+            #[doc = #doc]
+            pub struct #name #generics {
+                render_pipelines: [::wgpu::RenderPipeline; #count],
+            }
+
+            #cfg_attr
+            impl #generics #name #type_args {
+                pub fn new(device: &::wgpu::Device #(, #params)*) -> Self {
+                    Self {
+                        render_pipelines: [#(#creates),*],
+                    }
+                }
+
+                pub fn get(&self, key: #key) -> &::wgpu::RenderPipeline {
+                    &self.render_pipelines[key.index()]
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        #(#enums)*
+
+        #cfg_attr
+        #[doc = #key_doc]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+        pub struct #key {
+            #(pub #fields: #field_types,)*
+        }
+
+        #cfg_attr
+        impl #key {
+            /// Every key, in the order of [Self::index].
+            pub const ALL: [Self; #count] = [#(#all),*];
+
+            /// Where this key is in [Self::ALL].
+            pub const fn index(&self) -> usize {
+                #(#index_terms)+*
+            }
+
+            /// The shader defines set for this variant.
+            pub const fn defines(&self) -> &'static [&'static str] {
+                match self.index() {
+                    #(#indices => #defines,)*
+                    _ => unreachable!(),
+                }
+            }
+
+            /// The name of the pipeline generated for this variant.
+            pub const fn pipeline_name(&self) -> &'static str {
+                [#(#variant_names),*][self.index()]
+            }
+        }
+
+        #aggregate
+    })
+}
+
+/// Returns the entry point the pipeline uses for `stage`. When the config
+/// doesn't name one, the shader's only entry point for that stage is used,
+/// falling back to the conventional `vs_main`/`fs_main` if there are several.
+fn resolve_entry_point(
+    rp: &RenderPipelineConfig,
+    module: &naga::Module,
+    stage: naga::ShaderStage,
+) -> Result<String> {
+    let (given, conventional, field) = match stage {
+        naga::ShaderStage::Vertex => (&rp.vs_entry, "vs_main", "vs_entry"),
+        naga::ShaderStage::Fragment => (&rp.fs_entry, "fs_main", "fs_entry"),
+        naga::ShaderStage::Compute => unreachable!("render pipelines have no compute stage"),
+    };
+    let candidates = entry_point_names(module, stage);
+    if let Some(entry) = given {
+        check_entry_point(module, stage, entry)
+            .map_err(|e| anyhow::anyhow!("{}: {} {}", rp.name, rp.path, e))?;
+        return Ok(entry.clone());
+    }
+    match candidates[..] {
+        [entry] => Ok(entry.to_owned()),
+        _ if candidates.contains(&conventional) => Ok(conventional.to_owned()),
+        [] => anyhow::bail!(
+            "{}: {} has no {:?} entry points, so `{}` can't be inferred",
+            rp.name,
+            rp.path,
+            stage,
+            field
+        ),
+        _ => anyhow::bail!(
+            "{}: {} has several {:?} entry points ({}), so `{}` must be given",
+            rp.name,
+            rp.path,
+            stage,
+            candidates.join(", "),
+            field
+        ),
+    }
+}
+
+fn entry_point_names(module: &naga::Module, stage: naga::ShaderStage) -> Vec<&str> {
+    module
+        .entry_points
+        .iter()
+        .filter(|ep| ep.stage == stage)
+        .map(|ep| ep.name.as_str())
+        .collect()
+}
+
+/// Checks that `module` has a `stage` entry point called `entry`, otherwise
+/// describing what it has instead.
+fn check_entry_point(
+    module: &naga::Module,
+    stage: naga::ShaderStage,
+    entry: &str,
+) -> Result<(), String> {
+    if let Some(ep) = module.entry_points.iter().find(|ep| ep.name == entry) {
+        return match ep.stage == stage {
+            true => Ok(()),
+            false => Err(format!(
+                "declares `{}` as a {:?} entry point, not a {:?} one",
+                entry, ep.stage, stage
+            )),
+        };
+    }
+    match &entry_point_names(module, stage)[..] {
+        [] => Err(format!(
+            "has no {:?} entry points, so `{}` doesn't exist",
+            stage, entry
+        )),
+        available => Err(format!(
+            "has no {:?} entry point `{}`, the available ones are {}",
+            stage,
+            entry,
+            available.join(", ")
+        )),
+    }
+}
+
+fn cfg_attr(cfg: &Option<TokenStream>) -> TokenStream {
+    match cfg {
+        Some(cfg) => quote! { #[cfg(#cfg)] },
+        None => quote! {},
+    }
+}
+
+fn gen_registry(
+    pipelines: &[ExpandedPipeline],
+    runtime_targets: &HashMap<String, usize>,
+) -> TokenStream {
+    // Pipelines with several sample counts get an entry per count, and ones
+    // with variants get an entry per variant. Pipelines that need bind group
+    // layouts, a pipeline layout or target formats passed in can't be created
+    // from a name alone and are left out.
+    let registered = pipelines
+        .iter()
+        .map(|pipeline| (pipeline.rp.as_ref(), pipeline.cfg))
+        .filter(|(rp, _)| {
+            rp.bind_group_layouts.is_empty()
+                && rp.layout.is_none()
+                && runtime_targets[&rp.name] == 0
+        });
+    let entries = registered.flat_map(|(rp, cfg)| {
+        let name = &rp.name;
+        let ident = format_ident!("{}", rp.name);
+        let cfg_attr = cfg_attr(cfg);
+        let variants = match &rp.sample_counts {
+            Some(counts) => counts
+                .iter()
+                .map(|count| (*count, quote! { #ident::<#count> }))
+                .collect(),
+            None => vec![(1, quote! { #ident })],
+        };
+        variants.into_iter().map(move |(sample_count, ty)| {
+            quote! {
+                #cfg_attr
+                PipelineFactory {
+                    name: #name,
+                    sample_count: #sample_count,
+                    create: #ty::create_render_pipeline,
+                }
+            }
+        })
+    });
+    quote! {
+        /// Creates one of the generated pipelines given only its name.
+        #[derive(Debug, Clone, Copy)]
+        pub struct PipelineFactory {
+            pub name: &'static str,
+            pub sample_count: u32,
+            pub create: fn(&::wgpu::Device) -> ::wgpu::RenderPipeline,
+        }
+
+        /// Every generated pipeline that doesn't take bind group layouts, in
+        /// the order they were declared.
+        pub static PIPELINE_REGISTRY: &[PipelineFactory] = &[#(#entries),*];
+
+        /// Finds the factory for the pipeline called `name`. For pipelines
+        /// with several sample counts this is the first count listed.
+        pub fn find_pipeline(name: &str) -> Option<&'static PipelineFactory> {
+            PIPELINE_REGISTRY.iter().find(|factory| factory.name == name)
+        }
+
+        /// Finds the factory for the `sample_count` variant of the pipeline
+        /// called `name`.
+        pub fn find_pipeline_with_sample_count(
+            name: &str,
+            sample_count: u32,
+        ) -> Option<&'static PipelineFactory> {
+            PIPELINE_REGISTRY
+                .iter()
+                .find(|factory| factory.name == name && factory.sample_count == sample_count)
+        }
+    }
+}
+
+/// Emits `Pipelines`, which creates every pipeline in the config at once.
+/// Its constructor takes everything the pipelines' constructors do, with
+/// bind group layouts of the same name and `surface_format` shared between
+/// them. The `#pipeline_layout`s the pipelines use are created and kept
+/// alongside them. With `warmup` it also gets [gen_warmup]'s `warmup`.
+fn gen_pipeline_set(
+    config: &PipelineConfig,
+    (render_cfgs, compute_cfgs): (&[Option<TokenStream>], &[Option<TokenStream>]),
+    shared_layouts: &[SharedLayout],
+    runtime_targets: &HashMap<String, usize>,
+    warmup: bool,
+) -> TokenStream {
+    let mut params = Vec::<(String, TokenStream)>::new();
+    let mut param = |name: String, tokens: TokenStream| {
+        if !params.iter().any(|(n, _)| *n == name) {
+            params.push((name, tokens));
+        }
+    };
+    let mut layouts = Vec::new();
+    let mut shared = Vec::new();
+    let mut fields = Vec::new();
+    let mut inits = Vec::new();
+    // How to create each pipeline for `warmup`, with its name and sample
+    // count.
+    let mut creates = Vec::new();
+    for (rp, cfg) in config.render_configs.iter().zip(render_cfgs) {
+        let field = screaming_snake_case(&rp.name).to_lowercase();
+        // Variants are created by their aggregate, so they don't share layouts.
+        let shared_layout = shared_layouts.iter().enumerate().find(|(_, layout)| {
+            rp.variants.is_empty()
+                && layout
+                    .members
+                    .iter()
+                    .any(|(member, _)| member.name == rp.name)
+        });
+        if let Some((i, _)) = shared_layout {
+            if !shared.contains(&i) {
+                shared.push(i);
+            }
+        }
+        let mut args = Vec::new();
+        match &rp.layout {
+            Some(layout) => {
+                let ident = format_ident!("{}_layout", screaming_snake_case(layout).to_lowercase());
+                if !layouts.contains(layout) {
+                    layouts.push(layout.clone());
+                }
+                args.push(quote! { &#ident });
+            }
+            None => {
+                for name in &rp.bind_group_layouts {
+                    let ident = format_ident!("{}", name);
+                    param(name.clone(), quote! { #ident: &::wgpu::BindGroupLayout });
+                    args.push(quote! { #ident });
+                }
+            }
+        }
+        // Variants all take the same target formats, so the first one says
+        // how many.
+        let first = &expand_variants(rp)[0].0.name;
+        match runtime_targets[first] {
+            0 => (),
+            1 => {
+                param(
+                    "surface_format".to_owned(),
+                    quote! { surface_format: ::wgpu::TextureFormat },
+                );
+                args.push(quote! { surface_format });
+            }
+            count => {
+                let ident = format_ident!("{}_target_formats", field);
+                param(
+                    ident.to_string(),
+                    quote! { #ident: [::wgpu::TextureFormat; #count] },
+                );
+                args.push(quote! { #ident });
+            }
+        }
+        let name = format_ident!("{}", rp.name);
+        let cfg_attr = cfg_attr(cfg);
+        let types = match &rp.sample_counts {
+            Some(counts) => counts
+                .iter()
+                .map(|count| {
+                    (
+                        format_ident!("{}_x{}", field, count),
+                        quote! { #name<#count> },
+                        *count,
+                    )
+                })
+                .collect(),
+            None => vec![(format_ident!("{}", field), quote! { #name }, 1)],
+        };
+        for (field, ty, sample_count) in types {
+            fields.push(quote! { #cfg_attr pub #field: #ty });
+            let create = match shared_layout {
+                Some((i, _)) => {
+                    let layout = format_ident!("shared_layout{}", i);
+                    quote! { <#ty>::with_shared_layout(device, &#layout #(, #args)*) }
+                }
+                None => quote! { <#ty>::new(device #(, #args)*) },
+            };
+            inits.push(quote! { #cfg_attr #field: #create });
+            creates.push((cfg_attr.clone(), &rp.name, sample_count, create));
+        }
+    }
+    for (cp, cfg) in config.compute_configs.iter().zip(compute_cfgs) {
+        let field = format_ident!("{}", screaming_snake_case(&cp.name).to_lowercase());
+        let name = format_ident!("{}", cp.name);
+        let cfg_attr = cfg_attr(cfg);
+        fields.push(quote! { #cfg_attr pub #field: #name });
+        inits.push(quote! { #cfg_attr #field: #name::new(device) });
+        creates.push((cfg_attr, &cp.name, 1, quote! { #name::new(device) }));
+    }
+    let layout_idents = layouts
+        .iter()
+        .map(|layout| format_ident!("{}_layout", screaming_snake_case(layout).to_lowercase()))
+        .collect::<Vec<_>>();
+    let layout_types = layouts
+        .iter()
+        .map(|layout| pipeline_layout_type(layout))
+        .collect::<Vec<_>>();
+    let shared_idents = shared
+        .iter()
+        .map(|i| format_ident!("shared_layout{}", i))
+        .collect::<Vec<_>>();
+    let shared_types = shared
+        .iter()
+        .map(|&i| &shared_layouts[i].ident)
+        .collect::<Vec<_>>();
+    let shared_cfgs = shared
+        .iter()
+        .map(|&i| shared_layouts[i].cfg_attr())
+        .collect::<Vec<_>>();
+    let params = params
+        .into_iter()
+        .map(|(_, tokens)| tokens)
+        .collect::<Vec<_>>();
+    let setup = quote! {
+        #(let #layout_idents = #layout_types::new(device);)*
+        #(#shared_cfgs let #shared_idents = #shared_types::new(device);)*
+    };
+    let warmup = warmup.then(|| gen_warmup(&params, &setup, &creates));
+
+    quote! {
+        /// Every pipeline in the config, created together.
+        pub struct Pipelines {
+            #(#fields,)*
+            #(pub #layout_idents: #layout_types,)*
+            #(#shared_cfgs pub #shared_idents: #shared_types,)*
+        }
+
+        impl Pipelines {
+            // Parameters are only used by the pipelines that are compiled in,
+            // and there's one per bind group layout.
+            #[allow(unused_variables, clippy::too_many_arguments)]
+            pub fn new(device: &::wgpu::Device #(, #params)*) -> Self {
+                #setup
+                Self {
+                    #(#inits,)*
+                    #(#layout_idents,)*
+                    #(#shared_cfgs #shared_idents,)*
+                }
+            }
+        }
+
+        #warmup
+    }
+}
+
+/// Emits the `pipeline_tests` module for [CodegenOptions::tests], with a test
+/// named after each pipeline. Formats the constructor takes are given as
+/// `Rgba8Unorm`, which every adapter can render to, and pipelines with
+/// several sample counts are created with the first.
+fn gen_pipeline_tests(
+    pipelines: &[ExpandedPipeline],
+    (compute_configs, compute_cfgs): (&[ComputePipelineConfig], &[Option<TokenStream>]),
+    runtime_targets: &HashMap<String, usize>,
+) -> TokenStream {
+    let render_tests = pipelines
+        .iter()
+        // There's no telling what layouts they'd be given.
+        .filter(|ExpandedPipeline { rp, .. }| rp.bind_group_layouts.is_empty())
+        .map(|ExpandedPipeline { rp, cfg, .. }| {
+            let ty = format_ident!("{}", rp.name);
+            let ty = match &rp.sample_counts {
+                Some(counts) => {
+                    let count = counts[0];
+                    quote! { #ty::<#count> }
+                }
+                None => quote! { #ty },
+            };
+            let mut args = Vec::new();
+            if let Some(layout) = &rp.layout {
+                let layout = pipeline_layout_type(layout);
+                args.push(quote! { &#layout::new(&device) });
+            }
+            match runtime_targets[&rp.name] {
+                0 => (),
+                1 => args.push(quote! { ::wgpu::TextureFormat::Rgba8Unorm }),
+                count => args.push(quote! { [::wgpu::TextureFormat::Rgba8Unorm; #count] }),
+            }
+            let test = format_ident!("{}", screaming_snake_case(&rp.name).to_lowercase());
+            let create = quote! { #ty::new(&device #(, #args)*) };
+            let features = quote! {
+                if !device.features().contains(#ty::REQUIRED_FEATURES) {
+                    return;
+                }
+            };
+            (test, &rp.name, features, create, cfg_attr(cfg))
+        });
+    let compute_tests = compute_configs.iter().zip(compute_cfgs).map(|(cp, cfg)| {
+        let ty = format_ident!("{}", cp.name);
+        let test = format_ident!("{}", screaming_snake_case(&cp.name).to_lowercase());
+        (
+            test,
+            &cp.name,
+            quote! {},
+            quote! { #ty::new(&device) },
+            cfg_attr(cfg),
+        )
+    });
+    let tests =
+        render_tests
+            .chain(compute_tests)
+            .map(|(test, name, features, create, cfg_attr)| {
+                let message = format!("{} failed validation: {{}}", name);
+                quote! {
+                    #cfg_attr
+                    #[test]
+                    fn #test() {
+                        let Some(device) = device() else {
+                            return;
+                        };
+                        #features
+                        device.push_error_scope(::wgpu::ErrorFilter::Validation);
+                        let _pipeline = #create;
+                        if let Some(error) = block_on(device.pop_error_scope()) {
+                            panic!(#message, error);
+                        }
+                    }
+                }
+            });
+
+    quote! {
+        #[cfg(test)]
+        mod pipeline_tests {
+            use super::*;
+
+            /// A device with every feature and limit of the default adapter,
+            /// if there is one.
+            fn device() -> Option<::wgpu::Device> {
+                let instance = ::wgpu::Instance::new(::wgpu::Backends::all());
+                let adapter = block_on(instance.request_adapter(&::wgpu::RequestAdapterOptions::default()))?;
+                let descriptor = ::wgpu::DeviceDescriptor {
+                    label: None,
+                    features: adapter.features(),
+                    limits: adapter.limits(),
+                };
+                let (device, _queue) = block_on(adapter.request_device(&descriptor, None)).ok()?;
+                Some(device)
+            }
+
+            /// Runs `future` on this thread, so the tests don't need an
+            /// executor.
+            fn block_on<F: ::std::future::Future>(future: F) -> F::Output {
+                struct ThreadWaker(::std::thread::Thread);
+
+                impl ::std::task::Wake for ThreadWaker {
+                    fn wake(self: ::std::sync::Arc<Self>) {
+                        self.0.unpark();
+                    }
+                }
+
+                let waker = ::std::task::Waker::from(::std::sync::Arc::new(ThreadWaker(::std::thread::current())));
+                let mut context = ::std::task::Context::from_waker(&waker);
+                let mut future = ::std::pin::pin!(future);
+                loop {
+                    match future.as_mut().poll(&mut context) {
+                        ::std::task::Poll::Ready(output) => return output,
+                        ::std::task::Poll::Pending => ::std::thread::park(),
+                    }
+                }
+            }
+
+            #(#tests)*
+        }
+    }
+}
+
+/// Emits `Pipelines::warmup`, which times creating each pipeline in
+/// `creates` after running `setup`, which creates the layouts they share.
+/// It takes the same `params` as `Pipelines::new`.
+fn gen_warmup(
+    params: &[TokenStream],
+    setup: &TokenStream,
+    creates: &[(TokenStream, &String, u32, TokenStream)],
+) -> TokenStream {
+    let timings = creates
+        .iter()
+        .map(|(cfg_attr, name, sample_count, create)| {
+            quote! {
+                #cfg_attr
+                pipelines.push(timed(#name, #sample_count, || #create));
+            }
+        });
+    quote! {
+        /// How long [Pipelines::warmup] took to create one pipeline.
+        #[derive(Debug, Clone, Copy)]
+        pub struct PipelineTiming {
+            pub name: &'static str,
+            /// Always 1 for compute pipelines.
+            pub sample_count: u32,
+            pub duration: ::std::time::Duration,
+        }
+
+        /// The result of [Pipelines::warmup], in the order the pipelines
+        /// were declared.
+        #[derive(Debug, Clone, Default)]
+        pub struct WarmupReport {
+            pub pipelines: Vec<PipelineTiming>,
+        }
+
+        impl WarmupReport {
+            /// How long creating every pipeline took.
+            pub fn total(&self) -> ::std::time::Duration {
+                self.pipelines.iter().map(|timing| timing.duration).sum()
+            }
+        }
+
+        impl Pipelines {
+            /// Creates every pipeline once, the way [Pipelines::new] does,
+            /// and reports how long each took. This is mostly shader
+            /// compilation, so it can be tracked to catch regressions. The
+            /// pipelines are dropped as soon as they've been timed.
+            #[allow(unused_variables, unused_mut, clippy::too_many_arguments)]
+            pub fn warmup(device: &::wgpu::Device #(, #params)*) -> WarmupReport {
+                fn timed<T>(
+                    name: &'static str,
+                    sample_count: u32,
+                    create: impl FnOnce() -> T,
+                ) -> PipelineTiming {
+                    let start = ::std::time::Instant::now();
+                    let pipeline = create();
+                    let duration = start.elapsed();
+                    drop(pipeline);
+                    PipelineTiming {
+                        name,
+                        sample_count,
+                        duration,
+                    }
+                }
+
+                #setup
+                let mut pipelines = Vec::new();
+                #(#timings)*
+                WarmupReport { pipelines }
+            }
+        }
+    }
+}
+
+/// Parses a `cfg` predicate such as `feature = "editor"` into tokens that can
+/// be placed inside `#[cfg(...)]`.
+fn parse_cfg(cfg: &str) -> Result<TokenStream> {
+    let meta = syn::parse_str::<syn::Meta>(cfg)
+        .map_err(|e| anyhow::anyhow!("Invalid cfg predicate {:?}: {}", cfg, e))?;
+    Ok(quote! { #meta })
+}
+
+fn gen_render_pipeline(
+    rp: &RenderPipelineConfig,
+    config: &PipelineConfig,
+    (shader_name, wgsl_source): (&str, &TokenStream),
+    (vs_entry, fs_entry): (&str, &str),
+    (bindings, vertex_inputs, outputs): (&reflect::Bindings, &[reflect::VertexInput], &[u32]),
+    (labels, spirv): (bool, bool),
+    cfg_attr: &TokenStream,
+) -> TokenStream {
+    let name = format_ident!("{}", rp.name);
+    let label = rp.label();
+    let module_label = rp.label.as_deref().unwrap_or(shader_name);
+    // Labels only help debugging, so they can be left out of release builds.
+    let (pipeline_label, module_label) = if labels {
+        (quote! { Some(#label) }, quote! { Some(#module_label) })
+    } else {
+        (quote! { None }, quote! { None })
+    };
+    let shader_ident = format_ident!("{}", shader_name);
+    let primitive = primitive_state(rp);
+    let depth_stencil = depth_stencil_state(rp);
+    // Without `vertex_buffers`, the shader's inputs are packed into one
+    // buffer.
+    let (vertex_struct, vertex_items, vertex_buffers) = match vertex_inputs {
+        [] => (quote! {}, quote! {}, vertex_buffers(rp, config)),
+        inputs => {
+            let vertex = vertex_struct_ident(rp);
+            (
+                gen_vertex_struct(rp, inputs, cfg_attr),
+                quote! {
+                    /// The attributes of the vertex buffer, reflected from the shader's inputs.
+                    pub const VERTEX_ATTRIBUTES: &'static [::wgpu::VertexAttribute] = #vertex::ATTRIBUTES;
+                    /// The layout of the vertex buffer, reflected from the shader's inputs.
+                    pub const VERTEX_LAYOUT: ::wgpu::VertexBufferLayout<'static> = #vertex::LAYOUT;
+                },
+                quote! { &[Self::VERTEX_LAYOUT] },
+            )
+        }
+    };
+    let target_count = runtime_target_count(rp, outputs);
+    let targets = match target_count {
+        0 => color_targets(rp),
+        1 => runtime_color_targets(quote! { [surface_format] }),
+        _ => runtime_color_targets(quote! { target_formats }),
+    };
+    let layouts = rp
+        .bind_group_layouts
+        .iter()
+        .map(|name| format_ident!("{}", name))
+        .collect::<Vec<_>>();
+    let (params, args) = constructor_params(rp, target_count);
+    // A shared layout's features are needed too.
+    let mut required_features = match &rp.layout {
+        Some(layout) => {
+            let ty = pipeline_layout_type(layout);
+            let features = required_features(rp, bindings);
+            quote! { #features.union(#ty::REQUIRED_FEATURES) }
+        }
+        None => required_features(rp, bindings),
+    };
+    let mut feature_names = required_feature_names(rp, bindings);
+    if spirv {
+        required_features =
+            quote! { #required_features.union(::wgpu::Features::SPIRV_SHADER_PASSTHROUGH) };
+        feature_names.push((
+            "SPIRV_SHADER_PASSTHROUGH",
+            "the shader is embedded as SPIR-V",
+        ));
+    }
+    let feature_docs = feature_names.into_iter().map(|(feature, reason)| {
+        let doc = format!(" Requires `wgpu::Features::{}` ({}).", feature, reason);
+        quote! { #[doc = #doc] }
+    });
+    let summary_docs = pipeline_summary(rp, (vs_entry, fs_entry), bindings, target_count)
+        .into_iter()
+        .map(|line| {
+            let doc = format!(" - {}", line);
+            quote! { #[doc = #doc] }
+        });
+    let features_message = format!(
+        "{} requires features missing from the device: {{:?}}",
+        rp.name
+    );
+
+    // Pipelines used with several sample counts take the count as a const
+    // generic so each variant is its own type. Any count that wasn't listed
+    // fails to compile when `SAMPLE_COUNT_IS_LISTED` is evaluated.
+    let (generics, type_args, multisample, sample_count_items, sample_count_check) =
+        match &rp.sample_counts {
+            Some(counts) => {
+                let message = format!("{} only supports the sample counts {:?}", rp.name, counts);
+                (
+                    quote! { <const SAMPLES: u32> },
+                    quote! { <SAMPLES> },
+                    multisample_state(quote! { SAMPLES }),
+                    quote! {
+                        /// The sample counts this pipeline can be created with.
+                        pub const SAMPLE_COUNTS: &'static [u32] = &[#(#counts),*];
+                        const SAMPLE_COUNT_IS_LISTED: () = assert!(
+                            #(SAMPLES == #counts)||*,
+                            #message,
+                        );
+                    },
+                    quote! {
+                        #[allow(clippy::let_unit_value)]
+                        let () = Self::SAMPLE_COUNT_IS_LISTED;
+                    },
+                )
+            }
+            None => (
+                quote! {},
+                quote! {},
+                multisample_state(quote! { 1 }),
+                quote! {},
+                quote! {},
+            ),
+        };
+    let texture_sample_types = texture_sample_types(bindings).map(|types| {
+        quote! {
+            /// `(group, binding, sample type)` of every texture the pipeline uses.
+            pub const TEXTURE_SAMPLE_TYPES: &'static [(u32, u32, ::wgpu::TextureSampleType)] = #types;
+        }
+    });
+    let sampler_binding_types = sampler_binding_types(bindings).map(|types| {
+        quote! {
+            /// `(group, binding, binding type)` of every sampler the pipeline uses.
+            pub const SAMPLER_BINDING_TYPES: &'static [(u32, u32, ::wgpu::SamplerBindingType)] = #types;
+        }
+    });
+
+    // Pipelines using presets create and keep their own bind group layouts,
+    // behind an `Arc` so pipelines with identical layouts can share them.
+    let layout_entries = pipeline_layout_entries(rp, config, bindings);
+    let owns_layouts = layout_entries.is_some();
+    let (layouts_field, layouts_items, pipeline_layouts) = match layout_entries {
+        Some(entries) => {
+            let group_label = match labels {
+                true => {
+                    let group_label = format!("{} group {{}}", label);
+                    quote! { Some(&format!(#group_label, group)) }
+                }
+                false => quote! { None },
+            };
+            (
+                quote! { bind_group_layouts: ::std::sync::Arc<[::wgpu::BindGroupLayout]>, },
+                quote! {
+                    /// The entries of each bind group layout, in group order.
+                    pub const BIND_GROUP_LAYOUT_ENTRIES: &'static [&'static [::wgpu::BindGroupLayoutEntry]] = #entries;
+
+                    /// Creates the bind group layouts described by [Self::BIND_GROUP_LAYOUT_ENTRIES].
+                    pub fn create_bind_group_layouts(device: &::wgpu::Device) -> Vec<::wgpu::BindGroupLayout> {
+                        Self::BIND_GROUP_LAYOUT_ENTRIES
+                            .iter()
+                            .enumerate()
+                            .map(|(group, entries)| {
+                                device.create_bind_group_layout(&::wgpu::BindGroupLayoutDescriptor {
+                                    label: #group_label,
+                                    entries,
+                                })
+                            })
+                            .collect()
+                    }
+
+                    /// The layouts to create this pipeline's bind groups with.
+                    pub fn bind_group_layouts(&self) -> &[::wgpu::BindGroupLayout] {
+                        &self.bind_group_layouts
+                    }
+                },
+                quote! { &bind_group_layouts.iter().collect::<Vec<_>>() },
+            )
+        }
+        None => (quote! {}, quote! {}, quote! { &[#(#layouts),*] }),
+    };
+    let (push_constant_items, push_constant_ranges) = match push_constant_ranges(bindings) {
+        Some(ranges) => {
+            let pc = bindings.push_constants.as_ref().unwrap();
+            let setter = pc.struct_name.as_ref().map(|ty| {
+                let ty = format_ident!("{}", ty);
+                let stages = shader_stages(&pc.stages);
+                quote! {
+                    /// Sets the push constants read by the pipeline's shader.
+                    pub fn set_push_constants(render_pass: &mut ::wgpu::RenderPass<'_>, data: &#ty) {
+                        render_pass.set_push_constants(#stages, 0, data.as_bytes());
+                    }
+                }
+            });
+            (
+                quote! {
+                    /// The push constants the shader uses, which the pipeline's layout is created with.
+                    pub const PUSH_CONSTANT_RANGES: &'static [::wgpu::PushConstantRange] = #ranges;
+                    #setter
+                },
+                quote! { Self::PUSH_CONSTANT_RANGES },
+            )
+        }
+        None => (quote! {}, quote! { &[] }),
+    };
+    let pipeline_layout = match (&rp.layout, owns_layouts) {
+        (Some(_), _) => quote! { layout.layout() },
+        (None, true) => quote! {
+            device.create_pipeline_layout(&::wgpu::PipelineLayoutDescriptor {
+                label: #pipeline_label,
+                bind_group_layouts: #pipeline_layouts,
+                push_constant_ranges: #push_constant_ranges,
+            })
+        },
+        (None, false) => quote! {
+            &device.create_pipeline_layout(&::wgpu::PipelineLayoutDescriptor {
+                label: #pipeline_label,
+                bind_group_layouts: #pipeline_layouts,
+                push_constant_ranges: #push_constant_ranges,
+            })
+        },
+    };
+    let description = rp.description.as_ref().map(|description| {
+        let description = format!(" {}", description);
+        quote! { #[doc = #description] }
+    });
+    let metadata = metadata(rp).into_iter().map(|(name, ty, value)| {
+        let ident = format_ident!("{}", name);
+        quote! { pub const #ident: #ty = #value; }
+    });
+    let constants = override_constants(rp).map(|constants| {
+        quote! {
+            /// The values baked into the shader's `override` constants.
+            pub const CONSTANTS: &'static [(&'static str, f64)] = #constants;
+        }
+    });
+
+    let group_bindings = gen_group_bindings(rp, bindings, labels, cfg_attr);
+    let dynamic_offset_setters = dynamic_offset_setters(rp, bindings);
+    let draw_helpers = draw_helpers(rp, vertex_inputs);
+    let begin_pass = gen_begin_pass(rp, outputs, &pipeline_label);
+    let handle_label = if labels { rp.label() } else { &rp.name };
+
+    let create_module = match spirv {
+        // naga generated and validated the SPIR-V, so it's passed through.
+        true => quote! {
+            let module = unsafe {
+                device.create_shader_module_spirv(&::wgpu::ShaderModuleDescriptorSpirV {
+                    label: #module_label,
+                    source: ::std::borrow::Cow::Borrowed(#shader_ident),
+                })
+            };
+        },
+        false => quote! {
+            let module = device.create_shader_module(::wgpu::ShaderModuleDescriptor {
+                label: #module_label,
+                source: ::wgpu::ShaderSource::Wgsl(#wgsl_source),
+            });
+        },
+    };
+    // Pipelines that own their layouts are built from a pipeline layout
+    // passed in, which can be a shared one.
+    let build = quote! {
+        #sample_count_check
+        debug_assert!(
+            device.features().contains(Self::REQUIRED_FEATURES),
+            #features_message,
+            Self::REQUIRED_FEATURES - device.features(),
+        );
+        #create_module
+        device.create_render_pipeline(&::wgpu::RenderPipelineDescriptor {
+            label: #pipeline_label,
+            layout: Some(pipeline_layout),
+            vertex: ::wgpu::VertexState {
+                module: &module,
+                entry_point: #vs_entry,
+                buffers: #vertex_buffers,
+            },
+            primitive: #primitive,
+            depth_stencil: #depth_stencil,
+            multisample: #multisample,
+            fragment: Some(::wgpu::FragmentState {
+                module: &module,
+                entry_point: #fs_entry,
+                targets: #targets,
+            }),
+            // Might want to support this
+            multiview: None,
+        })
+    };
+    let (constructors, create_fn) = match owns_layouts {
+        true => (
+            quote! {
+                pub fn new(device: &::wgpu::Device #(, #params)*) -> Self {
+                    let bind_group_layouts: ::std::sync::Arc<[::wgpu::BindGroupLayout]> =
+                        Self::create_bind_group_layouts(device).into();
+                    let pipeline_layout = Self::create_pipeline_layout(device, &bind_group_layouts);
+                    Self {
+                        render_pipeline: Self::create_render_pipeline_with_layout(device, &pipeline_layout #(, #args)*),
+                        bind_group_layouts,
+                    }
+                }
+            },
+            quote! {
+                fn create_render_pipeline(
+                    device: &::wgpu::Device
+                    #(, #params)*
+                ) -> ::wgpu::RenderPipeline {
+                    let bind_group_layouts = Self::create_bind_group_layouts(device);
+                    let pipeline_layout = Self::create_pipeline_layout(device, &bind_group_layouts);
+                    Self::create_render_pipeline_with_layout(device, &pipeline_layout #(, #args)*)
+                }
+
+                fn create_pipeline_layout(
+                    device: &::wgpu::Device,
+                    bind_group_layouts: &[::wgpu::BindGroupLayout],
+                ) -> ::wgpu::PipelineLayout {
+                    #pipeline_layout
+                }
+
+                fn create_render_pipeline_with_layout(
+                    device: &::wgpu::Device,
+                    pipeline_layout: &::wgpu::PipelineLayout
+                    #(, #params)*
+                ) -> ::wgpu::RenderPipeline {
+                    #build
+                }
+            },
+        ),
+        false => (
+            quote! {
+                pub fn new(device: &::wgpu::Device #(, #params)*) -> Self {
+                    Self {
+                        render_pipeline: Self::create_render_pipeline(device #(, #args)*),
+                    }
+                }
+            },
+            quote! {
+                fn create_render_pipeline(
+                    device: &::wgpu::Device
+                    #(, #params)*
+                ) -> ::wgpu::RenderPipeline {
+                    let pipeline_layout = #pipeline_layout;
+                    #build
+                }
+            },
+        ),
+    };
+
+    quote! {
+        #vertex_struct
+        #group_bindings
+
+        #cfg_attr
+        #description
+        #(#feature_docs)*
+        #[doc = ""]
+        #(#summary_docs)*
+        pub struct #name #generics {
+            render_pipeline: ::wgpu::RenderPipeline,
+            #layouts_field
+        }
+
+        #cfg_attr
+        impl #generics #name #type_args {
+            /// The `wgpu::Features` the device must have for this pipeline to be created.
+            pub const REQUIRED_FEATURES: ::wgpu::Features = #required_features;
+            #(#metadata)*
+            #constants
+            #sample_count_items
+            #push_constant_items
+            #vertex_items
+            #texture_sample_types
+            #sampler_binding_types
+            #layouts_items
+            #dynamic_offset_setters
+
+            #constructors
+
+            /// The wrapped pipeline, for `RenderPass::set_pipeline`.
+            pub fn pipeline(&self) -> &::wgpu::RenderPipeline {
+                &self.render_pipeline
+            }
+
+            #draw_helpers
+            #begin_pass
+
+            #create_fn
+        }
+
+        #cfg_attr
+        impl #generics ::std::ops::Deref for #name #type_args {
+            type Target = ::wgpu::RenderPipeline;
+
+            fn deref(&self) -> &Self::Target {
+                &self.render_pipeline
+            }
+        }
+
+        #cfg_attr
+        impl #generics RenderPipelineHandle for #name #type_args {
+            fn label() -> &'static str {
+                #handle_label
+            }
+
+            fn raw(&self) -> &::wgpu::RenderPipeline {
+                &self.render_pipeline
+            }
+        }
+    }
+}
+
+/// Emits `RenderPipelineHandle`, which every generated render pipeline
+/// implements.
+fn gen_render_pipeline_handle() -> TokenStream {
+    quote! {
+        /// Implemented by every generated render pipeline, so pipelines of
+        /// different types can be stored together as `dyn RenderPipelineHandle`.
+        pub trait RenderPipelineHandle {
+            /// The pipeline's label, or its name if labels were turned off.
+            fn label() -> &'static str
+            where
+                Self: Sized;
+
+            /// The wrapped pipeline.
+            fn raw(&self) -> &::wgpu::RenderPipeline;
+        }
+    }
+}
+
+/// Emits the pieces of a pipeline as free constants prefixed with the
+/// pipeline's name in `SCREAMING_SNAKE_CASE`, eg. `TEXTURED_PIPELINE_VS_ENTRY`.
+fn gen_render_constants(
+    rp: &RenderPipelineConfig,
+    config: &PipelineConfig,
+    shader_ident: &proc_macro2::Ident,
+    (vs_entry, fs_entry): (&str, &str),
+    (bindings, vertex_inputs, _): (&reflect::Bindings, &[reflect::VertexInput], &[u32]),
+    cfg_attr: &TokenStream,
+) -> TokenStream {
+    let prefix = screaming_snake_case(&rp.name);
+    let label_ident = format_ident!("{}_LABEL", prefix);
+    let shader_src_ident = format_ident!("{}_SHADER", prefix);
+    let vs_entry_ident = format_ident!("{}_VS_ENTRY", prefix);
+    let fs_entry_ident = format_ident!("{}_FS_ENTRY", prefix);
+    let primitive_ident = format_ident!("{}_PRIMITIVE", prefix);
+    let depth_stencil_ident = format_ident!("{}_DEPTH_STENCIL", prefix);
+    let multisample_ident = format_ident!("{}_MULTISAMPLE", prefix);
+    let sample_counts_ident = format_ident!("{}_SAMPLE_COUNTS", prefix);
+    let bind_group_layouts_ident = format_ident!("{}_BIND_GROUP_LAYOUTS", prefix);
+    let texture_sample_types_ident = format_ident!("{}_TEXTURE_SAMPLE_TYPES", prefix);
+    let sampler_binding_types_ident = format_ident!("{}_SAMPLER_BINDING_TYPES", prefix);
+    let required_features_ident = format_ident!("{}_REQUIRED_FEATURES", prefix);
+    let label = rp.label();
+    let primitive = primitive_state(rp);
+    let depth_stencil = depth_stencil_state(rp);
+    // With several sample counts the multisample state uses the first one;
+    // callers pick the count they need from `_SAMPLE_COUNTS`.
+    let (multisample, sample_counts) = match &rp.sample_counts {
+        Some(counts) => {
+            let first = counts[0];
+            (
+                multisample_state(quote! { #first }),
+                quote! { #cfg_attr pub const #sample_counts_ident: &[u32] = &[#(#counts),*]; },
+            )
+        }
+        None => (multisample_state(quote! { 1 }), quote! {}),
+    };
+    let bind_group_layouts = match &rp.bind_group_layouts[..] {
+        [] => quote! {},
+        names => {
+            quote! { #cfg_attr pub const #bind_group_layouts_ident: &[&str] = &[#(#names),*]; }
+        }
+    };
+    let pipeline_layout = rp.layout.as_ref().map(|layout| {
+        let ident = format_ident!("{}_PIPELINE_LAYOUT", prefix);
+        quote! { #cfg_attr pub const #ident: &str = #layout; }
+    });
+    let texture_sample_types = texture_sample_types(bindings).map(|types| {
+        quote! {
+            #cfg_attr pub const #texture_sample_types_ident: &[(u32, u32, ::wgpu::TextureSampleType)] = #types;
+        }
+    });
+    let sampler_binding_types = sampler_binding_types(bindings).map(|types| {
+        quote! {
+            #cfg_attr pub const #sampler_binding_types_ident: &[(u32, u32, ::wgpu::SamplerBindingType)] = #types;
+        }
+    });
+    let layout_entries = pipeline_layout_entries(rp, config, bindings).map(|entries| {
+        let ident = format_ident!("{}_BIND_GROUP_LAYOUT_ENTRIES", prefix);
+        quote! { #cfg_attr pub const #ident: &[&[::wgpu::BindGroupLayoutEntry]] = #entries; }
+    });
+    let required_features = required_features(rp, bindings);
+    let push_constant_ranges = push_constant_ranges(bindings).map(|ranges| {
+        let ident = format_ident!("{}_PUSH_CONSTANT_RANGES", prefix);
+        quote! { #cfg_attr pub const #ident: &[::wgpu::PushConstantRange] = #ranges; }
+    });
+    let targets = (!(rp.targets.is_empty() && rp.target_formats.is_empty())).then(|| {
+        let ident = format_ident!("{}_TARGETS", prefix);
+        let targets = color_targets(rp);
+        quote! { #cfg_attr pub const #ident: &[Option<::wgpu::ColorTargetState>] = #targets; }
+    });
+    let buffers_ident = format_ident!("{}_VERTEX_BUFFERS", prefix);
+    let vertex_buffers = match (&rp.vertex_buffers[..], vertex_inputs) {
+        ([], []) => quote! {},
+        ([], inputs) => {
+            let attributes_ident = format_ident!("{}_VERTEX_ATTRIBUTES", prefix);
+            let layout_ident = format_ident!("{}_VERTEX_LAYOUT", prefix);
+            let vertex = vertex_struct_ident(rp);
+            let vertex_struct = gen_vertex_struct(rp, inputs, cfg_attr);
+            quote! {
+                #vertex_struct
+                #cfg_attr pub const #attributes_ident: &[::wgpu::VertexAttribute] = #vertex::ATTRIBUTES;
+                #cfg_attr pub const #layout_ident: ::wgpu::VertexBufferLayout<'static> = #vertex::LAYOUT;
+                #cfg_attr pub const #buffers_ident: &[::wgpu::VertexBufferLayout] = &[#layout_ident];
+            }
+        }
+        _ => {
+            let buffers = vertex_buffers(rp, config);
+            quote! { #cfg_attr pub const #buffers_ident: &[::wgpu::VertexBufferLayout] = #buffers; }
+        }
+    };
+    let metadata = metadata(rp).into_iter().map(|(name, ty, value)| {
+        let ident = format_ident!("{}_{}", prefix, name);
+        quote! { #cfg_attr pub const #ident: #ty = #value; }
+    });
+    let constants = override_constants(rp).map(|constants| {
+        let ident = format_ident!("{}_CONSTANTS", prefix);
+        quote! { #cfg_attr pub const #ident: &[(&str, f64)] = #constants; }
+    });
+
+    quote! {
+        #cfg_attr pub const #label_ident: &str = #label;
+        #cfg_attr pub const #shader_src_ident: &str = #shader_ident;
+        #cfg_attr pub const #vs_entry_ident: &str = #vs_entry;
+        #cfg_attr pub const #fs_entry_ident: &str = #fs_entry;
+        #cfg_attr pub const #primitive_ident: ::wgpu::PrimitiveState = #primitive;
+        #cfg_attr pub const #depth_stencil_ident: Option<::wgpu::DepthStencilState> = #depth_stencil;
+        #cfg_attr pub const #multisample_ident: ::wgpu::MultisampleState = #multisample;
+        #sample_counts
+        #bind_group_layouts
+        #pipeline_layout
+        #layout_entries
+        #vertex_buffers
+        #targets
+        #texture_sample_types
+        #sampler_binding_types
+        #cfg_attr pub const #required_features_ident: ::wgpu::Features = #required_features;
+        #push_constant_ranges
+        #constants
+        #(#metadata)*
+    }
+}
+
+fn gen_compute_pipeline(
+    cp: &ComputePipelineConfig,
+    (shader_name, wgsl_source): (&str, &TokenStream),
+    workgroup_size: [u32; 3],
+    labels: bool,
+    cfg_attr: &TokenStream,
+) -> TokenStream {
+    let name = format_ident!("{}", cp.name);
+    let entry = &cp.entry;
+    let (pipeline_label, module_label) = if labels {
+        let label = &cp.name;
+        (quote! { Some(#label) }, quote! { Some(#shader_name) })
+    } else {
+        (quote! { None }, quote! { None })
+    };
+
+    quote! {
+        #cfg_attr
+        pub struct #name {
+            compute_pipeline: ::wgpu::ComputePipeline,
+        }
+
+        #cfg_attr
+        impl #name {
+            /// The entry point's `@workgroup_size`, for working out how many workgroups to dispatch.
+            pub const WORKGROUP_SIZE: [u32; 3] = [#(#workgroup_size),*];
+
+            pub fn new(device: &::wgpu::Device) -> Self {
+                Self {
+                    compute_pipeline: Self::create_compute_pipeline(device),
+                }
+            }
+
+            /// The wrapped pipeline, for `ComputePass::set_pipeline`.
+            pub fn pipeline(&self) -> &::wgpu::ComputePipeline {
+                &self.compute_pipeline
+            }
+
+            fn create_compute_pipeline(device: &::wgpu::Device) -> ::wgpu::ComputePipeline {
+                let module = device.create_shader_module(::wgpu::ShaderModuleDescriptor {
+                    label: #module_label,
+                    source: ::wgpu::ShaderSource::Wgsl(#wgsl_source),
+                });
+                // The layout is derived from the shader.
+                device.create_compute_pipeline(&::wgpu::ComputePipelineDescriptor {
+                    label: #pipeline_label,
+                    layout: None,
+                    module: &module,
+                    entry_point: #entry,
+                })
+            }
+        }
+
+        #cfg_attr
+        impl ::std::ops::Deref for #name {
+            type Target = ::wgpu::ComputePipeline;
+
+            fn deref(&self) -> &Self::Target {
+                &self.compute_pipeline
+            }
+        }
+    }
+}
+
+/// Emits a compute pipeline's label, shader source and entry point as
+/// prefixed free constants, like [gen_render_constants].
+fn gen_compute_constants(
+    cp: &ComputePipelineConfig,
+    shader_ident: &proc_macro2::Ident,
+    workgroup_size: [u32; 3],
+    cfg_attr: &TokenStream,
+) -> TokenStream {
+    let prefix = screaming_snake_case(&cp.name);
+    let workgroup_size_ident = format_ident!("{}_WORKGROUP_SIZE", prefix);
+    let label_ident = format_ident!("{}_LABEL", prefix);
+    let shader_src_ident = format_ident!("{}_SHADER", prefix);
+    let cs_entry_ident = format_ident!("{}_CS_ENTRY", prefix);
+    let label = &cp.name;
+    let entry = &cp.entry;
+
+    quote! {
+        #cfg_attr pub const #label_ident: &str = #label;
+        #cfg_attr pub const #shader_src_ident: &str = #shader_ident;
+        #cfg_attr pub const #cs_entry_ident: &str = #entry;
+        #cfg_attr pub const #workgroup_size_ident: [u32; 3] = [#(#workgroup_size),*];
+    }
+}
+
+/// `{NAME}_SOURCE_LOCATION`, with where the pipeline called `name` is
+/// declared. Pipelines from [PipelineConfig::from_src] only have a line.
+fn gen_source_location(
+    name: &str,
+    declared_at: &Option<IncludeSite>,
+    cfg_attr: &TokenStream,
+) -> TokenStream {
+    let ident = format_ident!("{}_SOURCE_LOCATION", screaming_snake_case(name));
+    let location = match declared_at {
+        Some(site) => {
+            let cwd = std::env::current_dir().ok();
+            let path = site.path.as_deref().map(|path| {
+                cwd.as_deref()
+                    .and_then(|cwd| path.strip_prefix(cwd).ok())
+                    .unwrap_or(path)
+                    .to_owned()
+            });
+            IncludeSite {
+                path,
+                line: site.line,
+            }
+            .to_string()
+        }
+        None => "unknown".to_owned(),
+    };
+    quote! {
+        #[doc(hidden)]
+        #cfg_attr pub const #ident: &str = #location;
+    }
+}
+
+/// `&[&[wgpu::BindGroupLayoutEntry]]` for a pipeline's or layout's
+/// `bind_groups`, or `None` if there aren't any.
+fn bind_group_layout_entries(
+    groups: &[BindGroupRef],
+    config: &PipelineConfig,
+) -> Option<TokenStream> {
+    if groups.is_empty() {
+        return None;
+    }
+    // Presets and groups are checked when the config is parsed.
+    let groups = groups.iter().map(|group| match group {
+        BindGroupRef::Named(name) => {
+            let group = config
+                .bind_group(name)
+                .expect("bind groups are resolved while parsing");
+            layout_entries(&group.entries.iter().collect::<Vec<_>>())
+        }
+        BindGroupRef::Presets(names) => layout_entries(
+            &names
+                .iter()
+                .map(|name| {
+                    config
+                        .binding_preset(name)
+                        .expect("binding presets are resolved while parsing")
+                })
+                .collect::<Vec<_>>(),
+        ),
+    });
+    Some(quote! { &[#(#groups),*] })
+}
+
+/// `&[&[wgpu::BindGroupLayoutEntry]]` for the bind group layouts a pipeline
+/// creates itself: those of its `bind_groups`, or if it's given no layouts
+/// at all, those reflected from its shader. `None` if it doesn't create any.
+fn pipeline_layout_entries(
+    rp: &RenderPipelineConfig,
+    config: &PipelineConfig,
+    bindings: &reflect::Bindings,
+) -> Option<TokenStream> {
+    if !rp.bind_groups.is_empty() {
+        return bind_group_layout_entries(&rp.bind_groups, config);
+    }
+    if !rp.bind_group_layouts.is_empty() || rp.layout.is_some() {
+        return None;
+    }
+    // Groups the shader skips are left empty.
+    let last = bindings.entries.iter().map(|entry| entry.group).max()?;
+    let groups = (0..=last).map(|group| {
+        let entries = bindings
+            .entries
+            .iter()
+            .filter(|entry| entry.group == group)
+            .map(|entry| {
+                let binding = entry.binding;
+                let visibility = shader_stages(&entry.visibility);
+                let ty = &entry.ty;
+                let count = match entry.count {
+                    Some(count) => quote! { ::std::num::NonZeroU32::new(#count) },
+                    None => quote! { None },
+                };
+                quote! {
+                    ::wgpu::BindGroupLayoutEntry {
+                        binding: #binding,
+                        visibility: #visibility,
+                        ty: #ty,
+                        count: #count,
+                    }
+                }
+            });
+        quote! { &[#(#entries),*] }
+    });
+    Some(quote! { &[#(#groups),*] })
+}
+
+/// Emits `{Pipeline}Group{N}Bindings` for each bind group reflected from a
+/// pipeline's shader, holding a resource for each of its entries so bind
+/// groups can only be created from what the shader expects. Pipelines given
+/// their layouts, or whose groups come from presets, use those instead.
+fn gen_group_bindings(
+    rp: &RenderPipelineConfig,
+    bindings: &reflect::Bindings,
+    labels: bool,
+    cfg_attr: &TokenStream,
+) -> TokenStream {
+    if !rp.bind_groups.is_empty() || !rp.bind_group_layouts.is_empty() || rp.layout.is_some() {
+        return quote! {};
+    }
+    let mut groups = bindings
+        .entries
+        .iter()
+        .map(|entry| entry.group)
+        .collect::<Vec<_>>();
+    groups.dedup();
+    let structs = groups.into_iter().map(|group| {
+        let ident = format_ident!("{}Group{}Bindings", rp.name, group);
+        let entries = bindings
+            .entries
+            .iter()
+            .filter(|entry| entry.group == group)
+            .collect::<Vec<_>>();
+        let names = entries
+            .iter()
+            .map(|entry| layout::field_ident(&entry.name))
+            .collect::<Vec<_>>();
+        let types = entries.iter().map(|entry| {
+            let ty = match entry.ty {
+                reflect::EntryType::Buffer { .. } if entry.count.is_some() => {
+                    quote! { ::wgpu::BufferBinding<'a> }
+                }
+                reflect::EntryType::Buffer { .. } => quote! { ::wgpu::Buffer },
+                reflect::EntryType::Texture { .. } | reflect::EntryType::StorageTexture { .. } => {
+                    quote! { ::wgpu::TextureView }
+                }
+                reflect::EntryType::Sampler(_) => quote! { ::wgpu::Sampler },
+            };
+            match entry.count {
+                Some(_) => quote! { &'a [&'a #ty] },
+                None => quote! { &'a #ty },
+            }
+        });
+        let resources = entries.iter().zip(&names).map(|(entry, name)| {
+            let binding = entry.binding;
+            let resource = match (&entry.ty, entry.count) {
+                // Each offset binds one struct's worth of the buffer.
+                (
+                    reflect::EntryType::Buffer {
+                        dynamic_size: Some(size),
+                        ..
+                    },
+                    None,
+                ) => {
+                    let size = *size as u64;
+                    quote! {
+                        ::wgpu::BindingResource::Buffer(::wgpu::BufferBinding {
+                            buffer: self.#name,
+                            offset: 0,
+                            size: ::std::num::NonZeroU64::new(#size),
+                        })
+                    }
+                }
+                (reflect::EntryType::Buffer { .. }, None) => {
+                    quote! { self.#name.as_entire_binding() }
+                }
+                (reflect::EntryType::Buffer { .. }, Some(_)) => {
+                    quote! { ::wgpu::BindingResource::BufferArray(self.#name) }
+                }
+                (reflect::EntryType::Sampler(_), None) => {
+                    quote! { ::wgpu::BindingResource::Sampler(self.#name) }
+                }
+                (reflect::EntryType::Sampler(_), Some(_)) => {
+                    quote! { ::wgpu::BindingResource::SamplerArray(self.#name) }
+                }
+                (_, None) => quote! { ::wgpu::BindingResource::TextureView(self.#name) },
+                (_, Some(_)) => quote! { ::wgpu::BindingResource::TextureViewArray(self.#name) },
+            };
+            quote! {
+                ::wgpu::BindGroupEntry {
+                    binding: #binding,
+                    resource: #resource,
+                }
+            }
+        });
+        let label = match labels {
+            true => {
+                let label = format!("{} group {}", rp.label(), group);
+                quote! { Some(#label) }
+            }
+            false => quote! { None },
+        };
+        let doc = format!(" The resources bound to group {} of {}.", group, rp.name);
+        let layout_doc = format!(
+            " Creates the bind group, with `layout` being `bind_group_layouts()[{}]` of {}.",
+            group, rp.name
+        );
+        quote! {
+            #[doc = #doc]
+            #cfg_attr
+            #[derive(Debug, Clone, Copy)]
+            pub struct #ident<'a> {
+                #(pub #names: #types,)*
+            }
+
+            #cfg_attr
+            impl #ident<'_> {
+                #[doc = #layout_doc]
+                pub fn create_bind_group(
+                    &self,
+                    device: &::wgpu::Device,
+                    layout: &::wgpu::BindGroupLayout,
+                ) -> ::wgpu::BindGroup {
+                    device.create_bind_group(&::wgpu::BindGroupDescriptor {
+                        label: #label,
+                        layout,
+                        entries: &[#(#resources),*],
+                    })
+                }
+            }
+        }
+    });
+    quote! { #(#structs)* }
+}
+
+/// The lines of the list at the end of a render pipeline's docs, which sum
+/// up what it was generated from so its API can be understood without
+/// finding its config.
+fn pipeline_summary(
+    rp: &RenderPipelineConfig,
+    (vs_entry, fs_entry): (&str, &str),
+    bindings: &reflect::Bindings,
+    target_count: usize,
+) -> Vec<String> {
+    let names = |names: &[String]| {
+        names
+            .iter()
+            .map(|name| format!("`{}`", name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut lines = vec![
+        format!(
+            "Shader: `{}`, entry points `{}` and `{}`",
+            rp.path, vs_entry, fs_entry
+        ),
+        format!("Topology: `{}`", rp.primitive.topology),
+    ];
+    lines.push(match target_count {
+        0 if rp.targets.is_empty() => format!("Color targets: {}", names(&rp.target_formats)),
+        0 => format!("Color targets: {}", names(&rp.targets)),
+        1 => "Color target: the `surface_format` passed to `new`".to_owned(),
+        count => format!(
+            "Color targets: the {} `target_formats` passed to `new`",
+            count
+        ),
+    });
+    if let Some(ds) = &rp.depth_stencil {
+        lines.push(format!(
+            "Depth: `{}`, compared with `{}`",
+            ds.format, ds.depth_compare
+        ));
+    }
+    if let Some(layout) = &rp.layout {
+        lines.push(format!("Pipeline layout: `{}`", layout));
+    } else if !rp.bind_group_layouts.is_empty() {
+        lines.push(format!(
+            "Bind group layouts passed to `new`: {}",
+            names(&rp.bind_group_layouts)
+        ));
+    } else if !rp.bind_groups.is_empty() {
+        for (group, bind_group) in rp.bind_groups.iter().enumerate() {
+            lines.push(match bind_group {
+                BindGroupRef::Named(name) => format!("Bind group {}: `{}`", group, name),
+                BindGroupRef::Presets(presets) => {
+                    format!("Bind group {}: {}", group, names(presets))
+                }
+            });
+        }
+    } else {
+        let mut groups = bindings
+            .entries
+            .iter()
+            .map(|entry| entry.group)
+            .collect::<Vec<_>>();
+        groups.dedup();
+        for group in groups {
+            let entries = bindings
+                .entries
+                .iter()
+                .filter(|entry| entry.group == group)
+                .map(|entry| format!("`{}` ({})", entry.name, manifest::entry_kind(&entry.ty)));
+            lines.push(format!(
+                "Bind group {}: {}",
+                group,
+                entries.collect::<Vec<_>>().join(", ")
+            ));
+        }
+    }
+    lines
+}
+
+/// Emits `bind`, `draw` and `draw_indexed`, which set the pipeline and take
+/// a buffer for each of its vertex buffer layouts, in slot order.
+fn draw_helpers(rp: &RenderPipelineConfig, vertex_inputs: &[reflect::VertexInput]) -> TokenStream {
+    let count = match vertex_inputs {
+        [] => rp.vertex_buffers.len(),
+        _ => 1,
+    };
+    let (vertex_params, set_vertex_buffers) = match count {
+        0 => (quote! {}, quote! {}),
+        1 => (
+            quote! { vertex_buffer: ::wgpu::BufferSlice<'a>, },
+            quote! { render_pass.set_vertex_buffer(0, vertex_buffer); },
+        ),
+        _ => (
+            quote! { vertex_buffers: [::wgpu::BufferSlice<'a>; #count], },
+            quote! {
+                for (slot, buffer) in vertex_buffers.into_iter().enumerate() {
+                    render_pass.set_vertex_buffer(slot as u32, buffer);
+                }
+            },
+        ),
+    };
+    // Strips are cut where an index is the format's maximum, so indices have
+    // to be in the format the pipeline was created with.
+    let index_format_check = rp.primitive.strip_index_format.as_ref().map(|format| {
+        let format = format_ident!("{}", format);
+        let message = format!(
+            "{} is created with a strip index format of {}",
+            rp.name, format
+        );
+        quote! { debug_assert_eq!(::wgpu::IndexFormat::#format, index_format, #message); }
+    });
+
+    quote! {
+        /// Sets the pipeline on `render_pass`.
+        pub fn bind<'a>(&'a self, render_pass: &mut ::wgpu::RenderPass<'a>) {
+            render_pass.set_pipeline(&self.render_pipeline);
+        }
+
+        /// Sets the pipeline and its vertex buffers, then draws `vertices`.
+        pub fn draw<'a>(
+            &'a self,
+            render_pass: &mut ::wgpu::RenderPass<'a>,
+            #vertex_params
+            vertices: ::std::ops::Range<u32>,
+            instances: ::std::ops::Range<u32>,
+        ) {
+            self.bind(render_pass);
+            #set_vertex_buffers
+            render_pass.draw(vertices, instances);
+        }
+
+        /// Sets the pipeline, its vertex buffers and `index_buffer`, then
+        /// draws `indices`.
+        pub fn draw_indexed<'a>(
+            &'a self,
+            render_pass: &mut ::wgpu::RenderPass<'a>,
+            #vertex_params
+            index_buffer: ::wgpu::BufferSlice<'a>,
+            index_format: ::wgpu::IndexFormat,
+            indices: ::std::ops::Range<u32>,
+            instances: ::std::ops::Range<u32>,
+        ) {
+            #index_format_check
+            self.bind(render_pass);
+            #set_vertex_buffers
+            render_pass.set_index_buffer(index_buffer, index_format);
+            render_pass.draw_indexed(indices, 0, instances);
+        }
+    }
+}
+
+/// Emits `begin_pass`, which begins a render pass with an attachment for
+/// each of the pipeline's color targets and its depth buffer, so the pass
+/// can't be begun with a different number of them.
+fn gen_begin_pass(rp: &RenderPipelineConfig, outputs: &[u32], label: &TokenStream) -> TokenStream {
+    let count = match rp.targets.len() + rp.target_formats.len() {
+        0 => color_target_count(outputs),
+        count => count,
+    };
+    // Multisampled views are resolved into single-sampled ones, if given.
+    let (resolve_param, resolve_target) = match rp.sample_counts {
+        Some(_) => (
+            quote! { resolve_targets: [Option<&'a ::wgpu::TextureView>; #count], },
+            quote! { resolve_targets[i] },
+        ),
+        None => (quote! {}, quote! { None }),
+    };
+    let (depth_params, depth_attachment, depth_doc) = match &rp.depth_stencil {
+        Some(ds) => {
+            let stencil_ops = match ds.format.contains("Stencil") {
+                true => {
+                    quote! { Some(::wgpu::Operations { load: ::wgpu::LoadOp::Load, store: true }) }
+                }
+                false => quote! { None },
+            };
+            let doc = format!(" and `depth_view`, which must be {}", ds.format);
+            (
+                quote! {
+                    depth_view: &'a ::wgpu::TextureView,
+                    depth_load: ::wgpu::LoadOp<f32>,
+                },
+                quote! {
+                    Some(::wgpu::RenderPassDepthStencilAttachment {
+                        view: depth_view,
+                        depth_ops: Some(::wgpu::Operations { load: depth_load, store: true }),
+                        stencil_ops: #stencil_ops,
+                    })
+                },
+                doc,
+            )
+        }
+        None => (quote! {}, quote! { None }, String::new()),
+    };
+    let doc = format!(
+        " Begins a render pass drawing to `color_views`, one for each of the pipeline's color targets in order{}. \
+        Every color attachment is loaded with `load`.",
+        depth_doc
+    );
+
+    quote! {
+        #[doc = #doc]
+        pub fn begin_pass<'a>(
+            encoder: &'a mut ::wgpu::CommandEncoder,
+            color_views: [&'a ::wgpu::TextureView; #count],
+            #resolve_param
+            load: ::wgpu::LoadOp<::wgpu::Color>,
+            #depth_params
+        ) -> ::wgpu::RenderPass<'a> {
+            let color_attachments: [_; #count] = ::std::array::from_fn(|i| {
+                Some(::wgpu::RenderPassColorAttachment {
+                    view: color_views[i],
+                    resolve_target: #resolve_target,
+                    ops: ::wgpu::Operations { load, store: true },
+                })
+            });
+            encoder.begin_render_pass(&::wgpu::RenderPassDescriptor {
+                label: #label,
+                color_attachments: &color_attachments,
+                depth_stencil_attachment: #depth_attachment,
+            })
+        }
+    }
+}
+
+/// Emits `set_bind_group_{N}` for each reflected group with uniform buffers
+/// bound at dynamic offsets, which takes an offset for each of them and
+/// checks it's aligned before setting the group.
+fn dynamic_offset_setters(rp: &RenderPipelineConfig, bindings: &reflect::Bindings) -> TokenStream {
+    if !rp.bind_groups.is_empty() || !rp.bind_group_layouts.is_empty() || rp.layout.is_some() {
+        return quote! {};
+    }
+    let mut groups = bindings
+        .entries
+        .iter()
+        .map(|entry| entry.group)
+        .collect::<Vec<_>>();
+    groups.dedup();
+    let setters = groups.into_iter().filter_map(|group| {
+        // Offsets are given in binding order, which the entries are in.
+        let dynamic = bindings
+            .entries
+            .iter()
+            .filter(|entry| entry.group == group)
+            .filter(|entry| matches!(entry.ty, reflect::EntryType::Buffer { dynamic_size: Some(_), .. }))
+            .map(|entry| format!("`{}`", entry.name))
+            .collect::<Vec<_>>();
+        if dynamic.is_empty() {
+            return None;
+        }
+        let ident = format_ident!("set_bind_group_{}", group);
+        let count = dynamic.len();
+        let doc = format!(
+            " Sets group {} with the offsets of {}, which must be multiples of `min_uniform_buffer_offset_alignment`.",
+            group,
+            dynamic.join(", ")
+        );
+        let message = format!(
+            "{}: offset {{}} of group {} isn't a multiple of min_uniform_buffer_offset_alignment ({{}})",
+            rp.name, group
+        );
+        Some(quote! {
+            #[doc = #doc]
+            pub fn #ident<'a>(
+                render_pass: &mut ::wgpu::RenderPass<'a>,
+                bind_group: &'a ::wgpu::BindGroup,
+                offsets: [u32; #count],
+                limits: &::wgpu::Limits,
+            ) {
+                let alignment = limits.min_uniform_buffer_offset_alignment;
+                for offset in offsets {
+                    assert!(offset % alignment == 0, #message, offset, alignment);
+                }
+                render_pass.set_bind_group(#group, bind_group, &offsets);
+            }
+        })
+    });
+    quote! { #(#setters)* }
+}
+
+/// Emits `{Name}BindGroupLayout`, which creates and owns the layout of a
+/// `#bind_group` and creates bind groups from it with a parameter per
+/// entry. In [CodegenMode::ConstantsOnly] only the entries are emitted.
+fn gen_bind_group(group: &BindGroupConfig, options: &CodegenOptions) -> Result<TokenStream> {
+    let entries = layout_entries(&group.entries.iter().collect::<Vec<_>>());
+    if options.mode == CodegenMode::ConstantsOnly {
+        let ident = format_ident!(
+            "{}_BIND_GROUP_LAYOUT_ENTRIES",
+            screaming_snake_case(&group.name)
+        );
+        return Ok(quote! { pub const #ident: &[::wgpu::BindGroupLayoutEntry] = #entries; });
+    }
+
+    let name = format_ident!("{}BindGroupLayout", group.name);
+    let label = if options.labels {
+        let label = &group.name;
+        quote! { Some(#label) }
+    } else {
+        quote! { None }
+    };
+    let params = group
+        .entries
+        .iter()
+        .map(|entry| {
+            syn::parse_str::<syn::Ident>(&entry.name).map_err(|_| {
+                anyhow::anyhow!(
+                    "{}: bind group entry {:?} is a reserved word",
+                    group.name,
+                    entry.name
+                )
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let param_types = group.entries.iter().map(|entry| match entry.ty {
+        BindingPresetType::Uniform | BindingPresetType::Storage { .. } => {
+            quote! { &::wgpu::Buffer }
+        }
+        BindingPresetType::Sampler(_) => quote! { &::wgpu::Sampler },
+        BindingPresetType::Texture(_) => quote! { &::wgpu::TextureView },
+    });
+    let resources =
+        group
+            .entries
+            .iter()
+            .zip(&params)
+            .enumerate()
+            .map(|(binding, (entry, param))| {
+                let binding = binding as u32;
+                let resource = match entry.ty {
+                    BindingPresetType::Uniform | BindingPresetType::Storage { .. } => {
+                        quote! { #param.as_entire_binding() }
+                    }
+                    BindingPresetType::Sampler(_) => {
+                        quote! { ::wgpu::BindingResource::Sampler(#param) }
+                    }
+                    BindingPresetType::Texture(_) => {
+                        quote! { ::wgpu::BindingResource::TextureView(#param) }
+                    }
+                };
+                quote! {
+                    ::wgpu::BindGroupEntry {
+                        binding: #binding,
+                        resource: #resource,
+                    }
+                }
+            });
+
+    Ok(quote! {
+        pub struct #name {
+            layout: ::wgpu::BindGroupLayout,
+        }
+
+        impl #name {
+            pub const ENTRIES: &'static [::wgpu::BindGroupLayoutEntry] = #entries;
+
+            pub fn new(device: &::wgpu::Device) -> Self {
+                Self {
+                    layout: device.create_bind_group_layout(&::wgpu::BindGroupLayoutDescriptor {
+                        label: #label,
+                        entries: Self::ENTRIES,
+                    }),
+                }
+            }
+
+            pub fn layout(&self) -> &::wgpu::BindGroupLayout {
+                &self.layout
+            }
+
+            pub fn create_bind_group(
+                &self,
+                device: &::wgpu::Device
+                #(, #params: #param_types)*
+            ) -> ::wgpu::BindGroup {
+                device.create_bind_group(&::wgpu::BindGroupDescriptor {
+                    label: #label,
+                    layout: &self.layout,
+                    entries: &[#(#resources),*],
+                })
+            }
+        }
+    })
+}
+
+/// The parameters a pipeline's constructor takes after the device, along
+/// with the arguments that pass them on. Pipelines that write
+/// `target_count` color targets without saying what they are take their
+/// formats last, as `surface_format` when there's only one.
+fn constructor_params(
+    rp: &RenderPipelineConfig,
+    target_count: usize,
+) -> (Vec<TokenStream>, Vec<TokenStream>) {
+    let (mut params, mut args): (Vec<_>, Vec<_>) = match &rp.layout {
+        Some(layout) => {
+            let ty = pipeline_layout_type(layout);
+            (vec![quote! { layout: &#ty }], vec![quote! { layout }])
+        }
+        None => rp
+            .bind_group_layouts
+            .iter()
+            .map(|name| {
+                let ident = format_ident!("{}", name);
+                (
+                    quote! { #ident: &::wgpu::BindGroupLayout },
+                    quote! { #ident },
+                )
+            })
+            .unzip(),
+    };
+    match target_count {
+        0 => (),
+        1 => {
+            params.push(quote! { surface_format: ::wgpu::TextureFormat });
+            args.push(quote! { surface_format });
+        }
+        count => {
+            params.push(quote! { target_formats: [::wgpu::TextureFormat; #count] });
+            args.push(quote! { target_formats });
+        }
+    }
+    (params, args)
+}
+
+fn pipeline_layout_type(name: &str) -> proc_macro2::Ident {
+    format_ident!("{}PipelineLayout", name)
+}
+
+/// A pipeline layout created once for pipelines that would each create
+/// identical bind group layouts and push constant ranges.
+struct SharedLayout<'c> {
+    ident: proc_macro2::Ident,
+    entries: TokenStream,
+    ranges: Option<TokenStream>,
+    members: Vec<(&'c RenderPipelineConfig, &'c Option<TokenStream>)>,
+}
+
+impl SharedLayout<'_> {
+    /// The layout is only compiled in when at least one of its pipelines is.
+    fn cfg_attr(&self) -> TokenStream {
+        match self
+            .members
+            .iter()
+            .map(|(_, cfg)| cfg.as_ref())
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(cfgs) => quote! { #[cfg(any(#(#cfgs),*))] },
+            None => quote! {},
+        }
+    }
+}
+
+/// Groups the pipelines that create their own layouts by the entries and
+/// ranges they create them from, keeping the groups that would create more
+/// than one pipeline. A pipeline with several sample counts creates one per
+/// count.
+fn shared_layouts<'c>(
+    owned: Vec<(
+        &'c RenderPipelineConfig,
+        &'c Option<TokenStream>,
+        TokenStream,
+        Option<TokenStream>,
+    )>,
+) -> Vec<SharedLayout<'c>> {
+    let mut groups = Vec::<(String, SharedLayout)>::new();
+    for (rp, cfg, entries, ranges) in owned {
+        let key = format!(
+            "{} {:?}",
+            entries,
+            ranges.as_ref().map(|ranges| ranges.to_string())
+        );
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, group)) => group.members.push((rp, cfg)),
+            None => {
+                let ident = format_ident!("SharedPipelineLayout");
+                groups.push((
+                    key,
+                    SharedLayout {
+                        ident,
+                        entries,
+                        ranges,
+                        members: vec![(rp, cfg)],
+                    },
+                ));
+            }
+        }
+    }
+    let pipelines = |group: &SharedLayout| -> usize {
+        group
+            .members
+            .iter()
+            .map(|(rp, _)| rp.sample_counts.as_ref().map_or(1, Vec::len))
+            .sum()
+    };
+    groups
+        .into_iter()
+        .map(|(_, group)| group)
+        .filter(|group| pipelines(group) > 1)
+        .enumerate()
+        .map(|(i, group)| SharedLayout {
+            ident: format_ident!("SharedPipelineLayout{}", i),
+            ..group
+        })
+        .collect()
+}
+
+/// Emits `SharedPipelineLayout{N}` and a `with_shared_layout` constructor for
+/// each of the pipelines sharing it.
+fn gen_shared_layout(
+    shared: &SharedLayout,
+    runtime_targets: &HashMap<String, usize>,
+    labels: bool,
+) -> TokenStream {
+    let name = &shared.ident;
+    let entries = &shared.entries;
+    let (ranges, required_features) = match &shared.ranges {
+        Some(ranges) => (ranges.clone(), quote! { ::wgpu::Features::PUSH_CONSTANTS }),
+        None => (quote! { &[] }, quote! { ::wgpu::Features::empty() }),
+    };
+    let (label, group_label) = if labels {
+        let label = name.to_string();
+        let group_label = format!("{} group {{}}", label);
+        (
+            quote! { Some(#label) },
+            quote! { Some(&format!(#group_label, group)) },
+        )
+    } else {
+        (quote! { None }, quote! { None })
+    };
+    let names = shared
+        .members
+        .iter()
+        .map(|(rp, _)| format!("`{}`", rp.name))
+        .collect::<Vec<_>>();
+    let doc = format!(
+        " The pipeline layout of {}, which create identical bind group layouts, so bind groups can be used with any of them.",
+        names.join(", ")
+    );
+    let cfg_attr = shared.cfg_attr();
+    let constructors = shared.members.iter().map(|(rp, cfg)| {
+        let ty = format_ident!("{}", rp.name);
+        let (params, args) = constructor_params(rp, runtime_targets[&rp.name]);
+        let (generics, type_args) = match rp.sample_counts {
+            Some(_) => (quote! { <const SAMPLES: u32> }, quote! { <SAMPLES> }),
+            None => (quote! {}, quote! {}),
+        };
+        let cfg_attr = self::cfg_attr(cfg);
+        quote! {
+            #cfg_attr
+            impl #generics #ty #type_args {
+                /// Creates the pipeline with the layouts of `layout` instead of its own.
+                pub fn with_shared_layout(device: &::wgpu::Device, layout: &#name #(, #params)*) -> Self {
+                    Self {
+                        render_pipeline: Self::create_render_pipeline_with_layout(device, layout.layout() #(, #args)*),
+                        bind_group_layouts: layout.bind_group_layouts.clone(),
+                    }
+                }
+            }
+        }
+    });
+
+    quote! {
+        #cfg_attr
+        #[doc = #doc]
+        pub struct #name {
+            bind_group_layouts: ::std::sync::Arc<[::wgpu::BindGroupLayout]>,
+            layout: ::wgpu::PipelineLayout,
+        }
+
+        #cfg_attr
+        impl #name {
+            /// The `wgpu::Features` the device must have for this layout to be created.
+            pub const REQUIRED_FEATURES: ::wgpu::Features = #required_features;
+            /// The entries of each bind group layout, in group order.
+            pub const BIND_GROUP_LAYOUT_ENTRIES: &'static [&'static [::wgpu::BindGroupLayoutEntry]] = #entries;
+            pub const PUSH_CONSTANT_RANGES: &'static [::wgpu::PushConstantRange] = #ranges;
+
+            pub fn new(device: &::wgpu::Device) -> Self {
+                let bind_group_layouts = Self::BIND_GROUP_LAYOUT_ENTRIES
+                    .iter()
+                    .enumerate()
+                    .map(|(group, entries)| {
+                        device.create_bind_group_layout(&::wgpu::BindGroupLayoutDescriptor {
+                            label: #group_label,
+                            entries,
+                        })
+                    })
+                    .collect::<::std::sync::Arc<[_]>>();
+                let layout = device.create_pipeline_layout(&::wgpu::PipelineLayoutDescriptor {
+                    label: #label,
+                    bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+                    push_constant_ranges: Self::PUSH_CONSTANT_RANGES,
+                });
+                Self {
+                    bind_group_layouts,
+                    layout,
+                }
+            }
+
+            pub fn layout(&self) -> &::wgpu::PipelineLayout {
+                &self.layout
+            }
+
+            /// The layouts to create bind groups for pipelines using this layout with.
+            pub fn bind_group_layouts(&self) -> &[::wgpu::BindGroupLayout] {
+                &self.bind_group_layouts
+            }
+        }
+
+        #(#constructors)*
+    }
+}
+
+/// Emits `{Name}PipelineLayout`, which creates and owns a `#pipeline_layout`
+/// and its bind group layouts so several pipelines can share them. In
+/// [CodegenMode::ConstantsOnly] only the entries and ranges are emitted.
+fn gen_pipeline_layout(
+    layout: &PipelineLayoutConfig,
+    config: &PipelineConfig,
+    options: &CodegenOptions,
+) -> TokenStream {
+    let entries =
+        bind_group_layout_entries(&layout.bind_groups, config).unwrap_or_else(|| quote! { &[] });
+    let ranges = layout.push_constants.iter().map(|push_constant| {
+        let stages = stages(&push_constant.stages);
+        let (start, end) = (push_constant.range.start, push_constant.range.end);
+        quote! {
+            ::wgpu::PushConstantRange {
+                stages: #stages,
+                range: #start..#end,
+            }
+        }
+    });
+    let ranges = quote! { &[#(#ranges),*] };
+    let required_features = match layout.push_constants.is_empty() {
+        true => quote! { ::wgpu::Features::empty() },
+        false => quote! { ::wgpu::Features::PUSH_CONSTANTS },
+    };
+    if options.mode == CodegenMode::ConstantsOnly {
+        let prefix = screaming_snake_case(&layout.name);
+        let entries_ident = format_ident!("{}_PIPELINE_LAYOUT_BIND_GROUP_LAYOUT_ENTRIES", prefix);
+        let ranges_ident = format_ident!("{}_PIPELINE_LAYOUT_PUSH_CONSTANT_RANGES", prefix);
+        let features_ident = format_ident!("{}_PIPELINE_LAYOUT_REQUIRED_FEATURES", prefix);
+        return quote! {
+            pub const #entries_ident: &[&[::wgpu::BindGroupLayoutEntry]] = #entries;
+            pub const #ranges_ident: &[::wgpu::PushConstantRange] = #ranges;
+            pub const #features_ident: ::wgpu::Features = #required_features;
+        };
+    }
+
+    let name = pipeline_layout_type(&layout.name);
+    let (label, group_label) = if options.labels {
+        let label = &layout.name;
+        let group_label = format!("{} group {{}}", label);
+        (
+            quote! { Some(#label) },
+            quote! { Some(&format!(#group_label, group)) },
+        )
+    } else {
+        (quote! { None }, quote! { None })
+    };
+
+    quote! {
+        pub struct #name {
+            bind_group_layouts: Vec<::wgpu::BindGroupLayout>,
+            layout: ::wgpu::PipelineLayout,
+        }
+
+        impl #name {
+            /// The `wgpu::Features` the device must have for this layout to be created.
+            pub const REQUIRED_FEATURES: ::wgpu::Features = #required_features;
+            /// The entries of each bind group layout, in group order.
+            pub const BIND_GROUP_LAYOUT_ENTRIES: &'static [&'static [::wgpu::BindGroupLayoutEntry]] = #entries;
+            pub const PUSH_CONSTANT_RANGES: &'static [::wgpu::PushConstantRange] = #ranges;
+
+            pub fn new(device: &::wgpu::Device) -> Self {
+                let bind_group_layouts = Self::BIND_GROUP_LAYOUT_ENTRIES
+                    .iter()
+                    .enumerate()
+                    .map(|(group, entries)| {
+                        device.create_bind_group_layout(&::wgpu::BindGroupLayoutDescriptor {
+                            label: #group_label,
+                            entries,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                let layout = device.create_pipeline_layout(&::wgpu::PipelineLayoutDescriptor {
+                    label: #label,
+                    bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+                    push_constant_ranges: Self::PUSH_CONSTANT_RANGES,
+                });
+                Self {
+                    bind_group_layouts,
+                    layout,
+                }
+            }
+
+            pub fn layout(&self) -> &::wgpu::PipelineLayout {
+                &self.layout
+            }
+
+            /// The layouts to create bind groups for pipelines using this layout with.
+            pub fn bind_group_layouts(&self) -> &[::wgpu::BindGroupLayout] {
+                &self.bind_group_layouts
+            }
+        }
+    }
+}
+
+/// `&[wgpu::PushConstantRange]` covering the push constants the shader
+/// uses, or `None` if it doesn't use any.
+fn push_constant_ranges(bindings: &reflect::Bindings) -> Option<TokenStream> {
+    let pc = bindings.push_constants.as_ref()?;
+    let stages = shader_stages(&pc.stages);
+    let size = pc.size;
+    Some(quote! {
+        &[::wgpu::PushConstantRange {
+            stages: #stages,
+            range: 0..#size,
+        }]
+    })
+}
+
+/// `wgpu::ShaderStages` for the stages of reflected entry points.
+fn shader_stages(stages: &[naga::ShaderStage]) -> TokenStream {
+    self::stages(
+        &stages
+            .iter()
+            .map(|stage| format!("{:?}", stage))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// `wgpu::ShaderStages` for each of [variants::SHADER_STAGES] in `stages`.
+fn stages(stages: &[String]) -> TokenStream {
+    stages
+        .iter()
+        .map(|stage| format_ident!("{}", stage.to_uppercase()))
+        .map(|stage| quote! { ::wgpu::ShaderStages::#stage })
+        .reduce(|stages, stage| quote! { #stages.union(#stage) })
+        .unwrap_or_else(|| quote! { ::wgpu::ShaderStages::NONE })
+}
+
+/// Emits `{PREFIX}_VERTEX_ATTRIBUTES` and `{PREFIX}_VERTEX_LAYOUT` for a
+/// `#vertex_layout`, which pipelines refer to for their vertex buffers.
+fn gen_vertex_layout(layout: &VertexLayoutConfig) -> TokenStream {
+    let prefix = screaming_snake_case(&layout.name);
+    let attributes_ident = format_ident!("{}_VERTEX_ATTRIBUTES", prefix);
+    let layout_ident = format_ident!("{}_VERTEX_LAYOUT", prefix);
+    let step_mode = format_ident!("{}", layout.step_mode);
+    let (attributes, stride) = packed_attributes(
+        layout
+            .attributes
+            .iter()
+            .map(String::as_str)
+            .zip(layout.locations()),
+    );
+
+    quote! {
+        pub const #attributes_ident: &[::wgpu::VertexAttribute] = #attributes;
+        pub const #layout_ident: ::wgpu::VertexBufferLayout<'static> = ::wgpu::VertexBufferLayout {
+            array_stride: #stride,
+            step_mode: ::wgpu::VertexStepMode::#step_mode,
+            attributes: #attributes_ident,
+        };
+    }
+}
+
+/// `{Pipeline}Vertex`, the struct generated for a pipeline's reflected
+/// vertex inputs.
+fn vertex_struct_ident(rp: &RenderPipelineConfig) -> proc_macro2::Ident {
+    format_ident!("{}Vertex", rp.name)
+}
+
+/// Emits a `#[repr(C)]` struct with a field for each of `inputs`, in
+/// location order, along with the `ATTRIBUTES` and `LAYOUT` of a vertex
+/// buffer holding them.
+fn gen_vertex_struct(
+    rp: &RenderPipelineConfig,
+    inputs: &[reflect::VertexInput],
+    cfg_attr: &TokenStream,
+) -> TokenStream {
+    let ident = vertex_struct_ident(rp);
+    let doc = format!(
+        " A vertex of {}, with a field for each of its vertex shader's inputs.",
+        rp.name
+    );
+    let fields = inputs.iter().map(|input| {
+        let name = layout::field_ident(&input.name);
+        // Formats are named `{Scalar}{Bits}` or `{Scalar}{Bits}x{Count}`.
+        let (scalar, count) = input.format.split_once('x').unwrap_or((input.format, "1"));
+        let scalar = format_ident!(
+            "{}",
+            match scalar {
+                "Float32" => "f32",
+                "Float64" => "f64",
+                "Sint32" => "i32",
+                "Uint32" => "u32",
+                _ => unreachable!("{} isn't a reflected vertex format", input.format),
+            }
+        );
+        match count.parse::<usize>().unwrap() {
+            1 => quote! { pub #name: #scalar },
+            count => quote! { pub #name: [#scalar; #count] },
+        }
+    });
+    let (attributes, stride) =
+        packed_attributes(inputs.iter().map(|input| (input.format, input.location)));
+
+    quote! {
+        #[doc = #doc]
+        #cfg_attr
+        #[repr(C)]
+        #[derive(Debug, Clone, Copy, PartialEq, Default)]
+        pub struct #ident {
+            #(#fields,)*
+        }
+
+        #cfg_attr
+        const _: () = assert!(::std::mem::size_of::<#ident>() as u64 == #ident::LAYOUT.array_stride);
+
+        #cfg_attr
+        impl #ident {
+            /// The attributes of a vertex buffer holding this struct.
+            pub const ATTRIBUTES: &'static [::wgpu::VertexAttribute] = #attributes;
+            /// The layout of a vertex buffer holding this struct.
+            pub const LAYOUT: ::wgpu::VertexBufferLayout<'static> = ::wgpu::VertexBufferLayout {
+                array_stride: #stride,
+                step_mode: ::wgpu::VertexStepMode::Vertex,
+                attributes: Self::ATTRIBUTES,
+            };
+
+            /// The bytes to write to a vertex buffer holding `vertices`.
+            pub fn slice_as_bytes(vertices: &[Self]) -> &[u8] {
+                // SAFETY: the struct is `repr(C)` and, as checked above, has
+                // no padding, so every byte is initialized.
+                unsafe {
+                    ::std::slice::from_raw_parts(vertices.as_ptr().cast::<u8>(), ::std::mem::size_of_val(vertices))
+                }
+            }
+        }
+    }
+}
+
+/// `&[wgpu::VertexAttribute]` for attributes of the given formats at the
+/// given locations, along with the stride of the buffer holding them.
+/// Attributes are packed, so each offset is the size of the ones before it.
+fn packed_attributes<'f>(
+    attributes: impl IntoIterator<Item = (&'f str, u32)>,
+) -> (TokenStream, TokenStream) {
+    let (formats, locations): (Vec<_>, Vec<_>) = attributes
+        .into_iter()
+        .map(|(format, location)| {
+            let format = format_ident!("{}", format);
+            (quote! { ::wgpu::VertexFormat::#format }, location)
+        })
+        .unzip();
+    let attributes = formats
+        .iter()
+        .zip(&locations)
+        .enumerate()
+        .map(|(i, (format, location))| {
+            let before = &formats[..i];
+            quote! {
+                ::wgpu::VertexAttribute {
+                    format: #format,
+                    offset: 0 #(+ #before.size())*,
+                    shader_location: #location,
+                }
+            }
+        });
+    (
+        quote! { &[#(#attributes),*] },
+        quote! { 0 #(+ #formats.size())* },
+    )
+}
+
+/// Emits `{PREFIX}_COLOR_TARGET` for a `#color_target`, which pipelines
+/// refer to for their fragment targets.
+fn gen_color_target(target: &ColorTargetConfig) -> TokenStream {
+    let ident = format_ident!("{}_COLOR_TARGET", screaming_snake_case(&target.name));
+    let format = format_ident!("{}", target.format);
+    let blend = match target.blend.as_deref() {
+        None => quote! { None },
+        Some("Additive") => quote! {
+            Some(::wgpu::BlendState {
+                color: ::wgpu::BlendComponent {
+                    src_factor: ::wgpu::BlendFactor::One,
+                    dst_factor: ::wgpu::BlendFactor::One,
+                    operation: ::wgpu::BlendOperation::Add,
+                },
+                alpha: ::wgpu::BlendComponent {
+                    src_factor: ::wgpu::BlendFactor::One,
+                    dst_factor: ::wgpu::BlendFactor::One,
+                    operation: ::wgpu::BlendOperation::Add,
+                },
+            })
+        },
+        Some(blend) => {
+            let blend = format_ident!("{}", screaming_snake_case(blend));
+            quote! { Some(::wgpu::BlendState::#blend) }
+        }
+    };
+    quote! {
+        pub const #ident: ::wgpu::ColorTargetState = ::wgpu::ColorTargetState {
+            format: ::wgpu::TextureFormat::#format,
+            blend: #blend,
+            write_mask: ::wgpu::ColorWrites::ALL,
+        };
+    }
+}
+
+/// `&[Option<wgpu::ColorTargetState>]` for the pipeline's `targets`.
+fn color_targets(rp: &RenderPipelineConfig) -> TokenStream {
+    let targets = rp.targets.iter().map(|name| {
+        let ident = format_ident!("{}_COLOR_TARGET", screaming_snake_case(name));
+        quote! { #ident }
+    });
+    let formats = rp.target_formats.iter().map(|format| {
+        let format = format_ident!("{}", format);
+        quote! {
+            ::wgpu::ColorTargetState {
+                format: ::wgpu::TextureFormat::#format,
+                blend: None,
+                write_mask: ::wgpu::ColorWrites::ALL,
+            }
+        }
+    });
+    let targets = targets.chain(formats);
+    quote! { &[#(Some(#targets)),*] }
+}
+
+/// The number of color targets the fragment stage writes, counting up to
+/// the last location it writes.
+fn color_target_count(outputs: &[u32]) -> usize {
+    outputs.last().map_or(0, |last| *last as usize + 1)
+}
+
+/// How many target formats the pipeline's constructor takes: one for each
+/// color target when neither `targets` nor `target_formats` is given.
+fn runtime_target_count(rp: &RenderPipelineConfig, outputs: &[u32]) -> usize {
+    match rp.targets.is_empty() && rp.target_formats.is_empty() {
+        true => color_target_count(outputs),
+        false => 0,
+    }
+}
+
+/// `&[Option<wgpu::ColorTargetState>]` for the formats the constructor was
+/// given, as an array expression.
+fn runtime_color_targets(formats: TokenStream) -> TokenStream {
+    quote! {
+        &#formats.map(|format| {
+            Some(::wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: ::wgpu::ColorWrites::ALL,
+            })
+        })
+    }
+}
+
+/// Fails unless the pipeline has a color target for every location its
+/// fragment stage writes, and no more. Pipelines that give no targets at all
+/// are given their formats when they're created.
+fn check_color_targets(rp: &RenderPipelineConfig, outputs: &[u32]) -> Result<()> {
+    let count = rp.targets.len() + rp.target_formats.len();
+    let needed = color_target_count(outputs);
+    match (count, needed) {
+        (0, _) => Ok(()),
+        _ if count == needed => Ok(()),
+        _ => anyhow::bail!(
+            "{}: the fragment stage writes {} color targets, but {} are given",
+            rp.name,
+            needed,
+            count
+        ),
+    }
+}
+
+/// `&[wgpu::VertexBufferLayout]` for the pipeline's `vertex_buffers`.
+fn vertex_buffers(rp: &RenderPipelineConfig, config: &PipelineConfig) -> TokenStream {
+    let layouts = rp
+        .vertex_buffers
+        .iter()
+        .map(|name| match config.rust_import(name) {
+            Some(import) => {
+                rust_import_layout(import).expect("imports are checked before generating")
+            }
+            None => {
+                let ident = format_ident!("{}_VERTEX_LAYOUT", screaming_snake_case(name));
+                quote! { #ident }
+            }
+        });
+    quote! { &[#(#layouts),*] }
+}
+
+/// `path::LAYOUT` for an `#import_rust`, failing if the path or const
+/// aren't valid Rust.
+fn rust_import_layout(import: &RustImportConfig) -> Result<TokenStream> {
+    let path = syn::parse_str::<syn::Path>(&import.path)
+        .map_err(|_| anyhow::anyhow!("{}: {:?} is not a Rust path", import.name, import.path))?;
+    let layout = syn::parse_str::<syn::Ident>(&import.layout).map_err(|_| {
+        anyhow::anyhow!(
+            "{}: {:?} is not a Rust identifier",
+            import.name,
+            import.layout
+        )
+    })?;
+    Ok(quote! { #path::#layout })
+}
+
+/// Fails if two pipelines have the same name, since each one is a struct
+/// named after it. Pipelines gated by different `cfg`s can share a name, so
+/// one can stand in for another on some targets.
+fn check_pipeline_names<'p>(
+    pipelines: impl IntoIterator<Item = (&'p str, &'p Option<IncludeSite>, &'p Option<TokenStream>)>,
+) -> Result<()> {
+    let mut seen = HashMap::new();
+    for (name, declared_at, cfg) in pipelines {
+        let Some((other_at, other_cfg)) = seen.insert(name, (declared_at, cfg)) else {
+            continue;
+        };
+        if let (Some(other_cfg), Some(cfg)) = (other_cfg, cfg) {
+            if other_cfg.to_string() != cfg.to_string() {
+                continue;
+            }
+        }
+        match (other_at, declared_at) {
+            (Some(other_at), Some(declared_at)) => anyhow::bail!(
+                "{}: pipeline is declared more than once, at {} and {}",
+                name,
+                other_at,
+                declared_at
+            ),
+            _ => anyhow::bail!("{}: pipeline is declared more than once", name),
+        }
+    }
+    Ok(())
+}
+
+/// Fails if two of the pipeline's vertex buffers use the same shader
+/// location.
+fn check_vertex_locations(rp: &RenderPipelineConfig, config: &PipelineConfig) -> Result<()> {
+    // Layouts are checked when the config is parsed. The locations of
+    // imported types aren't known.
+    let layouts = rp
+        .vertex_buffers
+        .iter()
+        .filter_map(|name| config.vertex_layout(name))
+        .collect::<Vec<_>>();
+    for (i, layout) in layouts.iter().enumerate() {
+        let overlapping = layouts[..i].iter().find(|other| {
+            layout.locations().start < other.locations().end
+                && other.locations().start < layout.locations().end
+        });
+        if let Some(other) = overlapping {
+            anyhow::bail!(
+                "{}: vertex layouts {} and {} use the same shader locations",
+                rp.name,
+                other.name,
+                layout.name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// `&[wgpu::BindGroupLayoutEntry]` with each preset bound at its index.
+fn layout_entries(presets: &[&BindingPreset]) -> TokenStream {
+    let entries = presets.iter().enumerate().map(|(binding, preset)| {
+        let binding = binding as u32;
+        let visibility = stages(&preset.visibility);
+        let ty = match &preset.ty {
+            BindingPresetType::Uniform => quote! {
+                ::wgpu::BindingType::Buffer {
+                    ty: ::wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                }
+            },
+            BindingPresetType::Storage { read_only } => quote! {
+                ::wgpu::BindingType::Buffer {
+                    ty: ::wgpu::BufferBindingType::Storage { read_only: #read_only },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                }
+            },
+            BindingPresetType::Sampler(kind) => {
+                let kind = format_ident!("{}", kind);
+                quote! { ::wgpu::BindingType::Sampler(::wgpu::SamplerBindingType::#kind) }
+            }
+            BindingPresetType::Texture(sample_type) => {
+                let sample_type = match sample_type.as_str() {
+                    "Float" => quote! { Float { filterable: true } },
+                    "UnfilterableFloat" => quote! { Float { filterable: false } },
+                    other => {
+                        let other = format_ident!("{}", other);
+                        quote! { #other }
+                    }
+                };
+                quote! {
+                    ::wgpu::BindingType::Texture {
+                        sample_type: ::wgpu::TextureSampleType::#sample_type,
+                        view_dimension: ::wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    }
+                }
+            }
+        };
+        quote! {
+            ::wgpu::BindGroupLayoutEntry {
+                binding: #binding,
+                visibility: #visibility,
+                ty: #ty,
+                count: None,
+            }
+        }
+    });
+    quote! { &[#(#entries),*] }
+}
+
+/// The `AUTHOR`, `DESCRIPTION` and `TAGS` constants for the metadata the
+/// pipeline was given, as `(name, type, value)`.
+fn metadata(rp: &RenderPipelineConfig) -> Vec<(&'static str, TokenStream, TokenStream)> {
+    let mut items = Vec::new();
+    if let Some(author) = &rp.author {
+        items.push(("AUTHOR", quote! { &'static str }, quote! { #author }));
+    }
+    if let Some(description) = &rp.description {
+        items.push((
+            "DESCRIPTION",
+            quote! { &'static str },
+            quote! { #description },
+        ));
+    }
+    if !rp.tags.is_empty() {
+        let tags = &rp.tags;
+        items.push((
+            "TAGS",
+            quote! { &'static [&'static str] },
+            quote! { &[#(#tags),*] },
+        ));
+    }
+    items
+}
+
+/// `&[(name, value)]` for the pipeline's `constants`, or `None` if it doesn't
+/// give any.
+fn override_constants(rp: &RenderPipelineConfig) -> Option<TokenStream> {
+    if rp.constants.is_empty() {
+        return None;
+    }
+    let constants = rp
+        .constants
+        .iter()
+        .map(|(name, value)| quote! { (#name, #value) });
+    Some(quote! { &[#(#constants),*] })
+}
+
+/// `&[(group, binding, wgpu::TextureSampleType)]`, or `None` if the pipeline
+/// doesn't use any textures.
+fn texture_sample_types(bindings: &reflect::Bindings) -> Option<TokenStream> {
+    if bindings.textures.is_empty() {
+        return None;
+    }
+    let entries = bindings.textures.iter().map(|b| {
+        let (group, binding, ty) = (b.group, b.binding, b.ty);
+        quote! { (#group, #binding, #ty) }
+    });
+    Some(quote! { &[#(#entries),*] })
+}
+
+/// `&[(group, binding, wgpu::SamplerBindingType)]`, or `None` if the
+/// pipeline doesn't use any samplers.
+fn sampler_binding_types(bindings: &reflect::Bindings) -> Option<TokenStream> {
+    if bindings.samplers.is_empty() {
+        return None;
+    }
+    let entries = bindings.samplers.iter().map(|b| {
+        let (group, binding, ty) = (b.group, b.binding, b.ty);
+        quote! { (#group, #binding, #ty) }
+    });
+    Some(quote! { &[#(#entries),*] })
+}
+
+fn primitive_state(rp: &RenderPipelineConfig) -> TokenStream {
+    let primitive = &rp.primitive;
+    let topology = format_ident!("{}", primitive.topology);
+    let strip_index_format = match &primitive.strip_index_format {
+        Some(format) => {
+            let format = format_ident!("{}", format);
+            quote! { Some(::wgpu::IndexFormat::#format) }
+        }
+        None => quote! { None },
+    };
+    let front_face = format_ident!("{}", primitive.front_face);
+    let cull_mode = match &primitive.cull_mode {
+        Some(face) => {
+            let face = format_ident!("{}", face);
+            quote! { Some(::wgpu::Face::#face) }
+        }
+        None => quote! { None },
+    };
+    let polygon_mode = format_ident!("{}", primitive.polygon_mode);
+    let unclipped_depth = primitive.unclipped_depth;
+    let conservative = primitive.conservative;
+    quote! {
+        ::wgpu::PrimitiveState {
+            topology: ::wgpu::PrimitiveTopology::#topology,
+            strip_index_format: #strip_index_format,
+            front_face: ::wgpu::FrontFace::#front_face,
+            cull_mode: #cull_mode,
+            unclipped_depth: #unclipped_depth,
+            polygon_mode: ::wgpu::PolygonMode::#polygon_mode,
+            conservative: #conservative,
+        }
+    }
+}
+
+fn depth_stencil_state(rp: &RenderPipelineConfig) -> TokenStream {
+    let Some(ds) = &rp.depth_stencil else {
+        return quote! { None };
+    };
+    let format = format_ident!("{}", ds.format);
+    let depth_write_enabled = ds.depth_write_enabled;
+    let depth_compare = format_ident!("{}", ds.depth_compare);
+    let constant = ds.depth_bias.constant;
+    let slope_scale = ds.depth_bias.slope_scale;
+    let clamp = ds.depth_bias.clamp;
+    quote! {
+        Some(::wgpu::DepthStencilState {
+            format: ::wgpu::TextureFormat::#format,
+            depth_write_enabled: #depth_write_enabled,
+            depth_compare: ::wgpu::CompareFunction::#depth_compare,
+            stencil: ::wgpu::StencilState {
+                front: ::wgpu::StencilFaceState::IGNORE,
+                back: ::wgpu::StencilFaceState::IGNORE,
+                read_mask: 0,
+                write_mask: 0,
+            },
+            bias: ::wgpu::DepthBiasState {
+                constant: #constant,
+                slope_scale: #slope_scale,
+                clamp: #clamp,
+            },
+        })
+    }
+}
+
+/// Lists the `wgpu::Features` a pipeline needs along with the config field
+/// that requires it.
+fn required_feature_names(
+    rp: &RenderPipelineConfig,
+    bindings: &reflect::Bindings,
+) -> Vec<(&'static str, &'static str)> {
+    let mut features = Vec::new();
+    if bindings.push_constants.is_some() {
+        features.push(("PUSH_CONSTANTS", "the shader uses push constants"));
+    }
+    if rp.primitive.conservative {
+        features.push(("CONSERVATIVE_RASTERIZATION", "conservative: true"));
+    }
+    if rp.primitive.unclipped_depth {
+        features.push(("DEPTH_CLIP_CONTROL", "unclipped_depth: true"));
+    }
+    match rp.primitive.polygon_mode.as_str() {
+        "Line" => features.push(("POLYGON_MODE_LINE", "polygon_mode: Line")),
+        "Point" => features.push(("POLYGON_MODE_POINT", "polygon_mode: Point")),
+        _ => (),
+    }
+    match rp.depth_stencil.as_ref().map(|ds| ds.format.as_str()) {
+        Some("Depth32FloatStencil8") => {
+            features.push(("DEPTH32FLOAT_STENCIL8", "format: Depth32FloatStencil8"))
+        }
+        Some("Depth24UnormStencil8") => {
+            features.push(("DEPTH24UNORM_STENCIL8", "format: Depth24UnormStencil8"))
+        }
+        _ => (),
+    }
+    features
+}
+
+/// Builds a const expression for the `wgpu::Features` a pipeline needs.
+fn required_features(rp: &RenderPipelineConfig, bindings: &reflect::Bindings) -> TokenStream {
+    let features = required_feature_names(rp, bindings)
+        .into_iter()
+        .map(|(feature, _)| format_ident!("{}", feature));
+    quote! {
+        ::wgpu::Features::from_bits_truncate(0 #(| ::wgpu::Features::#features.bits())*)
+    }
+}
+
+fn multisample_state(count: TokenStream) -> TokenStream {
+    quote! {
+        ::wgpu::MultisampleState {
+            count: #count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        }
+    }
+}
+
+/// The name of the shader at `path` in constants, eg. `SKY_BOX` for
+/// `./shaders/sky-box.wgsl`.
+fn path_const_name(path: &str) -> String {
+    let stem = Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let stem = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    screaming_snake_case(&stem)
+}
+
+/// Six hex digits of the FNV-1a hash of `src`, which unlike `std`'s hashers
+/// is the same on every platform and Rust version.
+fn content_hash(src: &str) -> String {
+    let hash = src.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:06X}", hash & 0xff_ffff)
+}
+
+/// The `Cow<str>` a pipeline's shader module is created from.
+fn wgsl_source(shader_ident: &syn::Ident, runtime_path: Option<&Path>) -> TokenStream {
+    match runtime_path {
+        Some(path) => {
+            let path = path.to_string_lossy();
+            quote! { read_shader_source(#path, #shader_ident) }
+        }
+        None => quote! { ::std::borrow::Cow::from(#shader_ident) },
+    }
+}
+
+fn gen_read_shader_source() -> TokenStream {
+    quote! {
+        /// Reads the shader at `path` in debug builds so edits show up
+        /// without rebuilding, or returns `embedded` when it can't be read
+        /// and in release builds.
+        fn read_shader_source(path: &str, embedded: &'static str) -> ::std::borrow::Cow<'static, str> {
+            if cfg!(debug_assertions) {
+                if let Ok(src) = ::std::fs::read_to_string(path) {
+                    return ::std::borrow::Cow::Owned(src);
+                }
+            }
+            ::std::borrow::Cow::Borrowed(embedded)
+        }
+    }
+}
+
+/// Compiles `shader` to SPIR-V with the options wgpu's Vulkan backend uses,
+/// except debug info, which would make the output depend on how codegen was
+/// built.
+fn spirv_words(
+    module: &naga::Module,
+    info: &naga::valid::ModuleInfo,
+) -> Result<Vec<u32>, naga::back::spv::Error> {
+    use naga::proc::BoundsCheckPolicy;
+    let options = naga::back::spv::Options {
+        flags: naga::back::spv::WriterFlags::LABEL_VARYINGS
+            | naga::back::spv::WriterFlags::FORCE_POINT_SIZE,
+        bounds_check_policies: naga::proc::BoundsCheckPolicies {
+            index: BoundsCheckPolicy::Restrict,
+            buffer: BoundsCheckPolicy::Restrict,
+            image: BoundsCheckPolicy::Restrict,
+            binding_array: BoundsCheckPolicy::Unchecked,
+        },
+        ..Default::default()
+    };
+    naga::back::spv::write_vec(module, info, &options, None)
+}
+
+fn validator() -> naga::valid::Validator {
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+}
+
+/// Converts a `PascalCase` name into `SCREAMING_SNAKE_CASE`.
+fn screaming_snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase() || prev.is_numeric() || (prev.is_uppercase() && next_is_lower) {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_uppercase());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipeline_config_from() {}
+
+    #[test]
+    fn screaming_snake_case_names() {
+        assert_eq!(
+            "TEXTURED_PIPELINE",
+            screaming_snake_case("TexturedPipeline")
+        );
+        assert_eq!("HDR_PIPELINE", screaming_snake_case("HDRPipeline"));
+        assert_eq!("PASS2_BLUR", screaming_snake_case("Pass2Blur"));
+        assert_eq!("SPRITE", screaming_snake_case("sprite"));
+    }
+
+    #[test]
+    fn path_const_names() {
+        assert_eq!("SKY_BOX", path_const_name("./shaders/sky-box.wgsl"));
+        assert_eq!("PBR_LIT", path_const_name("pbrLit.wgsl"));
+        assert_eq!("WATER_V2", path_const_name("../water.v2.wgsl"));
+        // FNV-1a of "a" is 0xaf63dc4c8601ec8c.
+        assert_eq!("01EC8C", content_hash("a"));
+        assert_eq!(content_hash("fn main() {}"), content_hash("fn main() {}"));
+    }
 }
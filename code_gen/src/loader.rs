@@ -0,0 +1,291 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    #[error("Unable to read {path:?}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{path:?} resolves to a location outside of {root:?}")]
+    PathOutsideRoot { path: PathBuf, root: PathBuf },
+}
+
+/// Resolves the paths in a config to their contents. Codegen reads every
+/// file through a loader so that configs from untrusted sources can be
+/// restricted to a directory with [JailedLoader].
+pub trait ShaderLoader: std::fmt::Debug {
+    fn load(&self, path: &str) -> Result<String, LoadError>;
+
+    /// Returns the absolute location of `path`. This is used when generated
+    /// code references the file directly, eg. with `include_str!`.
+    fn resolve(&self, path: &str) -> Result<PathBuf, LoadError>;
+
+    /// Returns the size of `path` in bytes without reading it, or `None` if
+    /// it isn't known. This lets codegen reject files that are too large
+    /// before loading them. Defaults to the size of the file `path` resolves
+    /// to, so loaders that aren't backed by the file system should override
+    /// it.
+    fn len(&self, path: &str) -> Result<Option<u64>, LoadError> {
+        let resolved = self.resolve(path)?;
+        let metadata = std::fs::metadata(&resolved).map_err(|source| LoadError::Io {
+            path: resolved,
+            source,
+        })?;
+        Ok(Some(metadata.len()))
+    }
+}
+
+/// Reads paths from the file system as-is, relative to the current working
+/// directory.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsLoader;
+
+impl ShaderLoader for FsLoader {
+    fn load(&self, path: &str) -> Result<String, LoadError> {
+        std::fs::read_to_string(path).map_err(|source| LoadError::Io {
+            path: path.into(),
+            source,
+        })
+    }
+
+    fn resolve(&self, path: &str) -> Result<PathBuf, LoadError> {
+        Path::new(path)
+            .canonicalize()
+            .map_err(|source| LoadError::Io {
+                path: path.into(),
+                source,
+            })
+    }
+}
+
+/// Reads paths relative to `root`, rejecting any path that would escape it.
+/// Absolute paths, `..` components and symlinks pointing outside of `root`
+/// all fail with [LoadError::PathOutsideRoot].
+#[derive(Debug, Clone)]
+pub struct JailedLoader {
+    root: PathBuf,
+}
+
+impl JailedLoader {
+    pub fn new(root: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let root = root.as_ref();
+        let root = root.canonicalize().map_err(|source| LoadError::Io {
+            path: root.to_owned(),
+            source,
+        })?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl ShaderLoader for JailedLoader {
+    fn load(&self, path: &str) -> Result<String, LoadError> {
+        let resolved = self.resolve(path)?;
+        std::fs::read_to_string(&resolved).map_err(|source| LoadError::Io {
+            path: resolved,
+            source,
+        })
+    }
+
+    /// Returns the canonical location of `path` if it's inside the root.
+    fn resolve(&self, path: &str) -> Result<PathBuf, LoadError> {
+        let outside = || LoadError::PathOutsideRoot {
+            path: path.into(),
+            root: self.root.clone(),
+        };
+        let relative = Path::new(path);
+        let escapes = relative.components().any(|c| {
+            matches!(
+                c,
+                Component::ParentDir | Component::RootDir | Component::Prefix(_)
+            )
+        });
+        if escapes {
+            return Err(outside());
+        }
+
+        let joined = self.root.join(relative);
+        // Canonicalizing follows symlinks, so this catches links that point
+        // outside of the root as well.
+        let resolved = joined.canonicalize().map_err(|source| LoadError::Io {
+            path: joined.clone(),
+            source,
+        })?;
+        if !resolved.starts_with(&self.root) {
+            return Err(outside());
+        }
+        Ok(resolved)
+    }
+}
+
+/// Joins `path` onto `base` if it's relative, then drops `.` components and
+/// applies `..` to the component before it, so different spellings of the
+/// same path compare equal. The file system isn't touched, so paths that
+/// don't exist still fail when they're loaded.
+pub(crate) fn normalize(base: Option<&Path>, path: &str) -> String {
+    let joined = match base {
+        Some(base) => base.join(path),
+        None => PathBuf::from(path),
+    };
+    let mut normalized = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    match normalized.as_os_str().is_empty() {
+        true => ".".to_owned(),
+        false => normalized.to_string_lossy().into_owned(),
+    }
+}
+
+/// Replaces each `${NAME}` in `path` with `NAME` from `variables`, or from
+/// the environment if it isn't one of them.
+pub(crate) fn interpolate<'p>(
+    path: &'p str,
+    variables: &BTreeMap<String, String>,
+) -> Result<Cow<'p, str>, String> {
+    if !path.contains("${") {
+        return Ok(Cow::Borrowed(path));
+    }
+    let mut out = String::with_capacity(path.len());
+    let mut rest = path;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("{}: `${{` is never closed", path))?;
+        let name = &rest[start + 2..start + end];
+        match variables.get(name) {
+            Some(value) => out.push_str(value),
+            None => match std::env::var(name) {
+                Ok(value) => out.push_str(&value),
+                Err(_) => {
+                    return Err(format!(
+                        "{}: `${{{}}}` isn't a path variable or set in the environment",
+                        path, name
+                    ))
+                }
+            },
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(Cow::Owned(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pipemd-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("root/shaders")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn normalize_paths() {
+        for path in [
+            "shaders/a.wgsl",
+            "./shaders/a.wgsl",
+            "shaders/./b/../a.wgsl",
+        ] {
+            assert_eq!("shaders/a.wgsl", normalize(None, path));
+        }
+        assert_eq!("../a.wgsl", normalize(None, "./../a.wgsl"));
+        assert_eq!(
+            "assets/shaders/a.wgsl",
+            normalize(Some(Path::new("./assets")), "shaders/a.wgsl")
+        );
+        assert_eq!(
+            "/shaders/a.wgsl",
+            normalize(Some(Path::new("assets")), "/shaders/a.wgsl")
+        );
+    }
+
+    #[test]
+    fn interpolate_paths() {
+        let variables = BTreeMap::from([("ASSETS".to_owned(), "./assets".to_owned())]);
+        assert_eq!(
+            "./assets/a.wgsl",
+            interpolate("${ASSETS}/a.wgsl", &variables).unwrap()
+        );
+        assert!(matches!(
+            interpolate("a.wgsl", &variables),
+            Ok(Cow::Borrowed("a.wgsl"))
+        ));
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+        assert_eq!(
+            format!("{}/shaders/./assets", manifest_dir),
+            interpolate("${CARGO_MANIFEST_DIR}/shaders/${ASSETS}", &variables).unwrap()
+        );
+        assert_eq!(
+            "${PIPEMD_UNSET}/a.wgsl: `${PIPEMD_UNSET}` isn't a path variable or set in the environment",
+            interpolate("${PIPEMD_UNSET}/a.wgsl", &variables).unwrap_err()
+        );
+        assert!(interpolate("${ASSETS/a.wgsl", &variables).is_err());
+    }
+
+    #[test]
+    fn jailed_loader_loads_inside_root() {
+        let dir = temp_dir("jail-inside");
+        std::fs::write(dir.join("root/shaders/a.wgsl"), "// a").unwrap();
+        let loader = JailedLoader::new(dir.join("root")).unwrap();
+        assert_eq!("// a", loader.load("shaders/a.wgsl").unwrap());
+        assert_eq!("// a", loader.load("./shaders/a.wgsl").unwrap());
+        assert!(matches!(
+            loader.load("shaders/missing.wgsl"),
+            Err(LoadError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn jailed_loader_rejects_escapes() {
+        let dir = temp_dir("jail-escape");
+        std::fs::write(dir.join("secret.wgsl"), "// secret").unwrap();
+        let loader = JailedLoader::new(dir.join("root")).unwrap();
+        let absolute = dir.join("secret.wgsl");
+        for path in [
+            "../secret.wgsl",
+            "shaders/../../secret.wgsl",
+            absolute.to_str().unwrap(),
+        ] {
+            match loader.load(path) {
+                Err(LoadError::PathOutsideRoot { .. }) => (),
+                r => panic!("Expected PathOutsideRoot for {:?} but found {:?}", path, r),
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn jailed_loader_rejects_symlink_escapes() {
+        let dir = temp_dir("jail-symlink");
+        std::fs::write(dir.join("secret.wgsl"), "// secret").unwrap();
+        std::os::unix::fs::symlink(dir.join("secret.wgsl"), dir.join("root/shaders/link.wgsl"))
+            .unwrap();
+        let loader = JailedLoader::new(dir.join("root")).unwrap();
+        assert!(matches!(
+            loader.load("shaders/link.wgsl"),
+            Err(LoadError::PathOutsideRoot { .. })
+        ));
+    }
+}
@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+
+use crate::reflect::{self, EntryType};
+use crate::{ComputePipelineConfig, PipelineConfig, RenderPipelineConfig};
+
+/// Bumped whenever the layout of [Manifest] changes in a way older tooling
+/// can't read.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// A machine-readable description of what the generated code for a config
+/// exposes: its pipelines, their shaders and entry points, and the vertex
+/// buffers and bind groups they expect. Unlike [crate::Descriptor] it's
+/// filled in from the shaders, so it's only available once code has been
+/// generated, eg. with [crate::gen_pipeline_code_with_manifest].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// Render pipelines, one per variant, then compute pipelines, in the
+    /// order they're declared.
+    pub pipelines: Vec<PipelineManifest>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineManifest {
+    pub name: String,
+    pub kind: PipelineKind,
+    pub label: String,
+    /// The shader's path as the config wrote it.
+    pub shader: String,
+    /// The vertex and fragment entry points, or the compute one.
+    pub entry_points: Vec<String>,
+    /// The vertex buffers in slot order. Always empty for compute pipelines.
+    pub vertex_buffers: Vec<VertexBufferManifest>,
+    /// The bind groups the shader uses, in group order.
+    pub bind_groups: Vec<BindGroupManifest>,
+    /// The workgroup size of compute pipelines.
+    pub workgroup_size: Option<[u32; 3]>,
+    pub cfg: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelineKind {
+    Render,
+    Compute,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VertexBufferManifest {
+    /// The `#vertex_layout` or `#rust_import` it comes from, or the
+    /// generated `{Pipeline}Vertex` struct when the inputs are reflected.
+    pub name: String,
+    /// `Vertex` or `Instance`.
+    pub step_mode: String,
+    /// Tightly packed in this order. Empty for imported types, whose layout
+    /// is only known to Rust.
+    pub attributes: Vec<VertexAttributeManifest>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VertexAttributeManifest {
+    pub location: u32,
+    /// A `wgpu::VertexFormat`, eg. `Float32x3`.
+    pub format: String,
+    /// The name of the shader input, when it was reflected.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BindGroupManifest {
+    pub group: u32,
+    pub entries: Vec<BindingManifest>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BindingManifest {
+    pub binding: u32,
+    /// The name of the shader's global variable.
+    pub name: String,
+    /// eg. `uniform buffer`, `texture` or `sampler`.
+    pub kind: String,
+    /// The stages that use it, eg. `["Vertex", "Fragment"]`.
+    pub visibility: Vec<String>,
+    /// The length of a binding array.
+    pub count: Option<u32>,
+}
+
+impl Manifest {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Manifest should always serialize")
+    }
+
+    pub fn from_json(src: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(src)
+    }
+}
+
+/// A short name for the kind of resource an entry binds.
+pub(crate) fn entry_kind(ty: &EntryType) -> &'static str {
+    match ty {
+        EntryType::Buffer { uniform: true, .. } => "uniform buffer",
+        EntryType::Buffer {
+            read_only: true, ..
+        } => "read-only storage buffer",
+        EntryType::Buffer { .. } => "storage buffer",
+        EntryType::Texture { .. } => "texture",
+        EntryType::StorageTexture { .. } => "storage texture",
+        EntryType::Sampler(_) => "sampler",
+    }
+}
+
+fn bind_groups(bindings: &reflect::Bindings) -> Vec<BindGroupManifest> {
+    let mut groups: Vec<BindGroupManifest> = Vec::new();
+    for entry in &bindings.entries {
+        let binding = BindingManifest {
+            binding: entry.binding,
+            name: entry.name.clone(),
+            kind: entry_kind(&entry.ty).to_owned(),
+            visibility: entry
+                .visibility
+                .iter()
+                .map(|stage| format!("{:?}", stage))
+                .collect(),
+            count: entry.count,
+        };
+        match groups.last_mut() {
+            Some(group) if group.group == entry.group => group.entries.push(binding),
+            _ => groups.push(BindGroupManifest {
+                group: entry.group,
+                entries: vec![binding],
+            }),
+        }
+    }
+    groups
+}
+
+pub(crate) fn render_pipeline(
+    rp: &RenderPipelineConfig,
+    config: &PipelineConfig,
+    (vs_entry, fs_entry): (&str, &str),
+    bindings: &reflect::Bindings,
+    vertex_inputs: &[reflect::VertexInput],
+) -> PipelineManifest {
+    let vertex_buffers = match &rp.vertex_buffers[..] {
+        [] if vertex_inputs.is_empty() => Vec::new(),
+        [] => vec![VertexBufferManifest {
+            name: format!("{}Vertex", rp.name),
+            step_mode: "Vertex".to_owned(),
+            attributes: vertex_inputs
+                .iter()
+                .map(|input| VertexAttributeManifest {
+                    location: input.location,
+                    format: input.format.to_owned(),
+                    name: Some(input.name.clone()),
+                })
+                .collect(),
+        }],
+        names => names
+            .iter()
+            .map(|name| match config.vertex_layout(name) {
+                Some(layout) => VertexBufferManifest {
+                    name: name.clone(),
+                    step_mode: layout.step_mode.clone(),
+                    attributes: layout
+                        .attributes
+                        .iter()
+                        .zip(layout.locations())
+                        .map(|(format, location)| VertexAttributeManifest {
+                            location,
+                            format: format.clone(),
+                            name: None,
+                        })
+                        .collect(),
+                },
+                None => VertexBufferManifest {
+                    name: name.clone(),
+                    step_mode: "Vertex".to_owned(),
+                    attributes: Vec::new(),
+                },
+            })
+            .collect(),
+    };
+    PipelineManifest {
+        name: rp.name.clone(),
+        kind: PipelineKind::Render,
+        label: rp.label().to_owned(),
+        shader: rp.path.clone(),
+        entry_points: vec![vs_entry.to_owned(), fs_entry.to_owned()],
+        vertex_buffers,
+        bind_groups: bind_groups(bindings),
+        workgroup_size: None,
+        cfg: rp.cfg.clone(),
+    }
+}
+
+pub(crate) fn compute_pipeline(
+    cp: &ComputePipelineConfig,
+    workgroup_size: [u32; 3],
+    bindings: &reflect::Bindings,
+) -> PipelineManifest {
+    PipelineManifest {
+        name: cp.name.clone(),
+        kind: PipelineKind::Compute,
+        label: cp.name.clone(),
+        shader: cp.path.clone(),
+        entry_points: vec![cp.entry.clone()],
+        vertex_buffers: Vec::new(),
+        bind_groups: bind_groups(bindings),
+        workgroup_size: Some(workgroup_size),
+        cfg: cp.cfg.clone(),
+    }
+}
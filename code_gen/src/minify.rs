@@ -0,0 +1,72 @@
+//! Shrinking WGSL source before it's embedded.
+
+/// Removes the comments and whitespace from `src` that don't change what
+/// it means. Whitespace is only kept, as a single space, between characters
+/// that would otherwise be read as one token, eg. `let x` or `a - -b`.
+pub(crate) fn minify(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    let mut separated = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().take_while(|&c| c != '\n').for_each(drop);
+                separated = true;
+            }
+            // Block comments can be nested.
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some('/') if chars.peek() == Some(&'*') => {
+                            chars.next();
+                            depth += 1;
+                        }
+                        Some('*') if chars.peek() == Some(&'/') => {
+                            chars.next();
+                            depth -= 1;
+                        }
+                        Some(_) => (),
+                        None => break,
+                    }
+                }
+                separated = true;
+            }
+            c if c.is_whitespace() => separated = true,
+            c => {
+                if separated && out.chars().last().is_some_and(|last| fuses(last, c)) {
+                    out.push(' ');
+                }
+                separated = false;
+                out.push(c);
+            }
+        }
+    }
+    out
+}
+
+/// Whether `a` followed directly by `b` could be read as a single token.
+/// naga reads a `-` followed by a number as a negative literal, so the
+/// space in `x - 1.0` has to stay.
+fn fuses(a: char, b: char) -> bool {
+    let word = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    let operator = |c: char| "+-*/%&|^<>=!~".contains(c);
+    (word(a) && word(b)) || (operator(a) && operator(b)) || (a == '-' && b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minify_source() {
+        let src = "// Lighting.\n@vertex\nfn vs_main( /* a /* nested */ comment */ i : u32 ) -> vec4<f32> {\n    let x = 1.0 - -f32(i); // trailing\n    return vec4( x );\n}\n";
+        assert_eq!(
+            "@vertex fn vs_main(i:u32)->vec4<f32>{let x=1.0- -f32(i);return vec4(x);}",
+            minify(src)
+        );
+        assert_eq!("a/ *b", minify("a / *b"));
+        assert_eq!("2.0- 1.0", minify("2.0 - 1.0"));
+    }
+}
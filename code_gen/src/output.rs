@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+
+/// The first line of every file written by [write_generated].
+pub const GENERATED_HEADER: &str = "// @generated by pipemd. Do not edit by hand.";
+
+const CHECKSUM_PREFIX: &str = "// pipemd-checksum: fnv1a64:";
+
+#[derive(Debug, thiserror::Error)]
+pub enum WriteError {
+    #[error("Unable to access {path:?}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{path:?} wasn't generated by pipemd, refusing to overwrite it without force")]
+    NotGenerated { path: PathBuf },
+    #[error("{path:?} was edited by hand since it was generated, refusing to overwrite it without force")]
+    HandEdited { path: PathBuf },
+}
+
+/// What [write_generated] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOutcome {
+    Created,
+    Updated,
+    /// The file already had this content so it wasn't touched, which keeps
+    /// its modification time for build systems that look at it.
+    Unchanged,
+}
+
+/// Writes `code` to `path` after a header holding its checksum.
+///
+/// If `path` already exists it's only overwritten when its header is intact
+/// and its content still matches the checksum, so hand edits aren't lost by
+/// regenerating. `force` skips that check.
+pub fn write_generated(path: &Path, code: &str, force: bool) -> Result<WriteOutcome, WriteError> {
+    let io = |source| WriteError::Io {
+        path: path.to_owned(),
+        source,
+    };
+    let contents = with_header(code);
+    let outcome = match std::fs::read_to_string(path) {
+        Ok(existing) if existing == contents => return Ok(WriteOutcome::Unchanged),
+        Ok(existing) => {
+            if !force {
+                match verify(&existing) {
+                    Some(true) => (),
+                    Some(false) => {
+                        return Err(WriteError::HandEdited {
+                            path: path.to_owned(),
+                        })
+                    }
+                    None => {
+                        return Err(WriteError::NotGenerated {
+                            path: path.to_owned(),
+                        })
+                    }
+                }
+            }
+            WriteOutcome::Updated
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => WriteOutcome::Created,
+        Err(e) => return Err(io(e)),
+    };
+    std::fs::write(path, contents).map_err(io)?;
+    Ok(outcome)
+}
+
+fn with_header(code: &str) -> String {
+    format!(
+        "{}\n{}{:016x}\n{}",
+        GENERATED_HEADER,
+        CHECKSUM_PREFIX,
+        fnv1a64(code.as_bytes()),
+        code
+    )
+}
+
+/// Checks a previously generated file. Returns `None` if it doesn't have a
+/// pipemd header and whether its content matches the checksum otherwise.
+fn verify(contents: &str) -> Option<bool> {
+    let rest = contents
+        .strip_prefix(GENERATED_HEADER)?
+        .strip_prefix('\n')?;
+    let (checksum_line, code) = rest.split_once('\n').unwrap_or((rest, ""));
+    let checksum = checksum_line.strip_prefix(CHECKSUM_PREFIX)?;
+    let checksum = u64::from_str_radix(checksum, 16).ok()?;
+    Some(checksum == fnv1a64(code.as_bytes()))
+}
+
+/// 64 bit FNV-1a. It isn't cryptographic, but it's stable across platforms
+/// and releases, which is all the generated file checks need.
+pub(crate) fn fnv1a64(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pipemd-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("pipelines.rs")
+    }
+
+    #[test]
+    fn fnv1a64_known_values() {
+        assert_eq!(0xcbf2_9ce4_8422_2325, fnv1a64(b""));
+        assert_eq!(0xaf63_dc4c_8601_ec8c, fnv1a64(b"a"));
+        assert_eq!(0x8594_4171_f739_67e8, fnv1a64(b"foobar"));
+    }
+
+    #[test]
+    fn write_generated_creates_and_updates() {
+        let path = temp_file("write-generated");
+        assert_eq!(
+            WriteOutcome::Created,
+            write_generated(&path, "const A: u32 = 1;\n", false).unwrap()
+        );
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with(GENERATED_HEADER));
+        assert!(written.ends_with("\nconst A: u32 = 1;\n"));
+        assert_eq!(Some(true), verify(&written));
+
+        assert_eq!(
+            WriteOutcome::Unchanged,
+            write_generated(&path, "const A: u32 = 1;\n", false).unwrap()
+        );
+        assert_eq!(
+            WriteOutcome::Updated,
+            write_generated(&path, "const A: u32 = 2;\n", false).unwrap()
+        );
+    }
+
+    #[test]
+    fn write_generated_protects_edits() {
+        let path = temp_file("write-generated-edits");
+        write_generated(&path, "const A: u32 = 1;\n", false).unwrap();
+        let edited = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("= 1;", "= 3;");
+        std::fs::write(&path, &edited).unwrap();
+        assert!(matches!(
+            write_generated(&path, "const A: u32 = 2;\n", false),
+            Err(WriteError::HandEdited { .. })
+        ));
+        assert_eq!(edited, std::fs::read_to_string(&path).unwrap());
+        assert_eq!(
+            WriteOutcome::Updated,
+            write_generated(&path, "const A: u32 = 2;\n", true).unwrap()
+        );
+
+        std::fs::write(&path, "fn main() {}\n").unwrap();
+        assert!(matches!(
+            write_generated(&path, "const A: u32 = 2;\n", false),
+            Err(WriteError::NotGenerated { .. })
+        ));
+        assert_eq!(
+            WriteOutcome::Updated,
+            write_generated(&path, "const A: u32 = 2;\n", true).unwrap()
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Shaders specialized with the values of WGSL `override` constants.
+
+use std::borrow::Cow;
+
+/// Rewrites every `override` declaration in `src` into a module-scope `let`
+/// holding its value from `constants`, or its default if it isn't given one.
+/// wgpu 0.13 has no way to set override values when a pipeline is created
+/// and its naga doesn't parse `override`, so the values are baked into the
+/// source instead. Each declaration is rewritten on its own line so naga's
+/// errors still point at the right line of the original file.
+///
+/// Sources without any `override` declarations are returned as-is.
+pub(crate) fn specialize<'s>(
+    src: &'s str,
+    constants: &[(String, f64)],
+) -> Result<Cow<'s, str>, String> {
+    if constants.is_empty() && !src.lines().any(|line| parse_override(line).is_some()) {
+        return Ok(Cow::Borrowed(src));
+    }
+    let mut found = Vec::new();
+    let mut out = String::with_capacity(src.len());
+    for (i, line) in src.lines().enumerate() {
+        match parse_override(line) {
+            Some(decl) => {
+                let value = constants
+                    .iter()
+                    .find(|(name, _)| name == decl.name)
+                    .map(|(_, value)| *value);
+                let value = match (value, decl.default) {
+                    (Some(value), _) => {
+                        let ty = decl.ty.ok_or_else(|| {
+                            format!(
+                                "line {}: override {} needs a type to be given a value",
+                                i + 1,
+                                decl.name
+                            )
+                        })?;
+                        literal(ty, value)
+                            .map_err(|e| format!("line {}: override {} {}", i + 1, decl.name, e))?
+                    }
+                    (None, Some(default)) => default.to_owned(),
+                    (None, None) => {
+                        return Err(format!(
+                            "line {}: override {} has no default, so it needs a value in constants",
+                            i + 1,
+                            decl.name
+                        ))
+                    }
+                };
+                out.push_str(decl.indent);
+                out.push_str("let ");
+                out.push_str(decl.name);
+                if let Some(ty) = decl.ty {
+                    out.push_str(": ");
+                    out.push_str(ty);
+                }
+                out.push_str(" = ");
+                out.push_str(&value);
+                out.push(';');
+                out.push_str(decl.rest);
+                found.push(decl.name);
+            }
+            None => out.push_str(line),
+        }
+        out.push('\n');
+    }
+    if let Some((name, _)) = constants
+        .iter()
+        .find(|(name, _)| !found.contains(&name.as_str()))
+    {
+        return Err(format!("no override named {}", name));
+    }
+    Ok(Cow::Owned(out))
+}
+
+/// `override name: ty = default;`, split into its parts.
+struct OverrideDecl<'s> {
+    indent: &'s str,
+    name: &'s str,
+    ty: Option<&'s str>,
+    default: Option<&'s str>,
+    /// Whatever follows the `;`, such as a comment.
+    rest: &'s str,
+}
+
+/// Parses a line holding an `override` declaration, optionally with an
+/// `@id(...)` attribute, which is dropped.
+fn parse_override(line: &str) -> Option<OverrideDecl<'_>> {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+    let decl = match trimmed.strip_prefix("@id(") {
+        Some(attributed) => attributed.split_once(')')?.1.trim_start(),
+        None => trimmed,
+    };
+    let decl = decl.strip_prefix("override")?;
+    if !decl.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let (decl, rest) = decl.split_once(';')?;
+    let (decl, default) = match decl.split_once('=') {
+        Some((decl, default)) => (decl, Some(default.trim())),
+        None => (decl, None),
+    };
+    let (name, ty) = match decl.split_once(':') {
+        Some((name, ty)) => (name.trim(), Some(ty.trim())),
+        None => (decl.trim(), None),
+    };
+    Some(OverrideDecl {
+        indent,
+        name,
+        ty,
+        default,
+        rest,
+    })
+}
+
+/// Writes `value` as a WGSL literal of type `ty`.
+fn literal(ty: &str, value: f64) -> Result<String, String> {
+    let integer = || {
+        if value.fract() == 0.0 {
+            Ok(value as i64)
+        } else {
+            Err(format!("is a {}, but was given {}", ty, value))
+        }
+    };
+    match ty {
+        "bool" => Ok((value != 0.0).to_string()),
+        "f32" => Ok(format!("{:?}", value as f32)),
+        "i32" => match integer()? {
+            value if i32::try_from(value).is_ok() => Ok(value.to_string()),
+            value => Err(format!("is an i32, but was given {}", value)),
+        },
+        "u32" => match integer()? {
+            value if u32::try_from(value).is_ok() => Ok(format!("{}u", value)),
+            value => Err(format!("is a u32, but was given {}", value)),
+        },
+        _ => Err(format!("has type {}, which can't be given a value", ty)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn specialize_overrides() {
+        let src =
+            "override MAX_LIGHTS: u32 = 4u;\n  @id(1) override USE_FOG: f32; // fog\nlet a = 1;";
+        let constants = vec![("USE_FOG".to_owned(), 1.0)];
+        assert_eq!(
+            "let MAX_LIGHTS: u32 = 4u;\n  let USE_FOG: f32 = 1.0; // fog\nlet a = 1;\n",
+            specialize(src, &constants).unwrap()
+        );
+        let constants = vec![("MAX_LIGHTS".to_owned(), 64.0), ("USE_FOG".to_owned(), 0.0)];
+        assert!(specialize(src, &constants)
+            .unwrap()
+            .starts_with("let MAX_LIGHTS: u32 = 64u;"));
+        assert!(specialize(src, &[]).is_err());
+        assert!(specialize(
+            src,
+            &[("MAX_LIGHTS".to_owned(), 1.5), ("USE_FOG".to_owned(), 0.0)]
+        )
+        .is_err());
+        assert!(specialize("let a = 1;", &[("MISSING".to_owned(), 1.0)]).is_err());
+        assert!(matches!(
+            specialize("let overrides = 1;", &[]),
+            Ok(Cow::Borrowed(_))
+        ));
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Receives events while the code for a config is generated so that long
+/// runs can report their status, eg. with a progress bar.
+///
+/// Codegen reports shaders and pipelines as they're processed. Reading the
+/// config and writing the output happen outside of codegen, apart from the
+/// configs [generate_many](crate::generate_many) reads, so whatever does
+/// those is expected to report [ProgressObserver::file_parsed] and
+/// [ProgressObserver::bytes_written] itself. Every method does nothing by
+/// default.
+pub trait ProgressObserver: std::fmt::Debug {
+    /// A config file at `path` was parsed.
+    fn file_parsed(&self, _path: &Path) {}
+
+    /// The shader at `path` was loaded and parsed.
+    fn shader_reflected(&self, _path: &str) {}
+
+    /// A file was read through the loader, eg. a shader or a file a shader
+    /// imports. `path` is where the loader resolved it to.
+    fn file_read(&self, _path: &Path) {}
+
+    /// Something in the config or a shader will likely fail at runtime even
+    /// though code could be generated for it.
+    fn warning(&self, _message: &str) {}
+
+    /// The code for the pipeline called `name` was generated. `done` counts
+    /// the pipelines generated so far, out of `total`.
+    fn pipeline_generated(&self, _name: &str, _done: usize, _total: usize) {}
+
+    /// `len` bytes of output were written to `path`.
+    fn bytes_written(&self, _path: &Path, _len: usize) {}
+}
+
+/// Collects every file that's parsed or read while generating code, for
+/// build scripts to tell cargo about:
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// let tracker = Arc::new(code_gen::DependencyTracker::default());
+/// let options = code_gen::CodegenOptions {
+///     observer: Some(tracker.clone()),
+///     ..Default::default()
+/// };
+/// let results = code_gen::generate_many(&["shaders/scene.pmd".into()], &options);
+/// tracker.print_rerun_if_changed();
+/// ```
+#[derive(Debug, Default)]
+pub struct DependencyTracker {
+    files: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl DependencyTracker {
+    /// The files seen so far, sorted and without duplicates.
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.files.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Prints a `cargo:rerun-if-changed` line for each file, so cargo reruns
+    /// the build script when any of them change.
+    pub fn print_rerun_if_changed(&self) {
+        for file in self.files.lock().unwrap().iter() {
+            println!("cargo:rerun-if-changed={}", file.display());
+        }
+    }
+
+    /// A Makefile style depfile saying `targets` depend on every file, like
+    /// the ones `gcc -MD` writes, for build systems like Make and Ninja.
+    pub fn depfile(&self, targets: &[&Path]) -> String {
+        let targets = targets
+            .iter()
+            .map(|target| escape_make(target))
+            .collect::<Vec<_>>();
+        let mut depfile = format!("{}:", targets.join(" "));
+        for file in self.files.lock().unwrap().iter() {
+            depfile.push_str(" \\\n  ");
+            depfile.push_str(&escape_make(file));
+        }
+        depfile.push('\n');
+        depfile
+    }
+
+    /// Writes [DependencyTracker::depfile] to `path`.
+    pub fn write_depfile(&self, path: &Path, targets: &[&Path]) -> std::io::Result<()> {
+        std::fs::write(path, self.depfile(targets))
+    }
+}
+
+/// Escapes the characters Make treats specially in a path.
+fn escape_make(path: &Path) -> String {
+    let mut escaped = String::new();
+    for c in path.to_string_lossy().chars() {
+        match c {
+            ' ' | '#' => escaped.push('\\'),
+            '$' => escaped.push('$'),
+            _ => (),
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl ProgressObserver for DependencyTracker {
+    fn file_parsed(&self, path: &Path) {
+        self.files.lock().unwrap().insert(path.to_owned());
+    }
+
+    fn file_read(&self, path: &Path) {
+        self.files.lock().unwrap().insert(path.to_owned());
+    }
+}
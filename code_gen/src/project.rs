@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::{CodegenMode, CodegenOptions, SourceEmbedding, Visibility};
+
+/// The name of the project file that holds codegen settings.
+pub const PROJECT_FILE: &str = "pipemd.toml";
+
+/// Codegen settings shared by every config in a project, read from
+/// `pipemd.toml`:
+///
+/// ```toml
+/// [profile.debug]
+/// registry = true
+///
+/// [profile.release]
+/// labels = false
+/// source_embedding = "include_str"
+/// ```
+///
+/// A profile is picked with [ProjectConfig::options], or from cargo's
+/// `PROFILE` with [ProjectConfig::options_from_env] in build scripts, so one
+/// project file serves both debug and release builds.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    #[serde(default, rename = "profile")]
+    pub profiles: BTreeMap<String, ProfileOptions>,
+}
+
+/// The [CodegenOptions] a profile sets. Anything left out keeps its default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileOptions {
+    pub mode: Option<CodegenMode>,
+    pub registry: Option<bool>,
+    pub warmup: Option<bool>,
+    pub labels: Option<bool>,
+    pub source_embedding: Option<SourceEmbedding>,
+    pub max_inline_source_len: Option<usize>,
+    pub bytemuck: Option<bool>,
+    pub visibility: Option<Visibility>,
+    pub tests: Option<bool>,
+    pub prune_shaders: Option<bool>,
+    pub minify_shaders: Option<bool>,
+    pub runtime_shaders: Option<bool>,
+    pub source_locations: Option<bool>,
+}
+
+impl ProjectConfig {
+    pub fn from_toml(src: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(src)
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::from_toml(&src)
+            .with_context(|| format!("{} is not a valid project file", path.display()))
+    }
+
+    /// The options for `profile`. Profiles that aren't in the file use the
+    /// defaults.
+    pub fn options(&self, profile: &str) -> CodegenOptions {
+        let mut options = CodegenOptions::default();
+        if let Some(profile) = self.profiles.get(profile) {
+            profile.apply(&mut options);
+        }
+        options
+    }
+
+    /// The options for the profile cargo is building with, taken from the
+    /// `PROFILE` variable it gives build scripts. Falls back to `debug`.
+    pub fn options_from_env(&self) -> CodegenOptions {
+        let profile = std::env::var("PROFILE").unwrap_or_else(|_| "debug".to_owned());
+        self.options(&profile)
+    }
+}
+
+impl ProfileOptions {
+    fn apply(&self, options: &mut CodegenOptions) {
+        if let Some(mode) = self.mode {
+            options.mode = mode;
+        }
+        if let Some(registry) = self.registry {
+            options.registry = registry;
+        }
+        if let Some(warmup) = self.warmup {
+            options.warmup = warmup;
+        }
+        if let Some(labels) = self.labels {
+            options.labels = labels;
+        }
+        if let Some(source_embedding) = self.source_embedding {
+            options.source_embedding = source_embedding;
+        }
+        if let Some(len) = self.max_inline_source_len {
+            options.max_inline_source_len = len;
+        }
+        if let Some(bytemuck) = self.bytemuck {
+            options.bytemuck = bytemuck;
+        }
+        if let Some(visibility) = self.visibility {
+            options.visibility = visibility;
+        }
+        if let Some(tests) = self.tests {
+            options.tests = tests;
+        }
+        if let Some(prune_shaders) = self.prune_shaders {
+            options.prune_shaders = prune_shaders;
+        }
+        if let Some(minify_shaders) = self.minify_shaders {
+            options.minify_shaders = minify_shaders;
+        }
+        if let Some(runtime_shaders) = self.runtime_shaders {
+            options.runtime_shaders = runtime_shaders;
+        }
+        if let Some(source_locations) = self.source_locations {
+            options.source_locations = source_locations;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles() {
+        let project = ProjectConfig::from_toml(
+            r#"
+            [profile.debug]
+            registry = true
+            runtime_shaders = true
+
+            [profile.release]
+            labels = false
+            source_embedding = "include_str"
+            mode = "constants_only"
+            visibility = "crate"
+            tests = true
+            prune_shaders = true
+            minify_shaders = true
+            "#,
+        )
+        .unwrap();
+        let debug = project.options("debug");
+        assert!(debug.registry);
+        assert!(debug.labels);
+        let release = project.options("release");
+        assert!(!release.registry);
+        assert!(!release.labels);
+        assert_eq!(SourceEmbedding::IncludeStr, release.source_embedding);
+        assert_eq!(CodegenMode::ConstantsOnly, release.mode);
+        assert_eq!(Visibility::Crate, release.visibility);
+        assert_eq!(Visibility::Public, debug.visibility);
+        assert!(release.tests && !debug.tests);
+        assert!(release.prune_shaders && !debug.prune_shaders);
+        assert!(release.minify_shaders && !debug.minify_shaders);
+        assert!(debug.runtime_shaders && !release.runtime_shaders);
+        assert!(!project.options("bench").registry);
+
+        assert!(ProjectConfig::from_toml("[profile.debug]\nhot_reload = true").is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! Removing the entry points none of a config's pipelines use from the
+//! shaders it embeds, along with the functions only they call.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use naga::{Arena, Block, Expression, Function, Handle, Module, Span, Statement};
+
+/// Returns `module` with only the entry points named in `used` and the
+/// functions they call, or `None` if that's everything. Types, constants
+/// and global variables are all kept, since naga's frontend refers to every
+/// global from every function whether it's used or not.
+pub(crate) fn prune(module: Module, used: &BTreeSet<String>) -> Option<Module> {
+    let mut functions = HashSet::new();
+    let mut pending = module
+        .entry_points
+        .iter()
+        .filter(|ep| used.contains(&ep.name))
+        .map(|ep| &ep.function)
+        .collect::<Vec<_>>();
+    let entry_point_count = pending.len();
+    while let Some(function) = pending.pop() {
+        let mut callees = Vec::new();
+        visit_calls(&function.body, &mut |callee| callees.push(*callee));
+        for callee in callees {
+            if functions.insert(callee) {
+                pending.push(&module.functions[callee]);
+            }
+        }
+    }
+    if entry_point_count == module.entry_points.len() && functions.len() == module.functions.len() {
+        return None;
+    }
+
+    let mut pruned = Module::default();
+    let mut remapped = HashMap::new();
+    // Functions come before their callers, so callees are always remapped
+    // first.
+    for (handle, span, mut function) in into_items(module.functions) {
+        if functions.contains(&handle) {
+            remap_calls(&mut function, &remapped);
+            remapped.insert(handle, pruned.functions.append(function, span));
+        }
+    }
+    pruned.entry_points = module
+        .entry_points
+        .into_iter()
+        .filter(|ep| used.contains(&ep.name))
+        .collect();
+    for ep in &mut pruned.entry_points {
+        remap_calls(&mut ep.function, &remapped);
+    }
+    pruned.types = module.types;
+    pruned.constants = module.constants;
+    pruned.global_variables = module.global_variables;
+    Some(pruned)
+}
+
+/// Takes the items out of `arena` along with their handles and spans.
+fn into_items<T>(arena: Arena<T>) -> Vec<(Handle<T>, Span, T)> {
+    let handles = arena
+        .iter()
+        .map(|(handle, _)| (handle, arena.get_span(handle)))
+        .collect::<Vec<_>>();
+    handles
+        .into_iter()
+        .zip(arena.into_inner())
+        .map(|((handle, span), item)| (handle, span, item))
+        .collect()
+}
+
+/// Calls `f` with the function of every call in `block`.
+fn visit_calls<'b>(block: &'b Block, f: &mut dyn FnMut(&'b Handle<Function>)) {
+    for statement in block.iter() {
+        match statement {
+            Statement::Call { function, .. } => f(function),
+            Statement::Block(block) => visit_calls(block, f),
+            Statement::If { accept, reject, .. } => {
+                visit_calls(accept, f);
+                visit_calls(reject, f);
+            }
+            Statement::Switch { cases, .. } => {
+                cases.iter().for_each(|case| visit_calls(&case.body, f))
+            }
+            Statement::Loop {
+                body, continuing, ..
+            } => {
+                visit_calls(body, f);
+                visit_calls(continuing, f);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Points the calls in `function` at the pruned module's functions, which
+/// `remapped` holds keyed by their handles in the original one.
+fn remap_calls(function: &mut Function, remapped: &HashMap<Handle<Function>, Handle<Function>>) {
+    for (_, expression) in function.expressions.iter_mut() {
+        if let Expression::CallResult(callee) = expression {
+            *callee = remapped[callee];
+        }
+    }
+    remap_block(&mut function.body, remapped);
+}
+
+fn remap_block(block: &mut Block, remapped: &HashMap<Handle<Function>, Handle<Function>>) {
+    for statement in block.iter_mut() {
+        match statement {
+            Statement::Call { function, .. } => *function = remapped[function],
+            Statement::Block(block) => remap_block(block, remapped),
+            Statement::If { accept, reject, .. } => {
+                remap_block(accept, remapped);
+                remap_block(reject, remapped);
+            }
+            Statement::Switch { cases, .. } => cases
+                .iter_mut()
+                .for_each(|case| remap_block(&mut case.body, remapped)),
+            Statement::Loop {
+                body, continuing, ..
+            } => {
+                remap_block(body, remapped);
+                remap_block(continuing, remapped);
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = "
+        @group(0) @binding(0) var<uniform> tint: vec4<f32>;
+        @group(0) @binding(1) var<uniform> debug_tint: vec4<f32>;
+
+        fn shade() -> vec4<f32> { return tint; }
+        fn debug_shade() -> vec4<f32> { return debug_tint; }
+
+        @vertex fn vs_main() -> @builtin(position) vec4<f32> { return vec4(0.0); }
+        @fragment fn fs_main() -> @location(0) vec4<f32> { return shade(); }
+        @fragment fn fs_debug() -> @location(0) vec4<f32> { return debug_shade() * shade(); }
+    ";
+
+    #[test]
+    fn prune_unused_entry_points() {
+        let module = || naga::front::wgsl::parse_str(SRC).unwrap();
+        let used = BTreeSet::from(["vs_main".to_owned(), "fs_main".to_owned()]);
+        let pruned = prune(module(), &used).unwrap();
+        let names = |module: &Module| {
+            module
+                .entry_points
+                .iter()
+                .map(|ep| ep.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["vs_main", "fs_main"], names(&pruned));
+        let function_names = pruned
+            .functions
+            .iter()
+            .map(|(_, f)| f.name.clone().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["shade"], function_names);
+        // The pruned module is still valid.
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&pruned)
+        .unwrap();
+
+        let used = BTreeSet::from([
+            "vs_main".to_owned(),
+            "fs_main".to_owned(),
+            "fs_debug".to_owned(),
+        ]);
+        assert!(prune(module(), &used).is_none());
+    }
+}
@@ -0,0 +1,1238 @@
+//! Works out how a pipeline's textures and samplers need to be bound, what
+//! its vertex buffers hold and which color targets it writes, from the
+//! shader's naga module.
+
+use std::collections::{BTreeSet, HashMap};
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, ToTokens};
+
+use crate::{variants, BindingConfig};
+
+/// Mirrors `wgpu::TextureSampleType`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SampleType {
+    Float { filterable: bool },
+    Depth,
+    Sint,
+    Uint,
+}
+
+impl SampleType {
+    /// `name` is one of [variants::TEXTURE_SAMPLE_TYPES].
+    fn from_name(name: &str) -> Self {
+        match name {
+            "Float" => Self::Float { filterable: true },
+            "UnfilterableFloat" => Self::Float { filterable: false },
+            "Depth" => Self::Depth,
+            "Sint" => Self::Sint,
+            "Uint" => Self::Uint,
+            _ => unreachable!("{} isn't a sample type", name),
+        }
+    }
+
+    /// `format` is one of [variants::TEXTURE_FORMATS].
+    fn from_format(format: &str) -> Self {
+        if format.starts_with("Depth") {
+            Self::Depth
+        } else if format.ends_with("Sint") {
+            Self::Sint
+        } else if format.ends_with("Uint") {
+            Self::Uint
+        } else {
+            Self::Float {
+                filterable: !variants::UNFILTERABLE_FLOAT_FORMATS.contains(&format),
+            }
+        }
+    }
+
+    /// Whether a texture declared with `class` can be bound with this.
+    fn matches(self, class: naga::ImageClass) -> bool {
+        matches!(
+            (self, class),
+            (Self::Depth, naga::ImageClass::Depth { .. })
+                | (
+                    Self::Float { .. },
+                    naga::ImageClass::Sampled {
+                        kind: naga::ScalarKind::Float,
+                        ..
+                    }
+                )
+                | (
+                    Self::Sint,
+                    naga::ImageClass::Sampled {
+                        kind: naga::ScalarKind::Sint,
+                        ..
+                    }
+                )
+                | (
+                    Self::Uint,
+                    naga::ImageClass::Sampled {
+                        kind: naga::ScalarKind::Uint,
+                        ..
+                    }
+                )
+        )
+    }
+}
+
+impl ToTokens for SampleType {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.extend(match self {
+            Self::Float { filterable } => {
+                quote! { ::wgpu::TextureSampleType::Float { filterable: #filterable } }
+            }
+            Self::Depth => quote! { ::wgpu::TextureSampleType::Depth },
+            Self::Sint => quote! { ::wgpu::TextureSampleType::Sint },
+            Self::Uint => quote! { ::wgpu::TextureSampleType::Uint },
+        })
+    }
+}
+
+/// Mirrors `wgpu::SamplerBindingType`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SamplerType {
+    Filtering,
+    NonFiltering,
+    Comparison,
+}
+
+impl ToTokens for SamplerType {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.extend(match self {
+            Self::Filtering => quote! { ::wgpu::SamplerBindingType::Filtering },
+            Self::NonFiltering => quote! { ::wgpu::SamplerBindingType::NonFiltering },
+            Self::Comparison => quote! { ::wgpu::SamplerBindingType::Comparison },
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Binding<T> {
+    /// The name of the global variable.
+    pub name: String,
+    pub group: u32,
+    pub binding: u32,
+    pub ty: T,
+}
+
+/// What a bind group layout entry binds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum EntryType {
+    Buffer {
+        uniform: bool,
+        read_only: bool,
+        /// The size bound at each offset, for buffers bound with dynamic
+        /// offsets.
+        dynamic_size: Option<u32>,
+    },
+    Texture {
+        sample_type: SampleType,
+        /// A `wgpu::TextureViewDimension`, eg. `D2Array`.
+        dimension: &'static str,
+        multisampled: bool,
+    },
+    StorageTexture {
+        /// A `wgpu::StorageTextureAccess`, eg. `WriteOnly`.
+        access: &'static str,
+        /// A `wgpu::TextureFormat`, eg. `Rgba16Float`.
+        format: String,
+        dimension: &'static str,
+    },
+    Sampler(SamplerType),
+}
+
+impl ToTokens for EntryType {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.extend(match self {
+            Self::Buffer {
+                uniform: true,
+                dynamic_size: Some(size),
+                ..
+            } => {
+                let size = *size as u64;
+                quote! {
+                    ::wgpu::BindingType::Buffer {
+                        ty: ::wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: ::std::num::NonZeroU64::new(#size),
+                    }
+                }
+            }
+            Self::Buffer { uniform: true, .. } => quote! {
+                ::wgpu::BindingType::Buffer {
+                    ty: ::wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                }
+            },
+            Self::Buffer { read_only, .. } => quote! {
+                ::wgpu::BindingType::Buffer {
+                    ty: ::wgpu::BufferBindingType::Storage { read_only: #read_only },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                }
+            },
+            Self::Texture {
+                sample_type,
+                dimension,
+                multisampled,
+            } => {
+                let dimension = format_ident!("{}", dimension);
+                quote! {
+                    ::wgpu::BindingType::Texture {
+                        sample_type: #sample_type,
+                        view_dimension: ::wgpu::TextureViewDimension::#dimension,
+                        multisampled: #multisampled,
+                    }
+                }
+            }
+            Self::StorageTexture {
+                access,
+                format,
+                dimension,
+            } => {
+                let (access, format, dimension) = (
+                    format_ident!("{}", access),
+                    format_ident!("{}", format),
+                    format_ident!("{}", dimension),
+                );
+                quote! {
+                    ::wgpu::BindingType::StorageTexture {
+                        access: ::wgpu::StorageTextureAccess::#access,
+                        format: ::wgpu::TextureFormat::#format,
+                        view_dimension: ::wgpu::TextureViewDimension::#dimension,
+                    }
+                }
+            }
+            Self::Sampler(ty) => quote! { ::wgpu::BindingType::Sampler(#ty) },
+        })
+    }
+}
+
+/// A resource used by some entry points, which their pipeline layout needs
+/// an entry for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LayoutEntry {
+    /// The name of the global variable.
+    pub name: String,
+    pub group: u32,
+    pub binding: u32,
+    /// The stages of the entry points that use it.
+    pub visibility: Vec<naga::ShaderStage>,
+    pub ty: EntryType,
+    /// The length of a binding array.
+    pub count: Option<u32>,
+}
+
+/// The `var<push_constant>` used by some entry points.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PushConstants {
+    pub ty: naga::Handle<naga::Type>,
+    /// The name of its type, if it's a struct.
+    pub struct_name: Option<String>,
+    pub size: u32,
+    /// The stages of the entry points that use it.
+    pub stages: Vec<naga::ShaderStage>,
+}
+
+/// The textures and samplers used by some entry points, ordered by group and
+/// binding.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Bindings {
+    pub textures: Vec<Binding<SampleType>>,
+    pub samplers: Vec<Binding<SamplerType>>,
+    /// Every resource used, including buffers and storage textures.
+    pub entries: Vec<LayoutEntry>,
+    /// The uniform buffers used, with the type each holds.
+    pub uniforms: Vec<Binding<naga::Handle<naga::Type>>>,
+    /// The storage buffers used, with the type each holds.
+    pub storage: Vec<Binding<naga::Handle<naga::Type>>>,
+    pub push_constants: Option<PushConstants>,
+    /// Combinations that are valid as far as the shader is concerned, but
+    /// will fail when the pipeline is used.
+    pub warnings: Vec<String>,
+}
+
+/// Reflects the texture and sampler bindings used by `entry_points`, along
+/// with the layout entries of every resource they use.
+///
+/// Float textures are filterable if a filtering sampler samples them. Those
+/// that are only loaded from are left unfilterable, which accepts any float
+/// format. Samplers filter unless they're comparison samplers. `overrides`
+/// take precedence over both, keyed by the name of the binding.
+pub(crate) fn reflect_bindings(
+    module: &naga::Module,
+    entry_points: &[&str],
+    overrides: &[(String, BindingConfig)],
+) -> Result<Bindings, String> {
+    // The analysis the validator does is what says which globals are used.
+    // Expressions can't be relied on for that, since the WGSL frontend adds
+    // one for every global to every function.
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::empty(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(module)
+    .map_err(|e| e.into_inner().to_string())?;
+    // Ordered sets, so functions are visited in the same order every time.
+    let mut used = BTreeSet::new();
+    let mut stages = HashMap::<_, Vec<naga::ShaderStage>>::new();
+    let mut functions = Vec::new();
+    let mut called = BTreeSet::new();
+    for (i, ep) in module.entry_points.iter().enumerate() {
+        if !entry_points.contains(&ep.name.as_str()) {
+            continue;
+        }
+        let ep_info = info.get_entry_point(i);
+        for (handle, _) in module
+            .global_variables
+            .iter()
+            .filter(|(h, _)| !ep_info[*h].is_empty())
+        {
+            used.insert(handle);
+            let stages = stages.entry(handle).or_default();
+            if !stages.contains(&ep.stage) {
+                stages.push(ep.stage);
+            }
+        }
+        functions.push(&ep.function);
+        collect_calls(&ep.function.body, &mut called);
+    }
+    // Calls are collected until no new functions are found.
+    let mut visited = BTreeSet::new();
+    while let Some(&handle) = called.iter().find(|h| !visited.contains(*h)) {
+        visited.insert(handle);
+        let function = &module.functions[handle];
+        functions.push(function);
+        collect_calls(&function.body, &mut called);
+    }
+
+    let mut pairs = Vec::new();
+    for function in &functions {
+        for (_, expr) in function.expressions.iter() {
+            if let naga::Expression::ImageSample { image, sampler, .. } = *expr {
+                if let (Some(image), Some(sampler)) =
+                    (global_of(function, image), global_of(function, sampler))
+                {
+                    pairs.push((image, sampler));
+                }
+            }
+        }
+    }
+
+    let binding_override = |name: &str| {
+        overrides
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, b)| b)
+    };
+    let mut bindings = Bindings::default();
+    let mut globals = used.into_iter().collect::<Vec<_>>();
+    globals.sort_by_key(|h| {
+        module.global_variables[*h]
+            .binding
+            .as_ref()
+            .map(|b| (b.group, b.binding))
+    });
+    for handle in globals {
+        let global = &module.global_variables[handle];
+        if global.space == naga::AddressSpace::PushConstant {
+            let struct_name = match module.types[global.ty].inner {
+                naga::TypeInner::Struct { .. } => module.types[global.ty].name.clone(),
+                _ => None,
+            };
+            bindings.push_constants = Some(PushConstants {
+                ty: global.ty,
+                struct_name,
+                size: module.types[global.ty].inner.size(&module.constants),
+                stages: stages[&handle].clone(),
+            });
+            continue;
+        }
+        let (Some(name), Some(binding)) = (&global.name, &global.binding) else {
+            continue;
+        };
+        let mut inner = &module.types[global.ty].inner;
+        let mut count = None;
+        if let naga::TypeInner::BindingArray { base, size } = *inner {
+            inner = &module.types[base].inner;
+            count = Some(array_length(module, size).ok_or_else(|| {
+                format!(
+                    "`{}` is a binding array without a constant length, which can't be reflected",
+                    name
+                )
+            })?);
+        }
+        let mut entry = |ty| {
+            bindings.entries.push(LayoutEntry {
+                name: name.clone(),
+                group: binding.group,
+                binding: binding.binding,
+                visibility: stages[&handle].clone(),
+                ty,
+                count,
+            })
+        };
+        let config = binding_override(name);
+        match *inner {
+            naga::TypeInner::Image {
+                class: naga::ImageClass::Storage { format, access },
+                dim,
+                arrayed,
+            } => {
+                if config.is_some() {
+                    return Err(format!(
+                        "`{}` is a storage texture, so its binding can't be configured",
+                        name
+                    ));
+                }
+                let access = match (
+                    access.contains(naga::StorageAccess::LOAD),
+                    access.contains(naga::StorageAccess::STORE),
+                ) {
+                    (true, true) => "ReadWrite",
+                    (true, false) => "ReadOnly",
+                    _ => "WriteOnly",
+                };
+                entry(EntryType::StorageTexture {
+                    access,
+                    format: format!("{:?}", format),
+                    dimension: view_dimension(dim, arrayed),
+                });
+            }
+            naga::TypeInner::Image {
+                class,
+                dim,
+                arrayed,
+            } => {
+                let reflected = match class {
+                    naga::ImageClass::Sampled {
+                        kind: naga::ScalarKind::Sint,
+                        ..
+                    } => Some(SampleType::Sint),
+                    naga::ImageClass::Sampled {
+                        kind: naga::ScalarKind::Uint,
+                        ..
+                    } => Some(SampleType::Uint),
+                    // Float textures depend on how they're sampled.
+                    naga::ImageClass::Sampled { .. } => None,
+                    naga::ImageClass::Depth { .. } => Some(SampleType::Depth),
+                    naga::ImageClass::Storage { .. } => {
+                        unreachable!("storage textures are matched above")
+                    }
+                };
+                if config.is_some_and(|c| c.filtering.is_some() || c.dynamic.is_some()) {
+                    return Err(format!(
+                        "`{}` is a texture, so it can't set `filtering` or `dynamic`",
+                        name
+                    ));
+                }
+                let format = config.and_then(|c| c.format.as_deref());
+                let given = match config.and_then(|c| c.sample_type.as_deref()) {
+                    Some(sample_type) => Some(SampleType::from_name(sample_type)),
+                    // Depth formats read as unfilterable floats through
+                    // `texture_2d<f32>`.
+                    None => format.map(|format| match (SampleType::from_format(format), class) {
+                        (SampleType::Depth, naga::ImageClass::Sampled { .. }) => {
+                            SampleType::Float { filterable: false }
+                        }
+                        (ty, _) => ty,
+                    }),
+                };
+                if let Some(given) = given.filter(|ty| !ty.matches(class)) {
+                    return Err(format!(
+                        "`{}` is declared as {:?}, which can't be bound as {:?}",
+                        name, class, given
+                    ));
+                }
+                let samplers = pairs
+                    .iter()
+                    .filter(|(image, _)| *image == handle)
+                    .filter_map(|(_, sampler)| module.global_variables[*sampler].name.as_deref())
+                    .filter(|sampler| {
+                        sampler_type(module, overrides, sampler) == Some(SamplerType::Filtering)
+                    })
+                    .collect::<Vec<_>>();
+                let ty = given.or(reflected).unwrap_or(SampleType::Float {
+                    filterable: !samplers.is_empty(),
+                });
+                if let (SampleType::Float { filterable: false }, Some(sampler)) =
+                    (ty, samplers.first())
+                {
+                    let reason = match format {
+                        Some(format) if variants::UNFILTERABLE_FLOAT_FORMATS.contains(&format) => format!(
+                            "{} is only filterable with `TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES` on adapters that \
+                            support it",
+                            format
+                        ),
+                        _ => "it's configured as unfilterable".to_owned(),
+                    };
+                    bindings.warnings.push(format!(
+                        "`{}` is sampled with the filtering sampler `{}`, but {}",
+                        name, sampler, reason
+                    ));
+                }
+                let multisampled = matches!(
+                    class,
+                    naga::ImageClass::Sampled { multi: true, .. }
+                        | naga::ImageClass::Depth { multi: true }
+                );
+                entry(EntryType::Texture {
+                    sample_type: ty,
+                    dimension: view_dimension(dim, arrayed),
+                    multisampled,
+                });
+                bindings.textures.push(Binding {
+                    name: name.clone(),
+                    group: binding.group,
+                    binding: binding.binding,
+                    ty,
+                });
+            }
+            naga::TypeInner::Sampler { .. } => {
+                if config.is_some_and(|c| {
+                    c.sample_type.is_some() || c.format.is_some() || c.dynamic.is_some()
+                }) {
+                    return Err(format!(
+                        "`{}` is a sampler, so it can only set `filtering`",
+                        name
+                    ));
+                }
+                let ty = sampler_type(module, overrides, name).unwrap();
+                entry(EntryType::Sampler(ty));
+                bindings.samplers.push(Binding {
+                    name: name.clone(),
+                    group: binding.group,
+                    binding: binding.binding,
+                    ty,
+                });
+            }
+            _ => {
+                match global.space {
+                    naga::AddressSpace::Uniform => {
+                        if config.is_some_and(|c| {
+                            c.sample_type.is_some() || c.format.is_some() || c.filtering.is_some()
+                        }) {
+                            return Err(format!(
+                                "`{}` is a uniform buffer, so it can only set `dynamic`",
+                                name
+                            ));
+                        }
+                        let dynamic = config.and_then(|c| c.dynamic).unwrap_or(false);
+                        if dynamic && count.is_some() {
+                            return Err(format!("`{}` is a binding array, so it can't be bound with dynamic offsets", name));
+                        }
+                        entry(EntryType::Buffer {
+                            uniform: true,
+                            read_only: true,
+                            dynamic_size: dynamic
+                                .then(|| module.types[global.ty].inner.size(&module.constants)),
+                        });
+                        bindings.uniforms.push(Binding {
+                            name: name.clone(),
+                            group: binding.group,
+                            binding: binding.binding,
+                            ty: global.ty,
+                        });
+                    }
+                    naga::AddressSpace::Storage { access } => {
+                        entry(EntryType::Buffer {
+                            uniform: false,
+                            read_only: !access.contains(naga::StorageAccess::STORE),
+                            dynamic_size: None,
+                        });
+                        bindings.storage.push(Binding {
+                            name: name.clone(),
+                            group: binding.group,
+                            binding: binding.binding,
+                            ty: global.ty,
+                        });
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    for (name, _) in overrides {
+        let found = bindings.textures.iter().any(|b| b.name == *name)
+            || bindings.samplers.iter().any(|b| b.name == *name)
+            || bindings.uniforms.iter().any(|b| b.name == *name);
+        if !found {
+            return Err(format!(
+                "`bindings` names `{}`, which isn't a texture, sampler or uniform buffer used by the pipeline's entry points",
+                name
+            ));
+        }
+    }
+    Ok(bindings)
+}
+
+/// A `@location` input of a vertex entry point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct VertexInput {
+    /// The name of the argument or struct member.
+    pub name: String,
+    pub location: u32,
+    /// The `wgpu::VertexFormat` matching the input's type, eg. `Float32x2`.
+    pub format: &'static str,
+}
+
+/// Reflects the `@location` inputs of the vertex entry point `entry`, either
+/// given as arguments or as members of struct arguments, in location order.
+pub(crate) fn reflect_vertex_inputs(
+    module: &naga::Module,
+    entry: &str,
+) -> Result<Vec<VertexInput>, String> {
+    let ep = module
+        .entry_points
+        .iter()
+        .find(|ep| ep.name == entry && ep.stage == naga::ShaderStage::Vertex)
+        .ok_or_else(|| format!("there's no vertex entry point {:?}", entry))?;
+    let mut inputs = Vec::new();
+    let mut add =
+        |name: &Option<String>, binding: &Option<naga::Binding>, ty: naga::Handle<naga::Type>| {
+            if let Some(naga::Binding::Location { location, .. }) = *binding {
+                let format = vertex_format(&module.types[ty].inner).ok_or_else(|| {
+                    format!(
+                        "the input at location {} can't be read from a vertex buffer",
+                        location
+                    )
+                })?;
+                let name = name
+                    .clone()
+                    .unwrap_or_else(|| format!("location{}", location));
+                inputs.push(VertexInput {
+                    name,
+                    location,
+                    format,
+                });
+            }
+            Ok::<_, String>(())
+        };
+    for argument in &ep.function.arguments {
+        match &module.types[argument.ty].inner {
+            naga::TypeInner::Struct { members, .. } if argument.binding.is_none() => {
+                for member in members {
+                    add(&member.name, &member.binding, member.ty)?;
+                }
+            }
+            _ => add(&argument.name, &argument.binding, argument.ty)?,
+        }
+    }
+    inputs.sort_by_key(|input| input.location);
+    Ok(inputs)
+}
+
+/// Reflects the `@location`s the fragment entry point `entry` writes, either
+/// as its result or as members of a struct result, in location order.
+pub(crate) fn reflect_fragment_outputs(
+    module: &naga::Module,
+    entry: &str,
+) -> Result<Vec<u32>, String> {
+    let ep = module
+        .entry_points
+        .iter()
+        .find(|ep| ep.name == entry && ep.stage == naga::ShaderStage::Fragment)
+        .ok_or_else(|| format!("there's no fragment entry point {:?}", entry))?;
+    let Some(result) = &ep.function.result else {
+        return Ok(Vec::new());
+    };
+    let location = |binding: &Option<naga::Binding>| match *binding {
+        Some(naga::Binding::Location { location, .. }) => Some(location),
+        _ => None,
+    };
+    let mut outputs = match &module.types[result.ty].inner {
+        naga::TypeInner::Struct { members, .. } if result.binding.is_none() => members
+            .iter()
+            .filter_map(|member| location(&member.binding))
+            .collect(),
+        _ => location(&result.binding).into_iter().collect::<Vec<_>>(),
+    };
+    outputs.sort_unstable();
+    Ok(outputs)
+}
+
+/// Fails unless every `@location` the fragment entry point `fs` reads is
+/// written by the vertex entry point `vs` with the same type and
+/// interpolation. Outputs the fragment stage ignores are fine.
+pub(crate) fn check_stage_interface(
+    module: &naga::Module,
+    vs: &str,
+    fs: &str,
+) -> Result<(), String> {
+    let find = |name: &str, stage| {
+        module
+            .entry_points
+            .iter()
+            .find(|ep| ep.name == name && ep.stage == stage)
+            .ok_or_else(|| format!("there's no {:?} entry point {:?}", stage, name))
+    };
+    let (vs_ep, fs_ep) = (
+        find(vs, naga::ShaderStage::Vertex)?,
+        find(fs, naga::ShaderStage::Fragment)?,
+    );
+    let outputs = vs_ep
+        .function
+        .result
+        .iter()
+        .flat_map(|result| locations(module, &result.binding, result.ty))
+        .collect::<Vec<_>>();
+    let mut inputs = fs_ep
+        .function
+        .arguments
+        .iter()
+        .flat_map(|argument| locations(module, &argument.binding, argument.ty))
+        .collect::<Vec<_>>();
+    inputs.sort_by_key(|input| input.0);
+    for (location, input, ty) in inputs {
+        let Some((_, output, output_ty)) = outputs.iter().find(|output| output.0 == location)
+        else {
+            return Err(format!(
+                "{} reads location {}, which {} doesn't write",
+                fs, location, vs
+            ));
+        };
+        let (ty, output_ty) = (&module.types[ty].inner, &module.types[*output_ty].inner);
+        if ty != output_ty {
+            return Err(format!(
+                "{} writes location {} as {}, but {} reads it as {}",
+                vs,
+                location,
+                type_name(output_ty),
+                fs,
+                type_name(ty)
+            ));
+        }
+        if input != *output {
+            return Err(format!(
+                "{} writes location {} with `@interpolate({})`, but {} reads it with `@interpolate({})`",
+                vs,
+                location,
+                interpolation_name(*output),
+                fs,
+                interpolation_name(input)
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A `@location`'s interpolation and sampling.
+type Interpolation = (Option<naga::Interpolation>, Option<naga::Sampling>);
+
+/// How an interpolation is written in WGSL, eg. `perspective, center`.
+fn interpolation_name((interpolation, sampling): Interpolation) -> String {
+    let name = match (interpolation, sampling) {
+        (Some(interpolation), Some(sampling)) => format!("{:?}, {:?}", interpolation, sampling),
+        (Some(interpolation), None) => format!("{:?}", interpolation),
+        (None, _) => "none".to_owned(),
+    };
+    name.to_lowercase()
+}
+
+/// The `@location`s of an argument or result, looking through structs.
+fn locations(
+    module: &naga::Module,
+    binding: &Option<naga::Binding>,
+    ty: naga::Handle<naga::Type>,
+) -> Vec<(u32, Interpolation, naga::Handle<naga::Type>)> {
+    let location = |binding: &Option<naga::Binding>, ty| match *binding {
+        Some(naga::Binding::Location {
+            location,
+            interpolation,
+            sampling,
+        }) => {
+            // Leaving out the sampling is the same as asking for `center`.
+            let sampling = match interpolation {
+                Some(naga::Interpolation::Perspective | naga::Interpolation::Linear) => {
+                    sampling.or(Some(naga::Sampling::Center))
+                }
+                _ => sampling,
+            };
+            Some((location, (interpolation, sampling), ty))
+        }
+        _ => None,
+    };
+    match &module.types[ty].inner {
+        naga::TypeInner::Struct { members, .. } if binding.is_none() => members
+            .iter()
+            .filter_map(|member| location(&member.binding, member.ty))
+            .collect(),
+        _ => location(binding, ty).into_iter().collect(),
+    }
+}
+
+/// How a scalar or vector type is written in WGSL, eg. `vec3<f32>`.
+fn type_name(ty: &naga::TypeInner) -> String {
+    let scalar = |kind, width| match (kind, width) {
+        (naga::ScalarKind::Float, 4) => "f32".to_owned(),
+        (naga::ScalarKind::Sint, 4) => "i32".to_owned(),
+        (naga::ScalarKind::Uint, 4) => "u32".to_owned(),
+        (naga::ScalarKind::Bool, _) => "bool".to_owned(),
+        (kind, width) => format!("{:?}{}", kind, width as u32 * 8),
+    };
+    match *ty {
+        naga::TypeInner::Scalar { kind, width } => scalar(kind, width),
+        naga::TypeInner::Vector { size, kind, width } => {
+            format!("vec{}<{}>", size as u32, scalar(kind, width))
+        }
+        ref ty => format!("{:?}", ty),
+    }
+}
+
+/// The 32 and 64 bit `wgpu::VertexFormat` a scalar or vector input is read
+/// as. Narrower formats need a `#vertex_layout`.
+fn vertex_format(ty: &naga::TypeInner) -> Option<&'static str> {
+    let (kind, width, size) = match *ty {
+        naga::TypeInner::Scalar { kind, width } => (kind, width, 1),
+        naga::TypeInner::Vector { size, kind, width } => (kind, width, size as usize),
+        _ => return None,
+    };
+    let formats: [&'static str; 4] = match (kind, width) {
+        (naga::ScalarKind::Float, 4) => ["Float32", "Float32x2", "Float32x3", "Float32x4"],
+        (naga::ScalarKind::Float, 8) => ["Float64", "Float64x2", "Float64x3", "Float64x4"],
+        (naga::ScalarKind::Uint, 4) => ["Uint32", "Uint32x2", "Uint32x3", "Uint32x4"],
+        (naga::ScalarKind::Sint, 4) => ["Sint32", "Sint32x2", "Sint32x3", "Sint32x4"],
+        _ => return None,
+    };
+    Some(formats[size - 1])
+}
+
+/// The `wgpu::TextureViewDimension` a texture declared with `dim` is viewed
+/// with.
+fn view_dimension(dim: naga::ImageDimension, arrayed: bool) -> &'static str {
+    match (dim, arrayed) {
+        (naga::ImageDimension::D1, _) => "D1",
+        (naga::ImageDimension::D2, false) => "D2",
+        (naga::ImageDimension::D2, true) => "D2Array",
+        (naga::ImageDimension::D3, _) => "D3",
+        (naga::ImageDimension::Cube, false) => "Cube",
+        (naga::ImageDimension::Cube, true) => "CubeArray",
+    }
+}
+
+/// The length of a constant sized array, or `None` if its length is only
+/// known at runtime.
+fn array_length(module: &naga::Module, size: naga::ArraySize) -> Option<u32> {
+    let naga::ArraySize::Constant(constant) = size else {
+        return None;
+    };
+    match module.constants[constant].inner {
+        naga::ConstantInner::Scalar {
+            value: naga::ScalarValue::Uint(n),
+            ..
+        } => u32::try_from(n).ok(),
+        naga::ConstantInner::Scalar {
+            value: naga::ScalarValue::Sint(n),
+            ..
+        } => u32::try_from(n).ok(),
+        _ => None,
+    }
+}
+
+/// The type of the sampler called `name`, or `None` if there's no such sampler.
+fn sampler_type(
+    module: &naga::Module,
+    overrides: &[(String, BindingConfig)],
+    name: &str,
+) -> Option<SamplerType> {
+    let (_, global) = module
+        .global_variables
+        .iter()
+        .find(|(_, g)| g.name.as_deref() == Some(name))?;
+    let naga::TypeInner::Sampler { comparison } = module.types[global.ty].inner else {
+        return None;
+    };
+    let filtering = overrides
+        .iter()
+        .rev()
+        .find(|(n, _)| n == name)
+        .and_then(|(_, b)| b.filtering);
+    Some(match (comparison, filtering) {
+        (true, _) => SamplerType::Comparison,
+        (false, Some(false)) => SamplerType::NonFiltering,
+        (false, _) => SamplerType::Filtering,
+    })
+}
+
+/// The global variable `expr` reads, looking through indexing into binding
+/// arrays. `None` if it comes from somewhere else, eg. a function argument.
+fn global_of(
+    function: &naga::Function,
+    expr: naga::Handle<naga::Expression>,
+) -> Option<naga::Handle<naga::GlobalVariable>> {
+    match function.expressions[expr] {
+        naga::Expression::GlobalVariable(global) => Some(global),
+        naga::Expression::Access { base, .. } | naga::Expression::AccessIndex { base, .. } => {
+            global_of(function, base)
+        }
+        _ => None,
+    }
+}
+
+fn collect_calls(block: &naga::Block, called: &mut BTreeSet<naga::Handle<naga::Function>>) {
+    for statement in block.iter() {
+        match statement {
+            naga::Statement::Call { function, .. } => {
+                called.insert(*function);
+            }
+            naga::Statement::Block(block) => collect_calls(block, called),
+            naga::Statement::If { accept, reject, .. } => {
+                collect_calls(accept, called);
+                collect_calls(reject, called);
+            }
+            naga::Statement::Switch { cases, .. } => {
+                for case in cases {
+                    collect_calls(&case.body, called);
+                }
+            }
+            naga::Statement::Loop {
+                body, continuing, ..
+            } => {
+                collect_calls(body, called);
+                collect_calls(continuing, called);
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = r#"
+        @group(0) @binding(0) var t_albedo: texture_2d<f32>;
+        @group(0) @binding(1) var s_linear: sampler;
+        @group(0) @binding(2) var t_positions: texture_2d<f32>;
+        @group(0) @binding(3) var t_ids: texture_2d<u32>;
+        @group(1) @binding(0) var t_shadow: texture_depth_2d;
+        @group(1) @binding(1) var s_shadow: sampler_comparison;
+        @group(2) @binding(0) var t_unused: texture_2d<f32>;
+
+        fn shadow(uv: vec2<f32>) -> f32 {
+            return textureSampleCompare(t_shadow, s_shadow, uv, 0.5);
+        }
+
+        @vertex
+        fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+            return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        }
+
+        @fragment
+        fn fs_main(@builtin(position) p: vec4<f32>) -> @location(0) vec4<f32> {
+            let uv = p.xy;
+            let id = textureLoad(t_ids, vec2<i32>(0, 0), 0);
+            let position = textureLoad(t_positions, vec2<i32>(0, 0), 0);
+            return textureSample(t_albedo, s_linear, uv) * shadow(uv) + position * f32(id.x);
+        }
+
+        @fragment
+        fn fs_unused() -> @location(0) vec4<f32> {
+            return textureLoad(t_unused, vec2<i32>(0, 0), 0);
+        }
+    "#;
+
+    fn reflect(overrides: &[(&str, BindingConfig)]) -> Result<Bindings, String> {
+        let module = naga::front::wgsl::parse_str(SRC).unwrap();
+        let overrides = overrides
+            .iter()
+            .map(|(n, b)| (n.to_string(), b.clone()))
+            .collect::<Vec<_>>();
+        reflect_bindings(&module, &["vs_main", "fs_main"], &overrides)
+    }
+
+    #[test]
+    fn reflect_bindings_infers_sample_types() {
+        let bindings = reflect(&[]).unwrap();
+        let textures = bindings
+            .textures
+            .iter()
+            .map(|b| (b.name.as_str(), b.group, b.binding, b.ty))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("t_albedo", 0, 0, SampleType::Float { filterable: true }),
+                ("t_positions", 0, 2, SampleType::Float { filterable: false }),
+                ("t_ids", 0, 3, SampleType::Uint),
+                ("t_shadow", 1, 0, SampleType::Depth),
+            ],
+            textures,
+        );
+        let samplers = bindings
+            .samplers
+            .iter()
+            .map(|b| (b.name.as_str(), b.ty))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("s_linear", SamplerType::Filtering),
+                ("s_shadow", SamplerType::Comparison)
+            ],
+            samplers
+        );
+        assert!(bindings.warnings.is_empty());
+    }
+
+    #[test]
+    fn reflect_bindings_overrides() {
+        let hdr = BindingConfig {
+            format: Some("Rgba32Float".to_owned()),
+            ..Default::default()
+        };
+        let bindings = reflect(&[("t_albedo", hdr.clone())]).unwrap();
+        assert_eq!(
+            SampleType::Float { filterable: false },
+            bindings.textures[0].ty
+        );
+        assert_eq!(1, bindings.warnings.len());
+        assert!(
+            bindings.warnings[0].contains("Rgba32Float"),
+            "{}",
+            bindings.warnings[0]
+        );
+
+        let nearest = BindingConfig {
+            filtering: Some(false),
+            ..Default::default()
+        };
+        let bindings = reflect(&[("t_albedo", hdr), ("s_linear", nearest.clone())]).unwrap();
+        assert_eq!(SamplerType::NonFiltering, bindings.samplers[0].ty);
+        assert!(bindings.warnings.is_empty());
+
+        let uint = BindingConfig {
+            sample_type: Some("Uint".to_owned()),
+            ..Default::default()
+        };
+        assert!(reflect(&[("t_albedo", uint)])
+            .unwrap_err()
+            .contains("can't be bound as Uint"));
+        assert!(reflect(&[("t_albedo", nearest.clone())])
+            .unwrap_err()
+            .contains("is a texture"));
+        assert!(reflect(&[("t_unused", nearest)])
+            .unwrap_err()
+            .contains("isn't a texture, sampler or uniform buffer"));
+    }
+
+    #[test]
+    fn stage_interface() {
+        let module = naga::front::wgsl::parse_str(
+            r#"
+            struct VSOut {
+                @builtin(position) clip_pos: vec4<f32>,
+                @location(0) uv: vec2<f32>,
+                @location(1) @interpolate(flat) id: u32,
+                @location(2) unused: f32,
+            }
+
+            @vertex
+            fn vs_main() -> VSOut {
+                return VSOut(vec4<f32>(0.0), vec2<f32>(0.0), 0u, 0.0);
+            }
+
+            @fragment
+            fn fs_ok(@location(1) @interpolate(flat) id: u32, @location(0) uv: vec2<f32>) -> @location(0) vec4<f32> {
+                return vec4<f32>(uv, f32(id), 1.0);
+            }
+
+            @fragment
+            fn fs_missing(@location(3) x: f32) -> @location(0) vec4<f32> {
+                return vec4<f32>(x);
+            }
+
+            @fragment
+            fn fs_type(@location(0) uv: vec3<f32>) -> @location(0) vec4<f32> {
+                return vec4<f32>(uv, 1.0);
+            }
+
+            @fragment
+            fn fs_interpolation(@location(0) @interpolate(linear) uv: vec2<f32>) -> @location(0) vec4<f32> {
+                return vec4<f32>(uv, 0.0, 1.0);
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(Ok(()), check_stage_interface(&module, "vs_main", "fs_ok"));
+        assert_eq!(
+            Err("fs_missing reads location 3, which vs_main doesn't write".to_owned()),
+            check_stage_interface(&module, "vs_main", "fs_missing")
+        );
+        assert_eq!(
+            Err(
+                "vs_main writes location 0 as vec2<f32>, but fs_type reads it as vec3<f32>"
+                    .to_owned()
+            ),
+            check_stage_interface(&module, "vs_main", "fs_type")
+        );
+        let err = check_stage_interface(&module, "vs_main", "fs_interpolation").unwrap_err();
+        assert!(
+            err.ends_with("but fs_interpolation reads it with `@interpolate(linear, center)`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn reflect_bindings_layout_entries() {
+        let module = naga::front::wgsl::parse_str(
+            r#"
+            struct Camera {
+                view_proj: mat4x4<f32>,
+            }
+
+            @group(0) @binding(0) var<uniform> camera: Camera;
+            @group(0) @binding(1) var<storage, read> weights: array<f32>;
+            @group(1) @binding(0) var t_output: texture_storage_2d<rgba16float, write>;
+            @group(1) @binding(1) var t_layers: binding_array<texture_2d<f32>, 4>;
+
+            @vertex
+            fn vs_main() -> @builtin(position) vec4<f32> {
+                return camera.view_proj * vec4<f32>(weights[0]);
+            }
+
+            @fragment
+            fn fs_main() -> @location(0) vec4<f32> {
+                textureStore(t_output, vec2<i32>(0, 0), camera.view_proj[0]);
+                return textureLoad(t_layers[0], vec2<i32>(0, 0), 0);
+            }
+            "#,
+        )
+        .unwrap();
+        let bindings = reflect_bindings(&module, &["vs_main", "fs_main"], &[]).unwrap();
+        let entries = bindings
+            .entries
+            .iter()
+            .map(|e| (e.group, e.binding, e.visibility.clone(), e.count))
+            .collect::<Vec<_>>();
+        use naga::ShaderStage::{Fragment, Vertex};
+        assert_eq!(
+            vec![
+                (0, 0, vec![Vertex, Fragment], None),
+                (0, 1, vec![Vertex], None),
+                (1, 0, vec![Fragment], None),
+                (1, 1, vec![Fragment], Some(4)),
+            ],
+            entries,
+        );
+        assert_eq!(
+            EntryType::Buffer {
+                uniform: true,
+                read_only: true,
+                dynamic_size: None
+            },
+            bindings.entries[0].ty
+        );
+        assert_eq!(
+            EntryType::Buffer {
+                uniform: false,
+                read_only: true,
+                dynamic_size: None
+            },
+            bindings.entries[1].ty
+        );
+        let dynamic = BindingConfig {
+            dynamic: Some(true),
+            ..Default::default()
+        };
+        let overrides = [("camera".to_owned(), dynamic.clone())];
+        let bindings = reflect_bindings(&module, &["vs_main", "fs_main"], &overrides).unwrap();
+        assert_eq!(
+            EntryType::Buffer {
+                uniform: true,
+                read_only: true,
+                dynamic_size: Some(64)
+            },
+            bindings.entries[0].ty
+        );
+        let overrides = [
+            ("t_output".to_owned(), dynamic.clone()),
+            ("weights".to_owned(), dynamic),
+        ];
+        let e = reflect_bindings(&module, &["vs_main", "fs_main"], &overrides).unwrap_err();
+        assert!(e.contains("storage texture"), "{}", e);
+        let e = reflect_bindings(&module, &["vs_main", "fs_main"], &overrides[1..]).unwrap_err();
+        assert!(
+            e.contains("isn't a texture, sampler or uniform buffer"),
+            "{}",
+            e
+        );
+        assert_eq!(
+            EntryType::StorageTexture {
+                access: "WriteOnly",
+                format: "Rgba16Float".to_owned(),
+                dimension: "D2",
+            },
+            bindings.entries[2].ty,
+        );
+    }
+
+    #[test]
+    fn reflect_vertex_inputs_in_location_order() {
+        let module = naga::front::wgsl::parse_str(
+            r#"
+            struct Vertex {
+                @location(2) color: vec4<f32>,
+                @location(0) position: vec3<f32>,
+            }
+
+            @vertex
+            fn vs_main(vertex: Vertex, @location(1) id: u32, @builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+                return vec4<f32>(vertex.position, 1.0);
+            }
+
+            @vertex
+            fn vs_bool(@location(0) flag: vec2<bool>) -> @builtin(position) vec4<f32> {
+                return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+            }
+            "#,
+        )
+        .unwrap();
+        let inputs = reflect_vertex_inputs(&module, "vs_main").unwrap();
+        let inputs = inputs
+            .iter()
+            .map(|input| (input.location, input.format))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![(0, "Float32x3"), (1, "Uint32"), (2, "Float32x4")],
+            inputs
+        );
+        assert!(reflect_vertex_inputs(&module, "vs_bool")
+            .unwrap_err()
+            .contains("location 0"));
+        assert!(reflect_vertex_inputs(&module, "vs_missing").is_err());
+    }
+
+    #[test]
+    fn reflect_fragment_outputs_in_location_order() {
+        let module = naga::front::wgsl::parse_str(
+            r#"
+            struct GBuffer {
+                @location(1) normal: vec4<f32>,
+                @location(0) albedo: vec4<f32>,
+                @builtin(frag_depth) depth: f32,
+            }
+
+            @fragment
+            fn fs_gbuffer() -> GBuffer {
+                return GBuffer(vec4<f32>(0.0), vec4<f32>(1.0), 0.5);
+            }
+
+            @fragment
+            fn fs_depth_only() {}
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![0, 1],
+            reflect_fragment_outputs(&module, "fs_gbuffer").unwrap()
+        );
+        assert!(reflect_fragment_outputs(&module, "fs_depth_only")
+            .unwrap()
+            .is_empty());
+        assert!(reflect_fragment_outputs(&module, "fs_missing").is_err());
+    }
+}
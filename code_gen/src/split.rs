@@ -0,0 +1,162 @@
+//! Generating code into a file per pipeline, so a change to one pipeline
+//! only touches its own file.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+use crate::{
+    format_code, gen_code_parts, screaming_snake_case, with_visibility, write_generated, CodeParts,
+    CodegenOptions, PipelineConfig, ShaderCache, Visibility, WriteError, WriteOutcome,
+    GENERATED_HEADER,
+};
+
+/// The code for a config split up by pipeline, from
+/// [gen_pipeline_code_split].
+#[derive(Debug, Clone)]
+pub struct SplitCode {
+    /// Everything that isn't one pipeline's, eg. the shader sources, bind
+    /// groups, registry and tests.
+    pub shared: TokenStream,
+    /// Each pipeline's items by name. Variants are pipelines of their own,
+    /// eg. `LitPipelineSkinned`.
+    pub pipelines: BTreeMap<String, TokenStream>,
+    /// What the pipelines are re-exported as from the shared module.
+    pub visibility: Visibility,
+}
+
+/// Like [gen_pipeline_code_with_options](crate::gen_pipeline_code_with_options),
+/// but with each pipeline's items kept apart so they can be written to
+/// their own files with [write_split].
+pub fn gen_pipeline_code_split(
+    config: &PipelineConfig,
+    options: &CodegenOptions,
+) -> Result<SplitCode> {
+    let CodeParts {
+        head,
+        pipelines,
+        tail,
+    } = gen_code_parts(
+        config,
+        options,
+        &mut ShaderCache::default(),
+        &mut Vec::new(),
+    )?;
+    let shared = quote! {
+        #head
+        #tail
+    };
+    // The shared module has to see the pipelines to re-export them.
+    let pipeline_visibility = match options.visibility {
+        Visibility::Private => quote! { pub(super) },
+        visibility => visibility.tokens(),
+    };
+    Ok(SplitCode {
+        shared: match options.visibility {
+            Visibility::Public => shared,
+            visibility => with_visibility(shared, visibility.tokens()),
+        },
+        pipelines: pipelines
+            .into_iter()
+            .map(|(name, code)| (name, with_visibility(code, pipeline_visibility.clone())))
+            .collect(),
+        visibility: options.visibility,
+    })
+}
+
+impl SplitCode {
+    /// The module a pipeline's file is, eg. `textured_pipeline` for
+    /// `TexturedPipeline`.
+    pub fn module_name(pipeline: &str) -> String {
+        screaming_snake_case(pipeline).to_lowercase()
+    }
+
+    /// The shared code, with a module for each pipeline whose items are
+    /// re-exported from it.
+    pub fn mod_rs(&self) -> TokenStream {
+        let shared = &self.shared;
+        let visibility = self.visibility.tokens();
+        let modules = self.pipelines.keys().map(|name| {
+            let module = Self::module_name(name);
+            // Modules named after keywords, eg. `Type`, still go in `type.rs`.
+            let module = match syn::parse_str::<syn::Ident>(&module) {
+                Ok(ident) => ident,
+                Err(_) => format_ident!("r#{}", module),
+            };
+            quote! {
+                mod #module;
+                #[allow(unused_imports)]
+                #visibility use #module::*;
+            }
+        });
+        quote! {
+            #shared
+            #(#modules)*
+        }
+    }
+
+    /// A pipeline's file, which uses the shared items from its parent.
+    pub fn pipeline_rs(&self, name: &str) -> Option<TokenStream> {
+        let code = self.pipelines.get(name)?;
+        Some(quote! {
+            #[allow(unused_imports)]
+            use super::*;
+            #code
+        })
+    }
+}
+
+/// Writes `code` to `dir` with [write_generated]: a file for each pipeline
+/// named after its [SplitCode::module_name], and a `mod.rs` with the shared
+/// code that declares them. It's pulled in like a single file:
+///
+/// ```ignore
+/// mod pipelines {
+///     include!(concat!(env!("OUT_DIR"), "/pipelines/mod.rs"));
+/// }
+/// ```
+///
+/// Generated files in `dir` from pipelines that have since been removed
+/// are deleted. Returns the files in `dir` and what was done to each.
+pub fn write_split(
+    dir: &Path,
+    code: &SplitCode,
+    force: bool,
+) -> Result<Vec<(PathBuf, WriteOutcome)>, WriteError> {
+    let io = |path: &Path| {
+        let path = path.to_owned();
+        move |source| WriteError::Io { path, source }
+    };
+    std::fs::create_dir_all(dir).map_err(io(dir))?;
+    let mut files = Vec::new();
+    let mod_rs = dir.join("mod.rs");
+    let outcome = write_generated(&mod_rs, &format_code(&code.mod_rs()), force)?;
+    files.push((mod_rs, outcome));
+    for name in code.pipelines.keys() {
+        let path = dir.join(SplitCode::module_name(name)).with_extension("rs");
+        let pipeline = code
+            .pipeline_rs(name)
+            .expect("every pipeline should have a file");
+        let outcome = write_generated(&path, &format_code(&pipeline), force)?;
+        files.push((path, outcome));
+    }
+
+    for entry in std::fs::read_dir(dir).map_err(io(dir))? {
+        let path = entry.map_err(io(dir))?.path();
+        if path.extension().is_none_or(|ext| ext != "rs")
+            || files.iter().any(|(file, _)| *file == path)
+        {
+            continue;
+        }
+        // Only files pipemd wrote, in case the directory is shared.
+        let generated =
+            std::fs::read_to_string(&path).is_ok_and(|src| src.starts_with(GENERATED_HEADER));
+        if generated {
+            std::fs::remove_file(&path).map_err(io(&path))?;
+        }
+    }
+    Ok(files)
+}
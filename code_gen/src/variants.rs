@@ -0,0 +1,178 @@
+//! The names of the wgpu enum variants that can be used as config values.
+//! These match wgpu 0.13, and are what the config docs refer to, eg. for
+//! editors completing values.
+
+pub const DEPTH_FORMATS: &[&str] = &[
+    "Depth32Float",
+    "Depth32FloatStencil8",
+    "Depth24Plus",
+    "Depth24PlusStencil8",
+    "Depth24UnormStencil8",
+];
+
+pub const COMPARE_FUNCTIONS: &[&str] = &[
+    "Never",
+    "Less",
+    "Equal",
+    "LessEqual",
+    "Greater",
+    "NotEqual",
+    "GreaterEqual",
+    "Always",
+];
+
+pub const PRIMITIVE_TOPOLOGIES: &[&str] = &[
+    "PointList",
+    "LineList",
+    "LineStrip",
+    "TriangleList",
+    "TriangleStrip",
+];
+
+pub const INDEX_FORMATS: &[&str] = &["Uint16", "Uint32"];
+
+pub const FRONT_FACES: &[&str] = &["Ccw", "Cw"];
+
+/// `None` disables culling.
+pub const CULL_MODES: &[&str] = &["None", "Front", "Back"];
+
+pub const POLYGON_MODES: &[&str] = &["Fill", "Line", "Point"];
+
+/// `UnfilterableFloat` is `wgpu::TextureSampleType::Float { filterable: false }`.
+pub const TEXTURE_SAMPLE_TYPES: &[&str] = &["Float", "UnfilterableFloat", "Depth", "Sint", "Uint"];
+
+pub const SAMPLER_BINDING_TYPES: &[&str] = &["Filtering", "NonFiltering", "Comparison"];
+
+/// The stages a binding can be visible to, as `wgpu::ShaderStages` flags.
+/// `Additive` adds the source to the destination for both color and alpha;
+/// the rest are the `wgpu::BlendState` constants of the same name.
+pub const BLEND_MODES: &[&str] = &[
+    "Replace",
+    "AlphaBlending",
+    "PremultipliedAlphaBlending",
+    "Additive",
+];
+
+/// GLSL is translated to WGSL with naga's GLSL frontend.
+pub const SHADER_LANGUAGES: &[&str] = &["Wgsl", "Glsl"];
+
+pub const VERTEX_STEP_MODES: &[&str] = &["Vertex", "Instance"];
+
+/// The `Float64` formats need `wgpu::Features::VERTEX_ATTRIBUTE_64BIT`.
+pub const VERTEX_FORMATS: &[&str] = &[
+    "Uint8x2",
+    "Uint8x4",
+    "Sint8x2",
+    "Sint8x4",
+    "Unorm8x2",
+    "Unorm8x4",
+    "Snorm8x2",
+    "Snorm8x4",
+    "Uint16x2",
+    "Uint16x4",
+    "Sint16x2",
+    "Sint16x4",
+    "Unorm16x2",
+    "Unorm16x4",
+    "Snorm16x2",
+    "Snorm16x4",
+    "Float16x2",
+    "Float16x4",
+    "Float32",
+    "Float32x2",
+    "Float32x3",
+    "Float32x4",
+    "Uint32",
+    "Uint32x2",
+    "Uint32x3",
+    "Uint32x4",
+    "Sint32",
+    "Sint32x2",
+    "Sint32x3",
+    "Sint32x4",
+    "Float64",
+    "Float64x2",
+    "Float64x3",
+    "Float64x4",
+];
+
+pub const SHADER_STAGES: &[&str] = &["Vertex", "Fragment", "Compute"];
+
+/// Every texture format except `Astc`, which has fields.
+pub const TEXTURE_FORMATS: &[&str] = &[
+    "R8Unorm",
+    "R8Snorm",
+    "R8Uint",
+    "R8Sint",
+    "R16Uint",
+    "R16Sint",
+    "R16Unorm",
+    "R16Snorm",
+    "R16Float",
+    "Rg8Unorm",
+    "Rg8Snorm",
+    "Rg8Uint",
+    "Rg8Sint",
+    "R32Uint",
+    "R32Sint",
+    "R32Float",
+    "Rg16Uint",
+    "Rg16Sint",
+    "Rg16Unorm",
+    "Rg16Snorm",
+    "Rg16Float",
+    "Rgba8Unorm",
+    "Rgba8UnormSrgb",
+    "Rgba8Snorm",
+    "Rgba8Uint",
+    "Rgba8Sint",
+    "Bgra8Unorm",
+    "Bgra8UnormSrgb",
+    "Rgb10a2Unorm",
+    "Rg11b10Float",
+    "Rg32Uint",
+    "Rg32Sint",
+    "Rg32Float",
+    "Rgba16Uint",
+    "Rgba16Sint",
+    "Rgba16Unorm",
+    "Rgba16Snorm",
+    "Rgba16Float",
+    "Rgba32Uint",
+    "Rgba32Sint",
+    "Rgba32Float",
+    "Depth32Float",
+    "Depth32FloatStencil8",
+    "Depth24Plus",
+    "Depth24PlusStencil8",
+    "Depth24UnormStencil8",
+    "Rgb9e5Ufloat",
+    "Bc1RgbaUnorm",
+    "Bc1RgbaUnormSrgb",
+    "Bc2RgbaUnorm",
+    "Bc2RgbaUnormSrgb",
+    "Bc3RgbaUnorm",
+    "Bc3RgbaUnormSrgb",
+    "Bc4RUnorm",
+    "Bc4RSnorm",
+    "Bc5RgUnorm",
+    "Bc5RgSnorm",
+    "Bc6hRgbUfloat",
+    "Bc6hRgbSfloat",
+    "Bc7RgbaUnorm",
+    "Bc7RgbaUnormSrgb",
+    "Etc2Rgb8Unorm",
+    "Etc2Rgb8UnormSrgb",
+    "Etc2Rgb8A1Unorm",
+    "Etc2Rgb8A1UnormSrgb",
+    "Etc2Rgba8Unorm",
+    "Etc2Rgba8UnormSrgb",
+    "EacR11Unorm",
+    "EacR11Snorm",
+    "EacRg11Unorm",
+    "EacRg11Snorm",
+];
+
+/// Float formats that can't be filtered unless the adapter supports it
+/// through `wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES`.
+pub const UNFILTERABLE_FLOAT_FORMATS: &[&str] = &["R32Float", "Rg32Float", "Rgba32Float"];
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::Result;
+
+use crate::{find_configs, BuildReport, Builder};
+
+/// Regenerates a [Builder]'s configs whenever they, or any file they read,
+/// change. Files are polled rather than watched with the OS, so it works
+/// the same everywhere.
+///
+/// ```no_run
+/// let builder = code_gen::Builder::new().add_dir("shaders").out_dir("src/generated");
+/// code_gen::Watcher::new(builder).run(|result| match result {
+///     Ok(report) => println!("regenerated {} files", report.changed().count()),
+///     Err(e) => eprintln!("{:#}", e),
+/// });
+/// ```
+#[derive(Debug)]
+pub struct Watcher {
+    builder: Builder,
+    debounce: Duration,
+    interval: Duration,
+    /// The files read by the last build, which are watched along with the
+    /// configs in the builder's directories.
+    dependencies: Vec<PathBuf>,
+    /// When each watched file was last modified, or `None` if it's missing.
+    modified: BTreeMap<PathBuf, Option<SystemTime>>,
+    /// When a change was last seen, if it hasn't been built yet.
+    changed_at: Option<Instant>,
+    built: bool,
+}
+
+impl Watcher {
+    pub fn new(builder: Builder) -> Self {
+        Self {
+            builder,
+            debounce: Duration::from_millis(100),
+            interval: Duration::from_millis(200),
+            dependencies: Vec::new(),
+            modified: BTreeMap::new(),
+            changed_at: None,
+            built: false,
+        }
+    }
+
+    /// How long files have to stay unchanged before regenerating, so saving
+    /// several files at once, or an editor writing a file in steps, only
+    /// rebuilds once. Defaults to 100ms.
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// How often [Watcher::run] polls. Defaults to 200ms.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Checks the files for changes, and regenerates once they've settled.
+    /// Returns the result of regenerating if it did, which is always the case
+    /// the first time it's called.
+    pub fn poll(&mut self) -> Option<Result<BuildReport>> {
+        let modified = self.modified();
+        if modified != self.modified {
+            self.modified = modified;
+            self.changed_at = Some(Instant::now());
+            if self.built {
+                return None;
+            }
+        }
+        let settled = self
+            .changed_at
+            .is_some_and(|at| at.elapsed() >= self.debounce);
+        if self.built && !settled {
+            return None;
+        }
+
+        // Taken before building, so files changed while it runs are still
+        // seen as changed afterwards.
+        let started = SystemTime::now();
+        let before = self.modified();
+        let result = self.builder.generate();
+        if let Ok(report) = &result {
+            self.dependencies = report.dependencies.clone();
+        }
+        // Failed builds keep watching what the last good one read, since
+        // they stop at the first broken file. Files read for the first time
+        // are only known to be unchanged if they're older than the build,
+        // otherwise they're left out so the next poll builds again.
+        self.modified = self
+            .modified()
+            .into_iter()
+            .filter_map(|(path, modified)| match before.get(&path) {
+                Some(before) => Some((path, *before)),
+                None => modified
+                    .is_none_or(|modified| modified < started)
+                    .then_some((path, modified)),
+            })
+            .collect();
+        self.changed_at = None;
+        self.built = true;
+        Some(result)
+    }
+
+    /// Polls forever, calling `on_build` each time the configs are
+    /// regenerated.
+    pub fn run(&mut self, mut on_build: impl FnMut(Result<BuildReport>)) -> ! {
+        loop {
+            if let Some(result) = self.poll() {
+                on_build(result);
+            }
+            std::thread::sleep(self.interval);
+        }
+    }
+
+    fn modified(&self) -> BTreeMap<PathBuf, Option<SystemTime>> {
+        // Directories are scanned each time so new configs are picked up.
+        let configs = self
+            .builder
+            .dirs()
+            .iter()
+            .flat_map(|dir| find_configs(dir).unwrap_or_default());
+        configs
+            .chain(self.dependencies.iter().cloned())
+            .map(|path| {
+                let modified = std::fs::metadata(&path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok();
+                (path, modified)
+            })
+            .collect()
+    }
+}
+
+/// Regenerates `builder`'s configs whenever they change, with the default
+/// [Watcher] settings.
+pub fn watch(builder: Builder, on_build: impl FnMut(Result<BuildReport>)) -> ! {
+    Watcher::new(builder).run(on_build)
+}
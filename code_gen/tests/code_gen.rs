@@ -1,30 +1,3347 @@
 use std::fs::read_to_string;
 use std::io::Write;
 
-use code_gen;
-
 #[cfg(test)]
 mod tests {
-    use code_gen::PipelineConfig;
+    use std::sync::Arc;
+
+    use code_gen::{
+        BindGroupManifest, BindingManifest, Builder, CodegenError, CodegenMode, CodegenOptions,
+        DependencyTracker, Descriptor, Diagnostic, JailedLoader, Level, LoadError, Manifest,
+        PipelineConfig, PipelineKind, PipelineManifest, ProgressObserver, SourceEmbedding,
+        SourceTooLarge, Span, VertexAttributeManifest, VertexBufferManifest, Visibility, Watcher,
+        WriteOutcome, DESCRIPTOR_VERSION, MANIFEST_VERSION,
+    };
+    use proc_macro2::TokenStream;
     use quote::quote;
 
     use super::*;
 
+    /// Writes `tokens` to `./tests/temp/<name>.rs` and checks that it compiles.
+    fn assert_compiles(name: &str, tokens: TokenStream) {
+        let path = format!("./tests/temp/{}.rs", name);
+        let mut file = std::fs::File::create(&path).unwrap();
+        write!(file, "{}", tokens).unwrap();
+
+        let tests = trybuild::TestCases::new();
+        tests.pass(&path);
+    }
+
     #[test]
     fn textured() {
         let src = read_to_string("./tests/texture.pmd").unwrap();
         let config = PipelineConfig::from_src(&src).unwrap();
         let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
-        let tokens = quote!{
-            #pipeline_code
+        assert_compiles(
+            "texture",
+            quote! {
+                #pipeline_code
+
+                fn draw<'a>(
+                    render_pass: &mut ::wgpu::RenderPass<'a>,
+                    pipeline: &'a TexturedPipeline,
+                    (vertices, indices): (&'a ::wgpu::Buffer, &'a ::wgpu::Buffer),
+                ) {
+                    render_pass.set_pipeline(pipeline.pipeline());
+                    // Through `Deref`.
+                    render_pass.set_pipeline(pipeline);
+                    // The shader's inputs are packed into one buffer.
+                    pipeline.draw_indexed(render_pass, vertices.slice(..), indices.slice(..), ::wgpu::IndexFormat::Uint16, 0..6, 0..1);
+                    pipeline.draw(render_pass, vertices.slice(..), 0..3, 0..1);
+                }
 
-            fn main() {}
+                fn main() {
+                    let _ = draw;
+                }
+            },
+        );
+
+        // The docs sum up the config and what was reflected.
+        let generated = pipeline_code.to_string();
+        for line in [
+            "\" - Shader: `./tests/texture.wgsl`, entry points `vs_textured` and `fs_textured`\"",
+            "\" - Topology: `TriangleList`\"",
+            "\" - Color target: the `surface_format` passed to `new`\"",
+            "\" - Bind group 0: `tex` (texture), `samp` (sampler)\"",
+        ] {
+            assert!(generated.contains(line), "{} in {}", line, generated);
+        }
+    }
+
+    #[test]
+    fn textured_constants_only() {
+        let src = read_to_string("./tests/texture.pmd").unwrap();
+        let config = PipelineConfig::from_src(&src).unwrap();
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
         };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "texture_constants",
+            quote! {
+                #pipeline_code
 
-        let mut file = std::fs::File::create("./tests/temp/texture.rs").unwrap();
-        write!(file, "{}", tokens).unwrap();
+                fn main() {
+                    let _: &str = TEXTURED_PIPELINE_SHADER;
+                    let _: ::wgpu::PrimitiveState = TEXTURED_PIPELINE_PRIMITIVE;
+                    let _ = (TEXTURED_PIPELINE_VS_ENTRY, TEXTURED_PIPELINE_FS_ENTRY);
+                }
+            },
+        );
+    }
 
-        let tests = trybuild::TestCases::new();
-        tests.pass("./tests/temp/texture.rs");
+    #[test]
+    fn conservative() {
+        let src = read_to_string("./tests/conservative.pmd").unwrap();
+        let config = PipelineConfig::from_src(&src).unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "conservative",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(
+                        VoxelizePipeline::REQUIRED_FEATURES,
+                        ::wgpu::Features::CONSERVATIVE_RASTERIZATION | ::wgpu::Features::DEPTH_CLIP_CONTROL,
+                    );
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn shadow_depth_bias() {
+        let src = read_to_string("./tests/shadow.pmd").unwrap();
+        let config = PipelineConfig::from_src(&src).unwrap();
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "shadow_constants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let depth_stencil = SHADOW_PIPELINE_DEPTH_STENCIL.unwrap();
+                    assert_eq!(::wgpu::TextureFormat::Depth32Float, depth_stencil.format);
+                    assert_eq!(2, depth_stencil.bias.constant);
+                    assert_eq!(2.0, depth_stencil.bias.slope_scale);
+                    assert_eq!(Some(::wgpu::Face::Front), SHADOW_PIPELINE_PRIMITIVE.cull_mode);
+                }
+            },
+        );
+
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "shadow",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    // The pass needs a depth attachment, since the pipeline tests depth.
+                    let _: for<'a> fn(
+                        &'a mut ::wgpu::CommandEncoder,
+                        [&'a ::wgpu::TextureView; 1],
+                        ::wgpu::LoadOp<::wgpu::Color>,
+                        &'a ::wgpu::TextureView,
+                        ::wgpu::LoadOp<f32>,
+                    ) -> ::wgpu::RenderPass<'a> = ShadowPipeline::begin_pass;
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn cfg_gated_pipelines() {
+        let src = read_to_string("./tests/cfg.pmd").unwrap();
+        let config = PipelineConfig::from_src(&src).unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        // Shaders are named after their file and a hash of their source.
+        let shader = pipeline_code
+            .clone()
+            .into_iter()
+            .find_map(|token| match token {
+                proc_macro2::TokenTree::Ident(ident)
+                    if ident.to_string().starts_with("SHADER_TEXTURE_") =>
+                {
+                    Some(ident)
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!("SHADER_TEXTURE_".len() + 6, shader.to_string().len());
+        assert_compiles(
+            "cfg",
+            quote! {
+                #pipeline_code
+
+                // `editor` is never enabled so this can only compile if
+                // `GizmoPipeline` was removed.
+                struct GizmoPipeline;
+
+                fn main() {
+                    let _ = GizmoPipeline;
+                    let _ = DebugPipeline::REQUIRED_FEATURES;
+                    let _ = #shader;
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn cfg_blocks() {
+        let config = PipelineConfig::from_path("./tests/cfg_blocks.pmd").unwrap();
+        let cfgs = config
+            .render_pipelines()
+            .iter()
+            .map(|rp| (rp.name.as_str(), rp.cfg.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("CommonPipeline", Some(r#"feature = "editor""#)),
+                (
+                    "GizmoPipeline",
+                    Some(r#"all(feature = "editor", debug_assertions)"#)
+                ),
+                (
+                    "OutlinePipeline",
+                    Some(r#"all(feature = "editor", not(target_arch = "wasm32"))"#),
+                ),
+                ("RuntimePipeline", None),
+            ],
+            cfgs,
+        );
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "cfg_blocks",
+            quote! {
+                #pipeline_code
+
+                // Only `RuntimePipeline` is outside the `editor` block.
+                struct CommonPipeline;
+                struct GizmoPipeline;
+                struct OutlinePipeline;
+
+                fn main() {
+                    let _ = (CommonPipeline, GizmoPipeline, OutlinePipeline);
+                    let _ = RuntimePipeline::REQUIRED_FEATURES;
+                }
+            },
+        );
+
+        let e = PipelineConfig::from_src(
+            r#"#cfg("test") [ render_pipeline(name: "A", path: "a.wgsl")"#,
+        )
+        .unwrap_err();
+        assert_eq!("Unexpected end of input", e.to_string());
+    }
+
+    #[test]
+    fn duplicate_pipelines() {
+        let src = r#"
+            render_pipeline(name: "Textured", path: "./tests/texture.wgsl")
+            render_pipeline(name: "Textured", path: "./tests/texture.wgsl")
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let e = code_gen::gen_pipeline_code(&config).unwrap_err();
+        assert_eq!(
+            "Textured: pipeline is declared more than once, at line 2 and line 3",
+            e.to_string()
+        );
+
+        // Render and compute pipelines share a namespace.
+        let src = r#"
+            render_pipeline(name: "Textured", path: "./tests/texture.wgsl")
+            #compute_pipeline("Textured", "cs_integrate", "./tests/compute.wgsl")
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        assert!(code_gen::gen_pipeline_code(&config).is_err());
+
+        // Unless they're never both enabled.
+        let src = r#"
+            #cfg("target_arch = \"wasm32\"") [
+                render_pipeline(name: "Textured", path: "./tests/texture.wgsl")
+            ]
+            #cfg("not(target_arch = \"wasm32\")") [
+                render_pipeline(name: "Textured", path: "./tests/texture.wgsl", label: "Native")
+            ]
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "duplicate_pipelines",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> Textured = Textured::new;
+                }
+            },
+        );
+        let e = PipelineConfig::from_src(r#"render_pipeline(name: "A", path: "a.wgsl") ]"#)
+            .unwrap_err();
+        assert_eq!(
+            "Expected end of input, but found RightBracket",
+            e.to_string()
+        );
+    }
+
+    #[test]
+    fn pipeline_registry() {
+        let src = read_to_string("./tests/cfg.pmd").unwrap();
+        let config = PipelineConfig::from_src(&src).unwrap();
+        let options = CodegenOptions {
+            registry: true,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "registry",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let names = PIPELINE_REGISTRY.iter().map(|f| f.name).collect::<Vec<_>>();
+                    assert_eq!(vec!["DebugPipeline"], names);
+                    assert!(find_pipeline("DebugPipeline").is_some());
+                    assert!(find_pipeline("GizmoPipeline").is_none());
+                    let _: fn(&::wgpu::Device) -> ::wgpu::RenderPipeline = find_pipeline("DebugPipeline").unwrap().create;
+                }
+            },
+        );
+        assert!(!pipeline_code.to_string().contains("fn warmup"));
+
+        let options = CodegenOptions {
+            warmup: true,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        // Pipelines that need more than a device to create are timed too.
+        let generated = pipeline_code.to_string();
+        assert!(
+            generated.contains(r#"timed ("GizmoPipeline""#),
+            "{}",
+            generated
+        );
+        assert!(!generated.contains("PIPELINE_REGISTRY"), "{}", generated);
+        assert_compiles(
+            "warmup",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> WarmupReport = Pipelines::warmup;
+                    let report = WarmupReport {
+                        pipelines: vec![PipelineTiming {
+                            name: "DebugPipeline",
+                            sample_count: 1,
+                            duration: ::std::time::Duration::from_millis(3),
+                        }; 2],
+                    };
+                    assert_eq!(::std::time::Duration::from_millis(6), report.total());
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn sample_count_variants() {
+        let src = read_to_string("./tests/msaa.pmd").unwrap();
+        let config = PipelineConfig::from_src(&src).unwrap();
+        let options = CodegenOptions {
+            registry: true,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "msaa",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let _: fn(&::wgpu::Device) -> SpritePipeline<1> = SpritePipeline::<1>::new;
+                    let _: fn(&::wgpu::Device) -> SpritePipeline<4> = SpritePipeline::<4>::new;
+                    assert_eq!(&[1, 4], SpritePipeline::<4>::SAMPLE_COUNTS);
+                    let _: fn(&SpritePipeline<4>) -> &::wgpu::RenderPipeline = SpritePipeline::<4>::pipeline;
+                    let _: fn(&SpritePipeline<4>) -> &::wgpu::RenderPipeline = |p| p;
+                    let _: for<'a> fn(
+                        &'a mut ::wgpu::CommandEncoder,
+                        [&'a ::wgpu::TextureView; 1],
+                        [Option<&'a ::wgpu::TextureView>; 1],
+                        ::wgpu::LoadOp<::wgpu::Color>,
+                    ) -> ::wgpu::RenderPass<'a> = SpritePipeline::<4>::begin_pass;
+                    assert_eq!("SpritePipeline", SpritePipeline::<4>::label());
+                    let _ = |pipelines: Pipelines| -> (SpritePipeline<1>, SpritePipeline<4>) {
+                        (pipelines.sprite_pipeline_x1, pipelines.sprite_pipeline_x4)
+                    };
+                    // Pipelines of different types can be kept together.
+                    let _: fn(SpritePipeline<1>, SpritePipeline<4>) -> Vec<Box<dyn RenderPipelineHandle>> =
+                        |a, b| vec![Box::new(a), Box::new(b)];
+                    let counts = PIPELINE_REGISTRY.iter().map(|f| f.sample_count).collect::<Vec<_>>();
+                    assert_eq!(vec![1, 4], counts);
+                    assert!(find_pipeline_with_sample_count("SpritePipeline", 4).is_some());
+                    assert!(find_pipeline_with_sample_count("SpritePipeline", 2).is_none());
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "msaa_constants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(&[1, 4], SPRITE_PIPELINE_SAMPLE_COUNTS);
+                    assert_eq!(1, SPRITE_PIPELINE_MULTISAMPLE.count);
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn bind_group_layouts() {
+        let src = read_to_string("./tests/layouts.pmd").unwrap();
+        let config = PipelineConfig::from_src(&src).unwrap();
+        let options = CodegenOptions {
+            registry: true,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "layouts",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let _: fn(&::wgpu::Device, &::wgpu::BindGroupLayout, &::wgpu::BindGroupLayout, ::wgpu::TextureFormat) -> MaterialPipeline = MaterialPipeline::new;
+                    let _: fn(&::wgpu::Device, &::wgpu::BindGroupLayout, &::wgpu::BindGroupLayout, ::wgpu::TextureFormat) -> Pipelines = Pipelines::new;
+                    let _ = |pipelines: Pipelines| -> (MaterialPipeline, SkyPipeline) { (pipelines.material_pipeline, pipelines.sky_pipeline) };
+                    let names = PIPELINE_REGISTRY.iter().map(|f| f.name).collect::<Vec<_>>();
+                    assert_eq!(vec!["SkyPipeline"], names);
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "layouts_constants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(&["camera", "material"], MATERIAL_PIPELINE_BIND_GROUP_LAYOUTS);
+                }
+            },
+        );
+
+        let src = src.replace("\"material\"", "\"type\"");
+        let config = PipelineConfig::from_src(&src).unwrap();
+        assert!(code_gen::gen_pipeline_code(&config).is_err());
+    }
+
+    #[test]
+    fn generate_many_shares_shaders() {
+        #[derive(Debug, Default)]
+        struct Shaders(std::sync::Mutex<Vec<String>>);
+
+        impl ProgressObserver for Shaders {
+            fn shader_reflected(&self, path: &str) {
+                self.0.lock().unwrap().push(path.to_owned());
+            }
+        }
+
+        let shaders = Arc::new(Shaders::default());
+        let options = CodegenOptions {
+            observer: Some(shaders.clone()),
+            ..Default::default()
+        };
+        let inputs = [
+            "./tests/texture.pmd",
+            "./tests/missing.pmd",
+            "./tests/msaa.pmd",
+            "./tests/templates.pmd",
+        ]
+        .map(std::path::PathBuf::from);
+        let results = code_gen::generate_many(&inputs, &options);
+        assert_eq!(
+            inputs.to_vec(),
+            results
+                .iter()
+                .map(|(path, _)| path.clone())
+                .collect::<Vec<_>>()
+        );
+        assert!(results[0].1.is_ok());
+        assert!(matches!(results[1].1, Err(CodegenError::Parse(_))));
+        assert!(results[2].1.is_ok());
+        assert!(results[3].1.is_ok());
+        // `texture.pmd` and `msaa.pmd` use the same shader.
+        assert_eq!(
+            vec!["./tests/texture.wgsl", "./tests/fullscreen.wgsl"],
+            *shaders.0.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn progress_events() {
+        #[derive(Debug, Default)]
+        struct Recorder(std::sync::Mutex<Vec<String>>);
+
+        impl ProgressObserver for Recorder {
+            fn shader_reflected(&self, path: &str) {
+                self.0.lock().unwrap().push(format!("shader {}", path));
+            }
+
+            fn pipeline_generated(&self, name: &str, done: usize, total: usize) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("pipeline {} {}/{}", name, done, total));
+            }
+        }
+
+        let src = read_to_string("./tests/layouts.pmd").unwrap();
+        let config = PipelineConfig::from_src(&src).unwrap();
+        let recorder = Arc::new(Recorder::default());
+        let options = CodegenOptions {
+            observer: Some(recorder.clone()),
+            ..Default::default()
+        };
+        code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_eq!(
+            vec![
+                "shader ./tests/texture.wgsl",
+                "pipeline MaterialPipeline 1/2",
+                "pipeline SkyPipeline 2/2",
+            ],
+            *recorder.0.lock().unwrap(),
+        );
+    }
+
+    #[test]
+    fn texture_bindings() {
+        #[derive(Debug, Default)]
+        struct Warnings(std::sync::Mutex<Vec<String>>);
+
+        impl ProgressObserver for Warnings {
+            fn warning(&self, message: &str) {
+                self.0.lock().unwrap().push(message.to_owned());
+            }
+        }
+
+        let src = r#"
+            #defaults(target_formats: [Rgba8Unorm])
+            render_pipeline(name: "TexturedPipeline", path: "./tests/texture.wgsl")
+            render_pipeline(
+                name: "HdrPipeline",
+                path: "./tests/texture.wgsl",
+                bindings: (tex: (format: Rgba32Float)),
+            )
+            render_pipeline(
+                name: "NearestPipeline",
+                path: "./tests/texture.wgsl",
+                bindings: (tex: (format: Rgba32Float), samp: (filtering: false)),
+            )
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let warnings = Arc::new(Warnings::default());
+        let options = CodegenOptions {
+            observer: Some(warnings.clone()),
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        let warnings = warnings.0.lock().unwrap();
+        assert_eq!(1, warnings.len(), "{:?}", warnings);
+        assert!(
+            warnings[0]
+                .starts_with("HdrPipeline: `tex` is sampled with the filtering sampler `samp`"),
+            "{}",
+            warnings[0]
+        );
+        assert_compiles(
+            "texture_bindings",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    use ::wgpu::{SamplerBindingType, TextureSampleType};
+                    assert_eq!(
+                        &[(0, 0, TextureSampleType::Float { filterable: true })],
+                        TexturedPipeline::TEXTURE_SAMPLE_TYPES,
+                    );
+                    assert_eq!(&[(0, 1, SamplerBindingType::Filtering)], TexturedPipeline::SAMPLER_BINDING_TYPES);
+                    assert_eq!(
+                        &[(0, 0, TextureSampleType::Float { filterable: false })],
+                        NearestPipeline::TEXTURE_SAMPLE_TYPES,
+                    );
+                    assert_eq!(&[(0, 1, SamplerBindingType::NonFiltering)], NearestPipeline::SAMPLER_BINDING_TYPES);
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "texture_bindings_constants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(1, HDR_PIPELINE_TEXTURE_SAMPLE_TYPES.len());
+                    assert_eq!(1, HDR_PIPELINE_SAMPLER_BINDING_TYPES.len());
+                }
+            },
+        );
+
+        let src = r#"render_pipeline(name: "A", path: "./tests/texture.wgsl", bindings: (tex: (sample_type: Uint)))"#;
+        let e = code_gen::gen_pipeline_code(&PipelineConfig::from_src(src).unwrap()).unwrap_err();
+        assert!(
+            e.to_string().starts_with("A: `tex` is declared as"),
+            "{}",
+            e
+        );
+    }
+
+    #[test]
+    fn inferred_entry_points() {
+        let src = r#"
+            render_pipeline(name: "TexturedPipeline", path: "./tests/texture.wgsl")
+            render_pipeline(name: "FillPipeline", path: "./tests/fullscreen.wgsl", fs_entry: "fs_fill")
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "inferred_entry_points",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!("vs_textured", TEXTURED_PIPELINE_VS_ENTRY);
+                    assert_eq!("fs_textured", TEXTURED_PIPELINE_FS_ENTRY);
+                    // Several vertex entry points, so the conventional name wins.
+                    assert_eq!("vs_main", FILL_PIPELINE_VS_ENTRY);
+                    assert_eq!("fs_fill", FILL_PIPELINE_FS_ENTRY);
+                }
+            },
+        );
+
+        // Several fragment entry points and none called `fs_main`.
+        let src = r#"render_pipeline(name: "FillPipeline", path: "./tests/fullscreen.wgsl")"#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let e = code_gen::gen_pipeline_code(&config)
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("fs_fill, fs_checker"), "{}", e);
+        assert!(e.contains("`fs_entry` must be given"), "{}", e);
+    }
+
+    #[test]
+    fn invalid_shaders() {
+        let src = r#"render_pipeline(name: "A", path: "./tests/mismatched_types.wgsl")"#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let e = code_gen::gen_pipeline_code(&config)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            e.starts_with(
+                "./tests/mismatched_types.wgsl: Entry point fs_main at Fragment is invalid: "
+            ),
+            "{}",
+            e
+        );
+        assert!(
+            e.contains("does not match the function return value"),
+            "{}",
+            e
+        );
+        assert!(
+            e.contains(
+                "--> ./tests/mismatched_types.wgsl:8:16\n   |     let color = vec3(1.0, 0.0, 1.0);"
+            ),
+            "{}",
+            e
+        );
+
+        let src = r#"render_pipeline(name: "A", path: "./fixtures/invalid.wgsl")"#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let e = code_gen::gen_pipeline_code(&config)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            e.starts_with("./fixtures/invalid.wgsl: expected ';'"),
+            "{}",
+            e
+        );
+        assert!(e.contains("--> ./fixtures/invalid.wgsl:4:1"), "{}", e);
+
+        let src = r#"#shader(name: "lit", path: "./tests/glsl/lit.glsl") render_pipeline(name: "A", shader: "lit")"#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let e = code_gen::gen_pipeline_code(&config).unwrap_err();
+        assert_eq!("glsl_stage", Diagnostic::from_error(&e).code);
+        assert!(
+            e.to_string()
+                .contains("`.vert`, `.frag` or `.comp` extension"),
+            "{}",
+            e
+        );
+    }
+
+    #[test]
+    fn missing_entry_points() {
+        let gen_err = |src: &str| {
+            let config = PipelineConfig::from_src(src).unwrap();
+            code_gen::gen_pipeline_code(&config)
+                .unwrap_err()
+                .to_string()
+        };
+        let e = gen_err(
+            r#"render_pipeline(name: "A", path: "./tests/fullscreen.wgsl", fs_entry: "fs_blit")"#,
+        );
+        assert_eq!(
+            "A: ./tests/fullscreen.wgsl has no Fragment entry point `fs_blit`, the available ones are fs_fill, fs_checker",
+            e
+        );
+        let e = gen_err(
+            r#"render_pipeline(name: "A", path: "./tests/fullscreen.wgsl", vs_entry: "fs_fill", fs_entry: "fs_fill")"#,
+        );
+        assert!(
+            e.contains("declares `fs_fill` as a Fragment entry point, not a Vertex one"),
+            "{}",
+            e
+        );
+        let e = gen_err(r#"#compute_pipeline("A", "cs_main", "./tests/fullscreen.wgsl")"#);
+        assert!(
+            e.contains("has no Compute entry points, so `cs_main` doesn't exist"),
+            "{}",
+            e
+        );
+    }
+
+    #[test]
+    fn includes() {
+        let config = PipelineConfig::from_path("./tests/include/main.pmd").unwrap();
+        let names = config
+            .render_pipelines()
+            .iter()
+            .map(|rp| rp.name.as_str())
+            .collect::<Vec<_>>();
+        // `common.pmd` is included twice but only used once.
+        assert_eq!(
+            vec!["CommonPipeline", "PostPipeline", "MainPipeline"],
+            names
+        );
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "includes",
+            quote! {
+                #pipeline_code
+
+                fn main() {}
+            },
+        );
+
+        let e = PipelineConfig::from_path("./tests/include/cycle_a.pmd")
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("Include cycle: "), "{}", e);
+        assert!(e.contains("cycle_a.pmd -> "), "{}", e);
+        assert!(e.ends_with("cycle_a.pmd"), "{}", e);
+
+        let e = PipelineConfig::from_path("./tests/include/broken.pmd")
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("missing_field.pmd"), "{}", e);
+        assert!(e.contains("Missing field: \"path\""), "{}", e);
+
+        // Errors in included files say where they are and how they were
+        // reached, innermost include first.
+        let e = PipelineConfig::from_path("./tests/include/nested.pmd")
+            .unwrap_err()
+            .to_string();
+        let lines = e.lines().collect::<Vec<_>>();
+        assert_eq!(3, lines.len(), "{}", e);
+        assert!(
+            lines[0].ends_with("shared/missing_field.pmd:1: Missing field: \"path\""),
+            "{}",
+            e
+        );
+        assert!(lines[1].starts_with("  included from "), "{}", e);
+        assert!(lines[1].ends_with("include/broken.pmd:2"), "{}", e);
+        assert!(lines[2].ends_with("include/nested.pmd:5"), "{}", e);
+
+        let e = PipelineConfig::from_src("\n#include(\"./tests/include/broken.pmd\")")
+            .unwrap_err()
+            .to_string();
+        assert!(e.ends_with("broken.pmd:2\n  included from line 2"), "{}", e);
+
+        let e = PipelineConfig::from_path("./tests/include/shared/missing_field.pmd")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            "./tests/include/shared/missing_field.pmd:1: Missing field: \"path\"",
+            e
+        );
+    }
+
+    #[test]
+    fn includes_through_loader() {
+        let loader = JailedLoader::new("./tests/include").unwrap();
+        let config = PipelineConfig::from_path_with_loader("main.pmd", &loader).unwrap();
+        let names = config
+            .render_pipelines()
+            .iter()
+            .map(|rp| rp.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec!["CommonPipeline", "PostPipeline", "MainPipeline"],
+            names
+        );
+
+        let absolute = std::path::Path::new("./tests/texture.pmd")
+            .canonicalize()
+            .unwrap();
+        for src in [
+            "#include(\"../texture.pmd\")".to_owned(),
+            "#include(\"shared/../../texture.pmd\")".to_owned(),
+            format!("#include({:?})", absolute),
+            // From an included file.
+            "#include(\"escape.pmd\")".to_owned(),
+        ] {
+            let dir = std::path::Path::new("./tests/temp/jailed_include");
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("escape.pmd"), "#include(\"../../texture.pmd\")").unwrap();
+            let loader = JailedLoader::new(dir).unwrap();
+            let e = PipelineConfig::from_src_with_loader(&src, &loader).unwrap_err();
+            assert!(
+                matches!(
+                    e.downcast_ref::<LoadError>(),
+                    Some(LoadError::PathOutsideRoot { .. })
+                ),
+                "{}: {:?}",
+                src,
+                e
+            );
+            assert_eq!("path_outside_root", Diagnostic::from_error(&e).code);
+        }
+    }
+
+    #[test]
+    fn from_reader() {
+        let file = std::fs::File::open("./tests/texture.pmd").unwrap();
+        let config = PipelineConfig::from_reader(std::io::BufReader::new(file)).unwrap();
+        let src = read_to_string("./tests/texture.pmd").unwrap();
+        assert_eq!(
+            PipelineConfig::from_src(&src).unwrap().render_pipelines(),
+            config.render_pipelines(),
+        );
+
+        let piped = br#"render_pipeline(name: "PipedPipeline", path: "./tests/texture.wgsl")"#;
+        let config = PipelineConfig::from_reader(&piped[..]).unwrap();
+        assert_eq!("PipedPipeline", config.render_pipelines()[0].name);
+
+        let e = PipelineConfig::from_reader(&b"render_pipeline(name: 1)"[..]).unwrap_err();
+        assert!(e.to_string().contains("Unexpected token"), "{}", e);
+    }
+
+    #[test]
+    fn let_variables() {
+        let config = PipelineConfig::from_path("./tests/let.pmd").unwrap();
+        let [shadow, cascade] = config.render_pipelines() else {
+            panic!("expected two pipelines");
+        };
+        assert_eq!("./tests/texture.wgsl", cascade.path);
+        assert_eq!(shadow.depth_stencil, cascade.depth_stencil);
+        let depth_stencil = shadow.depth_stencil.as_ref().unwrap();
+        assert_eq!("Depth24Plus", depth_stencil.format);
+        assert_eq!(2, depth_stencil.depth_bias.constant);
+
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "let",
+            quote! {
+                #pipeline_code
+
+                fn main() {}
+            },
+        );
+
+        let e = PipelineConfig::from_src("#let(a: 1) #let(a: 2)")
+            .err()
+            .unwrap();
+        assert_eq!("Variable $a is already defined", e.to_string());
+        let e = PipelineConfig::from_src("render_pipeline(name: $name)")
+            .err()
+            .unwrap();
+        assert_eq!("Undefined variable: $name", e.to_string());
+    }
+
+    #[test]
+    fn defaults() {
+        let config = PipelineConfig::from_path("./tests/defaults.pmd").unwrap();
+        let [opaque, foliage, common, overlay] = config.render_pipelines() else {
+            panic!("expected four pipelines");
+        };
+        for rp in [opaque, foliage, overlay] {
+            assert_eq!("./tests/texture.wgsl", rp.path, "{}", rp.name);
+            assert_eq!(
+                "Depth32Float",
+                rp.depth_stencil.as_ref().unwrap().format,
+                "{}",
+                rp.name
+            );
+        }
+        assert_eq!(Some("Back"), opaque.primitive.cull_mode.as_deref());
+        assert_eq!(None, foliage.primitive.cull_mode);
+        assert_eq!(Some(&vec![4]), opaque.sample_counts.as_ref());
+        assert_eq!(Some(&vec![1, 4]), overlay.sample_counts.as_ref());
+        // Defaults don't apply to included files.
+        assert_eq!(None, common.depth_stencil);
+        assert_eq!(None, common.sample_counts);
+
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "defaults",
+            quote! {
+                #pipeline_code
+
+                fn main() {}
+            },
+        );
+
+        let e = PipelineConfig::from_src(r#"#defaults(name: "A")"#).unwrap_err();
+        assert!(
+            e.to_string().starts_with("Invalid value for \"name\""),
+            "{}",
+            e
+        );
+        let e = PipelineConfig::from_src("#defaults(cull_mode: Sideways)").unwrap_err();
+        assert!(
+            e.to_string()
+                .starts_with("Invalid value \"Sideways\" for \"cull_mode\""),
+            "{}",
+            e
+        );
+    }
+
+    #[test]
+    fn templates() {
+        let config = PipelineConfig::from_path("./tests/templates.pmd").unwrap();
+        let pipelines = config
+            .render_pipelines()
+            .iter()
+            .map(|rp| (rp.name.as_str(), rp.fs_entry.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("FillPipeline", Some("fs_fill")),
+                ("CheckerPipeline", Some("fs_checker"))
+            ],
+            pipelines
+        );
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "templates",
+            quote! {
+                #pipeline_code
+
+                fn main() {}
+            },
+        );
+
+        let template = r#"#template(post, name) [ render_pipeline(name: $name, path: "./tests/fullscreen.wgsl") ]"#;
+        let e = PipelineConfig::from_src(&format!("{} #instantiate(post)", template))
+            .unwrap_err()
+            .to_string();
+        assert_eq!("Template post needs a value for \"name\"", e);
+        let e = PipelineConfig::from_src(&format!(
+            r#"{} #instantiate(post, name: "A", size: 2)"#,
+            template
+        ))
+        .unwrap_err()
+        .to_string();
+        assert_eq!("Unexpected field: \"size\"", e);
+        let e = PipelineConfig::from_src(&format!("{} #instantiate(post, name: 2)", template))
+            .unwrap_err()
+            .to_string();
+        assert!(e.starts_with("In template post: Unexpected token"), "{}", e);
+        let e = PipelineConfig::from_src("#instantiate(bloom)").unwrap_err();
+        assert_eq!("Unknown template: bloom", e.to_string());
+        let e = PipelineConfig::from_src("#template(a) [ #instantiate(a) ] #instantiate(a)")
+            .unwrap_err();
+        assert_eq!("Template cycle: a -> a", e.to_string());
+        let e = PipelineConfig::from_src(
+            "#template(a) [ #instantiate(b) ] #template(b) [ #instantiate(a) ] #instantiate(a)",
+        )
+        .unwrap_err();
+        assert_eq!("Template cycle: a -> b -> a", e.to_string());
+        assert_eq!("template_cycle", e.code());
+    }
+
+    #[test]
+    fn grammar_versions() {
+        let src = "#grammar(\"1.8\")\nrender_pipeline(name: \"A\", path: \"a.wgsl\")\n#compute_pipeline(\"B\", \"main\", \"b.wgsl\")";
+        let e = PipelineConfig::from_src_with_loader(src, &code_gen::FsLoader).unwrap_err();
+        let diagnostic = Diagnostic::from_error(&e);
+        assert_eq!("newer_than_grammar", diagnostic.code);
+        assert_eq!(Some(3), diagnostic.span.map(|span| span.line));
+        assert_eq!(
+            "`#compute_pipeline` was added in version 1.9.0 of the pipemd grammar, but this config declares 1.8.0",
+            diagnostic.message
+        );
+        let e = PipelineConfig::from_src(
+            r#"#grammar("1.13") render_pipeline(name: "A", path: "a.wgsl", targets: [])"#,
+        )
+        .unwrap_err();
+        assert!(
+            e.to_string()
+                .starts_with("`targets` in `render_pipeline` was added in version 1.14.0"),
+            "{}",
+            e
+        );
+        // Without `#grammar` anything this build understands can be used.
+        PipelineConfig::from_src(r#"#compute_pipeline("B", "main", "b.wgsl")"#).unwrap();
+    }
+
+    #[test]
+    fn shader_variants() {
+        let config = PipelineConfig::from_path("./tests/variants.pmd").unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "shader_variants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let key = CheckerPipelineKey {
+                        alpha_test: true,
+                        quality: CheckerPipelineQuality::High,
+                    };
+                    assert_eq!(4, CheckerPipelineKey::ALL.len());
+                    assert_eq!(3, key.index());
+                    assert_eq!(&["ALPHA_TEST", "QUALITY_HIGH"], key.defines());
+                    assert_eq!("CheckerPipelineAlphaTestHigh", key.pipeline_name());
+                    assert_eq!("CheckerPipelineLow", CheckerPipelineKey::default().pipeline_name());
+                    for (i, key) in CheckerPipelineKey::ALL.iter().enumerate() {
+                        assert_eq!(i, key.index());
+                    }
+                    let _: fn(&CheckerPipeline<4>, CheckerPipelineKey) -> &wgpu::RenderPipeline = CheckerPipeline::get;
+                    let _ = CheckerPipelineAlphaTestLow::<4>::new;
+                    // Variants are kept together in their aggregate.
+                    let _ = |pipelines: Pipelines| -> CheckerPipeline<4> { pipelines.checker_pipeline_x4 };
+                }
+            },
+        );
+
+        // Each variant gets its own preprocessed shader.
+        let pipeline_code = pipeline_code.to_string();
+        assert_eq!(2, pipeline_code.matches("discard").count());
+        assert!(!pipeline_code.contains("#ifdef"));
+
+        let src = r#"render_pipeline(name: "A", path: "./tests/variants.wgsl", variants: [A, A])"#;
+        let e = PipelineConfig::from_src(src).unwrap_err();
+        assert!(e.to_string().contains("defines must be unique"), "{}", e);
+        let src = r#"render_pipeline(name: "A", path: "./tests/variants.wgsl", variants: [A, B, C, D, E, F, G])"#;
+        let e = PipelineConfig::from_src(src).unwrap_err();
+        assert!(e.to_string().contains("more than 64 combinations"), "{}", e);
+    }
+
+    #[test]
+    fn metadata() {
+        let config = PipelineConfig::from_path("./tests/metadata.pmd").unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "metadata",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!("rendering-team", BloomPipeline::AUTHOR);
+                    assert_eq!("Blurs the bright parts of the frame.", BloomPipeline::DESCRIPTION);
+                    assert_eq!(&["post", "hdr"], BloomPipeline::TAGS);
+                }
+            },
+        );
+        assert!(pipeline_code
+            .to_string()
+            .contains(r#"# [doc = " Blurs the bright parts of the frame."]"#));
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "metadata_constants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!("rendering-team", BLOOM_PIPELINE_AUTHOR);
+                    assert_eq!(&["post", "hdr"], BLOOM_PIPELINE_TAGS);
+                }
+            },
+        );
+        // Metadata that isn't given isn't generated.
+        assert!(!pipeline_code.to_string().contains("FILL_PIPELINE_AUTHOR"));
+
+        let descriptor = Descriptor::from_config(&config);
+        assert_eq!(DESCRIPTOR_VERSION, descriptor.version);
+        let bloom = &descriptor.pipelines[0];
+        assert_eq!(Some("rendering-team"), bloom.author.as_deref());
+        assert_eq!(vec!["post", "hdr"], bloom.tags);
+        assert_eq!(None, descriptor.pipelines[1].description);
+        assert_eq!(
+            descriptor,
+            Descriptor::from_json(&descriptor.to_json()).unwrap()
+        );
+    }
+
+    #[test]
+    fn compute_pipelines() {
+        let config = PipelineConfig::from_path("./tests/compute.pmd").unwrap();
+        let pipelines = config
+            .compute_pipelines()
+            .iter()
+            .map(|cp| (cp.name.as_str(), cp.entry.as_str(), cp.cfg.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("IntegratePipeline", "cs_integrate", None),
+                ("ResetPipeline", "cs_reset", Some(r#"feature = "editor""#)),
+            ],
+            pipelines,
+        );
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        // Both compute pipelines share a shader with each other, not with the
+        // render pipeline.
+        assert_eq!(2, pipeline_code.to_string().matches("const SHADER").count());
+        assert_compiles(
+            "compute_pipelines",
+            quote! {
+                #pipeline_code
+
+                struct ResetPipeline;
+
+                fn main() {
+                    let _: fn(&::wgpu::Device) -> IntegratePipeline = IntegratePipeline::new;
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> TexturedPipeline = TexturedPipeline::new;
+                    let _ = ResetPipeline;
+                    assert_eq!([64, 1, 1], IntegratePipeline::WORKGROUP_SIZE);
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> Pipelines = Pipelines::new;
+                    let _ = |pipelines: Pipelines| -> (IntegratePipeline, TexturedPipeline) {
+                        (pipelines.integrate_pipeline, pipelines.textured_pipeline)
+                    };
+                    fn dispatch<'a>(pass: &mut ::wgpu::ComputePass<'a>, pipeline: &'a IntegratePipeline) {
+                        pass.set_pipeline(pipeline.pipeline());
+                        pass.set_pipeline(pipeline);
+                    }
+                    let _ = dispatch;
+                    // The device is only borrowed, so it can create any number of pipelines.
+                    let _ = |device: &::wgpu::Device| {
+                        (IntegratePipeline::new(device), TexturedPipeline::new(device, ::wgpu::TextureFormat::Bgra8UnormSrgb))
+                    };
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "compute_pipelines_constants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!("cs_integrate", INTEGRATE_PIPELINE_CS_ENTRY);
+                    assert_eq!([64, 1, 1], INTEGRATE_PIPELINE_WORKGROUP_SIZE);
+                    assert!(INTEGRATE_PIPELINE_SHADER.contains("fn cs_integrate"));
+                }
+            },
+        );
+
+        let config = PipelineConfig::from_src(
+            r#"#compute_pipeline("Broken", "cs_missing", "./tests/compute.wgsl")"#,
+        )
+        .unwrap();
+        let e = code_gen::gen_pipeline_code(&config)
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("no Compute entry point `cs_missing`, the available ones are cs_integrate, cs_reset"), "{}", e);
+    }
+
+    #[test]
+    fn bind_groups() {
+        let config = PipelineConfig::from_path("./tests/bind_groups.pmd").unwrap();
+        let groups = config
+            .bind_groups()
+            .iter()
+            .map(|group| (group.name.as_str(), group.entries.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![("Material", 2), ("Camera", 1)], groups);
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "bind_groups",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(LitPipeline::BIND_GROUP_LAYOUT_ENTRIES[0], MaterialBindGroupLayout::ENTRIES);
+                    assert_eq!(OverlayPipeline::BIND_GROUP_LAYOUT_ENTRIES[1], CameraBindGroupLayout::ENTRIES);
+                    let _: fn(&::wgpu::Device) -> CameraBindGroupLayout = CameraBindGroupLayout::new;
+                    let _: fn(
+                        &MaterialBindGroupLayout,
+                        &::wgpu::Device,
+                        &::wgpu::TextureView,
+                        &::wgpu::Sampler,
+                    ) -> ::wgpu::BindGroup = MaterialBindGroupLayout::create_bind_group;
+                    let _: fn(&CameraBindGroupLayout, &::wgpu::Device, &::wgpu::Buffer) -> ::wgpu::BindGroup =
+                        CameraBindGroupLayout::create_bind_group;
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "bind_groups_constants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(2, MATERIAL_BIND_GROUP_LAYOUT_ENTRIES.len());
+                }
+            },
+        );
+
+        for (src, expected) in [
+            (
+                r#"render_pipeline(name: "A", path: "a.wgsl", bind_groups: ["Camera"])"#,
+                "Unknown bind group",
+            ),
+            (
+                r#"#bind_group(name: "Camera", entries: ["view"])"#,
+                "Unknown binding preset",
+            ),
+            (
+                r#"#bind_group(name: "A", entries: []) #bind_group(name: "A", entries: [])"#,
+                "already defined",
+            ),
+        ] {
+            let e = PipelineConfig::from_src(src).unwrap_err().to_string();
+            assert!(e.contains(expected), "{}", e);
+        }
+    }
+
+    #[test]
+    fn vertex_layouts() {
+        let config = PipelineConfig::from_path("./tests/vertex_layouts.pmd").unwrap();
+        assert_eq!(2, config.vertex_layouts().len());
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "vertex_layouts",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(16, TEXTURED_VERTEX_VERTEX_LAYOUT.array_stride);
+                    assert_eq!(20, INSTANCE_DATA_VERTEX_LAYOUT.array_stride);
+                    assert_eq!(::wgpu::VertexStepMode::Instance, INSTANCE_DATA_VERTEX_LAYOUT.step_mode);
+                    assert_eq!(16, INSTANCE_DATA_VERTEX_ATTRIBUTES[1].offset);
+                    assert_eq!(3, INSTANCE_DATA_VERTEX_ATTRIBUTES[1].shader_location);
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> TexturedPipeline = TexturedPipeline::new;
+                    // A buffer for each layout, in slot order.
+                    fn draw<'a>(render_pass: &mut ::wgpu::RenderPass<'a>, pipeline: &'a TexturedPipeline, buffer: &'a ::wgpu::Buffer) {
+                        pipeline.draw(render_pass, [buffer.slice(..16), buffer.slice(16..)], 0..3, 0..1);
+                    }
+                    let _ = draw;
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "vertex_layouts_constants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(2, TEXTURED_PIPELINE_VERTEX_BUFFERS.len());
+                    assert_eq!(TEXTURED_VERTEX_VERTEX_LAYOUT, UNTEXTURED_PIPELINE_VERTEX_BUFFERS[0]);
+                }
+            },
+        );
+
+        let src = r#"
+            #vertex_layout(name: "A", attributes: [Float32x2, Float32x2])
+            #vertex_layout(name: "B", first_location: 1, attributes: [Float32])
+            render_pipeline(name: "P", path: "./tests/texture.wgsl", vertex_buffers: ["A", "B"])
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let e = code_gen::gen_pipeline_code(&config)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            e.contains("vertex layouts A and B use the same shader locations"),
+            "{}",
+            e
+        );
+        let e = PipelineConfig::from_src(
+            r#"render_pipeline(name: "P", path: "p.wgsl", vertex_buffers: ["A"])"#,
+        )
+        .unwrap_err();
+        assert!(matches!(e, code_gen::ParseError::UnknownVertexLayout(name) if name == "A"));
+    }
+
+    #[test]
+    fn shader_modules() {
+        let config = PipelineConfig::from_path("./tests/shaders.pmd").unwrap();
+        let textured = &config.render_pipelines()[0];
+        assert_eq!(Some("textured"), textured.shader.as_deref());
+        assert_eq!("./tests/texture.wgsl", textured.path);
+        let options = CodegenOptions {
+            source_embedding: SourceEmbedding::IncludeStr,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        let code = pipeline_code.to_string();
+        // Declared modules get stable names, and their defines apply to every
+        // variant, so their source has to be inlined.
+        for name in [
+            "SHADER_TEXTURED",
+            "SHADER_CHECKER_BASE",
+            "SHADER_CHECKER_ALPHA_TEST",
+        ] {
+            assert!(code.contains(&format!("const {} ", name)), "{}", name);
+        }
+        // `textured`, the blit shader and the compute shader.
+        assert_eq!(3, code.matches("include_str !").count());
+        assert_compiles(
+            "shader_modules",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> TexturedPipeline = TexturedPipeline::new;
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> CheckerPipelineAlphaTest = CheckerPipelineAlphaTest::new;
+                    assert!(SHADER_CHECKER_BASE.contains("in.uv * 64.0"));
+                    assert!(!SHADER_CHECKER_BASE.contains("in.uv * 8.0"));
+                }
+            },
+        );
+
+        for (src, expected) in [
+            (
+                r#"render_pipeline(name: "A", shader: "missing")"#,
+                "Unknown shader",
+            ),
+            (
+                r#"render_pipeline(name: "A", shader: "a", path: "a.wgsl")"#,
+                "can't be used together with path",
+            ),
+            (
+                r#"#shader(name: "a", path: "a.wgsl") #shader(name: "a", path: "b.wgsl")"#,
+                "already defined",
+            ),
+            (r#"#shader(name: "a", path: "a.hlsl", lang: Hlsl)"#, "Hlsl"),
+        ] {
+            let e = PipelineConfig::from_src(src).unwrap_err().to_string();
+            assert!(e.contains(expected), "{}", e);
+        }
+    }
+
+    #[test]
+    fn pipeline_layouts() {
+        let config = PipelineConfig::from_path("./tests/pipeline_layouts.pmd").unwrap();
+        assert_eq!(2, config.pipeline_layouts().len());
+        let options = CodegenOptions {
+            registry: true,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "pipeline_layouts",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(2, ScenePipelineLayout::BIND_GROUP_LAYOUT_ENTRIES.len());
+                    assert_eq!(CameraBindGroupLayout::ENTRIES, ScenePipelineLayout::BIND_GROUP_LAYOUT_ENTRIES[0]);
+                    assert_eq!(0..64, PushedPipelineLayout::PUSH_CONSTANT_RANGES[0].range);
+                    assert_eq!(::wgpu::Features::PUSH_CONSTANTS, PushedPipeline::REQUIRED_FEATURES);
+                    assert_eq!(::wgpu::Features::empty(), OpaquePipeline::REQUIRED_FEATURES);
+                    let _: fn(&::wgpu::Device, &ScenePipelineLayout, ::wgpu::TextureFormat) -> OpaquePipeline = OpaquePipeline::new;
+                    let _: fn(&::wgpu::Device, &ScenePipelineLayout, ::wgpu::TextureFormat) -> TransparentPipeline = TransparentPipeline::new;
+                    let _: fn(&ScenePipelineLayout) -> &::wgpu::PipelineLayout = ScenePipelineLayout::layout;
+                    // They need the layout passed in.
+                    assert!(PIPELINE_REGISTRY.is_empty());
+                    // `Pipelines` creates the layouts itself and shares the surface format.
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> Pipelines = Pipelines::new;
+                    let _ = |pipelines: Pipelines| -> (OpaquePipeline, PushedPipeline, ScenePipelineLayout) {
+                        (pipelines.opaque_pipeline, pipelines.pushed_pipeline, pipelines.scene_layout)
+                    };
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "pipeline_layouts_constants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!("Scene", OPAQUE_PIPELINE_PIPELINE_LAYOUT);
+                    assert_eq!(1, PUSHED_PIPELINE_LAYOUT_PUSH_CONSTANT_RANGES.len());
+                }
+            },
+        );
+
+        for (src, expected) in [
+            (
+                r#"render_pipeline(name: "A", path: "a.wgsl", layout: "Scene")"#,
+                "Unknown pipeline layout",
+            ),
+            (
+                r#"#pipeline_layout(name: "Scene", bind_groups: ["Camera"])"#,
+                "Unknown bind group",
+            ),
+            (
+                r#"#pipeline_layout(name: "A") render_pipeline(name: "P", path: "p.wgsl", layout: "A", bind_group_layouts: ["a"])"#,
+                "can't be used together",
+            ),
+        ] {
+            let e = PipelineConfig::from_src(src).unwrap_err().to_string();
+            assert!(e.contains(expected), "{}", e);
+        }
+    }
+
+    #[test]
+    fn shared_layouts() {
+        // Both reflect the same texture and sampler, and Debug isn't compiled in.
+        let src = r#"
+            render_pipeline(name: "SpritePipeline", path: "./tests/texture.wgsl", vs_entry: "vs_textured", fs_entry: "fs_textured")
+            render_pipeline(name: "TextPipeline", path: "./tests/texture.wgsl", vs_entry: "vs_textured", fs_entry: "fs_textured", sample_counts: [1, 4])
+            render_pipeline(name: "DebugPipeline", path: "./tests/texture.wgsl", vs_entry: "vs_textured", fs_entry: "fs_textured", cfg: "any()")
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert!(!pipeline_code.to_string().contains("SharedPipelineLayout1"));
+        assert_compiles(
+            "shared_layouts",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(SpritePipeline::BIND_GROUP_LAYOUT_ENTRIES, SharedPipelineLayout0::BIND_GROUP_LAYOUT_ENTRIES);
+                    let _: fn(&::wgpu::Device, &SharedPipelineLayout0, ::wgpu::TextureFormat) -> SpritePipeline =
+                        SpritePipeline::with_shared_layout;
+                    let _: fn(&::wgpu::Device, &SharedPipelineLayout0, ::wgpu::TextureFormat) -> TextPipeline<4> =
+                        TextPipeline::<4>::with_shared_layout;
+                    // Pipelines created without it still make their own.
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> SpritePipeline = SpritePipeline::new;
+                    let _ = |pipelines: Pipelines| -> (SharedPipelineLayout0, SpritePipeline, TextPipeline<4>) {
+                        (pipelines.shared_layout0, pipelines.sprite_pipeline, pipelines.text_pipeline_x4)
+                    };
+                }
+            },
+        );
+
+        // One pipeline with one sample count has nothing to share with.
+        let src = r#"render_pipeline(name: "SpritePipeline", path: "./tests/texture.wgsl", vs_entry: "vs_textured", fs_entry: "fs_textured")"#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap().to_string();
+        assert!(
+            !pipeline_code.contains("SharedPipelineLayout"),
+            "{}",
+            pipeline_code
+        );
+    }
+
+    #[test]
+    fn color_targets() {
+        let config = PipelineConfig::from_path("./tests/color_targets.pmd").unwrap();
+        assert_eq!(3, config.color_targets().len());
+        assert_eq!(
+            vec!["Bgra8UnormSrgb"],
+            config.render_pipelines()[2].target_formats
+        );
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "color_targets",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(::wgpu::TextureFormat::Rgba16Float, HDR_COLOR_TARGET.format);
+                    assert_eq!(None, ALBEDO_COLOR_TARGET.blend);
+                    assert_eq!(Some(::wgpu::BlendState::ALPHA_BLENDING), UI_COLOR_TARGET.blend);
+                    assert_eq!(::wgpu::BlendFactor::One, HDR_COLOR_TARGET.blend.unwrap().color.dst_factor);
+                    let _: fn(&::wgpu::Device) -> GBufferPipeline = GBufferPipeline::new;
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "color_targets_constants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(&[Some(ALBEDO_COLOR_TARGET), Some(HDR_COLOR_TARGET)], G_BUFFER_PIPELINE_TARGETS);
+                    assert_eq!(::wgpu::TextureFormat::Bgra8UnormSrgb, BLIT_PIPELINE_TARGETS[0].as_ref().unwrap().format);
+                }
+            },
+        );
+
+        let e = PipelineConfig::from_src(
+            r#"render_pipeline(name: "A", path: "a.wgsl", targets: ["Hdr"])"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(e.contains("Unknown color target"), "{}", e);
+
+        let src = r#"
+            #color_target(name: "Hdr", format: Rgba16Float)
+            render_pipeline(name: "A", path: "./tests/gbuffer.wgsl", targets: ["Hdr"])
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let e = code_gen::gen_pipeline_code(&config)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            e.contains("writes 2 color targets, but 1 are given"),
+            "{}",
+            e
+        );
+
+        let src = r#"render_pipeline(name: "GBufferPipeline", path: "./tests/gbuffer.wgsl")"#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let options = CodegenOptions {
+            registry: true,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "runtime_target_formats",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let _: fn(&::wgpu::Device, [::wgpu::TextureFormat; 2]) -> GBufferPipeline = GBufferPipeline::new;
+                    let _: fn(&::wgpu::Device, [::wgpu::TextureFormat; 2]) -> Pipelines = Pipelines::new;
+                    let _ = |pipelines: Pipelines| -> GBufferPipeline { pipelines.g_buffer_pipeline };
+                    assert!(PIPELINE_REGISTRY.is_empty());
+                    // A view for each target.
+                    let _: for<'a> fn(&'a mut ::wgpu::CommandEncoder, [&'a ::wgpu::TextureView; 2], ::wgpu::LoadOp<::wgpu::Color>) -> ::wgpu::RenderPass<'a> =
+                        GBufferPipeline::begin_pass;
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn override_constants() {
+        let config = PipelineConfig::from_path("./tests/overrides.pmd").unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        let generated = pipeline_code.to_string();
+        assert!(
+            generated.contains("let MAX_LIGHTS: u32 = 64u;"),
+            "{}",
+            generated
+        );
+        assert!(
+            generated.contains("let MAX_LIGHTS: u32 = 4u;"),
+            "{}",
+            generated
+        );
+        assert!(!generated.contains("override MAX_LIGHTS"), "{}", generated);
+        // Each specialization is named after the file and its source, the
+        // same way every time.
+        assert_eq!(
+            2,
+            generated.matches("const SHADER_OVERRIDES_").count(),
+            "{}",
+            generated
+        );
+        for _ in 0..4 {
+            assert_eq!(
+                generated,
+                code_gen::gen_pipeline_code(&config).unwrap().to_string()
+            );
+        }
+        assert_compiles(
+            "override_constants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(&[("MAX_LIGHTS", 64.0), ("FOG_DENSITY", 0.5)], ForwardPipeline::CONSTANTS);
+                    assert_eq!(&[("FOG_DENSITY", 0.0)], ForwardLowPipeline::CONSTANTS);
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "override_constants_constants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(&[("FOG_DENSITY", 0.0)], FORWARD_LOW_PIPELINE_CONSTANTS);
+                    assert!(FORWARD_PIPELINE_SHADER.contains("let FOG_DENSITY: f32 = 0.5;"));
+                }
+            },
+        );
+
+        let config = PipelineConfig::from_src(
+            r#"render_pipeline(name: "A", path: "./tests/overrides.wgsl", constants: (MAX_LIGHTS: 8))"#,
+        )
+        .unwrap();
+        let e = code_gen::gen_pipeline_code(&config)
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("FOG_DENSITY has no default"), "{}", e);
+    }
+
+    #[test]
+    fn pipeline_defines() {
+        let config = PipelineConfig::from_path("./tests/defines.pmd").unwrap();
+        assert_eq!(vec!["SHADOWS"], config.render_pipelines()[0].defines);
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            source_embedding: SourceEmbedding::IncludeStr,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert!(!pipeline_code.to_string().contains("include_str"));
+        assert_compiles(
+            "pipeline_defines",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert!(LIT_PIPELINE_SHADER.contains("let shadow = 0.5;"));
+                    assert!(!LIT_PIPELINE_SHADER.contains("let shadow = 1.0;"));
+                    assert!(UNLIT_PIPELINE_SHADER.contains("let shadow = 1.0;"));
+                    assert!(!UNLIT_PIPELINE_SHADER.contains("#ifdef"));
+                }
+            },
+        );
+
+        let e = PipelineConfig::from_src(
+            r#"render_pipeline(name: "A", path: "a.wgsl", defines: [SKINNED], variants: [SKINNED])"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(e.contains("also in variants"), "{}", e);
+    }
+
+    #[test]
+    fn aliases() {
+        let config = PipelineConfig::from_path("./tests/aliases.pmd").unwrap();
+        let rp = &config.render_pipelines()[0];
+        assert_eq!(Some("Front"), rp.primitive.cull_mode.as_deref());
+        let depth_stencil = rp.depth_stencil.as_ref().unwrap();
+        assert_eq!("Depth32Float", depth_stencil.format);
+        assert_eq!("LessEqual", depth_stencil.depth_compare);
+        assert_eq!("Rgba16Float", config.color_targets()[0].format);
+
+        let e = PipelineConfig::from_src("#alias(Format: Rgba8Unorm) #alias(Format: Bgra8Unorm)")
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("already defined"), "{}", e);
+    }
+
+    #[test]
+    fn rust_imports() {
+        let config = PipelineConfig::from_path("./tests/rust_imports.pmd").unwrap();
+        assert_eq!("LAYOUT", config.rust_imports()[0].layout);
+        let mesh = quote! {
+            mod mesh {
+                pub struct Vertex;
+
+                impl Vertex {
+                    pub const LAYOUT: ::wgpu::VertexBufferLayout<'static> = ::wgpu::VertexBufferLayout {
+                        array_stride: 16,
+                        step_mode: ::wgpu::VertexStepMode::Vertex,
+                        attributes: &::wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2],
+                    };
+                }
+            }
+        };
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert!(!pipeline_code.to_string().contains("MESH_VERTEX"));
+        assert_compiles(
+            "rust_imports",
+            quote! {
+                #mesh
+                #pipeline_code
+
+                fn main() {
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> MeshPipeline = MeshPipeline::new;
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "rust_imports_constants",
+            quote! {
+                #mesh
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(16, MESH_PIPELINE_VERTEX_BUFFERS[0].array_stride);
+                }
+            },
+        );
+
+        let src = r#"
+            #import_rust(name: "A", path: "crate::mesh::Vertex")
+            #vertex_layout(name: "A", attributes: [Float32x2])
+        "#;
+        let e = PipelineConfig::from_src(src).unwrap_err().to_string();
+        assert!(e.contains("already defined"), "{}", e);
+        let src = r#"
+            #import_rust(name: "A", path: "crate::mesh::")
+            render_pipeline(name: "P", path: "./tests/texture.wgsl", vertex_buffers: ["A"])
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let e = code_gen::gen_pipeline_code(&config)
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("is not a Rust path"), "{}", e);
+    }
+
+    #[test]
+    fn reflected_vertex_buffers() {
+        let src = r#"
+            render_pipeline(name: "TexturedPipeline", path: "./tests/texture.wgsl")
+            render_pipeline(name: "FillPipeline", path: "./tests/fullscreen.wgsl", fs_entry: "fs_fill")
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "reflected_vertex_buffers",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(16, TexturedPipeline::VERTEX_LAYOUT.array_stride);
+                    assert_eq!(::wgpu::VertexFormat::Float32x2, TexturedPipeline::VERTEX_ATTRIBUTES[1].format);
+                    assert_eq!(8, TexturedPipeline::VERTEX_ATTRIBUTES[1].offset);
+                    assert_eq!(1, TexturedPipeline::VERTEX_ATTRIBUTES[1].shader_location);
+
+                    let vertices = [TexturedPipelineVertex {
+                        position: [1.0, 2.0],
+                        uv: [0.0, 1.0],
+                    }; 3];
+                    assert_eq!(48, TexturedPipelineVertex::slice_as_bytes(&vertices).len());
+                    assert_eq!(TexturedPipelineVertex::LAYOUT, TexturedPipeline::VERTEX_LAYOUT);
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert!(!pipeline_code
+            .to_string()
+            .contains("FILL_PIPELINE_VERTEX_BUFFERS"));
+        assert_compiles(
+            "reflected_vertex_buffers_constants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(&[TEXTURED_PIPELINE_VERTEX_LAYOUT], TEXTURED_PIPELINE_VERTEX_BUFFERS);
+                    assert_eq!(2, TEXTURED_PIPELINE_VERTEX_ATTRIBUTES.len());
+                    assert_eq!(16, ::std::mem::size_of::<TexturedPipelineVertex>());
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn reflected_bind_group_layouts() {
+        let src = r#"
+            render_pipeline(name: "LitPipeline", path: "./tests/lit.wgsl", target_formats: [Rgba16Float])
+            render_pipeline(
+                name: "ManualPipeline",
+                path: "./tests/lit.wgsl",
+                bind_group_layouts: ["camera", "empty", "lights"],
+                target_formats: [Rgba16Float],
+            )
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "reflected_bind_group_layouts",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    use ::wgpu::{BindingType, BufferBindingType, ShaderStages, TextureViewDimension};
+
+                    let groups = LitPipeline::BIND_GROUP_LAYOUT_ENTRIES;
+                    assert_eq!(3, groups.len());
+                    assert!(groups[1].is_empty());
+                    assert_eq!(ShaderStages::VERTEX, groups[0][0].visibility);
+                    assert!(matches!(groups[0][0].ty, BindingType::Buffer { ty: BufferBindingType::Uniform, .. }));
+                    assert!(matches!(
+                        groups[2][0].ty,
+                        BindingType::Buffer { ty: BufferBindingType::Storage { read_only: true }, .. }
+                    ));
+                    assert!(matches!(groups[2][1].ty, BindingType::Texture { view_dimension: TextureViewDimension::Cube, .. }));
+                    assert_eq!(ShaderStages::FRAGMENT, groups[2][2].visibility);
+                    let _: fn(&LitPipeline) -> &[::wgpu::BindGroupLayout] = LitPipeline::bind_group_layouts;
+
+                    fn bind_lights(
+                        device: &::wgpu::Device,
+                        pipeline: &LitPipeline,
+                        lights: &::wgpu::Buffer,
+                        t_env: &::wgpu::TextureView,
+                        s_env: &::wgpu::Sampler,
+                    ) -> ::wgpu::BindGroup {
+                        let bindings = LitPipelineGroup2Bindings { lights, t_env, s_env };
+                        bindings.create_bind_group(device, &pipeline.bind_group_layouts()[2])
+                    }
+                    let _ = bind_lights;
+                    let _ = |device: &::wgpu::Device, camera: &::wgpu::Buffer, layout: &::wgpu::BindGroupLayout| {
+                        LitPipelineGroup0Bindings { camera }.create_bind_group(device, layout)
+                    };
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        let generated = pipeline_code.to_string();
+        assert!(
+            generated.contains("LIT_PIPELINE_BIND_GROUP_LAYOUT_ENTRIES"),
+            "{}",
+            generated
+        );
+        assert!(
+            !generated.contains("MANUAL_PIPELINE_BIND_GROUP_LAYOUT_ENTRIES"),
+            "{}",
+            generated
+        );
+        assert!(!generated.contains("Group0Bindings"), "{}", generated);
+
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap().to_string();
+        assert!(
+            !pipeline_code.contains("ManualPipelineGroup0Bindings"),
+            "{}",
+            pipeline_code
+        );
+        assert!(
+            !pipeline_code.contains("LitPipelineGroup1Bindings"),
+            "{}",
+            pipeline_code
+        );
+    }
+
+    #[test]
+    fn uniform_structs() {
+        let src = r#"
+            render_pipeline(name: "UniformPipeline", path: "./tests/uniforms.wgsl", target_formats: [Rgba8Unorm])
+            render_pipeline(name: "OtherPipeline", path: "./tests/uniforms.wgsl", target_formats: [Rgba8Unorm])
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "uniform_structs",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    use ::std::mem::{offset_of, size_of};
+
+                    assert_eq!(32, size_of::<Light>());
+                    assert_eq!(16, offset_of!(Light, color));
+                    assert_eq!(64, offset_of!(Globals, normal));
+                    assert_eq!(112, offset_of!(Globals, time));
+                    assert_eq!(120, offset_of!(Globals, offset));
+                    assert_eq!(128, offset_of!(Globals, light));
+                    assert_eq!(160, offset_of!(Globals, weights));
+                    assert_eq!(192, Globals::default().as_bytes().len());
+                    let globals = Globals {
+                        time: 1.0,
+                        light: Light {
+                            intensity: 2.0,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    };
+                    assert_eq!(1.0f32.to_ne_bytes(), globals.as_bytes()[112..116]);
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            bytemuck: true,
+            ..Default::default()
+        };
+        let generated = code_gen::gen_pipeline_code_with_options(&config, &options)
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            1,
+            generated.matches("pub struct Globals").count(),
+            "{}",
+            generated
+        );
+        assert!(generated.contains(":: bytemuck :: Pod"), "{}", generated);
+    }
+
+    #[test]
+    fn dynamic_offsets() {
+        let src = r#"
+            render_pipeline(
+                name: "ObjectPipeline",
+                path: "./tests/uniforms.wgsl",
+                target_formats: [Rgba8Unorm],
+                bindings: (globals: (dynamic: true)),
+            )
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "dynamic_offsets",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let ::wgpu::BindingType::Buffer { has_dynamic_offset, min_binding_size, .. } =
+                        ObjectPipeline::BIND_GROUP_LAYOUT_ENTRIES[0][0].ty
+                    else {
+                        unreachable!()
+                    };
+                    assert!(has_dynamic_offset);
+                    assert_eq!(Some(192), min_binding_size.map(|size| size.get()));
+                    let _: for<'a> fn(&mut ::wgpu::RenderPass<'a>, &'a ::wgpu::BindGroup, [u32; 1], &::wgpu::Limits) =
+                        ObjectPipeline::set_bind_group_0;
+                }
+            },
+        );
+
+        let src = r#"render_pipeline(name: "A", path: "./tests/uniforms.wgsl", bindings: (globals: (filtering: true)))"#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let e = code_gen::gen_pipeline_code(&config)
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("can only set `dynamic`"), "{}", e);
+    }
+
+    #[test]
+    fn push_constant_structs() {
+        let src = r#"render_pipeline(name: "PushPipeline", path: "./tests/push_constants.wgsl", target_formats: [Rgba8Unorm])"#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "push_constant_structs",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(80, ::std::mem::size_of::<PushConstants>());
+                    let range = &PushPipeline::PUSH_CONSTANT_RANGES[0];
+                    assert_eq!(0..80, range.range);
+                    assert_eq!(::wgpu::ShaderStages::VERTEX_FRAGMENT, range.stages);
+                    assert!(PushPipeline::REQUIRED_FEATURES.contains(::wgpu::Features::PUSH_CONSTANTS));
+                    let _: fn(&mut ::wgpu::RenderPass<'_>, &PushConstants) = PushPipeline::set_push_constants;
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let generated = code_gen::gen_pipeline_code_with_options(&config, &options)
+            .unwrap()
+            .to_string();
+        assert!(
+            generated.contains("PUSH_PIPELINE_PUSH_CONSTANT_RANGES"),
+            "{}",
+            generated
+        );
+    }
+
+    #[test]
+    fn storage_structs() {
+        let src = r#"#compute_pipeline("StepPipeline", "cs_step", "./tests/storage.wgsl")"#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        assert_compiles(
+            "storage_structs",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    use ::std::mem::{offset_of, size_of};
+
+                    assert_eq!(32, size_of::<Particle>());
+                    assert_eq!(32, Particle::STRIDE);
+                    assert_eq!(16, offset_of!(Particle, velocity));
+                    assert_eq!(32, size_of::<Simulation>());
+                    assert_eq!(16, offset_of!(Simulation, gravity));
+                    assert_eq!(32, Simulation::ELEMENT_STRIDE);
+                    assert_eq!(32 + 10 * 32, Simulation::buffer_size(10));
+                    assert_eq!(8, size_of::<Grid>());
+                    assert_eq!(32, Simulation::default().as_bytes().len());
+                }
+            },
+        );
+
+        // compute.wgsl has a different `Particle`.
+        let src = r#"
+            #compute_pipeline("StepPipeline", "cs_step", "./tests/storage.wgsl")
+            #compute_pipeline("IntegratePipeline", "cs_integrate", "./tests/compute.wgsl")
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let err = code_gen::gen_pipeline_code(&config)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("IntegratePipeline: struct `Particle` has a different layout"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn binding_presets() {
+        let config = PipelineConfig::from_path("./tests/presets.pmd").unwrap();
+        let names = config
+            .binding_presets()
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["albedo", "linear_sampler", "camera"], names);
+        let options = CodegenOptions {
+            registry: true,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "binding_presets",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let groups = LitPipeline::BIND_GROUP_LAYOUT_ENTRIES;
+                    assert_eq!(2, groups.len());
+                    assert_eq!(1, groups[0][1].binding);
+                    assert_eq!(::wgpu::ShaderStages::VERTEX_FRAGMENT, groups[1][0].visibility);
+                    assert!(matches!(
+                        groups[0][1].ty,
+                        ::wgpu::BindingType::Sampler(::wgpu::SamplerBindingType::Filtering),
+                    ));
+                    let _: fn(&LitPipeline) -> &[::wgpu::BindGroupLayout] = LitPipeline::bind_group_layouts;
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> TexturedPipeline = TexturedPipeline::new;
+                    // Neither needs layouts passed in, but only `LitPipeline`
+                    // knows its target formats, so it's the only one registered.
+                    assert_eq!(vec!["LitPipeline"], PIPELINE_REGISTRY.iter().map(|f| f.name).collect::<Vec<_>>());
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert_compiles(
+            "binding_presets_constants",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    assert_eq!(1, TEXTURED_PIPELINE_BIND_GROUP_LAYOUT_ENTRIES.len());
+                }
+            },
+        );
+
+        let src = r#"render_pipeline(name: "A", path: "a.wgsl", bind_groups: [["camera"]])"#;
+        let e = PipelineConfig::from_src(src).unwrap_err();
+        assert_eq!("Unknown binding preset: \"camera\"", e.to_string());
+        let src = r#"
+            #binding_preset(name: "camera", ty: Uniform, visibility: Vertex)
+            #binding_preset(name: "camera", ty: Uniform, visibility: Fragment)
+        "#;
+        let e = PipelineConfig::from_src(src).unwrap_err();
+        assert_eq!(
+            "Binding preset \"camera\" is already defined",
+            e.to_string()
+        );
+        let src = r#"#binding_preset(name: "s", ty: Sampler(Linear), visibility: Fragment)"#;
+        let e = PipelineConfig::from_src(src).unwrap_err();
+        assert!(
+            e.to_string()
+                .starts_with("Invalid value \"Linear\" for \"ty\""),
+            "{}",
+            e
+        );
+    }
+
+    #[test]
+    fn formatted_output() {
+        let config = PipelineConfig::from_path("./tests/texture.pmd").unwrap();
+        let options = CodegenOptions::default();
+        let formatted = code_gen::gen_pipeline_code_formatted(&config, &options).unwrap();
+        assert!(formatted.lines().count() > 10, "{}", formatted);
+        assert!(
+            formatted.contains("\npub struct TexturedPipeline {"),
+            "{}",
+            formatted
+        );
+        let pipeline_code = formatted.parse::<TokenStream>().unwrap();
+        // Laid out the same way however it's reached.
+        assert_eq!(formatted, code_gen::format_code(&pipeline_code));
+        assert_compiles(
+            "formatted_output",
+            quote! {
+                #pipeline_code
+
+                fn main() {}
+            },
+        );
+    }
+
+    #[test]
+    fn visibility() {
+        let config = PipelineConfig::from_path("./tests/layouts.pmd").unwrap();
+        let options = CodegenOptions {
+            registry: true,
+            visibility: Visibility::Crate,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert!(pipeline_code
+            .to_string()
+            .contains("pub (crate) struct SkyPipeline"));
+        assert_compiles(
+            "visibility_crate",
+            quote! {
+                mod pipelines {
+                    #pipeline_code
+                }
+
+                fn main() {
+                    let _: fn(&::wgpu::Device) -> pipelines::SkyPipeline = pipelines::SkyPipeline::new;
+                    assert_eq!(1, pipelines::PIPELINE_REGISTRY.len());
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            visibility: Visibility::Private,
+            ..Default::default()
+        };
+        let generated = code_gen::gen_pipeline_code_with_options(&config, &options)
+            .unwrap()
+            .to_string();
+        assert!(generated.contains("struct SkyPipeline {"), "{}", generated);
+        assert!(
+            !generated.contains("pub struct SkyPipeline"),
+            "{}",
+            generated
+        );
+        // Methods stay `pub`, since the struct already limits who can call them.
+        assert!(generated.contains("pub fn new"), "{}", generated);
+    }
+
+    #[test]
+    fn pipeline_tests() {
+        let src = r#"
+            #binding_preset(name: "albedo", ty: Texture(Float), visibility: Fragment)
+            #binding_preset(name: "linear_sampler", ty: Sampler(Filtering), visibility: Fragment)
+            #pipeline_layout(name: "Scene", bind_groups: [["albedo", "linear_sampler"]])
+            render_pipeline(name: "SpritePipeline", path: "./tests/texture.wgsl", sample_counts: [4, 1])
+            render_pipeline(name: "OpaquePipeline", path: "./tests/texture.wgsl", layout: "Scene")
+            render_pipeline(name: "LayoutsPipeline", path: "./tests/texture.wgsl", bind_group_layouts: ["textures"])
+            #compute_pipeline("ResetPipeline", "cs_reset", "./tests/compute.wgsl")
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let options = CodegenOptions {
+            tests: true,
+            ..Default::default()
+        };
+        let generated = code_gen::gen_pipeline_code_with_options(&config, &options)
+            .unwrap()
+            .to_string();
+        assert!(
+            generated.contains("# [cfg (test)] mod pipeline_tests"),
+            "{}",
+            generated
+        );
+        assert!(
+            generated.contains("SpritePipeline :: < 4u32 > :: new"),
+            "{}",
+            generated
+        );
+        assert!(!generated.contains("fn layouts_pipeline"), "{}", generated);
+        // Compiled as ordinary functions, which pass without an adapter.
+        let pipeline_code = generated
+            .replace("# [cfg (test)] mod", "mod")
+            .replace("# [test] fn", "pub fn")
+            .parse::<TokenStream>()
+            .unwrap();
+        assert_compiles(
+            "pipeline_tests",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    pipeline_tests::sprite_pipeline();
+                    pipeline_tests::opaque_pipeline();
+                    pipeline_tests::reset_pipeline();
+                }
+            },
+        );
+
+        let generated = code_gen::gen_pipeline_code(&config).unwrap().to_string();
+        assert!(!generated.contains("pipeline_tests"));
+    }
+
+    #[test]
+    fn equivalent_shader_paths() {
+        let config = PipelineConfig::from_path("./tests/paths.pmd").unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        let generated = pipeline_code.to_string();
+        // Each spelling of the path shares one source.
+        assert_eq!(
+            1,
+            generated.matches("const SHADER").count(),
+            "{}",
+            generated
+        );
+        assert!(generated.contains("const SHADER_TEXTURE_"), "{}", generated);
+
+        // Relative paths are taken from the base.
+        let src = std::fs::read_to_string("./tests/paths.pmd").unwrap();
+        let src = src
+            .replace("tests/include/../", "")
+            .replace("./tests/", "")
+            .replace("tests/", "");
+        let config = PipelineConfig::from_src(&src).unwrap();
+        let options = CodegenOptions {
+            shader_base: Some("./tests".into()),
+            ..Default::default()
+        };
+        let based = code_gen::gen_pipeline_code_with_options(&config, &options)
+            .unwrap()
+            .to_string();
+        assert_eq!(1, based.matches("const SHADER").count(), "{}", based);
+        let name = |code: &str| code.split_whitespace().nth(1).map(str::to_owned);
+        assert_eq!(name(&generated), name(&based));
+    }
+
+    #[test]
+    fn spirv_embedding() {
+        let config = PipelineConfig::from_path("./tests/texture.pmd").unwrap();
+        let options = CodegenOptions {
+            source_embedding: SourceEmbedding::SpirV,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        let generated = pipeline_code.to_string();
+        // Only the binary is embedded.
+        assert!(!generated.contains("fn vs_textured"), "{}", generated);
+        assert!(
+            generated.contains("create_shader_module_spirv"),
+            "{}",
+            generated
+        );
+        let shader = generated
+            .split_whitespace()
+            .find(|ident| ident.starts_with("SHADER_TEXTURE_"))
+            .unwrap();
+        let shader = syn::parse_str::<syn::Ident>(shader).unwrap();
+        assert_compiles(
+            "spirv_embedding",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> TexturedPipeline = TexturedPipeline::new;
+                    assert!(TexturedPipeline::REQUIRED_FEATURES.contains(::wgpu::Features::SPIRV_SHADER_PASSTHROUGH));
+                    // The SPIR-V magic number.
+                    assert_eq!(0x0723_0203, #shader[0]);
+                }
+            },
+        );
+
+        let config = PipelineConfig::from_path("./tests/compute.pmd").unwrap();
+        let e = code_gen::gen_pipeline_code_with_options(&config, &options)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            e.starts_with("IntegratePipeline: compute pipelines can't be created from SPIR-V"),
+            "{}",
+            e
+        );
+        let options = CodegenOptions {
+            mode: CodegenMode::ConstantsOnly,
+            ..options
+        };
+        assert!(code_gen::gen_pipeline_code_with_options(&config, &options).is_ok());
+    }
+
+    #[test]
+    fn shader_imports() {
+        let src = r#"render_pipeline(name: "SkyPipeline", path: "./tests/imports/sky.wgsl")"#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let options = CodegenOptions {
+            source_embedding: SourceEmbedding::IncludeStr,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        // The composed source only exists in memory.
+        assert!(!pipeline_code.to_string().contains("include_str"));
+        assert_compiles(
+            "shader_imports",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> SkyPipeline = SkyPipeline::new;
+                    // Reflected from the imported module.
+                    let _ = |camera: Camera| -> f32 { camera.exposure };
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn prune_shaders() {
+        let src = r#"render_pipeline(name: "FillPipeline", path: "./tests/fullscreen.wgsl", fs_entry: "fs_fill")"#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let options = CodegenOptions {
+            prune_shaders: true,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        let generated = pipeline_code.to_string();
+        assert!(generated.contains("fn vs_main("), "{}", generated);
+        assert!(generated.contains("fn fs_fill("), "{}", generated);
+        assert!(!generated.contains("vs_flipped"), "{}", generated);
+        assert!(!generated.contains("fs_checker"), "{}", generated);
+        assert_compiles(
+            "prune_shaders",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> FillPipeline = FillPipeline::new;
+                }
+            },
+        );
+
+        // Pruning works on the SPIR-V too.
+        let options = CodegenOptions {
+            source_embedding: SourceEmbedding::SpirV,
+            ..options
+        };
+        let pruned = code_gen::gen_pipeline_code_with_options(&config, &options)
+            .unwrap()
+            .to_string();
+        let options = CodegenOptions {
+            prune_shaders: false,
+            ..options
+        };
+        let unpruned = code_gen::gen_pipeline_code_with_options(&config, &options)
+            .unwrap()
+            .to_string();
+        assert!(pruned.len() < unpruned.len());
+    }
+
+    #[test]
+    fn minify_shaders() {
+        let options = CodegenOptions {
+            minify_shaders: true,
+            source_embedding: SourceEmbedding::IncludeStr,
+            ..Default::default()
+        };
+        let config = PipelineConfig::from_path("./tests/texture.pmd").unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        let generated = pipeline_code.to_string();
+        assert!(!generated.contains("include_str"), "{}", generated);
+        let shader = generated
+            .split_whitespace()
+            .find(|ident| ident.starts_with("SHADER_TEXTURE_"))
+            .unwrap();
+        let shader = syn::parse_str::<syn::Ident>(shader).unwrap();
+        assert_compiles(
+            "minify_shaders",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> TexturedPipeline = TexturedPipeline::new;
+                    assert!(!#shader.contains('\n'));
+                    assert!(#shader.contains("fn vs_textured(in:VSIn)->VSOut{"));
+                }
+            },
+        );
+        // Every shader the tests use still parses once it's minified.
+        for path in [
+            "./tests/layouts.pmd",
+            "./tests/variants.pmd",
+            "./tests/overrides.pmd",
+            "./tests/compute.pmd",
+        ] {
+            let config = PipelineConfig::from_path(path).unwrap();
+            code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        }
+    }
+
+    #[test]
+    fn path_variables() {
+        let options = CodegenOptions {
+            path_variables: [("SHADERS".to_owned(), "./tests".to_owned())].into(),
+            ..Default::default()
+        };
+        let gen = |path: &str| {
+            let src = format!(
+                r#"render_pipeline(name: "A", path: "{}", vs_entry: "vs_textured", fs_entry: "fs_textured")"#,
+                path
+            );
+            let config = PipelineConfig::from_src(&src).unwrap();
+            code_gen::gen_pipeline_code_with_options(&config, &options).map(|code| code.to_string())
+        };
+        let expected = gen("./tests/texture.wgsl").unwrap();
+        let code = gen("${SHADERS}/texture.wgsl").unwrap();
+        assert_eq!(
+            expected.split_whitespace().nth(1),
+            code.split_whitespace().nth(1)
+        );
+        // Cargo sets this for tests as well as build scripts.
+        let code = gen("${CARGO_MANIFEST_DIR}/tests/texture.wgsl").unwrap();
+        assert_eq!(
+            expected.split_whitespace().nth(1),
+            code.split_whitespace().nth(1)
+        );
+        let e = gen("${PIPEMD_MISSING}/texture.wgsl")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            e.contains("`${PIPEMD_MISSING}` isn't a path variable or set in the environment"),
+            "{}",
+            e
+        );
+    }
+
+    #[test]
+    fn runtime_shaders() {
+        let options = CodegenOptions {
+            runtime_shaders: true,
+            ..Default::default()
+        };
+        let config = PipelineConfig::from_path("./tests/compute.pmd").unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        let generated = pipeline_code.to_string();
+        // Every pipeline's shader is read at runtime.
+        assert_eq!(
+            3,
+            generated.matches("read_shader_source (\"").count(),
+            "{}",
+            generated
+        );
+        let path = std::path::Path::new("./tests/compute.wgsl")
+            .canonicalize()
+            .unwrap();
+        let path = path.to_str().unwrap();
+        assert_compiles(
+            "runtime_shaders",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let _: fn(&::wgpu::Device) -> IntegratePipeline = IntegratePipeline::new;
+                    let _: fn(&::wgpu::Device, ::wgpu::TextureFormat) -> TexturedPipeline = TexturedPipeline::new;
+                    assert_eq!(::std::fs::read_to_string(#path).unwrap(), read_shader_source(#path, ""));
+                    assert_eq!("embedded", read_shader_source("./missing.wgsl", "embedded"));
+                }
+            },
+        );
+
+        // Preprocessed shaders don't match their files.
+        let config = PipelineConfig::from_path("./tests/overrides.pmd").unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert!(!pipeline_code.to_string().contains("read_shader_source"));
+    }
+
+    #[test]
+    fn builder() {
+        let out_dir = std::path::Path::new("./tests/temp/builder");
+        let _ = std::fs::remove_dir_all(out_dir);
+        let builder = Builder::new()
+            .add_dir("./tests/build")
+            .out_dir(out_dir)
+            .options(CodegenOptions::default())
+            .quiet();
+        let report = builder.generate().unwrap();
+        let outputs = report
+            .files
+            .iter()
+            .map(|file| (file.output.clone(), file.outcome))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (out_dir.join("particles/compute.rs"), WriteOutcome::Created),
+                (out_dir.join("textured.rs"), WriteOutcome::Created),
+            ],
+            outputs
+        );
+        let textured = read_to_string(out_dir.join("textured.rs")).unwrap();
+        assert!(textured.starts_with(code_gen::GENERATED_HEADER));
+        assert!(textured.contains("pub struct TexturedPipeline"));
+        // Nothing is rewritten when the configs haven't changed.
+        assert_eq!(0, builder.generate().unwrap().changed().count());
+
+        // Hand edits are only overwritten with force.
+        std::fs::write(
+            out_dir.join("textured.rs"),
+            textured.replace("pub struct", "struct"),
+        )
+        .unwrap();
+        let e = builder.generate().unwrap_err();
+        assert!(e.to_string().contains("edited by hand"), "{}", e);
+        let report = builder.clone().force().generate().unwrap();
+        let changed = report
+            .changed()
+            .map(|file| file.output.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![out_dir.join("textured.rs")], changed);
+        assert_eq!(
+            textured,
+            read_to_string(out_dir.join("textured.rs")).unwrap()
+        );
+
+        let broken = out_dir.join("broken");
+        std::fs::create_dir_all(&broken).unwrap();
+        std::fs::write(broken.join("a.pmd"), "render_pipeline(name: \"A\")").unwrap();
+        std::fs::write(
+            broken.join("b.pmd"),
+            "#compute_pipeline(\"B\", \"main\", \"./missing.wgsl\")",
+        )
+        .unwrap();
+        let e = Builder::new()
+            .add_dir(&broken)
+            .out_dir(out_dir.join("out"))
+            .quiet()
+            .generate()
+            .unwrap_err();
+        assert!(
+            e.to_string().starts_with("2 of 2 configs failed:\n"),
+            "{}",
+            e
+        );
+        assert!(e.to_string().contains("b.pmd: "), "{}", e);
+
+        let e = Builder::new()
+            .add_dir("./tests/build")
+            .add_dir("./tests/build")
+            .out_dir(out_dir)
+            .quiet()
+            .generate();
+        assert!(e
+            .unwrap_err()
+            .to_string()
+            .contains("would both be written to"));
+    }
+
+    #[test]
+    fn dependency_tracking() {
+        let tracker = Arc::new(DependencyTracker::default());
+        let options = CodegenOptions {
+            observer: Some(tracker.clone()),
+            ..Default::default()
+        };
+        let results = code_gen::generate_many(&["./tests/let.pmd".into()], &options);
+        assert!(results[0].1.is_ok());
+        let config = PipelineConfig::from_src(
+            r#"render_pipeline(name: "SkyPipeline", path: "./tests/imports/sky.wgsl")"#,
+        )
+        .unwrap();
+        code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        let canonical = |path: &str| std::path::Path::new(path).canonicalize().unwrap();
+        let expected = [
+            "./tests/imports/common/camera.wgsl",
+            "./tests/imports/common/tonemap.wgsl",
+            "./tests/imports/sky.wgsl",
+            "./tests/include/formats.pmd",
+            "./tests/let.pmd",
+            "./tests/texture.wgsl",
+        ];
+        let mut expected = expected.map(canonical).to_vec();
+        expected.sort();
+        assert_eq!(expected, tracker.files());
+        let config = PipelineConfig::from_path("./tests/let.pmd").unwrap();
+        assert_eq!(
+            vec![
+                canonical("./tests/include/formats.pmd"),
+                canonical("./tests/let.pmd")
+            ],
+            config.files()
+        );
+
+        let tracker = DependencyTracker::default();
+        tracker.file_read("shaders/a b.wgsl".as_ref());
+        tracker.file_parsed("scene#1.pmd".as_ref());
+        let depfile = tracker.depfile(&["out/scene.rs".as_ref(), "out/$x.rs".as_ref()]);
+        assert_eq!(
+            "out/scene.rs out/$$x.rs: \\\n  scene\\#1.pmd \\\n  shaders/a\\ b.wgsl\n",
+            depfile
+        );
+
+        let out_dir = std::path::Path::new("./tests/temp/depfile");
+        let _ = std::fs::remove_dir_all(out_dir);
+        let builder = Builder::new()
+            .add_dir("./tests/build")
+            .out_dir(out_dir)
+            .options(CodegenOptions::default());
+        builder
+            .quiet()
+            .depfile(out_dir.join("shaders.d"))
+            .generate()
+            .unwrap();
+        let depfile = read_to_string(out_dir.join("shaders.d")).unwrap();
+        let (targets, dependencies) = depfile.split_once(':').unwrap();
+        assert_eq!(
+            "./tests/temp/depfile/particles/compute.rs ./tests/temp/depfile/textured.rs",
+            targets
+        );
+        assert!(
+            dependencies.contains(&canonical("./tests/compute.wgsl").display().to_string()),
+            "{}",
+            depfile
+        );
+        assert!(
+            dependencies.contains(
+                &canonical("./tests/build/textured.pmd")
+                    .display()
+                    .to_string()
+            ),
+            "{}",
+            depfile
+        );
+    }
+
+    #[test]
+    fn format_fixtures() {
+        for path in code_gen::find_configs("./tests".as_ref()).unwrap() {
+            let src = read_to_string(&path).unwrap();
+            let formatted = code_gen::format_pmd(&src).unwrap();
+            assert_eq!(
+                formatted,
+                code_gen::format_pmd(&formatted).unwrap(),
+                "{}",
+                path.display()
+            );
+            // Formatting doesn't change what the config means, only which
+            // lines things are on.
+            if let Ok(config) = PipelineConfig::from_src(&src) {
+                let reformatted = PipelineConfig::from_src(&formatted).unwrap();
+                assert_eq!(
+                    without_locations(&config),
+                    without_locations(&reformatted),
+                    "{}",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    /// `config`'s `Debug` output without where each pipeline is declared.
+    fn without_locations(config: &PipelineConfig) -> String {
+        let debug = format!("{:?}", config);
+        let mut out = String::new();
+        let mut rest = debug.as_str();
+        while let Some(start) = rest.find("declared_at: ") {
+            out.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = match rest.strip_prefix("declared_at: None") {
+                Some(_) => "declared_at: None".len(),
+                None => rest.find("})").unwrap() + "})".len(),
+            };
+            rest = rest[end..].strip_prefix(", ").unwrap_or(&rest[end..]);
+        }
+        out + rest
+    }
+
+    #[test]
+    fn watcher() {
+        let dir = std::path::Path::new("./tests/temp/watch");
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir_all(dir.join("configs")).unwrap();
+        std::fs::copy("./tests/texture.wgsl", dir.join("texture.wgsl")).unwrap();
+        let config =
+            r#"render_pipeline(name: "TexturedPipeline", path: "./tests/temp/watch/texture.wgsl")"#;
+        std::fs::write(dir.join("configs/textured.pmd"), config).unwrap();
+        let builder = Builder::new()
+            .add_dir(dir.join("configs"))
+            .out_dir(dir.join("out"))
+            .options(CodegenOptions::default())
+            .quiet();
+        let mut watcher = Watcher::new(builder).debounce(std::time::Duration::ZERO);
+
+        let report = watcher.poll().unwrap().unwrap();
+        assert_eq!(1, report.changed().count());
+        assert!(report
+            .dependencies
+            .contains(&dir.join("texture.wgsl").canonicalize().unwrap()));
+        assert!(watcher.poll().is_none());
+
+        // Editing the shader is seen, then built once it's settled.
+        let shader = std::fs::File::options()
+            .append(true)
+            .open(dir.join("texture.wgsl"))
+            .unwrap();
+        shader
+            .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(10))
+            .unwrap();
+        assert!(watcher.poll().is_none());
+        assert!(watcher.poll().unwrap().is_ok());
+        assert!(watcher.poll().is_none());
+
+        // So are new configs.
+        std::fs::write(
+            dir.join("configs/copy.pmd"),
+            config.replace("Textured", "Copy"),
+        )
+        .unwrap();
+        assert!(watcher.poll().is_none());
+        let report = watcher.poll().unwrap().unwrap();
+        let changed = report
+            .changed()
+            .map(|file| file.output.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![dir.join("out/copy.rs")], changed);
+    }
+
+    #[test]
+    fn manifest() {
+        let out_dir = std::path::Path::new("./tests/temp/manifest");
+        let _ = std::fs::remove_dir_all(out_dir);
+        let builder = Builder::new()
+            .add_dir("./tests/build")
+            .out_dir(out_dir)
+            .options(CodegenOptions::default());
+        let report = builder.quiet().manifests().generate().unwrap();
+        assert_eq!(
+            Some(out_dir.join("textured.json")),
+            report.files[1].manifest
+        );
+
+        let manifest =
+            Manifest::from_json(&read_to_string(out_dir.join("textured.json")).unwrap()).unwrap();
+        let binding = |binding, name: &str, kind: &str| BindingManifest {
+            binding,
+            name: name.to_owned(),
+            kind: kind.to_owned(),
+            visibility: vec!["Fragment".to_owned()],
+            count: None,
+        };
+        let attribute = |location, name: &str| VertexAttributeManifest {
+            location,
+            format: "Float32x2".to_owned(),
+            name: Some(name.to_owned()),
+        };
+        let expected = PipelineManifest {
+            name: "TexturedPipeline".to_owned(),
+            kind: PipelineKind::Render,
+            label: "TexturedPipeline".to_owned(),
+            shader: "./tests/texture.wgsl".to_owned(),
+            entry_points: vec!["vs_textured".to_owned(), "fs_textured".to_owned()],
+            vertex_buffers: vec![VertexBufferManifest {
+                name: "TexturedPipelineVertex".to_owned(),
+                step_mode: "Vertex".to_owned(),
+                attributes: vec![attribute(0, "position"), attribute(1, "uv")],
+            }],
+            bind_groups: vec![BindGroupManifest {
+                group: 0,
+                entries: vec![binding(0, "tex", "texture"), binding(1, "samp", "sampler")],
+            }],
+            workgroup_size: None,
+            cfg: None,
+        };
+        assert_eq!(
+            Manifest {
+                version: MANIFEST_VERSION,
+                pipelines: vec![expected]
+            },
+            manifest
+        );
+
+        let config = PipelineConfig::from_path("./tests/build/particles/compute.pmd").unwrap();
+        let (_, manifest) =
+            code_gen::gen_pipeline_code_with_manifest(&config, &CodegenOptions::default()).unwrap();
+        let compute = &manifest.pipelines[0];
+        assert_eq!(PipelineKind::Compute, compute.kind);
+        assert_eq!(vec!["cs_integrate".to_owned()], compute.entry_points);
+        assert_eq!(Some([64, 1, 1]), compute.workgroup_size);
+        assert_eq!("storage buffer", compute.bind_groups[0].entries[0].kind);
+        assert_eq!(manifest, Manifest::from_json(&manifest.to_json()).unwrap());
+    }
+
+    #[test]
+    fn diagnostics() {
+        let e = PipelineConfig::from_path("./tests/include/nested.pmd").unwrap_err();
+        let diagnostic = Diagnostic::from_error(&e);
+        assert_eq!(e.to_string(), diagnostic.to_string());
+        assert_eq!(Level::Error, diagnostic.level);
+        assert_eq!("missing_field", diagnostic.code);
+        assert!(
+            diagnostic
+                .file
+                .as_ref()
+                .unwrap()
+                .ends_with("shared/missing_field.pmd"),
+            "{:?}",
+            diagnostic
+        );
+        assert_eq!(
+            Some(Span {
+                line: 1,
+                column: None
+            }),
+            diagnostic.span
+        );
+        assert_eq!(2, diagnostic.notes.len());
+        assert!(
+            diagnostic.notes[0].starts_with("included from "),
+            "{:?}",
+            diagnostic
+        );
+
+        let src = r#"render_pipeline(name: "A", path: "./tests/mismatched_types.wgsl")"#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let e = code_gen::gen_pipeline_code(&config)
+            .unwrap_err()
+            .context("failed to generate");
+        let diagnostic = Diagnostic::from_error(&e);
+        assert_eq!("shader_validation", diagnostic.code);
+        assert_eq!(
+            Some("./tests/mismatched_types.wgsl"),
+            diagnostic.file.as_deref()
+        );
+        assert_eq!(
+            Some(Span {
+                line: 8,
+                column: Some(16)
+            }),
+            diagnostic.span
+        );
+        assert_eq!(
+            "let color = vec3(1.0, 0.0, 1.0);",
+            diagnostic.labels[0].source_line.trim()
+        );
+        assert_eq!(vec!["failed to generate".to_owned()], diagnostic.notes);
+        assert_eq!(
+            diagnostic,
+            Diagnostic::from_json(&diagnostic.to_json()).unwrap()
+        );
+        assert!(!diagnostic.to_json().contains('\n'));
+
+        // Errors without a location still get a diagnostic.
+        let config =
+            PipelineConfig::from_src(r#"#compute_pipeline("A", "main", "./tests/texture.wgsl")"#)
+                .unwrap();
+        let diagnostic = Diagnostic::from_error(&code_gen::gen_pipeline_code(&config).unwrap_err());
+        assert_eq!("error", diagnostic.code);
+        assert_eq!((None, None), (diagnostic.file, diagnostic.span));
+    }
+
+    #[test]
+    fn import_diagnostics() {
+        let generate = |path: &str| {
+            let config = PipelineConfig::from_src(&format!(
+                r#"render_pipeline(name: "A", path: "{}")"#,
+                path
+            ))
+            .unwrap();
+            Diagnostic::from_error(&code_gen::gen_pipeline_code(&config).unwrap_err())
+        };
+        // The line in the shader, not in it with camera.wgsl pasted in.
+        let diagnostic = generate("./tests/imports/mismatched.wgsl");
+        assert_eq!(
+            Some("./tests/imports/mismatched.wgsl"),
+            diagnostic.file.as_deref()
+        );
+        assert_eq!(5, diagnostic.span.unwrap().line);
+        assert_eq!(
+            "let exposure: vec3<f32> = camera.exposure;",
+            diagnostic.labels[0].source_line.trim()
+        );
+        assert!(diagnostic.notes.is_empty(), "{:?}", diagnostic);
+
+        let diagnostic = generate("./tests/imports/broken_import.wgsl");
+        assert_eq!(
+            Some("tests/imports/broken/lights.wgsl"),
+            diagnostic.file.as_deref()
+        );
+        assert_eq!(2, diagnostic.span.unwrap().line);
+        assert_eq!(
+            vec!["imported by ./tests/imports/broken_import.wgsl".to_owned()],
+            diagnostic.notes
+        );
+        assert!(
+            diagnostic
+                .to_string()
+                .contains("--> tests/imports/broken/lights.wgsl:2:"),
+            "{}",
+            diagnostic
+        );
+        assert_eq!(
+            diagnostic,
+            Diagnostic::from_json(&diagnostic.to_json()).unwrap()
+        );
+    }
+
+    #[test]
+    fn describe_pipeline() {
+        let config = PipelineConfig::from_path("./tests/shadow.pmd").unwrap();
+        let description =
+            code_gen::describe_pipeline(&config, "ShadowPipeline", &CodegenOptions::default())
+                .unwrap();
+        assert!(
+            description.starts_with("ShadowPipeline (render pipeline)\n"),
+            "{}",
+            description
+        );
+        for line in [
+            "    entry_point: vs_textured\n",
+            "        binding 1: samp (sampler, visible to Fragment)\n",
+            "            location 1: Float32x2 uv\n",
+            "    cull_mode: Front\n",
+            "    format: Depth32Float\n",
+            "    depth_compare: LessEqual\n",
+            "    bias: (constant: 2, slope_scale: 2.0, clamp: 0.0)\n",
+        ] {
+            assert!(
+                description.contains(line),
+                "{:?} isn't in:\n{}",
+                line,
+                description
+            );
+        }
+
+        let config = PipelineConfig::from_path("./tests/compute.pmd").unwrap();
+        let description =
+            code_gen::describe_pipeline(&config, "IntegratePipeline", &CodegenOptions::default());
+        assert!(description
+            .unwrap()
+            .contains("workgroup_size: (64, 1, 1)\n"));
+        let e = code_gen::describe_pipeline(&config, "Missing", &CodegenOptions::default())
+            .unwrap_err();
+        let expected = "expected one of: TexturedPipeline, IntegratePipeline, ResetPipeline";
+        assert!(e.to_string().ends_with(expected), "{}", e);
+    }
+
+    #[test]
+    #[cfg(feature = "inspect")]
+    fn inspection() {
+        let dir = std::path::Path::new("./tests/temp/inspect");
+        let _ = std::fs::remove_dir_all(dir);
+        let config = PipelineConfig::from_path("./tests/compute.pmd").unwrap();
+        let written = code_gen::write_inspection(&config, &CodegenOptions::default(), dir).unwrap();
+        for file in [
+            "TexturedPipeline/shader.wgsl",
+            "TexturedPipeline/shader.metal",
+            "TexturedPipeline/shader.hlsl",
+            "TexturedPipeline/vs_textured.vert.glsl",
+            "TexturedPipeline/fs_textured.frag.glsl",
+            "IntegratePipeline/cs_integrate.comp.glsl",
+        ] {
+            assert!(
+                written.contains(&dir.join(file)),
+                "{} wasn't written: {:?}",
+                file,
+                written
+            );
+        }
+
+        // Pruned to the pipeline's entry points.
+        let metal = std::fs::read_to_string(dir.join("ResetPipeline/shader.metal")).unwrap();
+        assert!(
+            metal.contains("cs_reset") && !metal.contains("cs_integrate"),
+            "{}",
+            metal
+        );
+    }
+
+    #[test]
+    fn source_locations() {
+        let options = CodegenOptions {
+            source_locations: true,
+            ..Default::default()
+        };
+        let config = PipelineConfig::from_path("./tests/compute.pmd").unwrap();
+        let code = code_gen::gen_pipeline_code_with_options(&config, &options)
+            .unwrap()
+            .to_string();
+        let config = PipelineConfig::from_path("./tests/templates.pmd").unwrap();
+        let templates = code_gen::gen_pipeline_code_with_options(&config, &options)
+            .unwrap()
+            .to_string();
+        for (code, name, location) in [
+            (&code, "TEXTURED_PIPELINE", "tests/compute.pmd:5"),
+            (&code, "RESET_PIPELINE", "tests/compute.pmd:3"),
+            // Pipelines from templates are where they're instantiated.
+            (&templates, "CHECKER_PIPELINE", "tests/templates.pmd:12"),
+        ] {
+            let expected = format!(
+                "pub const {}_SOURCE_LOCATION : & str = \"{}\"",
+                name, location
+            );
+            assert!(code.contains(&expected), "{} isn't in:\n{}", expected, code);
+        }
+
+        let config = PipelineConfig::from_src(
+            "\n\nrender_pipeline(name: \"A\", path: \"./tests/texture.wgsl\")",
+        )
+        .unwrap();
+        let code = code_gen::gen_pipeline_code_with_options(&config, &options)
+            .unwrap()
+            .to_string();
+        assert!(
+            code.contains("A_SOURCE_LOCATION : & str = \"line 3\""),
+            "{}",
+            code
+        );
+        let code = code_gen::gen_pipeline_code(&config).unwrap().to_string();
+        assert!(!code.contains("SOURCE_LOCATION"));
+    }
+
+    #[test]
+    fn split_pipelines() {
+        let dir = std::path::Path::new("./tests/temp/split");
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir_all(dir).unwrap();
+        // Left from a pipeline that's since been removed.
+        code_gen::write_generated(&dir.join("old_pipeline.rs"), "", false).unwrap();
+        std::fs::write(dir.join("notes.rs"), "").unwrap();
+
+        let config = PipelineConfig::from_path("./tests/compute.pmd").unwrap();
+        let options = CodegenOptions {
+            visibility: Visibility::Crate,
+            ..Default::default()
+        };
+        let split = code_gen::gen_pipeline_code_split(&config, &options).unwrap();
+        let names = split
+            .pipelines
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec!["IntegratePipeline", "ResetPipeline", "TexturedPipeline"],
+            names
+        );
+        let written = code_gen::write_split(dir, &split, false).unwrap();
+        let files = written
+            .iter()
+            .map(|(path, _)| path.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "mod.rs",
+                "integrate_pipeline.rs",
+                "reset_pipeline.rs",
+                "textured_pipeline.rs"
+            ],
+            files
+        );
+        assert!(written
+            .iter()
+            .all(|(_, outcome)| *outcome == WriteOutcome::Created));
+        assert!(!dir.join("old_pipeline.rs").exists() && dir.join("notes.rs").exists());
+        let unchanged = code_gen::write_split(dir, &split, false).unwrap();
+        assert!(unchanged
+            .iter()
+            .all(|(_, outcome)| *outcome == WriteOutcome::Unchanged));
+
+        let mod_rs = dir
+            .join("mod.rs")
+            .canonicalize()
+            .unwrap()
+            .display()
+            .to_string();
+        assert_compiles(
+            "split",
+            quote! {
+                mod pipelines {
+                    include!(#mod_rs);
+                }
+
+                fn main() {
+                    let _ = pipelines::TexturedPipeline::new;
+                    let _ = pipelines::IntegratePipeline::new;
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn modules() {
+        // Both have a `TexturedPipeline`.
+        let scene = CodegenOptions {
+            module: Some("scene".to_owned()),
+            reexports: vec!["IntegratePipeline".to_owned()],
+            ..Default::default()
+        };
+        let config = PipelineConfig::from_path("./tests/compute.pmd").unwrap();
+        let scene = code_gen::gen_pipeline_code_with_options(&config, &scene).unwrap();
+        let ui = CodegenOptions {
+            module: Some("ui".to_owned()),
+            visibility: Visibility::Crate,
+            ..Default::default()
+        };
+        let config = PipelineConfig::from_path("./tests/texture.pmd").unwrap();
+        let ui = code_gen::gen_pipeline_code_with_options(&config, &ui).unwrap();
+        assert!(ui.to_string().starts_with("pub (crate) mod ui {"), "{}", ui);
+        assert_compiles(
+            "modules",
+            quote! {
+                mod pipelines {
+                    #scene
+                    #ui
+                }
+
+                fn main() {
+                    let _ = pipelines::scene::TexturedPipeline::new;
+                    let _ = pipelines::ui::TexturedPipeline::new;
+                    let _ = pipelines::IntegratePipeline::new;
+                }
+            },
+        );
+
+        let options = CodegenOptions {
+            module: Some("type".to_owned()),
+            ..Default::default()
+        };
+        let e = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap_err();
+        assert_eq!("module \"type\" isn't a valid identifier", e.to_string());
+    }
+
+    #[test]
+    fn glsl_shaders() {
+        let config = PipelineConfig::from_path("./tests/glsl/glsl.pmd").unwrap();
+        assert_eq!("Glsl", config.shaders()[0].language);
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap();
+        let code = pipeline_code.to_string();
+        // The translations are embedded, since the files themselves aren't WGSL.
+        assert!(!code.contains("include_str !"), "{}", code);
+        assert!(code.contains("fn main("), "{}", code);
+        assert_compiles(
+            "glsl_shaders",
+            quote! {
+                #pipeline_code
+
+                fn main() {
+                    let _: fn(&::wgpu::Device) -> IntegratePipeline = IntegratePipeline::new;
+                    let _: fn(&::wgpu::Device) -> ResetPipeline = ResetPipeline::new;
+                    assert_eq!([64, 1, 1], IntegratePipeline::WORKGROUP_SIZE);
+                    // `defines` go to the GLSL preprocessor.
+                    assert!(!SHADER_RESET.contains("0.5"));
+                }
+            },
+        );
+
+        let src = r#"#compute_pipeline("A", "main", "./tests/glsl/invalid.comp")"#;
+        let e = code_gen::gen_pipeline_code(&PipelineConfig::from_src(src).unwrap()).unwrap_err();
+        let diagnostic = Diagnostic::from_error(&e);
+        assert_eq!("shader_syntax", diagnostic.code);
+        assert_eq!(Some(6), diagnostic.span.map(|span| span.line), "{}", e);
+    }
+
+    #[test]
+    fn label_override() {
+        let src = r#"
+            render_pipeline(
+                name: "TextPipeline",
+                label: "ui/text (alpha blended)",
+                path: "./tests/texture.wgsl",
+                vs_entry: "vs_textured",
+                fs_entry: "fs_textured",
+            )
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let pipeline_code = code_gen::gen_pipeline_code(&config).unwrap().to_string();
+        // The shader module, pipeline layout, pipeline and render pass.
+        assert_eq!(
+            4,
+            pipeline_code
+                .matches(r#"Some ("ui/text (alpha blended)")"#)
+                .count()
+        );
+        assert!(!pipeline_code.contains(r#"Some ("TextPipeline")"#));
+        assert!(
+            pipeline_code.contains(r#"fn label () -> & 'static str { "ui/text (alpha blended)" }"#),
+            "{}",
+            pipeline_code
+        );
+
+        let options = CodegenOptions {
+            labels: false,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options)
+            .unwrap()
+            .to_string();
+        assert!(!pipeline_code.contains("ui/text"));
+    }
+
+    #[test]
+    fn jailed_loader_rejects_escaping_shader_paths() {
+        let src = r#"
+            render_pipeline(
+                name: "Escape",
+                path: "../code_gen/tests/texture.wgsl",
+                vs_entry: "vs_textured",
+                fs_entry: "fs_textured",
+            )
+        "#;
+        let config = PipelineConfig::from_src(src).unwrap();
+        let options = CodegenOptions {
+            loader: Arc::new(JailedLoader::new("./tests").unwrap()),
+            ..Default::default()
+        };
+        let err = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<LoadError>(),
+            Some(LoadError::PathOutsideRoot { .. })
+        ));
+    }
+
+    #[test]
+    fn large_sources_are_rejected_or_included() {
+        let src = read_to_string("./tests/texture.pmd").unwrap();
+        let config = PipelineConfig::from_src(&src).unwrap();
+        let options = CodegenOptions {
+            max_inline_source_len: 16,
+            ..Default::default()
+        };
+        let err = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap_err();
+        let err = err.downcast_ref::<SourceTooLarge>().unwrap();
+        assert_eq!(16, err.limit);
+        assert!(err.to_string().contains("include_str!"));
+
+        // The size is checked before the file is read.
+        #[derive(Debug)]
+        struct Unreadable;
+        impl code_gen::ShaderLoader for Unreadable {
+            fn load(&self, path: &str) -> Result<String, LoadError> {
+                panic!("{} shouldn't be read", path)
+            }
+            fn resolve(&self, path: &str) -> Result<std::path::PathBuf, LoadError> {
+                code_gen::FsLoader.resolve(path)
+            }
+        }
+        let options = CodegenOptions {
+            max_inline_source_len: 16,
+            loader: std::sync::Arc::new(Unreadable),
+            ..Default::default()
+        };
+        let err = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap_err();
+        let len = std::fs::metadata("./tests/texture.wgsl").unwrap().len() as usize;
+        assert_eq!(len, err.downcast_ref::<SourceTooLarge>().unwrap().len);
+
+        // Including for loaders that aren't backed by the file system.
+        #[derive(Debug)]
+        struct Remote;
+        impl code_gen::ShaderLoader for Remote {
+            fn load(&self, path: &str) -> Result<String, LoadError> {
+                panic!("{} shouldn't be read", path)
+            }
+            fn resolve(&self, path: &str) -> Result<std::path::PathBuf, LoadError> {
+                Err(LoadError::Io {
+                    path: path.into(),
+                    source: std::io::ErrorKind::Unsupported.into(),
+                })
+            }
+            fn len(&self, _: &str) -> Result<Option<u64>, LoadError> {
+                Ok(Some(1 << 20))
+            }
+        }
+        let options = CodegenOptions {
+            max_inline_source_len: 16,
+            loader: std::sync::Arc::new(Remote),
+            ..Default::default()
+        };
+        let err = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap_err();
+        assert_eq!(1 << 20, err.downcast_ref::<SourceTooLarge>().unwrap().len);
+
+        let options = CodegenOptions {
+            max_inline_source_len: 16,
+            source_embedding: SourceEmbedding::IncludeStr,
+            ..Default::default()
+        };
+        let pipeline_code = code_gen::gen_pipeline_code_with_options(&config, &options).unwrap();
+        assert!(pipeline_code.to_string().contains("include_str !"));
+        assert_compiles(
+            "texture_include_str",
+            quote! {
+                #pipeline_code
+
+                fn main() {}
+            },
+        );
     }
-}
\ No newline at end of file
+}
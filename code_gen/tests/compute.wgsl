@@ -0,0 +1,21 @@
+struct Particle {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+}
+
+@group(0)
+@binding(0)
+var<storage, read_write> particles: array<Particle>;
+
+@compute
+@workgroup_size(64)
+fn cs_integrate(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    particles[i].position = particles[i].position + particles[i].velocity;
+}
+
+@compute
+@workgroup_size(64)
+fn cs_reset(@builtin(global_invocation_id) id: vec3<u32>) {
+    particles[id.x].velocity = vec2<f32>(0.0, 0.0);
+}
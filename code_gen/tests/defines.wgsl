@@ -0,0 +1,19 @@
+struct VSOut {
+    @builtin(position) clip_pos: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VSOut {
+    let uv = vec2(f32((index << 1u) & 2u), f32(index & 2u));
+    return VSOut(vec4(uv * 2.0 - 1.0, 0.0, 1.0));
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+#ifdef SHADOWS
+    let shadow = 0.5;
+#else
+    let shadow = 1.0;
+#endif
+    return vec4(shadow, shadow, shadow, 1.0);
+}
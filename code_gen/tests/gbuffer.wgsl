@@ -0,0 +1,24 @@
+struct VSIn {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+}
+
+struct VSOut {
+    @location(0) uv: vec2<f32>,
+    @builtin(position) clip_pos: vec4<f32>,
+}
+
+struct GBuffer {
+    @location(0) albedo: vec4<f32>,
+    @location(1) emissive: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VSIn) -> VSOut {
+    return VSOut(in.uv, vec4(in.position, 0.0, 1.0));
+}
+
+@fragment
+fn fs_main(in: VSOut) -> GBuffer {
+    return GBuffer(vec4(in.uv, 0.0, 1.0), vec4(0.0, 0.0, 0.0, 1.0));
+}
@@ -0,0 +1,20 @@
+#version 450
+
+layout(local_size_x = 64) in;
+
+struct Particle {
+    vec2 position;
+    vec2 velocity;
+};
+
+layout(set = 0, binding = 0) buffer Particles {
+    Particle particles[];
+};
+
+void main() {
+#ifdef STOP
+    particles[gl_GlobalInvocationID.x].velocity = vec2(0.0);
+#else
+    particles[gl_GlobalInvocationID.x].velocity *= 0.5;
+#endif
+}
@@ -0,0 +1,4 @@
+fn attenuate(distance: f32) -> f32 {
+    let falloff: vec2<f32> = distance * distance;
+    return 1.0 / falloff;
+}
@@ -0,0 +1,6 @@
+#import "broken/lights.wgsl"
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4(attenuate(2.0));
+}
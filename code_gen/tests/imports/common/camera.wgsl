@@ -0,0 +1,9 @@
+#import "tonemap.wgsl"
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    exposure: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
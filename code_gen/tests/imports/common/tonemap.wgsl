@@ -0,0 +1,3 @@
+fn tonemap(color: vec3<f32>) -> vec3<f32> {
+    return color / (color + vec3(1.0));
+}
@@ -0,0 +1,7 @@
+#import "common/camera.wgsl"
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    let exposure: vec3<f32> = camera.exposure;
+    return camera.view_proj * vec4(position * exposure, 1.0);
+}
@@ -0,0 +1,12 @@
+#import "common/camera.wgsl"
+#import "common/tonemap.wgsl"
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return camera.view_proj * vec4(position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4(tonemap(vec3(camera.exposure)), 1.0);
+}
@@ -0,0 +1,28 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+}
+
+struct Light {
+    color: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(2) @binding(0)
+var<storage, read> lights: array<Light>;
+@group(2) @binding(1)
+var t_env: texture_cube<f32>;
+@group(2) @binding(2)
+var s_env: sampler;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return camera.view_proj * vec4(position, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let env = textureSample(t_env, s_env, position.xyz);
+    return lights[0].color * env;
+}
@@ -0,0 +1,18 @@
+override MAX_LIGHTS: u32 = 4u;
+@id(1) override FOG_DENSITY: f32;
+
+struct VSOut {
+    @builtin(position) clip_pos: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VSOut {
+    let uv = vec2(f32((index << 1u) & 2u), f32(index & 2u));
+    return VSOut(vec4(uv * 2.0 - 1.0, 0.0, 1.0));
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    let lights = f32(MAX_LIGHTS) / 64.0;
+    return vec4(lights, FOG_DENSITY, 0.0, 1.0);
+}
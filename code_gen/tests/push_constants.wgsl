@@ -0,0 +1,16 @@
+struct PushConstants {
+    transform: mat4x4<f32>,
+    tint: vec3<f32>,
+}
+
+var<push_constant> pc: PushConstants;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return pc.transform * vec4(position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4(pc.tint, 1.0);
+}
@@ -0,0 +1,30 @@
+struct Particle {
+    position: vec3<f32>,
+    mass: f32,
+    velocity: vec3<f32>,
+}
+
+struct Simulation {
+    dt: f32,
+    gravity: vec3<f32>,
+    particles: array<Particle>,
+}
+
+struct Grid {
+    size: vec2<u32>,
+}
+
+@group(0) @binding(0)
+var<storage, read_write> sim: Simulation;
+@group(0) @binding(1)
+var<storage, read> cells: array<u32>;
+@group(0) @binding(2)
+var<uniform> grid: Grid;
+
+@compute
+@workgroup_size(64)
+fn cs_step(@builtin(global_invocation_id) id: vec3<u32>) {
+    let cell = cells[id.x % (grid.size.x * grid.size.y)];
+    sim.particles[id.x].velocity = sim.particles[id.x].velocity + sim.gravity * sim.dt * f32(cell);
+    sim.particles[id.x].position = sim.particles[id.x].position + sim.particles[id.x].velocity * sim.dt;
+}
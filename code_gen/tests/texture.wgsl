@@ -22,6 +22,6 @@ fn vs_textured(in: VSIn) -> VSOut {
 }
 
 @fragment
-fn fs_textured(in: VSIn) -> @location(0) vec4<f32> {
+fn fs_textured(in: VSOut) -> @location(0) vec4<f32> {
     return textureSample(tex, samp, in.uv);
 }
\ No newline at end of file
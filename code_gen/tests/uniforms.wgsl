@@ -0,0 +1,28 @@
+struct Light {
+    position: vec3<f32>,
+    intensity: f32,
+    color: vec3<f32>,
+}
+
+struct Globals {
+    view_proj: mat4x4<f32>,
+    normal: mat3x3<f32>,
+    time: f32,
+    offset: vec2<f32>,
+    light: Light,
+    weights: array<vec3<f32>, 2>,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    let moved = globals.normal * position + vec3(globals.offset, globals.time) + globals.weights[1];
+    return globals.view_proj * vec4(moved, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4(globals.light.color * globals.light.intensity, 1.0);
+}
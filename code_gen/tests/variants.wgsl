@@ -0,0 +1,26 @@
+struct VSOut {
+    @location(0) uv: vec2<f32>,
+    @builtin(position) clip_pos: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32) -> VSOut {
+    let uv = vec2(f32((i << 1u) & 2u), f32(i & 2u));
+    return VSOut(uv, vec4(uv * 2.0 - 1.0, 0.0, 1.0));
+}
+
+@fragment
+fn fs_main(in: VSOut) -> @location(0) vec4<f32> {
+#ifdef QUALITY_HIGH
+    let cell = floor(in.uv * 64.0);
+#else
+    let cell = floor(in.uv * 8.0);
+#endif
+    var color = vec4(vec3(f32((u32(cell.x) + u32(cell.y)) & 1u)), 1.0);
+#ifdef ALPHA_TEST
+    if (color.r < 0.5) {
+        discard;
+    }
+#endif
+    return color;
+}
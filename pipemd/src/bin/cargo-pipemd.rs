@@ -0,0 +1,232 @@
+//! `cargo pipemd`, which runs the CLI on a cargo workspace's packages.
+//!
+//! `gen`, `check`, `fmt` and `watch` can be run without any paths, in which
+//! case they're run in each package of the workspace, or just the current
+//! one, that has configs. A package's configs are the `shaders` directory
+//! in its root unless it lists others in its manifest, along with where
+//! `gen` and `watch` write their output:
+//!
+//! ```toml
+//! [package.metadata.pipemd]
+//! dirs = ["assets/pipelines"]
+//! out_dir = "src/pipelines"
+//! ```
+//!
+//! The output directory defaults to `src/generated`. Everything else is
+//! passed on to `pipemd` as it is.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+
+use anyhow::{bail, Context, Result};
+use pipemd::Exit;
+
+/// A package with configs, and where to find them.
+#[derive(Debug)]
+struct Package {
+    name: String,
+    root: PathBuf,
+    dirs: Vec<String>,
+    out_dir: String,
+}
+
+fn main() -> ExitCode {
+    // Cargo passes the subcommand's name first, eg. `cargo-pipemd pipemd gen`.
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(String::as_str) == Some("pipemd") {
+        args.remove(0);
+    }
+    let command = args
+        .iter()
+        .find(|arg| !arg.starts_with('-'))
+        .cloned()
+        .unwrap_or_default();
+    if !matches!(command.as_str(), "gen" | "check" | "fmt" | "watch") || has_paths(&args) {
+        return pipemd::cli(args).into();
+    }
+
+    let packages = match packages() {
+        Ok(packages) => packages,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            return Exit::Error.into();
+        }
+    };
+    if command == "watch" && packages.len() > 1 {
+        let names = packages
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect::<Vec<_>>();
+        eprintln!(
+            "error: watch can only run in one package, run it in one of {}",
+            names.join(", ")
+        );
+        return Exit::Error.into();
+    }
+    let mut exit = Exit::Success;
+    for package in packages {
+        // Shader paths in configs are relative to the package's root, the
+        // same as in build scripts.
+        if let Err(e) = std::env::set_current_dir(&package.root) {
+            eprintln!("error: failed to enter {}: {}", package.root.display(), e);
+            return Exit::Io.into();
+        }
+        let mut args = args.clone();
+        args.extend(package.dirs.iter().cloned());
+        if matches!(command.as_str(), "gen" | "watch") {
+            args.extend(["-o".to_owned(), package.out_dir.clone()]);
+        }
+        exit = exit.max(pipemd::cli(args));
+    }
+    exit.into()
+}
+
+/// Whether any of `args` are paths rather than the command and its flags.
+fn has_paths(args: &[String]) -> bool {
+    let mut args = args.iter().skip_while(|arg| arg.starts_with('-')).skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => return true,
+            "--profile" | "--depfile" => {
+                args.next();
+            }
+            arg if !arg.starts_with('-') => return true,
+            _ => (),
+        }
+    }
+    false
+}
+
+/// The packages with configs in the workspace, or only the current package
+/// when run inside one.
+fn packages() -> Result<Vec<Package>> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let output = Command::new(cargo)
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .output()
+        .context("failed to run cargo metadata")?;
+    if !output.status.success() {
+        bail!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let metadata: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("cargo metadata printed invalid JSON")?;
+    let current_dir = std::env::current_dir().context("failed to read the current directory")?;
+    packages_in(&metadata, &current_dir)
+}
+
+/// The packages with configs in `metadata`, from `cargo metadata`, or only
+/// the one `current_dir` is in.
+fn packages_in(metadata: &serde_json::Value, current_dir: &Path) -> Result<Vec<Package>> {
+    let mut packages = Vec::new();
+    for package in metadata["packages"].as_array().into_iter().flatten() {
+        let manifest = Path::new(package["manifest_path"].as_str().unwrap_or_default());
+        let root = manifest.parent().unwrap_or(Path::new("")).to_owned();
+        let settings = &package["metadata"]["pipemd"];
+        let dirs = match settings["dirs"].as_array() {
+            Some(dirs) => dirs
+                .iter()
+                .filter_map(|dir| dir.as_str())
+                .map(str::to_owned)
+                .collect(),
+            None if root.join("shaders").is_dir() => vec!["shaders".to_owned()],
+            None => continue,
+        };
+        packages.push(Package {
+            name: package["name"].as_str().unwrap_or_default().to_owned(),
+            out_dir: settings["out_dir"]
+                .as_str()
+                .unwrap_or("src/generated")
+                .to_owned(),
+            root,
+            dirs,
+        });
+    }
+    // Inside a package only that one is used, like cargo does. A package can
+    // be inside another, eg. the workspace's root package, so it's the one
+    // with the deepest root.
+    let current = (0..packages.len())
+        .filter(|&i| current_dir.starts_with(&packages[i].root))
+        .max_by_key(|&i| packages[i].root.components().count());
+    if let Some(i) = current {
+        packages = vec![packages.remove(i)];
+    }
+    if packages.is_empty() {
+        bail!("no package has a `shaders` directory or `[package.metadata.pipemd]` dirs");
+    }
+    Ok(packages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn paths_in_args() {
+        assert!(!has_paths(&args(&["gen"])));
+        assert!(!has_paths(&args(&[
+            "--porcelain",
+            "check",
+            "--deny-warnings"
+        ])));
+        assert!(!has_paths(&args(&[
+            "gen",
+            "--profile",
+            "release",
+            "--depfile",
+            "out.d"
+        ])));
+        assert!(has_paths(&args(&["check", "shaders"])));
+        assert!(has_paths(&args(&["-q", "gen", "--force", "a.pmd"])));
+        assert!(has_paths(&args(&["gen", "-o", "src/generated"])));
+    }
+
+    #[test]
+    fn workspace_packages() {
+        let metadata = serde_json::json!({
+            "packages": [
+                {
+                    "name": "game",
+                    "manifest_path": "/work/Cargo.toml",
+                    "metadata": { "pipemd": { "dirs": ["pipelines"] } },
+                },
+                {
+                    "name": "renderer",
+                    "manifest_path": "/work/crates/renderer/Cargo.toml",
+                    "metadata": { "pipemd": { "dirs": ["shaders"], "out_dir": "src/gpu" } },
+                },
+                // Packages without configs are left out.
+                { "name": "assets", "manifest_path": "/work/crates/assets/Cargo.toml", "metadata": null },
+            ],
+        });
+        let names = |current_dir: &str| {
+            let packages = packages_in(&metadata, Path::new(current_dir)).unwrap();
+            packages
+                .into_iter()
+                .map(|package| package.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["game", "renderer"], names("/elsewhere"));
+        assert_eq!(vec!["game"], names("/work/src"));
+        assert_eq!(vec!["game"], names("/work/crates/assets"));
+        // The innermost package, not the workspace's root one.
+        assert_eq!(vec!["renderer"], names("/work/crates/renderer/shaders"));
+
+        let packages = packages_in(&metadata, Path::new("/work/crates/renderer")).unwrap();
+        assert_eq!(PathBuf::from("/work/crates/renderer"), packages[0].root);
+        assert_eq!(vec!["shaders"], packages[0].dirs);
+        assert_eq!("src/gpu", packages[0].out_dir);
+        let packages = packages_in(&metadata, Path::new("/work")).unwrap();
+        assert_eq!("src/generated", packages[0].out_dir);
+
+        let metadata = serde_json::json!({ "packages": [metadata["packages"][2]] });
+        let e = packages_in(&metadata, Path::new("/work")).unwrap_err();
+        assert!(e.to_string().starts_with("no package has"), "{}", e);
+    }
+}
@@ -0,0 +1,687 @@
+//! The pipemd command line, shared by the `pipemd` and `cargo-pipemd`
+//! binaries.
+
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use code_gen::{
+    CodegenError, CodegenOptions, DependencyTracker, Descriptor, Diagnostic, Interface, Manifest,
+    PipelineConfig, ProgressObserver, ProjectConfig, WriteOutcome,
+};
+use proc_macro2::TokenStream;
+
+const USAGE: &str = "\
+Usage:
+    pipemd [options] gen <config.pmd> [-o <out.rs>] [--profile <name>] [--force] [--manifest] [--depfile <out.d>]
+    pipemd [options] gen <dir>... -o <out dir> [--profile <name>] [--manifest] [--depfile <out.d>]
+    pipemd [options] check <dir or config.pmd>... [--profile <name>]
+    pipemd [options] fmt [--check] <dir or config.pmd>...
+    pipemd [options] watch <dir>... -o <out dir> [--profile <name>]
+    pipemd [options] interface <config.pmd> [-o <interface.json>]
+    pipemd [options] describe <config.pmd> [-o <descriptor.json>]
+    pipemd [options] describe <config.pmd> <pipeline> [--profile <name>]
+    pipemd [options] inspect <config.pmd> -o <dir> [--profile <name>]
+    pipemd [options] diff-interface <old.json> <new.json>
+
+Options:
+    --porcelain        Print diagnostics to stdout as `<level>\\t<message>` lines
+    --json             Print diagnostics to stdout as JSON, one object per line,
+                       with the file, line and column, code, message and notes
+    -q, --quiet        Don't draw progress or print warnings
+    --deny-warnings    Exit with 1 if there were any warnings
+
+gen writes the generated code to stdout or the -o file. Settings are taken
+from the given profile of pipemd.toml in the current directory, `debug` by
+default, if there is one. --force overwrites output files that were edited
+by hand or weren't written by pipemd. Given directories, it writes every
+config under them to the output directory at the same relative path.
+--manifest also writes a JSON manifest of the pipelines, their shaders,
+vertex buffers and bind groups next to each output file, eg. out.json.
+--depfile writes a Makefile style depfile saying the output depends on the
+configs, shaders and pipemd.toml that were read, for Make and Ninja.
+
+check parses every config under the given directories and validates their
+shaders without writing anything, reporting an error for each broken one.
+It uses the same pipemd.toml profile as gen.
+
+fmt rewrites configs in the canonical style. With --check nothing is
+written, and it fails if any config isn't formatted.
+
+describe writes a JSON descriptor of the config's pipelines. Given the name
+of a pipeline, it prints the state it's created with instead: after
+defaults, templates and variants, and with what's reflected from its shader.
+
+inspect writes the MSL, HLSL and GLSL naga translates each pipeline's
+shader to into a directory per pipeline, to see what runs on each backend.
+
+watch regenerates the configs under the given directories into the output
+directory whenever they or their shaders change, until it's stopped.
+
+Exit codes:
+    0    Success
+    1    There were warnings and --deny-warnings was given
+    2    An error, including breaking interface changes
+    3    An input or output couldn't be read or written
+";
+
+/// The exit codes scripts can rely on. These are part of the CLI's interface
+/// and must not change meaning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Exit {
+    Success = 0,
+    DeniedWarnings = 1,
+    Error = 2,
+    Io = 3,
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit as u8)
+    }
+}
+
+impl Exit {
+    /// I/O errors get their own code so scripts can tell a missing file from
+    /// a broken config. Input that was read but couldn't be understood isn't
+    /// an I/O error.
+    fn for_error(e: &anyhow::Error) -> Self {
+        let io = e.chain().any(|cause| {
+            cause
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|e| e.kind() != std::io::ErrorKind::InvalidData)
+        });
+        if io {
+            Self::Io
+        } else {
+            Self::Error
+        }
+    }
+}
+
+/// The options that apply to every command.
+#[derive(Debug, Clone, Copy, Default)]
+struct GlobalOptions {
+    porcelain: bool,
+    json: bool,
+    quiet: bool,
+    deny_warnings: bool,
+}
+
+impl GlobalOptions {
+    /// Takes the global options out of `args`, wherever they are.
+    fn parse(args: Vec<String>) -> (Self, Vec<String>) {
+        let mut options = Self::default();
+        let args = args
+            .into_iter()
+            .filter(|arg| {
+                match arg.as_str() {
+                    "--porcelain" => options.porcelain = true,
+                    "--json" => options.json = true,
+                    "-q" | "--quiet" => options.quiet = true,
+                    "--deny-warnings" => options.deny_warnings = true,
+                    _ => return true,
+                }
+                false
+            })
+            .collect();
+        (options, args)
+    }
+
+    /// Prints a diagnostic. Porcelain diagnostics are a single line on stdout
+    /// with newlines and tabs in the message escaped.
+    fn diagnostic(&self, level: &str, message: &str) {
+        if self.json {
+            let diagnostic = match level {
+                "warning" => Diagnostic::warning(message),
+                _ => Diagnostic::error("error", message),
+            };
+            println!("{}", diagnostic.to_json());
+        } else if self.porcelain {
+            let message = message
+                .replace('\\', "\\\\")
+                .replace('\n', "\\n")
+                .replace('\t', "\\t");
+            println!("{}\t{}", level, message);
+        } else {
+            eprintln!("{}: {}", level, message);
+        }
+    }
+
+    /// Prints an error. With --json it's printed as the [Diagnostic] in it,
+    /// and `config` is the file it's in if it doesn't say, otherwise it's
+    /// put in front of the message.
+    fn error(&self, e: &anyhow::Error, config: Option<&Path>) {
+        if self.json {
+            let mut diagnostic = Diagnostic::from_error(e);
+            if diagnostic.file.is_none() {
+                diagnostic.file = config.map(|path| path.display().to_string());
+            }
+            println!("{}", diagnostic.to_json());
+            return;
+        }
+        match config {
+            Some(path) => self.diagnostic("error", &format!("{}: {:#}", path.display(), e)),
+            None => self.diagnostic("error", &format!("{:#}", e)),
+        }
+    }
+}
+
+/// Runs the command in `args`, which don't include the program's name.
+pub fn cli(args: Vec<String>) -> Exit {
+    let (options, args) = GlobalOptions::parse(args);
+    let progress = Arc::new(ProgressBar::new(options));
+    match run(&args, &progress) {
+        Ok(Exit::Success) if options.deny_warnings && progress.warnings() > 0 => {
+            Exit::DeniedWarnings
+        }
+        Ok(exit) => exit,
+        Err(e) => {
+            options.error(&e, None);
+            Exit::for_error(&e)
+        }
+    }
+}
+
+fn run(args: &[String], progress: &Arc<ProgressBar>) -> Result<Exit> {
+    match args.first().map(String::as_str) {
+        Some("gen") => gen(&args[1..], progress),
+        Some("check") => check(&args[1..], progress),
+        Some("fmt") => fmt(&args[1..], progress.options),
+        Some("watch") => watch(&args[1..], progress),
+        Some("interface") => interface(&args[1..], progress),
+        Some("describe") => describe(&args[1..], progress),
+        Some("inspect") => inspect(&args[1..], progress),
+        Some("diff-interface") => diff_interface(&args[1..], progress.options),
+        Some("-h" | "--help") => {
+            print!("{}", USAGE);
+            Ok(Exit::Success)
+        }
+        Some(command) => bail!("unknown command {:?}\n\n{}", command, USAGE),
+        None => bail!("missing command\n\n{}", USAGE),
+    }
+}
+
+/// Takes `--profile <name>` out of `args`, defaulting to `debug`.
+fn take_profile_arg(args: &[String]) -> Result<(Vec<String>, String)> {
+    let mut profile = "debug".to_owned();
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--profile" => {
+                profile = iter
+                    .next()
+                    .ok_or_else(|| anyhow!("{} expects a name", arg))?
+                    .clone()
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+    Ok((rest, profile))
+}
+
+/// Takes `--depfile <path>` out of `args`.
+fn take_depfile_arg(args: &[String]) -> Result<(Vec<String>, Option<PathBuf>)> {
+    let mut depfile = None;
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--depfile" => {
+                depfile = Some(
+                    iter.next()
+                        .ok_or_else(|| anyhow!("{} expects a path", arg))?
+                        .into(),
+                )
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+    Ok((rest, depfile))
+}
+
+/// The options `profile` sets in `pipemd.toml` in the current directory, or
+/// the defaults if there isn't one.
+fn project_options(profile: &str) -> Result<CodegenOptions> {
+    match Path::new(code_gen::PROJECT_FILE).exists() {
+        true => Ok(ProjectConfig::from_path(code_gen::PROJECT_FILE)?.options(profile)),
+        false => Ok(CodegenOptions::default()),
+    }
+}
+
+/// Splits `args` into positional arguments and the value of `-o`/`--output`.
+fn parse_output_arg(args: &[String]) -> Result<(Vec<&str>, Option<PathBuf>)> {
+    let mut positional = Vec::new();
+    let mut output = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                let path = iter
+                    .next()
+                    .ok_or_else(|| anyhow!("{} expects a path", arg))?;
+                output = Some(PathBuf::from(path));
+            }
+            arg => positional.push(arg),
+        }
+    }
+    Ok((positional, output))
+}
+
+/// Renders a single line progress bar on stderr and reports warnings.
+/// Nothing is drawn unless stderr is a terminal so piped output stays clean,
+/// or in quiet, porcelain and JSON modes.
+#[derive(Debug)]
+struct ProgressBar {
+    enabled: bool,
+    options: GlobalOptions,
+    warnings: AtomicUsize,
+    /// Every file that was read, for `--depfile`.
+    dependencies: DependencyTracker,
+}
+
+impl ProgressBar {
+    const WIDTH: usize = 24;
+
+    fn new(options: GlobalOptions) -> Self {
+        Self {
+            enabled: std::io::stderr().is_terminal()
+                && !options.quiet
+                && !options.porcelain
+                && !options.json,
+            options,
+            warnings: AtomicUsize::new(0),
+            dependencies: DependencyTracker::default(),
+        }
+    }
+
+    /// How many warnings have been reported, including those that weren't
+    /// printed because of `--quiet`.
+    fn warnings(&self) -> usize {
+        self.warnings.load(Ordering::Relaxed)
+    }
+
+    fn status(&self, message: std::fmt::Arguments) {
+        if self.enabled {
+            // Clear the line so a shorter message doesn't leave old text behind.
+            eprint!("\r\x1b[2K{}", message);
+            let _ = std::io::stderr().flush();
+        }
+    }
+}
+
+impl ProgressObserver for ProgressBar {
+    fn file_parsed(&self, path: &Path) {
+        self.dependencies.file_parsed(path);
+        self.status(format_args!("parsed {}", path.display()));
+    }
+
+    fn file_read(&self, path: &Path) {
+        self.dependencies.file_read(path);
+    }
+
+    fn shader_reflected(&self, path: &str) {
+        self.status(format_args!("reflected {}", path));
+    }
+
+    /// Warnings are printed even when the bar isn't drawn, unless quiet.
+    fn warning(&self, message: &str) {
+        self.warnings.fetch_add(1, Ordering::Relaxed);
+        if !self.options.quiet {
+            self.status(format_args!(""));
+            self.options.diagnostic("warning", message);
+        }
+    }
+
+    fn pipeline_generated(&self, name: &str, done: usize, total: usize) {
+        let filled = Self::WIDTH * done / total.max(1);
+        self.status(format_args!(
+            "[{}{}] {}/{} {}",
+            "#".repeat(filled),
+            " ".repeat(Self::WIDTH - filled),
+            done,
+            total,
+            name
+        ));
+    }
+
+    fn bytes_written(&self, path: &Path, len: usize) {
+        self.status(format_args!("wrote {} bytes to {}", len, path.display()));
+        if self.enabled {
+            eprintln!();
+        }
+    }
+}
+
+fn generate(
+    path: &Path,
+    options: CodegenOptions,
+    progress: &Arc<ProgressBar>,
+) -> Result<(TokenStream, Manifest)> {
+    let config = PipelineConfig::from_path(path)?;
+    config
+        .files()
+        .iter()
+        .for_each(|file| progress.file_parsed(file));
+    let options = CodegenOptions {
+        observer: Some(progress.clone()),
+        ..options
+    };
+    let generated = code_gen::gen_pipeline_code_with_manifest(&config, &options)
+        .with_context(|| format!("failed to generate code for {}", path.display()))?;
+    // Clear the bar so it doesn't mix with output printed to stdout.
+    progress.status(format_args!(""));
+    Ok(generated)
+}
+
+fn read_interface(path: &str) -> Result<Interface> {
+    let src = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path))?;
+    Interface::from_json(&src).with_context(|| format!("{} is not a valid interface file", path))
+}
+
+/// Writes the code for a config, laid out to be read.
+fn gen(args: &[String], progress: &Arc<ProgressBar>) -> Result<Exit> {
+    let force = args.iter().any(|arg| arg == "--force");
+    let manifest = args.iter().any(|arg| arg == "--manifest");
+    let args = args
+        .iter()
+        .filter(|arg| !matches!(arg.as_str(), "--force" | "--manifest"))
+        .cloned()
+        .collect::<Vec<_>>();
+    let (args, profile) = take_profile_arg(&args)?;
+    let (args, depfile) = take_depfile_arg(&args)?;
+    let (positional, output) = parse_output_arg(&args)?;
+    let options = project_options(&profile)?;
+    if positional.iter().any(|path| Path::new(path).is_dir()) {
+        let Some(output) = output else {
+            bail!(
+                "gen expects an output directory for directories\n\n{}",
+                USAGE
+            );
+        };
+        let options = CodegenOptions {
+            observer: Some(progress.clone()),
+            ..options
+        };
+        let mut builder = positional
+            .iter()
+            .fold(code_gen::Builder::new(), |builder, dir| {
+                builder.add_dir(dir)
+            });
+        if manifest {
+            builder = builder.manifests();
+        }
+        if let Some(depfile) = depfile {
+            builder = builder.depfile(depfile);
+        }
+        if force {
+            builder = builder.force();
+        }
+        builder
+            .out_dir(output)
+            .options(options)
+            .quiet()
+            .generate()?;
+        return Ok(Exit::Success);
+    }
+    let [config] = positional[..] else {
+        bail!("gen expects exactly one config file\n\n{}", USAGE);
+    };
+
+    if manifest && output.is_none() {
+        bail!("gen expects an output file for --manifest\n\n{}", USAGE);
+    }
+    if depfile.is_some() && output.is_none() {
+        bail!("gen expects an output file for --depfile\n\n{}", USAGE);
+    }
+
+    let (code, pipelines) = generate(Path::new(config), options, progress)?;
+    let code = code_gen::format_code(&code);
+    match output {
+        Some(path) => {
+            let outcome = code_gen::write_generated(&path, &code, force)?;
+            if outcome != WriteOutcome::Unchanged {
+                progress.bytes_written(&path, code.len());
+            }
+            if manifest {
+                let path = path.with_extension("json");
+                let json = pipelines.to_json();
+                std::fs::write(&path, &json)
+                    .with_context(|| format!("failed to write {}", path.display()))?;
+                progress.bytes_written(&path, json.len());
+            }
+            if let Some(depfile) = depfile {
+                let project_file = Path::new(code_gen::PROJECT_FILE);
+                if project_file.exists() {
+                    progress.dependencies.file_parsed(project_file);
+                }
+                progress
+                    .dependencies
+                    .write_depfile(&depfile, &[&path])
+                    .with_context(|| format!("failed to write {}", depfile.display()))?;
+            }
+        }
+        None => print!("{}", code),
+    }
+    Ok(Exit::Success)
+}
+
+/// Generates the code for every config in memory, so broken configs and
+/// shaders are found without touching any output.
+fn check(args: &[String], progress: &Arc<ProgressBar>) -> Result<Exit> {
+    let (args, profile) = take_profile_arg(args)?;
+    if args.is_empty() {
+        bail!(
+            "check expects at least one directory or config file\n\n{}",
+            USAGE
+        );
+    }
+    let configs = find_configs(&args)?;
+    let options = CodegenOptions {
+        observer: Some(progress.clone()),
+        ..project_options(&profile)?
+    };
+    let results = code_gen::generate_many(&configs, &options);
+    progress.status(format_args!(""));
+    let mut failed = 0;
+    for (path, result) in results {
+        match result {
+            Ok(_) => continue,
+            // Parse errors already say which file they're in.
+            Err(CodegenError::Parse(e)) => progress.options.error(&e, None),
+            Err(CodegenError::Generate(e)) => progress.options.error(&e, Some(&path)),
+        }
+        failed += 1;
+    }
+    if failed > 0 {
+        progress.options.diagnostic(
+            "error",
+            &format!("{} of {} configs failed", failed, configs.len()),
+        );
+        return Ok(Exit::Error);
+    }
+    Ok(Exit::Success)
+}
+
+/// Formats configs in place, or reports the ones that aren't formatted with
+/// `--check`.
+fn fmt(args: &[String], options: GlobalOptions) -> Result<Exit> {
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths = args
+        .iter()
+        .filter(|arg| *arg != "--check")
+        .cloned()
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        bail!(
+            "fmt expects at least one directory or config file\n\n{}",
+            USAGE
+        );
+    }
+    let mut unformatted = 0;
+    for path in find_configs(&paths)? {
+        let src = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let formatted =
+            code_gen::format_pmd(&src).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        if formatted == src {
+            continue;
+        }
+        if check {
+            unformatted += 1;
+            options.diagnostic("error", &format!("{} isn't formatted", path.display()));
+        } else {
+            std::fs::write(&path, formatted)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+        }
+    }
+    match unformatted {
+        0 => Ok(Exit::Success),
+        _ => Ok(Exit::Error),
+    }
+}
+
+/// Regenerates configs as they change. Errors are reported and watching
+/// carries on, so they can be fixed without restarting it.
+fn watch(args: &[String], progress: &Arc<ProgressBar>) -> Result<Exit> {
+    let (args, profile) = take_profile_arg(args)?;
+    let (dirs, output) = parse_output_arg(&args)?;
+    let Some(output) = output else {
+        bail!("watch expects an output directory\n\n{}", USAGE);
+    };
+    if dirs.is_empty() {
+        bail!("watch expects at least one directory\n\n{}", USAGE);
+    }
+
+    let options = CodegenOptions {
+        observer: Some(progress.clone()),
+        ..project_options(&profile)?
+    };
+    let builder = dirs.iter().fold(code_gen::Builder::new(), |builder, dir| {
+        builder.add_dir(dir)
+    });
+    let builder = builder.out_dir(output).options(options).quiet();
+    code_gen::watch(builder, |result| match result {
+        Ok(_) => (),
+        Err(e) => {
+            progress.status(format_args!(""));
+            progress.options.error(&e, None);
+        }
+    })
+}
+
+/// The configs in `args`, with directories replaced by the configs in them.
+fn find_configs(args: &[String]) -> Result<Vec<PathBuf>> {
+    let mut configs = Vec::new();
+    for arg in args {
+        let path = Path::new(arg);
+        match path.is_dir() {
+            true => configs.extend(code_gen::find_configs(path)?),
+            false => configs.push(path.to_owned()),
+        }
+    }
+    Ok(configs)
+}
+
+fn interface(args: &[String], progress: &Arc<ProgressBar>) -> Result<Exit> {
+    let (positional, output) = parse_output_arg(args)?;
+    let [config] = positional[..] else {
+        bail!("interface expects exactly one config file\n\n{}", USAGE);
+    };
+
+    let (tokens, _) = generate(Path::new(config), CodegenOptions::default(), progress)?;
+    let interface =
+        Interface::from_tokens(&tokens).context("generated code could not be parsed")?;
+    write_json(interface.to_json(), output, progress)
+}
+
+/// Writes the pipelines' metadata as JSON. Unlike `interface` this only
+/// needs the config, so shaders aren't read.
+fn describe(args: &[String], progress: &Arc<ProgressBar>) -> Result<Exit> {
+    let (args, profile) = take_profile_arg(args)?;
+    let (positional, output) = parse_output_arg(&args)?;
+    match positional[..] {
+        [config] => {
+            let config = PipelineConfig::from_path(config)?;
+            write_json(Descriptor::from_config(&config).to_json(), output, progress)
+        }
+        [path, name] => {
+            let config = PipelineConfig::from_path(path)?;
+            let options = CodegenOptions {
+                observer: Some(progress.clone()),
+                ..project_options(&profile)?
+            };
+            let description = code_gen::describe_pipeline(&config, name, &options)
+                .with_context(|| format!("failed to describe {} in {}", name, path))?;
+            progress.status(format_args!(""));
+            print!("{}", description);
+            Ok(Exit::Success)
+        }
+        _ => bail!(
+            "describe expects a config file and optionally a pipeline\n\n{}",
+            USAGE
+        ),
+    }
+}
+
+/// Writes what each pipeline's shader is translated to on other backends.
+fn inspect(args: &[String], progress: &Arc<ProgressBar>) -> Result<Exit> {
+    let (args, profile) = take_profile_arg(args)?;
+    let (positional, output) = parse_output_arg(&args)?;
+    let ([config], Some(output)) = (&positional[..], output) else {
+        bail!(
+            "inspect expects one config file and an output directory\n\n{}",
+            USAGE
+        );
+    };
+
+    let path = Path::new(config);
+    let config = PipelineConfig::from_path(path)?;
+    let options = CodegenOptions {
+        observer: Some(progress.clone()),
+        ..project_options(&profile)?
+    };
+    code_gen::write_inspection(&config, &options, &output)
+        .with_context(|| format!("failed to inspect {}", path.display()))?;
+    progress.status(format_args!(""));
+    Ok(Exit::Success)
+}
+
+/// Writes `json` to `output`, or stdout if there isn't one.
+fn write_json(json: String, output: Option<PathBuf>, progress: &ProgressBar) -> Result<Exit> {
+    match output {
+        Some(path) => {
+            let json = json + "\n";
+            std::fs::write(&path, &json)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            progress.bytes_written(&path, json.len());
+        }
+        None => println!("{}", json),
+    }
+    Ok(Exit::Success)
+}
+
+/// Prints every change between two interface files. Exits with an error if
+/// any of them would break code using the generated API.
+fn diff_interface(args: &[String], options: GlobalOptions) -> Result<Exit> {
+    let [old, new] = args else {
+        bail!("diff-interface expects two interface files\n\n{}", USAGE);
+    };
+    let old = read_interface(old)?;
+    let new = read_interface(new)?;
+
+    let changes = new.diff(&old);
+    for change in &changes {
+        println!("{}", change);
+    }
+    if changes.iter().any(|c| c.is_breaking()) {
+        options.diagnostic("error", "generated interface has breaking changes");
+        Ok(Exit::Error)
+    } else {
+        Ok(Exit::Success)
+    }
+}
@@ -0,0 +1,5 @@
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    pipemd::cli(std::env::args().skip(1).collect()).into()
+}
@@ -0,0 +1,273 @@
+use std::fs::read_to_string;
+use std::process::Command;
+
+#[cfg(test)]
+mod tests {
+    use code_gen::Diagnostic;
+    use pipemd::Exit;
+
+    use super::*;
+
+    const HDR_WARNING: &str =
+        "Hdr: `tex` is sampled with the filtering sampler `samp`, but Rgba32Float is only \
+        filterable with `TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES` on adapters that support it";
+
+    /// Runs the CLI in this process, from the package's root.
+    fn cli(args: &[&str]) -> Exit {
+        pipemd::cli(args.iter().map(|arg| arg.to_string()).collect())
+    }
+
+    /// Runs the `pipemd` binary in `dir`, returning its exit code and what it
+    /// printed to stdout.
+    fn run_in(dir: &str, args: &[&str]) -> (i32, String) {
+        let output = Command::new(env!("CARGO_BIN_EXE_pipemd"))
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        (output.status.code().unwrap(), stdout)
+    }
+
+    fn run(args: &[&str]) -> (i32, String) {
+        run_in(".", args)
+    }
+
+    /// An empty `./tests/temp/<name>` directory.
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::path::Path::new("./tests/temp").join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(Exit::Success, cli(&["-q", "check", "tests/configs/valid"]));
+        assert_eq!(
+            Exit::DeniedWarnings,
+            cli(&["-q", "--deny-warnings", "check", "tests/configs/valid"])
+        );
+        assert_eq!(
+            Exit::Success,
+            cli(&[
+                "--deny-warnings",
+                "check",
+                "tests/configs/valid/textured.pmd"
+            ])
+        );
+        assert_eq!(
+            Exit::Error,
+            cli(&["-q", "check", "tests/configs/broken.pmd"])
+        );
+        assert_eq!(Exit::Error, cli(&["-q", "frobnicate"]));
+        assert_eq!(Exit::Io, cli(&["-q", "gen", "tests/configs/missing.pmd"]));
+
+        // The codes the process exits with are what scripts see.
+        assert_eq!(0, run(&["-q", "check", "tests/configs/valid"]).0);
+        assert_eq!(
+            1,
+            run(&["-q", "--deny-warnings", "check", "tests/configs/valid"]).0
+        );
+        assert_eq!(2, run(&["-q", "check", "tests/configs/broken.pmd"]).0);
+        assert_eq!(3, run(&["-q", "gen", "tests/configs/missing.pmd"]).0);
+    }
+
+    #[test]
+    fn porcelain_output() {
+        assert_eq!(
+            (0, format!("warning\t{}\n", HDR_WARNING)),
+            run(&["--porcelain", "check", "tests/configs/valid"])
+        );
+        // Quiet hides warnings, but they still count.
+        assert_eq!(
+            (0, String::new()),
+            run(&["--porcelain", "-q", "check", "tests/configs/valid"])
+        );
+        assert_eq!(
+            (1, String::new()),
+            run(&[
+                "--porcelain",
+                "-q",
+                "--deny-warnings",
+                "check",
+                "tests/configs/valid"
+            ])
+        );
+        assert_eq!(
+            (
+                2,
+                "error\ttests/configs/broken.pmd:3: Missing field: \"path\"\n\
+                error\t1 of 1 configs failed\n"
+                    .to_owned()
+            ),
+            run(&["--porcelain", "check", "tests/configs/broken.pmd"])
+        );
+        // Messages are kept to one line.
+        let (code, stdout) = run(&["--porcelain", "frobnicate"]);
+        assert_eq!(2, code);
+        assert!(
+            stdout.starts_with("error\tunknown command \"frobnicate\"\\n\\nUsage:\\n"),
+            "{}",
+            stdout
+        );
+        assert_eq!(1, stdout.lines().count(), "{}", stdout);
+    }
+
+    #[test]
+    fn json_output() {
+        assert_eq!(
+            (
+                0,
+                format!("{}\n", Diagnostic::warning(HDR_WARNING).to_json())
+            ),
+            run(&["--json", "check", "tests/configs/valid"])
+        );
+        assert_eq!(
+            (
+                2,
+                concat!(
+                    r#"{"level":"error","file":"tests/configs/broken.pmd","span":{"line":3,"column":null},"#,
+                    r#""code":"missing_field","message":"Missing field: \"path\"","notes":[],"labels":[]}"#,
+                    "\n",
+                    r#"{"level":"error","file":null,"span":null,"code":"error","#,
+                    r#""message":"1 of 1 configs failed","notes":[],"labels":[]}"#,
+                    "\n",
+                )
+                .to_owned()
+            ),
+            run(&["--json", "check", "tests/configs/broken.pmd"])
+        );
+        let (code, stdout) = run(&["--json", "gen", "tests/configs/missing.pmd"]);
+        assert_eq!(3, code);
+        let diagnostic = Diagnostic::from_json(stdout.trim_end()).unwrap();
+        assert!(
+            diagnostic
+                .message
+                .starts_with("failed to read tests/configs/missing.pmd"),
+            "{:?}",
+            diagnostic
+        );
+    }
+
+    #[test]
+    fn gen() {
+        let (code, stdout) = run(&["gen", "tests/configs/valid/textured.pmd"]);
+        assert_eq!(0, code);
+        assert!(stdout.contains("pub struct Textured"), "{}", stdout);
+
+        let dir = temp_dir("gen");
+        let output = dir.join("textured.rs");
+        let output = output.to_str().unwrap();
+        let gen = |extra: &[&str]| {
+            let mut args = vec![
+                "-q",
+                "gen",
+                "tests/configs/valid/textured.pmd",
+                "-o",
+                output,
+            ];
+            args.extend(extra);
+            cli(&args)
+        };
+        assert_eq!(Exit::Success, gen(&[]));
+        let generated = read_to_string(output).unwrap();
+        assert!(generated.starts_with(code_gen::GENERATED_HEADER));
+        assert_eq!(Exit::Success, gen(&[]));
+        // Edited output is only overwritten with --force.
+        std::fs::write(output, generated.replace("pub struct", "struct")).unwrap();
+        assert_eq!(Exit::Error, gen(&[]));
+        assert_eq!(Exit::Success, gen(&["--force"]));
+        assert_eq!(generated, read_to_string(output).unwrap());
+
+        // The same goes for directories.
+        let dir = temp_dir("gen_dir");
+        let out_dir = dir.to_str().unwrap();
+        let gen = |extra: &[&str]| {
+            let mut args = vec!["-q", "gen", "tests/configs/valid", "-o", out_dir];
+            args.extend(extra);
+            cli(&args)
+        };
+        assert_eq!(Exit::Success, gen(&[]));
+        let output = dir.join("hdr.rs");
+        let generated = read_to_string(&output).unwrap();
+        assert!(dir.join("textured.rs").exists());
+        std::fs::write(&output, generated.replace("pub struct", "struct")).unwrap();
+        assert_eq!(Exit::Error, gen(&[]));
+        assert_eq!(Exit::Success, gen(&["--force"]));
+        assert_eq!(generated, read_to_string(&output).unwrap());
+
+        assert_eq!(Exit::Error, cli(&["-q", "gen", "tests/configs/valid"]));
+    }
+
+    #[test]
+    fn check() {
+        let (code, stdout) = run(&["--porcelain", "check", "tests/configs/duplicate.pmd"]);
+        assert_eq!(2, code);
+        assert!(
+            stdout.contains("Textured: pipeline is declared more than once"),
+            "{}",
+            stdout
+        );
+        assert!(
+            stdout.ends_with("error\t1 of 1 configs failed\n"),
+            "{}",
+            stdout
+        );
+        assert_eq!(Exit::Error, cli(&["-q", "check"]));
+
+        // The release profile only allows tiny inlined shaders.
+        assert_eq!(
+            (0, String::new()),
+            run_in("tests/project", &["--porcelain", "check", "shaders"])
+        );
+        let (code, stdout) = run_in(
+            "tests/project",
+            &["--porcelain", "check", "shaders", "--profile", "release"],
+        );
+        assert_eq!(2, code);
+        assert!(
+            stdout
+                .starts_with("error\tshaders/textured.pmd: \"shaders/texture.wgsl\" is 510 bytes"),
+            "{}",
+            stdout
+        );
+    }
+
+    #[test]
+    fn fmt_check() {
+        assert_eq!(
+            Exit::Success,
+            cli(&["fmt", "--check", "tests/configs/valid"])
+        );
+        let unformatted = read_to_string("tests/configs/unformatted.pmd").unwrap();
+        assert_eq!(
+            (
+                2,
+                "error\ttests/configs/unformatted.pmd isn't formatted\n".to_owned()
+            ),
+            run(&[
+                "--porcelain",
+                "fmt",
+                "--check",
+                "tests/configs/unformatted.pmd"
+            ])
+        );
+        // Checking doesn't write anything.
+        assert_eq!(
+            unformatted,
+            read_to_string("tests/configs/unformatted.pmd").unwrap()
+        );
+
+        let dir = temp_dir("fmt");
+        let path = dir.join("unformatted.pmd");
+        std::fs::write(&path, &unformatted).unwrap();
+        let path = path.to_str().unwrap();
+        assert_eq!(Exit::Success, cli(&["fmt", path]));
+        assert_eq!(Exit::Success, cli(&["fmt", "--check", path]));
+        assert_eq!(
+            "render_pipeline(\n    name: \"Textured\",\n    path: \"./tests/configs/texture.wgsl\",\n)\n",
+            read_to_string(path).unwrap()
+        );
+    }
+}
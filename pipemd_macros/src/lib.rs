@@ -0,0 +1,178 @@
+//! Declares pipelines inline in Rust source, for projects too small to be
+//! worth a build script:
+//!
+//! ```ignore
+//! pipemd_macros::pipelines! {
+//!     render_pipeline(name: "SpritePipeline", path: "shaders/sprite.wgsl")
+//! }
+//! ```
+//!
+//! expands to the same code `code_gen` would write for a `.pmd` file with
+//! that content. `include_pmd!("shaders/pipelines.pmd")` does the same with
+//! a file. Paths are relative to the crate's root, and editing a shader
+//! rebuilds the crate.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use code_gen::{
+    CodegenOptions, DependencyTracker, PipelineConfig, ProgressObserver, SourceEmbedding,
+};
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use quote::quote;
+
+/// Generates the pipelines declared in the macro's input, which is written
+/// the same way as a `.pmd` file. Errors, including invalid shaders, are
+/// reported as compile errors.
+#[proc_macro]
+pub fn pipelines(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let mut src = String::new();
+    write_pmd(input.into(), &mut src);
+    let code = PipelineConfig::from_src(&src)
+        .map_err(|e| e.to_string())
+        .and_then(|config| generate(&config));
+    match code {
+        Ok(code) => code.into(),
+        Err(message) => quote!(::core::compile_error!(#message);).into(),
+    }
+}
+
+/// Generates the pipelines in the `.pmd` file at the given path, relative to
+/// the crate's root, eg. `include_pmd!("shaders/pipelines.pmd")`. The crate
+/// is rebuilt when the file or any of its shaders change.
+#[proc_macro]
+pub fn include_pmd(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    syn::parse(input)
+        .and_then(|path| include(&path))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Fills the module it's put on with the pipelines in a `.pmd` file, like
+/// [include_pmd!]:
+///
+/// ```ignore
+/// #[pipemd_macros::from_pmd("shaders/scene.pmd")]
+/// pub mod pipelines {}
+/// ```
+///
+/// Anything already in the module is kept after the generated items.
+/// `pipelines` itself is taken by the function-like macro, hence the name.
+#[proc_macro_attribute]
+pub fn from_pmd(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let module = |item| {
+        let path = syn::parse(attr)?;
+        let mut module: syn::ItemMod = syn::parse(item)?;
+        let Some((_, items)) = &mut module.content else {
+            return Err(syn::Error::new_spanned(
+                &module,
+                format!(
+                    "`#[from_pmd]` needs a module with a body, eg. `mod {} {{}}`",
+                    module.ident
+                ),
+            ));
+        };
+        let code = include(&path)?;
+        items.insert(0, syn::Item::Verbatim(code));
+        Ok(quote!(#module))
+    };
+    module(item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// The code for the `.pmd` file at `path`, relative to the crate's root.
+fn include(path: &syn::LitStr) -> syn::Result<TokenStream> {
+    PipelineConfig::from_path(crate_root().join(path.value()))
+        .map_err(|e| format!("{:#}", e))
+        .and_then(|config| generate(&config))
+        .map_err(|message| syn::Error::new(path.span(), message))
+}
+
+/// The root of the crate the macro is called from.
+fn crate_root() -> PathBuf {
+    std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(Into::into)
+        .unwrap_or_default()
+}
+
+/// Generates the code for `config`, along with an `include_bytes!` of each
+/// file it was read from and each shader so rustc rebuilds the crate when
+/// they change. Shaders that are preprocessed are embedded as strings,
+/// which rustc doesn't know came from a file.
+fn generate(config: &PipelineConfig) -> Result<TokenStream, String> {
+    let tracker = Arc::new(DependencyTracker::default());
+    config
+        .files()
+        .iter()
+        .for_each(|file| tracker.file_parsed(file));
+    let options = CodegenOptions {
+        source_embedding: SourceEmbedding::IncludeStr,
+        shader_base: Some(crate_root()),
+        observer: Some(tracker.clone()),
+        ..Default::default()
+    };
+    let code = code_gen::gen_pipeline_code_with_options(config, &options)
+        .map_err(|e| format!("{:#}", e))?;
+    let tracked = tracker
+        .files()
+        .into_iter()
+        .map(|file| file.to_string_lossy().into_owned());
+    Ok(quote! {
+        #(const _: &[u8] = include_bytes!(#tracked);)*
+        #code
+    })
+}
+
+/// Writes `tokens` back out as config source. Rust's tokenizer splits `$name`
+/// and `-1` in two, so those are joined back up where the config lexer
+/// expects them to be a single token.
+fn write_pmd(tokens: TokenStream, out: &mut String) {
+    for token in tokens {
+        match token {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::None => ("", ""),
+                };
+                out.push_str(open);
+                write_pmd(group.stream(), out);
+                out.push_str(close);
+                out.push(' ');
+            }
+            TokenTree::Punct(punct) => {
+                out.push(punct.as_char());
+                if !matches!(punct.as_char(), '$' | '-' | '#') {
+                    out.push(' ');
+                }
+            }
+            token => {
+                let _ = write!(out, "{} ", token);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_pmd_joins_tokens() {
+        let mut src = String::new();
+        let tokens = r#"#let(bias: -2) render_pipeline(path: "a.wgsl", bias: $bias)"#
+            .parse()
+            .unwrap();
+        write_pmd(tokens, &mut src);
+        assert_eq!(
+            "#let (bias : -2 ) render_pipeline (path : \"a.wgsl\" , bias : $bias ) ",
+            src
+        );
+    }
+}
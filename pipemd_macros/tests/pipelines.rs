@@ -0,0 +1,43 @@
+pipemd_macros::pipelines! {
+    #let(shader: "../code_gen/tests/texture.wgsl")
+    render_pipeline(
+        name: "TexturedPipeline",
+        path: $shader,
+        depth_stencil: (format: Depth32Float, depth_bias: (constant: -2, slope_scale: 2.0, clamp: 0.0)),
+    )
+    #compute_pipeline("IntegratePipeline", "cs_integrate", "../code_gen/tests/compute.wgsl")
+}
+
+#[test]
+fn pipelines() {
+    let _: fn(&wgpu::Device, wgpu::TextureFormat) -> TexturedPipeline = TexturedPipeline::new;
+    let _: fn(&wgpu::Device) -> IntegratePipeline = IntegratePipeline::new;
+    assert_eq!([64, 1, 1], IntegratePipeline::WORKGROUP_SIZE);
+}
+
+mod included {
+    pipemd_macros::include_pmd!("tests/textured.pmd");
+}
+
+#[test]
+fn include_pmd() {
+    let _: fn(&wgpu::Device, wgpu::TextureFormat) -> included::TexturedPipeline =
+        included::TexturedPipeline::new;
+}
+
+#[pipemd_macros::from_pmd("tests/textured.pmd")]
+mod module {
+    pub fn surface_format() -> wgpu::TextureFormat {
+        wgpu::TextureFormat::Bgra8UnormSrgb
+    }
+}
+
+#[test]
+fn from_pmd() {
+    let _: fn(&wgpu::Device, wgpu::TextureFormat) -> module::TexturedPipeline =
+        module::TexturedPipeline::new;
+    assert_eq!(
+        wgpu::TextureFormat::Bgra8UnormSrgb,
+        module::surface_format()
+    );
+}